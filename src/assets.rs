@@ -0,0 +1,115 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::config::Theme;
+use crate::hash;
+use std::fs;
+use std::path::Path;
+
+/// Files shared by the generated pages, relative to the repository root.
+/// They are written under a name including a hash of their content, so they
+/// can be cached forever.
+const STYLESHEET: &str = "assets/shurl.css";
+/// The colors of the dark theme, and the custom stylesheet copied from
+/// `custom_css`, loaded after the stylesheet so they win.
+const THEME_STYLESHEET: &str = "assets/theme.css";
+const CUSTOM_STYLESHEET: &str = "assets/custom.css";
+
+const STYLE: &str = include_str!("templates/shurl.css");
+const DARK_STYLE: &str = include_str!("templates/dark.css");
+
+pub struct Asset {
+    /// Fingerprinted path, e.g. `assets/shurl.0123abcd.css`.
+    pub path: String,
+    pub integrity: String,
+}
+
+/// Writes the shared assets of the theme and the custom stylesheet into the
+/// repository and returns their paths and integrity hashes. Files are only
+/// rewritten when their content changed, and copies left behind by earlier
+/// versions or themes are removed.
+pub fn write_stylesheets(
+    repo_path: &Path,
+    theme: Theme,
+    custom: Option<&str>,
+) -> Result<Vec<Asset>, String> {
+    let mut stylesheets = vec![(STYLESHEET, STYLE.to_string())];
+    match theme {
+        Theme::Light => {}
+        Theme::Dark => stylesheets.push((THEME_STYLESHEET, DARK_STYLE.to_string())),
+        Theme::Auto => stylesheets.push((
+            THEME_STYLESHEET,
+            format!("@media (prefers-color-scheme: dark) {{\n{DARK_STYLE}}}\n"),
+        )),
+    }
+    if let Some(custom) = custom {
+        stylesheets.push((CUSTOM_STYLESHEET, custom.to_string()));
+    }
+    for path in [THEME_STYLESHEET, CUSTOM_STYLESHEET] {
+        if !stylesheets.iter().any(|(written, _)| *written == path) {
+            let (stem, extension) = path.rsplit_once('.').unwrap_or((path, ""));
+            remove_stale(&repo_path.join(path), stem, extension)?;
+        }
+    }
+
+    let mut assets = vec![];
+    for (path, content) in &stylesheets {
+        let digest = hash::sha256(content.as_bytes());
+        let fingerprint: String = digest[..4].iter().map(|b| format!("{b:02x}")).collect();
+        let (stem, extension) = path.rsplit_once('.').unwrap_or((path, ""));
+        let path = format!("{stem}.{fingerprint}.{extension}");
+
+        let full_path = repo_path.join(&path);
+        if fs::read_to_string(&full_path).ok().as_ref() != Some(content) {
+            if let Some(parent) = full_path.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+            }
+            fs::write(&full_path, content).map_err(|e| format!("failed to write {path}: {e}"))?;
+        }
+        remove_stale(&full_path, stem, extension)?;
+
+        assets.push(Asset {
+            path,
+            integrity: hash::integrity(content.as_bytes()),
+        });
+    }
+    Ok(assets)
+}
+
+/// Removes other versions of an asset next to `current`, fingerprinted or
+/// from before fingerprinting.
+fn remove_stale(current: &Path, stem: &str, extension: &str) -> Result<(), String> {
+    let (Some(dir), Some(current_name)) = (current.parent(), current.file_name()) else {
+        return Ok(());
+    };
+    let stem = stem.rsplit('/').next().unwrap_or(stem);
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(());
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        if name == current_name {
+            continue;
+        }
+        let name = name.to_string_lossy();
+        if name.starts_with(&format!("{stem}.")) && name.ends_with(&format!(".{extension}")) {
+            fs::remove_file(entry.path())
+                .map_err(|e| format!("failed to remove {}: {e}", entry.path().display()))?;
+        }
+    }
+    Ok(())
+}
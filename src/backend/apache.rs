@@ -0,0 +1,102 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::{one_token, sorted_links, status_of, write_managed_block};
+use crate::config::ShurlConfig;
+use crate::manifest::Manifest;
+use std::path::Path;
+
+pub const HTACCESS_FILE: &str = ".htaccess";
+
+/// mod_rewrite rules for `.htaccess`. Patterns are relative to the directory
+/// of the file, so the links work wherever the repository is served from.
+/// Removed links answer 410 Gone.
+pub fn rules(cfg: &ShurlConfig, manifest: &Manifest, gone: &[&str]) -> String {
+    let mut rules = String::from("RewriteEngine On\n");
+    for link in sorted_links(manifest) {
+        rules.push_str(&format!(
+            "RewriteRule ^{}/?$ \"{}\" [R={},L,NE]\n",
+            escape_pattern(&link.slug),
+            escape_substitution(&link.target),
+            status_of(cfg, link)
+        ));
+    }
+    for slug in gone {
+        rules.push_str(&format!(
+            "RewriteRule ^{}/?$ - [G,L]\n",
+            escape_pattern(slug)
+        ));
+    }
+    rules
+}
+
+pub fn write(
+    repo_path: &Path,
+    cfg: &ShurlConfig,
+    manifest: &Manifest,
+    gone: &[&str],
+) -> Result<(), String> {
+    write_managed_block(
+        &repo_path.join(HTACCESS_FILE),
+        "#",
+        &rules(cfg, manifest, gone),
+    )
+}
+
+fn escape_pattern(slug: &str) -> String {
+    let mut escaped = String::with_capacity(slug.len());
+    for c in slug.chars() {
+        if ".^$*+?()[]{}|\\".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// `$N` and `%N` are back-references in substitutions, and quotes would end
+/// the argument. Quotes are encoded first, so their `%` is escaped too.
+fn escape_substitution(target: &str) -> String {
+    one_token(target)
+        .replace('"', "%22")
+        .replace('\\', "\\\\")
+        .replace('$', "\\$")
+        .replace('%', "\\%")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date;
+    use crate::manifest::Link;
+
+    fn manifest(target: &str) -> Manifest {
+        let mut manifest = Manifest::default();
+        manifest.insert(Link::new("docs", target, date::now()));
+        manifest
+    }
+
+    #[test]
+    fn targets_are_escaped_in_substitutions() {
+        let manifest = manifest("https://example.com/100% \"$1\"\n#top");
+        assert_eq!(
+            rules(&ShurlConfig::default(), &manifest, &["old"]),
+            "RewriteEngine On\n\
+            RewriteRule ^docs/?$ \"https://example.com/100\\%\\%20\\%22\\$1\\%22\\%0A#top\" [R=301,L,NE]\n\
+            RewriteRule ^old/?$ - [G,L]\n"
+        );
+    }
+}
@@ -0,0 +1,91 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::{one_token, sorted_links, status_of};
+use crate::config::ShurlConfig;
+use crate::manifest::Manifest;
+use std::fs;
+use std::path::Path;
+
+pub const CONFIG_FILE: &str = "shurl.caddy";
+
+/// One `redir` per link, to be imported into a Caddy site block. Removed
+/// links answer 410 Gone.
+pub fn config(cfg: &ShurlConfig, manifest: &Manifest, gone: &[&str]) -> String {
+    let mut config = String::from(
+        "# Generated by shurl from links.toml, do not edit.\n\
+        # Import this file in your site block with `import shurl.caddy`.\n",
+    );
+    for link in sorted_links(manifest) {
+        // Caddy expands `{...}` placeholders anywhere in a token, and
+        // backslashes escape in quoted ones.
+        let target = one_token(&link.target)
+            .replace('{', "%7B")
+            .replace('}', "%7D")
+            .replace('"', "%22")
+            .replace('\\', "%5C");
+        config.push_str(&format!(
+            "redir /{} \"{target}\" {}\n",
+            link.slug,
+            status_of(cfg, link)
+        ));
+    }
+    for slug in gone {
+        config.push_str(&format!(
+            "respond /{slug} \"This short link has been removed.\" 410\n"
+        ));
+    }
+    config
+}
+
+pub fn write(
+    repo_path: &Path,
+    cfg: &ShurlConfig,
+    manifest: &Manifest,
+    gone: &[&str],
+) -> Result<(), String> {
+    let path = repo_path.join(CONFIG_FILE);
+    let content = config(cfg, manifest, gone);
+    if fs::read_to_string(&path).ok().as_deref() != Some(content.as_str()) {
+        fs::write(&path, content).map_err(|e| format!("failed to write {CONFIG_FILE}: {e}"))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date;
+    use crate::manifest::Link;
+
+    fn manifest(target: &str) -> Manifest {
+        let mut manifest = Manifest::default();
+        manifest.insert(Link::new("docs", target, date::now()));
+        manifest
+    }
+
+    #[test]
+    fn targets_are_escaped_in_quoted_tokens() {
+        let manifest = manifest("https://example.com/{path} \"q\"\\ x");
+        let config = config(&ShurlConfig::default(), &manifest, &["old"]);
+        assert!(
+            config
+                .contains("redir /docs \"https://example.com/%7Bpath%7D%20%22q%22%5C%20x\" 301\n"),
+            "{config}"
+        );
+        assert!(config.ends_with("respond /old \"This short link has been removed.\" 410\n"));
+    }
+}
@@ -0,0 +1,101 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::{one_token, sorted_links, status_of, write_managed_block};
+use crate::config::ShurlConfig;
+use crate::manifest::Manifest;
+use std::path::Path;
+
+pub const REDIRECTS_FILE: &str = "_redirects";
+
+/// Cloudflare Pages only reads the first 2000 static redirects.
+const MAX_STATIC_RULES: usize = 2000;
+/// Warn once this share of the limit is used.
+const WARN_THRESHOLD: usize = MAX_STATIC_RULES * 9 / 10;
+/// Longer rules are rejected by Cloudflare Pages.
+const MAX_RULE_LENGTH: usize = 1000;
+
+/// One rule per link in Cloudflare Pages' `_redirects` syntax, plus warnings
+/// about its limits. Unlike Netlify there is no `!` suffix: Pages always
+/// applies redirects before serving files, so HTML fallback pages don't
+/// shadow them.
+pub fn rules(cfg: &ShurlConfig, manifest: &Manifest) -> (String, Vec<String>) {
+    let mut warnings = vec![];
+    let mut block = String::new();
+    let mut rules = 0;
+    for link in sorted_links(manifest) {
+        let rule = format!(
+            "/{} {} {}",
+            link.slug,
+            one_token(&link.target),
+            status_of(cfg, link)
+        );
+        if rule.len() > MAX_RULE_LENGTH {
+            warnings.push(format!(
+                "skipped `{}` in {REDIRECTS_FILE}: rule is longer than {MAX_RULE_LENGTH} characters",
+                link.slug
+            ));
+            continue;
+        }
+        block.push_str(&rule);
+        block.push('\n');
+        rules += 1;
+    }
+
+    if rules > MAX_STATIC_RULES {
+        warnings.push(format!(
+            "{REDIRECTS_FILE} has {rules} rules but Cloudflare Pages only applies the first \
+            {MAX_STATIC_RULES}"
+        ));
+    } else if rules >= WARN_THRESHOLD {
+        warnings.push(format!(
+            "{REDIRECTS_FILE} has {rules} of the {MAX_STATIC_RULES} rules Cloudflare Pages allows"
+        ));
+    }
+
+    (block, warnings)
+}
+
+pub fn write(
+    repo_path: &Path,
+    cfg: &ShurlConfig,
+    manifest: &Manifest,
+) -> Result<Vec<String>, String> {
+    let (rules, warnings) = rules(cfg, manifest);
+    write_managed_block(&repo_path.join(REDIRECTS_FILE), "#", &rules)?;
+    Ok(warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date;
+    use crate::manifest::Link;
+
+    fn manifest(target: &str) -> Manifest {
+        let mut manifest = Manifest::default();
+        manifest.insert(Link::new("docs", target, date::now()));
+        manifest
+    }
+
+    #[test]
+    fn targets_stay_one_token() {
+        let manifest = manifest("https://example.com/a b\tc\n#top");
+        let (rules, warnings) = rules(&ShurlConfig::default(), &manifest);
+        assert_eq!(rules, "/docs https://example.com/a%20b%09c%0A#top 301\n");
+        assert!(warnings.is_empty());
+    }
+}
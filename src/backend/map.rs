@@ -0,0 +1,98 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::{one_token, sorted_links};
+use crate::manifest::Manifest;
+use std::fs;
+use std::path::Path;
+
+pub const MAP_FILE: &str = "shurl.map";
+
+/// One `/slug<TAB>target` line per link, the plain text map format of
+/// Apache's `RewriteMap` and many CDNs. There are no comments, as not every
+/// consumer of such maps understands them.
+pub fn rules(manifest: &Manifest) -> String {
+    sorted_links(manifest)
+        .into_iter()
+        .map(|link| format!("/{}\t{}\n", link.slug, one_token(&link.target)))
+        .collect()
+}
+
+pub fn write(repo_path: &Path, manifest: &Manifest) -> Result<(), String> {
+    let path = repo_path.join(MAP_FILE);
+    let content = rules(manifest);
+    if fs::read_to_string(&path).ok().as_deref() != Some(content.as_str()) {
+        fs::write(&path, content).map_err(|e| format!("failed to write {MAP_FILE}: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Reads the `from to` pairs of a map, separated by tabs or spaces. Blank
+/// lines and `#` comments are skipped. Sources are returned as slugs, so
+/// `/docs`, `docs` and `https://old.example.com/docs` all become `docs`.
+pub fn parse(content: &str) -> Result<Vec<(String, String)>, String> {
+    let mut entries = vec![];
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split_whitespace();
+        let (Some(from), Some(to), None) = (fields.next(), fields.next(), fields.next()) else {
+            return Err(format!(
+                "line {}: expected a source and a target separated by a tab",
+                number + 1
+            ));
+        };
+        let path = match from.split_once("://") {
+            Some((_, rest)) => rest.split_once('/').map_or("", |(_, path)| path),
+            None => from,
+        };
+        let slug = path.trim_matches('/');
+        if slug.is_empty() {
+            return Err(format!("line {}: `{from}` has no path", number + 1));
+        }
+        entries.push((slug.to_string(), to.to_string()));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date;
+    use crate::manifest::Link;
+
+    fn manifest(target: &str) -> Manifest {
+        let mut manifest = Manifest::default();
+        manifest.insert(Link::new("docs", target, date::now()));
+        manifest
+    }
+
+    #[test]
+    fn targets_stay_one_token() {
+        let manifest = manifest("https://example.com/a b\n#top");
+        let map = rules(&manifest);
+        assert_eq!(map, "/docs\thttps://example.com/a%20b%0A#top\n");
+        assert_eq!(
+            parse(&map).unwrap(),
+            [(
+                "docs".to_string(),
+                "https://example.com/a%20b%0A#top".to_string()
+            )]
+        );
+    }
+}
@@ -0,0 +1,219 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Server-side redirect outputs, generated from the manifest next to (or
+//! instead of) the HTML redirect pages.
+
+mod apache;
+mod caddy;
+mod cloudflare;
+pub mod map;
+mod netlify;
+mod nginx;
+mod vercel;
+
+use crate::config::{Backend, ShurlConfig};
+use crate::manifest::{Link, Manifest};
+use clap::ValueEnum;
+use std::fs;
+use std::path::Path;
+
+/// Status codes that make sense for a short link.
+pub const REDIRECT_STATUSES: &[u16] = &[301, 302, 303, 307, 308];
+
+/// Page the server-side backends show for removed links.
+pub const GONE_FILE: &str = "gone.html";
+
+/// Writes the output of every selected server-side backend and returns
+/// warnings about the generated files. `gone` are the sorted slugs of
+/// removed links.
+pub fn write_all(
+    repo_path: &Path,
+    cfg: &ShurlConfig,
+    manifest: &Manifest,
+    gone: &[&str],
+) -> Result<Vec<String>, String> {
+    cfg.validate()?;
+
+    let mut warnings = vec![];
+    for &backend in &cfg.backends {
+        let gone = gone_for(backend, cfg, gone, &mut warnings);
+        match backend {
+            Backend::Html => {}
+            Backend::Netlify => netlify::write(repo_path, cfg, manifest, gone)?,
+            Backend::Cloudflare => warnings.extend(cloudflare::write(repo_path, cfg, manifest)?),
+            Backend::Vercel => vercel::write(repo_path, cfg, manifest)?,
+            Backend::Nginx => nginx::write(repo_path, cfg, manifest, gone)?,
+            Backend::Apache => apache::write(repo_path, cfg, manifest, gone)?,
+            Backend::Caddy => caddy::write(repo_path, cfg, manifest, gone)?,
+            Backend::Map => map::write(repo_path, manifest)?,
+        }
+    }
+    Ok(warnings)
+}
+
+/// Whether a backend can answer 410 Gone on its own, rather than only
+/// through the pages the html backend leaves behind.
+pub fn supports_gone(backend: Backend) -> bool {
+    matches!(
+        backend,
+        Backend::Netlify | Backend::Nginx | Backend::Apache | Backend::Caddy
+    )
+}
+
+/// The removed links a backend should answer 410 for, with a warning if the
+/// config asks for it but the backend can't.
+fn gone_for<'a>(
+    backend: Backend,
+    cfg: &ShurlConfig,
+    gone: &'a [&'a str],
+    warnings: &mut Vec<String>,
+) -> &'a [&'a str] {
+    if gone.is_empty() || !cfg.gone_backends.contains(&backend) || backend == Backend::Html {
+        return &[];
+    }
+    if !supports_gone(backend) {
+        warnings.push(format!(
+            "the {} backend can't answer 410 Gone, removed links fall back to their pages",
+            backend
+                .to_possible_value()
+                .map(|value| value.get_name().to_string())
+                .unwrap_or_default()
+        ));
+        return &[];
+    }
+    gone
+}
+
+/// Renders the rules of a backend on their own, for deploying the links
+/// somewhere the repository isn't. Returns the rules and any warnings.
+pub fn export(
+    backend: Backend,
+    repo_path: &Path,
+    cfg: &ShurlConfig,
+    manifest: &Manifest,
+    gone: &[&str],
+) -> Result<(String, Vec<String>), String> {
+    let mut warnings = vec![];
+    let gone = gone_for(backend, cfg, gone, &mut warnings);
+    let (rules, backend_warnings) = match backend {
+        Backend::Html => return Err("the html backend has nothing to export".to_string()),
+        Backend::Netlify => (netlify::rules(cfg, manifest, gone), vec![]),
+        Backend::Cloudflare => cloudflare::rules(cfg, manifest),
+        Backend::Vercel => {
+            let existing = vercel::existing_config(repo_path)?;
+            let config = vercel::config(cfg, manifest, existing);
+            (config.to_pretty_string() + "\n", vec![])
+        }
+        Backend::Nginx => (nginx::config(cfg, manifest, gone), vec![]),
+        Backend::Apache => (apache::rules(cfg, manifest, gone), vec![]),
+        Backend::Caddy => (caddy::config(cfg, manifest, gone), vec![]),
+        Backend::Map => (map::rules(manifest), vec![]),
+    };
+    warnings.extend(backend_warnings);
+    Ok((rules, warnings))
+}
+
+/// The file a backend writes into the repository.
+pub fn output_file(backend: Backend) -> Option<&'static str> {
+    match backend {
+        Backend::Html => None,
+        Backend::Netlify => Some(netlify::REDIRECTS_FILE),
+        Backend::Cloudflare => Some(cloudflare::REDIRECTS_FILE),
+        Backend::Vercel => Some(vercel::CONFIG_FILE),
+        Backend::Nginx => Some(nginx::CONFIG_FILE),
+        Backend::Apache => Some(apache::HTACCESS_FILE),
+        Backend::Caddy => Some(caddy::CONFIG_FILE),
+        Backend::Map => Some(map::MAP_FILE),
+    }
+}
+
+pub fn status_of(cfg: &ShurlConfig, link: &Link) -> u16 {
+    link.status.unwrap_or(cfg.redirect_status)
+}
+
+/// Links sorted by slug, so regenerated files only change where links did.
+/// Links counting down to their event are left to their pages until it
+/// starts, links with targets per language for good.
+fn sorted_links(manifest: &Manifest) -> Vec<&Link> {
+    let mut links: Vec<&Link> = manifest
+        .links
+        .iter()
+        .filter(|link| !link.needs_page())
+        .collect();
+    links.sort_by(|a, b| a.slug.cmp(&b.slug));
+    links
+}
+
+/// The target with whitespace and control characters percent-encoded, so it
+/// stays one token of a rule even if `links.toml` was edited by hand.
+fn one_token(target: &str) -> String {
+    let mut encoded = String::with_capacity(target.len());
+    for c in target.chars() {
+        if c.is_whitespace() || c.is_control() {
+            let mut bytes = [0; 4];
+            for byte in c.encode_utf8(&mut bytes).bytes() {
+                encoded.push_str(&format!("%{byte:02X}"));
+            }
+        } else {
+            encoded.push(c);
+        }
+    }
+    encoded
+}
+
+const BEGIN_MARKER: &str = "shurl:begin (generated from links.toml, do not edit)";
+const END_MARKER: &str = "shurl:end";
+
+/// Replaces the block of generated lines in `path`, keeping whatever the user
+/// wrote around it. The block is appended if the file doesn't have one yet.
+fn write_managed_block(path: &Path, comment: &str, block: &str) -> Result<(), String> {
+    let begin = format!("{comment} {BEGIN_MARKER}");
+    let end = format!("{comment} {END_MARKER}");
+    let existing = fs::read_to_string(path).unwrap_or_default();
+
+    let mut before = String::new();
+    let mut after = String::new();
+    let mut state = 0;
+    for line in existing.lines() {
+        match state {
+            0 if line.trim() == begin => state = 1,
+            0 => {
+                before.push_str(line);
+                before.push('\n');
+            }
+            1 if line.trim() == end => state = 2,
+            1 => {}
+            _ => {
+                after.push_str(line);
+                after.push('\n');
+            }
+        }
+    }
+
+    let mut content = before;
+    content.push_str(&begin);
+    content.push('\n');
+    content.push_str(block);
+    content.push_str(&end);
+    content.push('\n');
+    content.push_str(&after);
+
+    if existing != content {
+        fs::write(path, content).map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+    }
+    Ok(())
+}
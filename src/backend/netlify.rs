@@ -0,0 +1,76 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::{one_token, sorted_links, status_of, write_managed_block, GONE_FILE};
+use crate::config::ShurlConfig;
+use crate::manifest::Manifest;
+use std::path::Path;
+
+pub const REDIRECTS_FILE: &str = "_redirects";
+
+/// One rule per link in Netlify's `_redirects` syntax. Rules are forced
+/// (`301!`) so they also win over HTML fallback pages at the same path.
+/// Removed links show the gone page with a 410.
+pub fn rules(cfg: &ShurlConfig, manifest: &Manifest, gone: &[&str]) -> String {
+    let mut rules = String::new();
+    for link in sorted_links(manifest) {
+        rules.push_str(&format!(
+            "/{}  {}  {}!\n",
+            link.slug,
+            one_token(&link.target),
+            status_of(cfg, link)
+        ));
+    }
+    for slug in gone {
+        rules.push_str(&format!("/{slug}  /{GONE_FILE}  410!\n"));
+    }
+    rules
+}
+
+pub fn write(
+    repo_path: &Path,
+    cfg: &ShurlConfig,
+    manifest: &Manifest,
+    gone: &[&str],
+) -> Result<(), String> {
+    write_managed_block(
+        &repo_path.join(REDIRECTS_FILE),
+        "#",
+        &rules(cfg, manifest, gone),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date;
+    use crate::manifest::Link;
+
+    fn manifest(target: &str) -> Manifest {
+        let mut manifest = Manifest::default();
+        manifest.insert(Link::new("docs", target, date::now()));
+        manifest
+    }
+
+    #[test]
+    fn targets_stay_one_token() {
+        let manifest = manifest("https://example.com/a b\tc\n#top");
+        assert_eq!(
+            rules(&ShurlConfig::default(), &manifest, &["old"]),
+            "/docs  https://example.com/a%20b%09c%0A#top  301!\n/old  /gone.html  410!\n"
+        );
+    }
+}
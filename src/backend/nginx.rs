@@ -0,0 +1,115 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::{one_token, sorted_links, status_of, GONE_FILE};
+use crate::config::ShurlConfig;
+use crate::manifest::Manifest;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+pub const CONFIG_FILE: &str = "shurl.nginx.conf";
+
+/// `map` blocks from request paths to targets, one per status code, since
+/// `return` needs the code as a literal. Include the file in the `http`
+/// block; the `server` block then needs one `if`/`return` per map, as
+/// described in the generated header. Removed links get a map of their own
+/// for answering 410 Gone.
+pub fn config(cfg: &ShurlConfig, manifest: &Manifest, gone: &[&str]) -> String {
+    let mut by_status: BTreeMap<u16, Vec<String>> = BTreeMap::new();
+    for link in sorted_links(manifest) {
+        // nginx expands variables in map values, so a literal `$` has to be
+        // percent-encoded, and backslashes escape in quoted strings.
+        let target = one_token(&link.target)
+            .replace('$', "%24")
+            .replace('"', "%22")
+            .replace('\\', "%5C");
+        by_status
+            .entry(status_of(cfg, link))
+            .or_default()
+            .push(format!("    \"/{}\" \"{target}\";\n", link.slug));
+    }
+
+    let mut config = String::from(
+        "# Generated by shurl from links.toml, do not edit.\n\
+        # Include this file in the `http` block and add to the `server` block:\n#\n",
+    );
+    for status in by_status.keys() {
+        config.push_str(&format!(
+            "#     if ($shurl_{status}) {{ return {status} $shurl_{status}; }}\n"
+        ));
+    }
+    if !gone.is_empty() {
+        config.push_str(&format!(
+            "#     error_page 410 /{GONE_FILE};\n#     if ($shurl_gone) {{ return 410; }}\n"
+        ));
+    }
+    for (status, entries) in &by_status {
+        config.push_str(&format!(
+            "\nmap $uri $shurl_{status} {{\n    default \"\";\n"
+        ));
+        for entry in entries {
+            config.push_str(entry);
+        }
+        config.push_str("}\n");
+    }
+    if !gone.is_empty() {
+        config.push_str("\nmap $uri $shurl_gone {\n    default 0;\n");
+        for slug in gone {
+            config.push_str(&format!("    \"/{slug}\" 1;\n"));
+        }
+        config.push_str("}\n");
+    }
+    config
+}
+
+pub fn write(
+    repo_path: &Path,
+    cfg: &ShurlConfig,
+    manifest: &Manifest,
+    gone: &[&str],
+) -> Result<(), String> {
+    let path = repo_path.join(CONFIG_FILE);
+    let content = config(cfg, manifest, gone);
+    if fs::read_to_string(&path).ok().as_deref() != Some(content.as_str()) {
+        fs::write(&path, content).map_err(|e| format!("failed to write {CONFIG_FILE}: {e}"))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date;
+    use crate::manifest::Link;
+
+    fn manifest(target: &str) -> Manifest {
+        let mut manifest = Manifest::default();
+        manifest.insert(Link::new("docs", target, date::now()));
+        manifest
+    }
+
+    #[test]
+    fn targets_are_escaped_in_quoted_values() {
+        let manifest = manifest("https://example.com/$1 \"q\"\\x\n#top");
+        let config = config(&ShurlConfig::default(), &manifest, &["old"]);
+        assert!(
+            config.contains("    \"/docs\" \"https://example.com/%241%20%22q%22%5Cx%0A#top\";\n"),
+            "{config}"
+        );
+        assert!(config.contains("    \"/old\" 1;\n"), "{config}");
+    }
+}
@@ -0,0 +1,100 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::{sorted_links, status_of};
+use crate::config::ShurlConfig;
+use crate::json::{self, Value};
+use crate::manifest::Manifest;
+use std::fs;
+use std::path::Path;
+
+pub const CONFIG_FILE: &str = "vercel.json";
+
+/// `vercel.json` with its `redirects` array replaced by one entry per link.
+/// Everything else in an existing file is kept as it is.
+pub fn config(cfg: &ShurlConfig, manifest: &Manifest, existing: Option<Value>) -> Value {
+    let redirects = sorted_links(manifest)
+        .into_iter()
+        .map(|link| {
+            let mut redirect = Value::object();
+            redirect
+                .set("source", format!("/{}", link.slug))
+                .set("destination", link.target.as_str())
+                .set("statusCode", status_of(cfg, link));
+            redirect
+        })
+        .collect::<Vec<_>>();
+
+    let mut config = match existing {
+        Some(existing @ Value::Object(_)) => existing,
+        _ => Value::object(),
+    };
+    config.set("redirects", redirects);
+    config
+}
+
+/// Reads the `vercel.json` of the repository, if there is one.
+pub fn existing_config(repo_path: &Path) -> Result<Option<Value>, String> {
+    match fs::read_to_string(repo_path.join(CONFIG_FILE)) {
+        Ok(content) => json::parse(&content)
+            .map(Some)
+            .map_err(|e| format!("failed to parse {CONFIG_FILE}: {e}")),
+        Err(_) => Ok(None),
+    }
+}
+
+pub fn write(repo_path: &Path, cfg: &ShurlConfig, manifest: &Manifest) -> Result<(), String> {
+    let path = repo_path.join(CONFIG_FILE);
+    let content = config(cfg, manifest, existing_config(repo_path)?).to_pretty_string() + "\n";
+    if fs::read_to_string(&path).ok().as_deref() != Some(content.as_str()) {
+        fs::write(&path, content).map_err(|e| format!("failed to write {CONFIG_FILE}: {e}"))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date;
+    use crate::manifest::Link;
+
+    fn manifest(target: &str) -> Manifest {
+        let mut manifest = Manifest::default();
+        manifest.insert(Link::new("docs", target, date::now()));
+        manifest
+    }
+
+    #[test]
+    fn targets_are_json_strings() {
+        let target = "https://example.com/a b \"q\"\\\n#top";
+        let mut existing = Value::object();
+        existing.set("cleanUrls", true);
+        let config = config(&ShurlConfig::default(), &manifest(target), Some(existing));
+
+        let parsed = json::parse(&config.to_pretty_string()).unwrap();
+        assert_eq!(parsed.get("cleanUrls"), Some(&Value::Bool(true)));
+        let redirect = &parsed.get("redirects").and_then(Value::as_array).unwrap()[0];
+        assert_eq!(
+            redirect.get("source").and_then(Value::as_str),
+            Some("/docs")
+        );
+        assert_eq!(
+            redirect.get("destination").and_then(Value::as_str),
+            Some(target)
+        );
+        assert_eq!(redirect.get("statusCode"), Some(&Value::Number(301.0)));
+    }
+}
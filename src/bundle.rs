@@ -0,0 +1,112 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Bundles: one short link to a landing page listing several targets, for
+//! sharing a set of resources at once.
+//!
+//! Bundles are recorded in `bundles.toml` next to the manifest. They only
+//! exist as pages, so server-side backends serve them like any other file.
+
+use crate::date;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use toml::value::Datetime;
+
+pub const BUNDLES_FILE: &str = "bundles.toml";
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Bundles {
+    #[serde(default, rename = "bundle")]
+    pub bundles: Vec<Bundle>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Bundle {
+    pub slug: String,
+    /// Heading of the landing page, the slug if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub targets: Vec<String>,
+    pub created: Datetime,
+    /// Who created the bundle, `name` from the config of whoever ran shurl.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+}
+
+impl Bundles {
+    pub fn load(repo_path: &Path) -> Result<Self, String> {
+        let path = repo_path.join(BUNDLES_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content =
+            fs::read_to_string(&path).map_err(|e| format!("failed to read {BUNDLES_FILE}: {e}"))?;
+        toml::from_str(&content).map_err(|e| format!("failed to parse {BUNDLES_FILE}: {e}"))
+    }
+
+    /// Saves the bundles, removing the file once there are none.
+    pub fn save(&self, repo_path: &Path) -> Result<(), String> {
+        let path = repo_path.join(BUNDLES_FILE);
+        if self.bundles.is_empty() {
+            if path.exists() {
+                fs::remove_file(&path)
+                    .map_err(|e| format!("failed to remove {BUNDLES_FILE}: {e}"))?;
+            }
+            return Ok(());
+        }
+        let content = toml::to_string_pretty(self)
+            .map_err(|e| format!("failed to serialize {BUNDLES_FILE}: {e}"))?;
+        fs::write(path, content).map_err(|e| format!("failed to write {BUNDLES_FILE}: {e}"))
+    }
+
+    pub fn get(&self, slug: &str) -> Option<&Bundle> {
+        self.bundles.iter().find(|bundle| bundle.slug == slug)
+    }
+
+    pub fn get_mut(&mut self, slug: &str) -> Option<&mut Bundle> {
+        self.bundles.iter_mut().find(|bundle| bundle.slug == slug)
+    }
+
+    pub fn remove(&mut self, slug: &str) -> Option<Bundle> {
+        let position = self.bundles.iter().position(|bundle| bundle.slug == slug)?;
+        Some(self.bundles.remove(position))
+    }
+}
+
+impl Bundle {
+    pub fn new(slug: String, title: Option<String>, owner: String) -> Self {
+        Self {
+            slug,
+            title,
+            targets: vec![],
+            created: date::now(),
+            owner: Some(owner),
+        }
+    }
+
+    /// Adds targets not in the bundle yet, keeping their order. Returns how
+    /// many were added.
+    pub fn add(&mut self, targets: Vec<String>) -> usize {
+        let before = self.targets.len();
+        for target in targets {
+            if !self.targets.contains(&target) {
+                self.targets.push(target);
+            }
+        }
+        self.targets.len() - before
+    }
+}
@@ -0,0 +1,138 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Snapshot of the parsed manifest kept in the git directory, so listing the
+//! links of a huge repository doesn't parse all of `links.toml` every time.
+//!
+//! The snapshot is keyed by HEAD and the size and modification time of
+//! `links.toml`, so both commits and edits that aren't committed yet
+//! invalidate it. It's only ever an optimization: a snapshot that can't be
+//! read or written is ignored.
+
+use crate::json::{self, Value};
+use crate::manifest::{Link, Manifest, MANIFEST_FILE};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Relative to the git directory, so it's never committed.
+const CACHE_FILE: &str = "shurl/manifest.json";
+/// Changes whenever the snapshot is written differently.
+const FORMAT: u32 = 1;
+
+/// Loads the manifest, from the snapshot if it's still current. Otherwise
+/// the manifest is parsed and the snapshot replaced.
+pub fn load_manifest(repo: &git2::Repository, repo_path: &Path) -> Result<Manifest, String> {
+    let path = repo.path().join(CACHE_FILE);
+    let Some(key) = key(repo, repo_path) else {
+        return Manifest::load(repo_path);
+    };
+    if let Some(manifest) = read(&path, &key) {
+        return Ok(manifest);
+    }
+    let manifest = Manifest::load(repo_path)?;
+    let _ = write(&path, &key, &manifest);
+    Ok(manifest)
+}
+
+/// What the snapshot was taken of. `None` without a `links.toml`, e.g. in
+/// repositories from before the manifest, which aren't cached.
+fn key(repo: &git2::Repository, repo_path: &Path) -> Option<String> {
+    let metadata = fs::metadata(repo_path.join(MANIFEST_FILE)).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    let head = repo
+        .head()
+        .ok()
+        .and_then(|head| head.target())
+        .map(|id| id.to_string())
+        .unwrap_or_default();
+    Some(format!(
+        "{FORMAT}:{head}:{}:{}",
+        metadata.len(),
+        modified.as_nanos()
+    ))
+}
+
+fn read(path: &Path, key: &str) -> Option<Manifest> {
+    let content = fs::read_to_string(path).ok()?;
+    let snapshot = json::parse(&content).ok()?;
+    if snapshot.get("key")?.as_str()? != key {
+        return None;
+    }
+    let links = snapshot
+        .get("links")?
+        .as_array()?
+        .iter()
+        .map(|link| to_toml(link)?.try_into::<Link>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    Some(Manifest { links })
+}
+
+fn write(path: &Path, key: &str, manifest: &Manifest) -> Option<()> {
+    let links = manifest
+        .links
+        .iter()
+        .map(|link| toml::Value::try_from(link).ok().map(|link| to_json(&link)))
+        .collect::<Option<Vec<_>>>()?;
+    let mut snapshot = Value::object();
+    snapshot.set("key", key).set("links", links);
+
+    fs::create_dir_all(path.parent()?).ok()?;
+    // Written next to the snapshot first, so a reader never sees half of it.
+    let partial = PathBuf::from(format!("{}.partial", path.display()));
+    fs::write(&partial, snapshot.to_string()).ok()?;
+    fs::rename(&partial, path).ok()
+}
+
+fn to_json(value: &toml::Value) -> Value {
+    match value {
+        toml::Value::String(text) => Value::from(text.as_str()),
+        toml::Value::Integer(number) => Value::from(*number),
+        toml::Value::Float(number) => Value::from(*number),
+        toml::Value::Boolean(value) => Value::from(*value),
+        // Serializing a link leaves datetimes as tables, which toml turns
+        // back into datetimes when deserializing.
+        toml::Value::Datetime(datetime) => Value::from(datetime.to_string()),
+        toml::Value::Array(items) => Value::from(items.iter().map(to_json).collect::<Vec<_>>()),
+        toml::Value::Table(table) => {
+            let mut object = Value::object();
+            for (key, value) in table {
+                object.set(key, to_json(value));
+            }
+            object
+        }
+    }
+}
+
+fn to_toml(value: &Value) -> Option<toml::Value> {
+    Some(match value {
+        Value::Null => return None,
+        Value::Bool(value) => toml::Value::Boolean(*value),
+        // Links only have integers.
+        Value::Number(number) => toml::Value::Integer(*number as i64),
+        Value::String(text) => toml::Value::String(text.clone()),
+        Value::Array(items) => {
+            toml::Value::Array(items.iter().map(to_toml).collect::<Option<Vec<_>>>()?)
+        }
+        Value::Object(entries) => {
+            let mut table = toml::Table::new();
+            for (key, value) in entries {
+                table.insert(key.clone(), to_toml(value)?);
+            }
+            toml::Value::Table(table)
+        }
+    })
+}
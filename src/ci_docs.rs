@@ -0,0 +1,165 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! `shurl ci-docs`: short links for documentation pages added in a pull
+//! request, posted back to the pull request as a comment.
+
+use crate::config::ShurlConfig;
+use crate::slug;
+use std::path::Path;
+use url::Url;
+
+pub struct DocPage {
+    /// Source file in the docs repository, e.g. `docs/guide/install.md`.
+    pub path: String,
+    pub slug: String,
+    pub target: Url,
+}
+
+/// Maps changed files to the published pages of the documentation site.
+/// Files outside `ci_docs_dir` or with other extensions are skipped.
+pub fn doc_pages(cfg: &ShurlConfig, files: &[String]) -> Result<Vec<DocPage>, String> {
+    let site = cfg
+        .ci_docs_site
+        .as_ref()
+        .ok_or_else(|| "ci-docs needs `ci_docs_site` in the config".to_string())?;
+    let site = Url::parse(&format!("{}/", site.trim_end_matches('/')))
+        .map_err(|e| format!("failed to parse ci_docs_site: {e}"))?;
+    let dir = cfg.ci_docs_dir.trim_matches('/');
+
+    let mut pages = vec![];
+    for file in files {
+        let file = file.trim().trim_start_matches("./");
+        let relative = if dir.is_empty() {
+            file
+        } else {
+            match file
+                .strip_prefix(dir)
+                .and_then(|rest| rest.strip_prefix('/'))
+            {
+                Some(relative) => relative,
+                None => continue,
+            }
+        };
+
+        let path = Path::new(relative);
+        let Some(extension) = path.extension().and_then(|e| e.to_str()) else {
+            continue;
+        };
+        if !cfg.ci_docs_extensions.iter().any(|e| e == extension) {
+            continue;
+        }
+        let stem = &relative[..relative.len() - extension.len() - 1];
+
+        // `guide/index.md` is published as `guide/`.
+        let page = match stem.strip_suffix("index") {
+            Some(parent) if parent.is_empty() || parent.ends_with('/') => parent.to_string(),
+            _ => format!("{stem}{}", cfg.ci_docs_page_suffix),
+        };
+        let target = site
+            .join(&page)
+            .map_err(|e| format!("failed to build the URL of {file}: {e}"))?;
+
+        pages.push(DocPage {
+            path: file.to_string(),
+            slug: slug_for(stem),
+            target,
+        });
+    }
+    Ok(pages)
+}
+
+/// `guide/Getting Started` becomes `guide-getting-started`, and names
+/// shurl keeps for itself get a `docs-` prefix, e.g. `docs-404`.
+fn slug_for(stem: &str) -> String {
+    let stem = match stem.strip_suffix("index") {
+        Some(parent) if parent.is_empty() || parent.ends_with('/') => parent,
+        _ => stem,
+    };
+    let mut slug = String::new();
+    for c in stem.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_matches('-');
+    if slug.is_empty() {
+        "docs".to_string()
+    } else if slug::validate(slug).is_err() {
+        format!("docs-{slug}")
+    } else {
+        slug.to_string()
+    }
+}
+
+/// Markdown comment listing the created short links.
+pub fn comment_body(cfg: &ShurlConfig, created: &[(&DocPage, String)]) -> String {
+    let mut body = String::from("Short links for the new documentation pages:\n\n");
+    body.push_str("| Page | Short link |\n| --- | --- |\n");
+    for (page, slug) in created {
+        let short = cfg.short_url(slug).unwrap_or_else(|| slug.clone());
+        body.push_str(&format!(
+            "| [`{}`]({}) | {} |\n",
+            page.path, page.target, short
+        ));
+    }
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slugs(files: &[&str]) -> Vec<String> {
+        let cfg = ShurlConfig {
+            ci_docs_site: Some("https://docs.example.com".to_string()),
+            ..ShurlConfig::default()
+        };
+        let files: Vec<String> = files.iter().map(|file| file.to_string()).collect();
+        doc_pages(&cfg, &files)
+            .unwrap()
+            .into_iter()
+            .map(|page| page.slug)
+            .collect()
+    }
+
+    #[test]
+    fn doc_pages_are_named_after_their_path() {
+        assert_eq!(
+            slugs(&[
+                "docs/guide/Getting Started.md",
+                "docs/guide/index.md",
+                "src/main.rs"
+            ]),
+            ["guide-getting-started", "guide"]
+        );
+    }
+
+    #[test]
+    fn reserved_doc_pages_are_renamed() {
+        assert_eq!(
+            slugs(&[
+                "docs/404.md",
+                "docs/api.md",
+                "docs/sitemap.md",
+                "docs/index.md"
+            ]),
+            ["docs-404", "docs-api", "docs-sitemap", "docs"]
+        );
+    }
+}
@@ -0,0 +1,96 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! System clipboard access through the platform's clipboard tools, so no
+//! display server libraries need to be linked.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Commands reading the clipboard, tried in order.
+const PASTE_COMMANDS: &[&[&str]] = &[
+    &["wl-paste", "--no-newline"],
+    &["xclip", "-selection", "clipboard", "-o"],
+    &["xsel", "--clipboard", "--output"],
+    &["pbpaste"],
+    &["powershell", "-NoProfile", "-Command", "Get-Clipboard"],
+];
+
+/// Commands writing the clipboard from stdin, tried in order.
+const COPY_COMMANDS: &[&[&str]] = &[
+    &["wl-copy"],
+    &["xclip", "-selection", "clipboard"],
+    &["xsel", "--clipboard", "--input"],
+    &["pbcopy"],
+    &["clip"],
+];
+
+pub fn read() -> Result<String, String> {
+    for command in PASTE_COMMANDS {
+        let Ok(output) = Command::new(command[0])
+            .args(&command[1..])
+            .stderr(Stdio::null())
+            .output()
+        else {
+            continue;
+        };
+        if output.status.success() {
+            return Ok(String::from_utf8_lossy(&output.stdout).trim().to_string());
+        }
+    }
+    Err("failed to read the clipboard: install wl-clipboard, xclip or xsel".to_string())
+}
+
+pub fn write(text: &str) -> Result<(), String> {
+    for command in COPY_COMMANDS {
+        let Ok(mut child) = Command::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            continue;
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(text.as_bytes());
+        }
+        if child.wait().map(|status| status.success()).unwrap_or(false) {
+            return Ok(());
+        }
+    }
+    Err("failed to write the clipboard: install wl-clipboard, xclip or xsel".to_string())
+}
+
+/// Shows a desktop notification. Notifications are a nicety, so failures
+/// are ignored.
+pub fn notify(title: &str, body: &str) {
+    let shown = Command::new("notify-send")
+        .arg(title)
+        .arg(body)
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false);
+    if !shown {
+        let script = format!("display notification {:?} with title {:?}", body, title);
+        let _ = Command::new("osascript")
+            .arg("-e")
+            .arg(script)
+            .stderr(Stdio::null())
+            .status();
+    }
+}
@@ -0,0 +1,116 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! `shurl clipd`: shortens URLs of configured domains as soon as they are
+//! copied, replacing the clipboard contents with the short URL. Changes to
+//! the config apply without restarting.
+
+use crate::clipboard;
+use crate::config::ShurlConfig;
+use crate::output::Colorize;
+use crate::reload::Reloader;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+use url::Url;
+
+/// Polls the clipboard until interrupted. `shorten` creates the link with
+/// the current config and returns its slug.
+pub fn run(
+    cfg: &ShurlConfig,
+    repo_path: &Path,
+    mut shorten: impl FnMut(&ShurlConfig, &Url) -> Result<String, String>,
+) -> Result<(), String> {
+    if cfg.base_url.is_none() {
+        return Err("clipd needs `base_url` in the config to build short URLs".to_string());
+    }
+    if cfg.clipd_domains.is_empty() {
+        return Err(
+            "clipd needs `clipd_domains` in the config to know what to shorten".to_string(),
+        );
+    }
+
+    println!(
+        "{} {}",
+        "Info:".green(),
+        format!(
+            "watching the clipboard for links to {}",
+            cfg.clipd_domains.join(", ")
+        )
+        .bold()
+    );
+
+    // Whatever is on the clipboard already was copied before we started.
+    let mut last = clipboard::read()?;
+    let mut cfg = cfg.clone();
+    let mut reloader = Reloader::new(repo_path);
+    loop {
+        thread::sleep(Duration::from_millis(cfg.clipd_interval_ms));
+        if let Some(new) = reloader.poll(&cfg) {
+            cfg = new;
+        }
+
+        let Ok(text) = clipboard::read() else {
+            continue;
+        };
+        if text == last {
+            continue;
+        }
+        last = text.clone();
+
+        let Ok(url) = Url::parse(&text) else {
+            continue;
+        };
+        if !matches_domain(&url, &cfg.clipd_domains) {
+            continue;
+        }
+
+        match shorten(&cfg, &url).map(|slug| cfg.short_url(&slug).unwrap_or(slug)) {
+            Ok(short_url) => {
+                clipboard::write(&short_url)?;
+                last = short_url.clone();
+                clipboard::notify("Shortened link", &short_url);
+                println!("{} {} -> {}", "Info:".green(), url, short_url.bold());
+            }
+            Err(e) => {
+                clipboard::notify("Failed to shorten link", &e);
+                eprintln!(
+                    "{} {} {}",
+                    "Warning:".yellow(),
+                    format!("failed to shorten {url}:").bold(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// Whether the URL's host is one of the domains or a subdomain of one.
+pub fn matches_domain(url: &Url, domains: &[String]) -> bool {
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    domains.iter().any(|domain| {
+        let domain = domain.trim_start_matches('.');
+        host.eq_ignore_ascii_case(domain)
+            || host
+                .to_ascii_lowercase()
+                .ends_with(&format!(".{}", domain.to_ascii_lowercase()))
+    })
+}
@@ -0,0 +1,499 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Shell completion scripts, generated from the command line definition.
+//!
+//! Positional arguments of the subcommands given as dynamic are completed
+//! with the output of a shell command, e.g. the slugs of the repository.
+
+use clap::{Arg, Command, ValueEnum, ValueHint};
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+/// Completes the positional arguments of the subcommand at `path`, e.g.
+/// `trash restore`, with the lines printed by `source`.
+pub struct Dynamic<'a> {
+    pub path: &'a str,
+    pub source: &'a str,
+}
+
+enum Values {
+    /// A flag without a value.
+    None,
+    /// Anything, nothing to offer.
+    Any,
+    Files,
+    List(Vec<String>),
+    Source(String),
+}
+
+struct Opt {
+    /// `--long` and `-s` spellings.
+    flags: Vec<String>,
+    help: String,
+    values: Values,
+}
+
+/// A command or subcommand, `cmd` being the words leading to it.
+struct Node {
+    cmd: String,
+    /// Names of the subcommands with their description, aliases last.
+    subcommands: Vec<(String, String)>,
+    /// Words after `cmd` leading to a subcommand, and the subcommand's `cmd`.
+    transitions: Vec<(String, String)>,
+    options: Vec<Opt>,
+    positionals: Values,
+}
+
+/// Writes the completion script of `command` for the shell.
+pub fn generate(shell: Shell, command: &mut Command, dynamic: &[Dynamic]) -> String {
+    command.build();
+    let bin = command.get_name().to_string();
+    let mut nodes = vec![];
+    collect(command, bin.clone(), dynamic, &mut nodes);
+    match shell {
+        Shell::Bash => bash(&bin, &nodes),
+        Shell::Zsh => zsh(&bin, &nodes),
+        Shell::Fish => fish(&bin, &nodes),
+        Shell::Powershell => powershell(&bin, &nodes),
+    }
+}
+
+fn collect(command: &Command, cmd: String, dynamic: &[Dynamic], nodes: &mut Vec<Node>) {
+    let path = cmd.split_once(' ').map_or("", |(_, path)| path);
+    let source = dynamic
+        .iter()
+        .find(|dynamic| dynamic.path == path)
+        .map(|dynamic| dynamic.source.to_string());
+
+    let mut subcommands = vec![];
+    let mut transitions = vec![];
+    let mut children = vec![];
+    for sub in command.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+        let name = sub.get_name();
+        let about = sub
+            .get_about()
+            .map(|about| about.to_string())
+            .unwrap_or_default();
+        let child = format!("{cmd} {name}");
+        subcommands.push((name.to_string(), about.clone()));
+        transitions.push((name.to_string(), child.clone()));
+        for alias in sub.get_visible_aliases() {
+            subcommands.push((alias.to_string(), about.clone()));
+            transitions.push((alias.to_string(), child.clone()));
+        }
+        // `help` repeats every subcommand, its name is enough.
+        if name != "help" {
+            children.push((sub, child));
+        }
+    }
+
+    let options = command
+        .get_arguments()
+        .filter(|arg| !arg.is_positional() && !arg.is_hide_set())
+        .map(|arg| Opt {
+            flags: arg
+                .get_long_and_visible_aliases()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|long| format!("--{long}"))
+                .chain(
+                    arg.get_short_and_visible_aliases()
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|short| format!("-{short}")),
+                )
+                .collect(),
+            help: help(arg),
+            values: if arg.get_action().takes_values() {
+                values(arg)
+            } else {
+                Values::None
+            },
+        })
+        .filter(|opt| !opt.flags.is_empty())
+        .collect();
+
+    let positionals = match source {
+        Some(source) => Values::Source(source),
+        None => command
+            .get_positionals()
+            .find(|arg| !arg.is_hide_set())
+            .map_or(Values::Any, values),
+    };
+
+    nodes.push(Node {
+        cmd,
+        subcommands,
+        transitions,
+        options,
+        positionals,
+    });
+    for (sub, child) in children {
+        collect(sub, child, dynamic, nodes);
+    }
+}
+
+fn help(arg: &Arg) -> String {
+    let help = arg
+        .get_help()
+        .map(|help| help.to_string())
+        .unwrap_or_default();
+    // Only the first line, without the `[default: ...]` note.
+    let help = help.lines().next().unwrap_or_default();
+    help.split(" [default")
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn values(arg: &Arg) -> Values {
+    let possible: Vec<String> = arg
+        .get_possible_values()
+        .iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| value.get_name().to_string())
+        .collect();
+    if !possible.is_empty() {
+        return Values::List(possible);
+    }
+    match arg.get_value_hint() {
+        ValueHint::AnyPath | ValueHint::FilePath | ValueHint::DirPath => Values::Files,
+        _ => Values::Any,
+    }
+}
+
+/// Quotes text for POSIX shells, and for zsh and fish which share the rule.
+fn sh_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+fn bash(bin: &str, nodes: &[Node]) -> String {
+    let mut out = format!(
+        "_{bin}() {{\n    \
+         local cur=\"${{COMP_WORDS[COMP_CWORD]}}\" prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"\n    \
+         local cmd={bin} i\n    \
+         for ((i = 1; i < COMP_CWORD; i++)); do\n        \
+         case \"$cmd ${{COMP_WORDS[i]}}\" in\n"
+    );
+    for node in nodes {
+        for (word, child) in &node.transitions {
+            out.push_str(&format!(
+                "            {}) cmd={} ;;\n",
+                sh_quote(&format!("{} {word}", node.cmd)),
+                sh_quote(child)
+            ));
+        }
+    }
+    out.push_str("        esac\n    done\n\n    COMPREPLY=()\n    case \"$cmd\" in\n");
+    for node in nodes {
+        out.push_str(&format!("        {})\n", sh_quote(&node.cmd)));
+        out.push_str("            case \"$prev\" in\n");
+        for opt in &node.options {
+            if let Some(reply) = bash_reply(&opt.values) {
+                out.push_str(&format!(
+                    "                {}) {reply}; return ;;\n",
+                    opt.flags.join("|")
+                ));
+            }
+        }
+        out.push_str("            esac\n");
+        let flags: Vec<&str> = node
+            .options
+            .iter()
+            .flat_map(|opt| opt.flags.iter().map(String::as_str))
+            .collect();
+        out.push_str(&format!(
+            "            if [[ \"$cur\" == -* ]]; then\n                \
+             COMPREPLY=($(compgen -W {} -- \"$cur\"))\n",
+            sh_quote(&flags.join(" "))
+        ));
+        if !node.subcommands.is_empty() {
+            let names: Vec<&str> = node
+                .subcommands
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect();
+            out.push_str(&format!(
+                "            else\n                COMPREPLY=($(compgen -W {} -- \"$cur\"))\n",
+                sh_quote(&names.join(" "))
+            ));
+        } else if let Some(reply) = bash_reply(&node.positionals) {
+            out.push_str(&format!("            else\n                {reply}\n"));
+        }
+        out.push_str("            fi\n            ;;\n");
+    }
+    out.push_str(&format!("    esac\n}}\n\ncomplete -F _{bin} {bin}\n"));
+    out
+}
+
+/// What bash offers for values, `None` for flags taking no value.
+fn bash_reply(values: &Values) -> Option<String> {
+    match values {
+        Values::None => None,
+        Values::Any => Some(":".to_string()),
+        Values::Files => Some("COMPREPLY=($(compgen -f -- \"$cur\"))".to_string()),
+        Values::List(list) => Some(format!(
+            "COMPREPLY=($(compgen -W {} -- \"$cur\"))",
+            sh_quote(&list.join(" "))
+        )),
+        Values::Source(source) => Some(format!(
+            "COMPREPLY=($(compgen -W \"$({source} 2>/dev/null)\" -- \"$cur\"))"
+        )),
+    }
+}
+
+fn zsh(bin: &str, nodes: &[Node]) -> String {
+    let mut out = format!(
+        "#compdef {bin}\n\n_{bin}() {{\n    \
+         local cmd={bin} i\n    \
+         for ((i = 2; i < CURRENT; i++)); do\n        \
+         case \"$cmd ${{words[i]}}\" in\n"
+    );
+    for node in nodes {
+        for (word, child) in &node.transitions {
+            out.push_str(&format!(
+                "            ({}) cmd={} ;;\n",
+                sh_quote(&format!("{} {word}", node.cmd)),
+                sh_quote(child)
+            ));
+        }
+    }
+    out.push_str(
+        "        esac\n    done\n\n    \
+         local prev=\"${words[CURRENT-1]}\" cur=\"${words[CURRENT]}\"\n    \
+         local -a items\n    \
+         case \"$cmd\" in\n",
+    );
+    for node in nodes {
+        out.push_str(&format!("        ({})\n", sh_quote(&node.cmd)));
+        out.push_str("            case \"$prev\" in\n");
+        for opt in &node.options {
+            if let Some(reply) = zsh_reply(&opt.values) {
+                out.push_str(&format!(
+                    "                ({}) {reply}; return ;;\n",
+                    opt.flags.join("|")
+                ));
+            }
+        }
+        out.push_str("            esac\n");
+        let options: Vec<String> = node
+            .options
+            .iter()
+            .flat_map(|opt| {
+                opt.flags
+                    .iter()
+                    .map(|flag| sh_quote(&format!("{flag}:{}", opt.help)))
+            })
+            .collect();
+        out.push_str(&format!(
+            "            if [[ \"$cur\" == -* ]]; then\n                \
+             items=({})\n                \
+             _describe option items\n",
+            options.join(" ")
+        ));
+        if !node.subcommands.is_empty() {
+            let commands: Vec<String> = node
+                .subcommands
+                .iter()
+                .map(|(name, about)| sh_quote(&format!("{name}:{about}")))
+                .collect();
+            out.push_str(&format!(
+                "            else\n                \
+                 items=({})\n                \
+                 _describe command items\n",
+                commands.join(" ")
+            ));
+        } else if let Some(reply) = zsh_reply(&node.positionals) {
+            out.push_str(&format!("            else\n                {reply}\n"));
+        }
+        out.push_str("            fi\n            ;;\n");
+    }
+    out.push_str(&format!("    esac\n}}\n\n_{bin} \"$@\"\n"));
+    out
+}
+
+fn zsh_reply(values: &Values) -> Option<String> {
+    match values {
+        Values::None => None,
+        Values::Any => Some(":".to_string()),
+        Values::Files => Some("_files".to_string()),
+        Values::List(list) => Some(format!(
+            "compadd -- {}",
+            list.iter()
+                .map(|value| sh_quote(value))
+                .collect::<Vec<_>>()
+                .join(" ")
+        )),
+        Values::Source(source) => Some(format!("compadd -- ${{(f)\"$({source} 2>/dev/null)\"}}")),
+    }
+}
+
+fn fish(bin: &str, nodes: &[Node]) -> String {
+    let mut out = format!(
+        "function __{bin}_cmd\n    \
+         set -l cmd {bin}\n    \
+         for word in (commandline -opc)[2..-1]\n        \
+         switch \"$cmd $word\"\n"
+    );
+    for node in nodes {
+        for (word, child) in &node.transitions {
+            out.push_str(&format!(
+                "            case {}\n                set cmd {}\n",
+                sh_quote(&format!("{} {word}", node.cmd)),
+                sh_quote(child)
+            ));
+        }
+    }
+    out.push_str(&format!(
+        "        end\n    end\n    echo $cmd\nend\n\ncomplete -c {bin} -f\n"
+    ));
+    for node in nodes {
+        let condition = sh_quote(&format!("test (__{bin}_cmd) = {}", sh_quote(&node.cmd)));
+        for (name, about) in &node.subcommands {
+            out.push_str(&format!(
+                "complete -c {bin} -n {condition} -a {} -d {}\n",
+                sh_quote(name),
+                sh_quote(about)
+            ));
+        }
+        for opt in &node.options {
+            let mut line = format!("complete -c {bin} -n {condition}");
+            for flag in &opt.flags {
+                match flag.strip_prefix("--") {
+                    Some(long) => line.push_str(&format!(" -l {long}")),
+                    None => line.push_str(&format!(" -s {}", &flag[1..])),
+                }
+            }
+            match &opt.values {
+                Values::None => {}
+                Values::Any => line.push_str(" -x"),
+                Values::Files => line.push_str(" -r -F"),
+                Values::List(list) => {
+                    line.push_str(&format!(" -x -a {}", sh_quote(&list.join(" "))))
+                }
+                Values::Source(source) => line.push_str(&format!(
+                    " -x -a {}",
+                    sh_quote(&format!("({source} 2>/dev/null)"))
+                )),
+            }
+            line.push_str(&format!(" -d {}\n", sh_quote(&opt.help)));
+            out.push_str(&line);
+        }
+        if node.subcommands.is_empty() {
+            match &node.positionals {
+                Values::Files => out.push_str(&format!("complete -c {bin} -n {condition} -F\n")),
+                Values::Source(source) => out.push_str(&format!(
+                    "complete -c {bin} -n {condition} -a {}\n",
+                    sh_quote(&format!("({source} 2>/dev/null)"))
+                )),
+                _ => {}
+            }
+        }
+    }
+    out
+}
+
+/// Quotes text as a PowerShell string literal.
+fn ps_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+fn ps_list<'a>(items: impl Iterator<Item = &'a str>) -> String {
+    format!("@({})", items.map(ps_quote).collect::<Vec<_>>().join(", "))
+}
+
+fn powershell(bin: &str, nodes: &[Node]) -> String {
+    let mut out = format!(
+        "Register-ArgumentCompleter -Native -CommandName {bin} -ScriptBlock {{\n    \
+         param($wordToComplete, $commandAst, $cursorPosition)\n    \
+         $words = @($commandAst.CommandElements | Where-Object {{ $_.Extent.EndOffset -lt $cursorPosition }} | ForEach-Object {{ $_.ToString() }})\n    \
+         $cmd = '{bin}'\n    \
+         foreach ($word in $words | Select-Object -Skip 1) {{\n        \
+         switch (\"$cmd $word\") {{\n"
+    );
+    for node in nodes {
+        for (word, child) in &node.transitions {
+            out.push_str(&format!(
+                "            {} {{ $cmd = {} }}\n",
+                ps_quote(&format!("{} {word}", node.cmd)),
+                ps_quote(child)
+            ));
+        }
+    }
+    out.push_str(
+        "        }\n    }\n    \
+         $prev = if ($words.Count -gt 1) { $words[-1] } else { '' }\n    \
+         $candidates = @()\n",
+    );
+    for (i, node) in nodes.iter().enumerate() {
+        let keyword = if i == 0 { "if" } else { "elseif" };
+        out.push_str(&format!(
+            "    {keyword} ($cmd -eq {}) {{\n",
+            ps_quote(&node.cmd)
+        ));
+        let mut first = true;
+        for opt in &node.options {
+            let reply = match &opt.values {
+                Values::None => continue,
+                // Nothing offered falls back to paths.
+                Values::Any | Values::Files => "return".to_string(),
+                Values::List(list) => {
+                    format!("$candidates = {}", ps_list(list.iter().map(String::as_str)))
+                }
+                Values::Source(source) => format!("$candidates = @({source} 2>$null)"),
+            };
+            let keyword = if first { "if" } else { "elseif" };
+            first = false;
+            out.push_str(&format!(
+                "        {keyword} ($prev -in {}) {{ {reply} }}\n",
+                ps_list(opt.flags.iter().map(String::as_str))
+            ));
+        }
+        let keyword = if first { "if" } else { "elseif" };
+        let flags = node
+            .options
+            .iter()
+            .flat_map(|opt| opt.flags.iter().map(String::as_str));
+        out.push_str(&format!(
+            "        {keyword} ($wordToComplete -like '-*') {{ $candidates = {} }}\n",
+            ps_list(flags)
+        ));
+        if !node.subcommands.is_empty() {
+            out.push_str(&format!(
+                "        else {{ $candidates = {} }}\n",
+                ps_list(node.subcommands.iter().map(|(name, _)| name.as_str()))
+            ));
+        } else if let Values::Source(source) = &node.positionals {
+            out.push_str(&format!(
+                "        else {{ $candidates = @({source} 2>$null) }}\n"
+            ));
+        }
+        out.push_str("    }\n");
+    }
+    out.push_str(
+        "    $candidates | Where-Object { $_ -like \"$wordToComplete*\" } | ForEach-Object {\n        \
+         [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)\n    \
+         }\n}\n",
+    );
+    out
+}
@@ -0,0 +1,789 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::backend::REDIRECT_STATUSES;
+use crate::target;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use shellexpand::tilde;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// Where the config was kept before it moved to the platform's config
+/// directory, before expanding `~`.
+pub const LEGACY_CONFIG_FILE: &str = "~/.config/shurl_config.toml";
+
+/// Location of the user's config file, `shurl/config.toml` in the platform's
+/// config directory: `$XDG_CONFIG_HOME` or `~/.config` on Linux,
+/// `~/Library/Application Support` on macOS and `%APPDATA%` on Windows.
+pub fn config_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from(tilde("~/.config").as_ref()))
+        .join("shurl")
+        .join("config.toml")
+}
+
+/// Moves a config left at [`LEGACY_CONFIG_FILE`] to [`config_path`], unless
+/// there's a config there already. Returns where it was moved from.
+pub fn migrate_legacy_config() -> Result<Option<PathBuf>, String> {
+    let legacy = PathBuf::from(tilde(LEGACY_CONFIG_FILE).as_ref());
+    let path = config_path();
+    if path.exists() || !legacy.is_file() {
+        return Ok(None);
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("failed to create {}: {e}", dir.display()))?;
+    }
+    // Renaming fails when the config directory is on another file system.
+    if fs::rename(&legacy, &path).is_err() {
+        fs::copy(&legacy, &path).map_err(|e| {
+            format!(
+                "failed to copy {} to {}: {e}",
+                legacy.display(),
+                path.display()
+            )
+        })?;
+        fs::remove_file(&legacy)
+            .map_err(|e| format!("failed to remove {}: {e}", legacy.display()))?;
+    }
+    Ok(Some(legacy))
+}
+
+/// Settings of the config file and what they're for, in the order they're
+/// documented.
+pub const KEYS: &[(&str, &str)] = &[
+    ("repo_path", "Git repository the links are kept in."),
+    ("name", "Name commits are made with, also recorded as the owner of new links."),
+    ("email", "Email commits are made with."),
+    ("clean_urls", "Write redirects as slug/index.html so they're served at /slug."),
+    ("backends", "Outputs kept in sync with the manifest: html, netlify, cloudflare, vercel, nginx, apache, caddy and map."),
+    ("html_fallback", "Keep writing the HTML redirect pages when the html backend isn't selected."),
+    ("federation_refresh_hours", "Partner repositories are fetched again when their snapshot is older than this."),
+    ("redirect_status", "Status code of server-side redirects for links without their own."),
+    ("base_url", "Public URL the repository is served from, e.g. https://s.example.com/."),
+    ("clipd_domains", "Domains whose URLs shurl clipd shortens when they're copied."),
+    ("clipd_interval_ms", "How often shurl clipd checks the clipboard."),
+    ("github_token", "Token for the GitHub API, $GITHUB_TOKEN if not set."),
+    ("jira_url", "Base URL of the Jira site --issue keys like ORG-1234 belong to."),
+    ("jira_user", "Email of the Jira Cloud account jira_token belongs to."),
+    ("jira_token", "Jira API token, $JIRA_API_TOKEN if not set."),
+    ("verify_issues", "Check that the --issue of a new link exists before adding it."),
+    ("serve_address", "Address shurl serve listens on."),
+    ("serve_port", "Port shurl serve listens on."),
+    ("stats_referrers", "Also record where hits counted by shurl serve came from."),
+    ("api_token", "Bearer token for shurl serve --api, $SHURL_API_TOKEN if not set."),
+    ("api_batch_size", "Links created through the API are committed together, up to this many at once."),
+    ("api_batch_ms", "How long the API waits for more links before committing."),
+    ("gone_backends", "Backends answering 410 Gone for removed links rather than 404."),
+    ("trash_retention_days", "Days removed links stay in the trash, 0 keeps them forever."),
+    ("cloudflare_account_id", "Cloudflare account of shurl sync-kv."),
+    ("cloudflare_kv_namespace", "Workers KV namespace of shurl sync-kv."),
+    ("cloudflare_api_token", "API token with Workers KV write access, $CLOUDFLARE_API_TOKEN if not set."),
+    ("ci_docs_site", "Public URL of the documentation site shurl ci-docs links to."),
+    ("ci_docs_dir", "Directory of the documentation sources in the docs repository."),
+    ("ci_docs_extensions", "Extensions of documentation source files."),
+    ("ci_docs_page_suffix", "Replaces the source extension in published page URLs."),
+    ("qr_assets", "Formats to save the QR code of every link in under assets/qr/: png and svg."),
+    ("copy_short_url", "Copy the short URL of new links to the clipboard, like --copy."),
+    ("screenshots", "Take a screenshot of the target of new links, like --screenshot."),
+    ("previews", "Fetch OpenGraph previews of the target of new links, like --preview."),
+    ("screenshot_command", "Renderer saving a PNG screenshot of {url} to {output}, headless Chromium or Chrome if not set."),
+    ("slug_style", "How names of links created without one are made: random letters, hash of the target for the same name on every machine, or words like crisp-otter-42."),
+    ("slug_length", "Length of names made for links, 5 random or 7 hash characters if not set."),
+    ("slug_alphabet", "Characters of names made for links: lowercase, alphanumeric or base58, lowercase random or alphanumeric hash names if not set."),
+    ("slug_words", "Number of words of names made with slug_style = \"words\"."),
+    ("slug_separator", "What the words and number of those names are joined with: -, _, . or nothing."),
+    ("allowed_schemes", "Schemes targets may have, e.g. add mailto. javascript, data and vbscript are never allowed."),
+    ("https_policy", "What happens to http:// targets: allow them, upgrade them to https:// or require https:// instead."),
+    ("strip_params", "Query parameters left out of targets, a trailing * matching any end, e.g. [\"utm_*\", \"fbclid\"]."),
+    ("feed_size", "Number of the newest links in feed.xml, 0 for no feed."),
+    ("sitemap_pages", "List the pages of the links in sitemap.xml besides the indexes, so search engines index them too."),
+    ("noindex", "Ask search engines not to index the pages of links, unless a link is added with --index."),
+    ("referrer_policy", "What targets learn of where readers came from, unless a link has its own --referrer: no-referrer, origin, strict-origin or no-referrer-when-downgrade. Browsers send the origin if not set."),
+    ("canonical", "Name the target as the canonical URL of the pages of links, so search engines credit it with them."),
+    ("robots_txt", "Write a robots.txt keeping crawlers off the pages of links not to be indexed, pointing them at sitemap.xml."),
+    ("query_passthrough", "Add the query string and fragment readers open a short link with to its target, e.g. ?ref=mail, in its page and shurl serve's redirects."),
+    ("language", "Language of the text of the pages of links, unless a link has its own --lang: en, de, es, fr, it, nl or pt."),
+    ("redirect_delay", "Seconds the pages of links wait before redirecting, showing where they lead, unless a link has its own --delay."),
+    ("expired_notice", "Show a notice on the pages of links past their expiry date instead of redirecting, checked in the reader's browser."),
+    ("index_order", "Order of the links on the index: newest first, by slug, or grouped under their tags or the domains of their targets."),
+    ("index_page_size", "Number of links past which the index is split up, 0 for one page whatever the number."),
+    ("index_split", "How the index is split up past index_page_size: into pages of that many links, or leaving the links in directories to the indexes of their directories."),
+    ("theme", "Colors of the generated pages: light, dark, or auto to follow the reader's system."),
+    ("custom_css", "Stylesheet copied into assets/ of the repository and loaded by the generated pages after their own, relative paths being in the repository."),
+    ("analytics", "HTML put into the <head> of the pages of links and the index, e.g. the script tag of Plausible or GoatCounter. It may use {{slug}} and {{target}} of the link."),
+    ("branch", "Branch of origin the repository is pushed to."),
+    ("commit_style", "Messages of the commits shurl makes: plain, or conventional such as `feat(links): add abcde -> https://...` for commitlint."),
+    ("default_profile", "Profile used without --profile."),
+    ("profiles", "Named [profiles.<name>] tables with their own repo_path, base_url, name, email and branch, selected with --profile."),
+];
+
+/// Settings without an environment variable: profiles are selected with
+/// `SHURL_PROFILE` instead.
+const NO_ENV_KEYS: &[&str] = &["default_profile", "profiles"];
+
+/// Environment variable replacing a setting, e.g. `SHURL_REPO_PATH`.
+pub fn env_var(key: &str) -> Option<String> {
+    (!NO_ENV_KEYS.contains(&key)).then(|| format!("SHURL_{}", key.to_uppercase()))
+}
+
+/// Settings a profile can have, the ones telling repositories apart.
+pub const PROFILE_KEYS: &[&str] = &["repo_path", "base_url", "name", "email", "branch"];
+
+pub fn check_key(key: &str) -> Result<(), String> {
+    if KEYS.iter().any(|(known, _)| *known == key) {
+        return Ok(());
+    }
+    Err(format!(
+        "unknown config key `{key}`, `shurl manpage` lists them all"
+    ))
+}
+
+/// Keys of the config file that aren't settings, which would be ignored.
+pub fn unknown_keys(content: &str) -> Vec<String> {
+    let Ok(table) = content.parse::<toml::Table>() else {
+        return vec![];
+    };
+    table
+        .keys()
+        .filter(|key| check_key(key).is_err())
+        .cloned()
+        .collect()
+}
+
+/// Sets a setting in the config file, keeping the rest of the file and its
+/// comments as they are. The value is TOML, e.g. `true` or `["html"]`, and
+/// anything else is taken as a string. With a profile, the settings of
+/// [`PROFILE_KEYS`] are changed in its `[profiles.<name>]` table, the others
+/// are shared by every profile. Nothing is written unless the config stays
+/// valid.
+pub fn set_key(path: &Path, profile: Option<&str>, key: &str, value: &str) -> Result<(), String> {
+    let content =
+        fs::read_to_string(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    let mut document: toml_edit::Document = content
+        .parse()
+        .map_err(|e| format!("failed to parse {}: {e}", path.display()))?;
+    let value = value
+        .parse::<toml_edit::Value>()
+        .unwrap_or_else(|_| value.into());
+    match profile.filter(|_| PROFILE_KEYS.contains(&key)) {
+        Some(profile) => {
+            let profiles = document["profiles"].or_insert(toml_edit::table());
+            // Keeps `profiles` from getting a header of its own.
+            if let Some(profiles) = profiles.as_table_mut() {
+                profiles.set_implicit(true);
+            }
+            profiles[profile].or_insert(toml_edit::table())[key] = toml_edit::value(value);
+        }
+        None => document[key] = toml_edit::value(value),
+    }
+
+    let content = document.to_string();
+    let cfg: ShurlConfig =
+        toml::from_str(&content).map_err(|e| format!("invalid value of `{key}`: {e}"))?;
+    cfg.validate()?;
+    fs::write(path, content).map_err(|e| format!("failed to write {}: {e}", path.display()))
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ShurlConfig {
+    pub repo_path: PathBuf,
+    pub name: String,
+    pub email: String,
+    /// Write redirects as `slug/index.html` so they're served at `/slug`.
+    pub clean_urls: bool,
+    /// Outputs kept in sync with the manifest on every run.
+    pub backends: Vec<Backend>,
+    /// Keep writing the HTML redirect pages even when the `html` backend isn't
+    /// selected, as a fallback for server-side redirects and for local preview.
+    pub html_fallback: bool,
+    /// Partner repositories are fetched again when their snapshot is older
+    /// than this many hours.
+    pub federation_refresh_hours: u64,
+    /// Status code of server-side redirects for links without their own.
+    pub redirect_status: u16,
+    /// Public URL the repository is served from, e.g. `https://s.example.com/`.
+    pub base_url: Option<String>,
+    /// Domains whose URLs `shurl clipd` shortens when they're copied.
+    pub clipd_domains: Vec<String>,
+    /// How often `shurl clipd` checks the clipboard.
+    pub clipd_interval_ms: u64,
+    /// Token for the GitHub API, `$GITHUB_TOKEN` is used if not set.
+    pub github_token: Option<String>,
+    /// Base URL of the Jira site `--issue` keys like `ORG-1234` belong to.
+    pub jira_url: Option<String>,
+    /// Email of the Jira Cloud account `jira_token` belongs to. Without it
+    /// the token is sent as a personal access token of self-hosted Jira.
+    pub jira_user: Option<String>,
+    /// Jira API token, `$JIRA_API_TOKEN` is used if not set.
+    pub jira_token: Option<String>,
+    /// Checks that the `--issue` of a new link exists before adding it.
+    pub verify_issues: bool,
+    /// Address and port `shurl serve` listens on.
+    pub serve_address: String,
+    pub serve_port: u16,
+    /// Also record where hits counted by `shurl serve` came from.
+    pub stats_referrers: bool,
+    /// Bearer token for `shurl serve --api`, `$SHURL_API_TOKEN` is used if
+    /// not set.
+    pub api_token: Option<String>,
+    /// Links created through the API are committed together, up to this
+    /// many at once or whatever arrived within `api_batch_ms`.
+    pub api_batch_size: usize,
+    pub api_batch_ms: u64,
+    /// Backends answering 410 Gone for removed links rather than 404. For
+    /// `html` that's a page saying the link was removed, static hosts still
+    /// serve it with 200.
+    pub gone_backends: Vec<Backend>,
+    /// Days removed links stay in the trash before they are deleted for
+    /// good, 0 keeps them forever.
+    pub trash_retention_days: u32,
+    /// Cloudflare account and Workers KV namespace ids for `shurl sync-kv`.
+    pub cloudflare_account_id: Option<String>,
+    pub cloudflare_kv_namespace: Option<String>,
+    /// API token with Workers KV write access, `$CLOUDFLARE_API_TOKEN` is
+    /// used if not set.
+    pub cloudflare_api_token: Option<String>,
+    /// Public URL of the documentation site `shurl ci-docs` links to.
+    pub ci_docs_site: Option<String>,
+    /// Directory of the documentation sources in the docs repository.
+    pub ci_docs_dir: String,
+    /// Extensions of documentation source files.
+    pub ci_docs_extensions: Vec<String>,
+    /// Replaces the source extension in published page URLs.
+    pub ci_docs_page_suffix: String,
+    /// Formats to save the QR code of every link in, under `assets/qr/`.
+    /// Needs `base_url`.
+    pub qr_assets: Vec<QrFormat>,
+    /// Copies the short URL of new links to the clipboard, like `--copy`.
+    pub copy_short_url: bool,
+    /// Takes a screenshot of the target of new links, like `--screenshot`.
+    pub screenshots: bool,
+    /// Fetches the OpenGraph preview of the target of new links, like
+    /// `--preview`.
+    pub previews: bool,
+    /// Renderer saving a PNG screenshot of `{url}` to `{output}`, e.g.
+    /// `["wkhtmltoimage", "{url}", "{output}"]`. Headless Chromium or Chrome
+    /// is used if not set.
+    pub screenshot_command: Vec<String>,
+    /// How names of links created without one are made.
+    pub slug_style: SlugStyle,
+    /// Length and characters of those names, depending on `slug_style` if
+    /// not set.
+    pub slug_length: Option<usize>,
+    pub slug_alphabet: Option<SlugAlphabet>,
+    /// Number of words of names made of words, and what they're joined with.
+    pub slug_words: usize,
+    pub slug_separator: String,
+    /// Schemes targets may have. Those of [`target::BLOCKED_SCHEMES`] never
+    /// are.
+    pub allowed_schemes: Vec<String>,
+    /// What happens to `http://` targets.
+    pub https_policy: HttpsPolicy,
+    /// Query parameters left out of targets, e.g. `utm_*` for every
+    /// parameter starting with `utm_`.
+    pub strip_params: Vec<String>,
+    /// Number of the newest links in `feed.xml`, none written if 0.
+    pub feed_size: usize,
+    /// Whether `sitemap.xml` lists the pages of the links, not only the
+    /// indexes.
+    pub sitemap_pages: bool,
+    /// Whether the pages of links ask search engines not to index them.
+    pub noindex: bool,
+    /// Referrer policy of the pages of links and of `shurl serve`'s
+    /// redirects, the browser's default if not set.
+    pub referrer_policy: Option<ReferrerPolicy>,
+    /// Whether the pages of links have a `rel=canonical` link to the target.
+    pub canonical: bool,
+    /// Whether to write `robots.txt`.
+    pub robots_txt: bool,
+    /// Whether the query string and fragment of short links are passed on to
+    /// the target.
+    pub query_passthrough: bool,
+    /// Language the pages of links are written in, see [`crate::i18n`].
+    pub language: String,
+    /// Seconds before the pages of links redirect.
+    pub redirect_delay: u32,
+    /// Whether the pages of links say they've expired once they have,
+    /// rather than redirect.
+    pub expired_notice: bool,
+    /// Snippet of an analytics service put into the pages of links and the
+    /// index, with what it loads allowed by their Content Security Policy.
+    pub analytics: Option<String>,
+    pub index_order: IndexOrder,
+    /// Number of links past which the index is split up as `index_split`
+    /// says, never if 0.
+    pub index_page_size: usize,
+    pub index_split: IndexSplit,
+    pub theme: Theme,
+    /// Stylesheet loaded by the generated pages after the theme.
+    pub custom_css: Option<String>,
+    pub branch: String,
+    pub commit_style: CommitStyle,
+    pub default_profile: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Name of the profile in use, if any.
+    #[serde(skip)]
+    pub profile: Option<String>,
+    /// Who the changes of this run are for, from `--author-name` and
+    /// `--author-email`, when it's not the one committing them, e.g. for a
+    /// team bot.
+    #[serde(skip)]
+    pub author_name: Option<String>,
+    #[serde(skip)]
+    pub author_email: Option<String>,
+}
+
+/// A `[profiles.<name>]` table, replacing the settings it has when the
+/// profile is used, e.g. to keep links for two domains in two repositories.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repo_path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+}
+
+impl Default for ShurlConfig {
+    fn default() -> Self {
+        Self {
+            repo_path: PathBuf::from("/path_to_valid_and_empty_git_repo"),
+            name: "shurl".to_string(),
+            email: "example@example.com".to_string(),
+            clean_urls: false,
+            backends: vec![Backend::Html],
+            html_fallback: false,
+            federation_refresh_hours: 24,
+            redirect_status: 301,
+            base_url: None,
+            clipd_domains: vec![],
+            clipd_interval_ms: 500,
+            github_token: None,
+            jira_url: None,
+            jira_user: None,
+            jira_token: None,
+            verify_issues: false,
+            serve_address: "127.0.0.1".to_string(),
+            serve_port: 8080,
+            stats_referrers: false,
+            api_token: None,
+            api_batch_size: 20,
+            api_batch_ms: 2000,
+            gone_backends: vec![Backend::Html],
+            trash_retention_days: 30,
+            cloudflare_account_id: None,
+            cloudflare_kv_namespace: None,
+            cloudflare_api_token: None,
+            ci_docs_site: None,
+            ci_docs_dir: "docs".to_string(),
+            ci_docs_extensions: vec!["md".to_string()],
+            ci_docs_page_suffix: ".html".to_string(),
+            qr_assets: vec![],
+            copy_short_url: false,
+            screenshots: false,
+            previews: false,
+            screenshot_command: vec![],
+            slug_style: SlugStyle::Random,
+            slug_length: None,
+            slug_alphabet: None,
+            slug_words: 2,
+            slug_separator: "-".to_string(),
+            allowed_schemes: vec!["http".to_string(), "https".to_string()],
+            https_policy: HttpsPolicy::Allow,
+            strip_params: vec![],
+            feed_size: 20,
+            sitemap_pages: false,
+            noindex: false,
+            referrer_policy: None,
+            canonical: false,
+            robots_txt: false,
+            query_passthrough: false,
+            language: "en".to_string(),
+            redirect_delay: 0,
+            expired_notice: false,
+            analytics: None,
+            index_order: IndexOrder::Newest,
+            index_page_size: 0,
+            index_split: IndexSplit::Pages,
+            theme: Theme::Light,
+            custom_css: None,
+            branch: "master".to_string(),
+            commit_style: CommitStyle::Plain,
+            default_profile: None,
+            profiles: BTreeMap::new(),
+            profile: None,
+            author_name: None,
+            author_email: None,
+        }
+    }
+}
+
+impl ShurlConfig {
+    /// Name of the author of the commits, also the owner of new links.
+    pub fn author(&self) -> &str {
+        self.author_name.as_deref().unwrap_or(&self.name)
+    }
+
+    pub fn author_email(&self) -> &str {
+        self.author_email.as_deref().unwrap_or(&self.email)
+    }
+
+    pub fn writes_html_pages(&self) -> bool {
+        self.html_fallback || self.backends.contains(&Backend::Html)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        toml::from_str(&content).map_err(|e| format!("failed to parse {}: {e}", path.display()))
+    }
+
+    /// Applies the settings of a profile, `default_profile` if `name` is
+    /// `None`. Without either, the config stays as it is.
+    pub fn with_profile(mut self, name: Option<&str>) -> Result<Self, String> {
+        let Some(name) = name.or(self.default_profile.as_deref()) else {
+            return Ok(self);
+        };
+        let profile = self.profiles.get(name).cloned().ok_or_else(|| {
+            format!("no profile named `{name}`, add a [profiles.{name}] table to the config")
+        })?;
+        if let Some(repo_path) = profile.repo_path {
+            self.repo_path = repo_path;
+        }
+        if let Some(base_url) = profile.base_url {
+            self.base_url = Some(base_url);
+        }
+        if let Some(name) = profile.name {
+            self.name = name;
+        }
+        if let Some(email) = profile.email {
+            self.email = email;
+        }
+        if let Some(branch) = profile.branch {
+            self.branch = branch;
+        }
+        self.profile = Some(name.to_string());
+        Ok(self)
+    }
+
+    /// Replaces settings with the `SHURL_*` environment variables that are
+    /// set, e.g. `SHURL_BASE_URL`. Values are TOML as for `shurl config set`,
+    /// anything else is taken as a string, and lists can also be comma
+    /// separated, e.g. `SHURL_BACKENDS=html,netlify`.
+    pub fn with_env(self) -> Result<Self, String> {
+        let mut table =
+            toml::Table::try_from(&self).map_err(|e| format!("failed to serialize config: {e}"))?;
+        let mut cfg = self;
+        for (key, _) in KEYS {
+            let Some(var) = env_var(key) else {
+                continue;
+            };
+            let Ok(text) = env::var(&var) else {
+                continue;
+            };
+            let parsed = toml::Value::deserialize(toml::de::ValueDeserializer::new(&text))
+                .unwrap_or_else(|_| toml::Value::String(text.clone()));
+            let parsed = match (table.get(*key), parsed) {
+                (Some(toml::Value::Array(_)), toml::Value::String(text)) => toml::Value::Array(
+                    text.split(',')
+                        .map(|item| toml::Value::String(item.trim().to_string()))
+                        .filter(|item| item.as_str() != Some(""))
+                        .collect(),
+                ),
+                (_, parsed) => parsed,
+            };
+
+            // Strings that happen to be valid TOML, e.g. a name of digits,
+            // are still strings.
+            let mut error = None;
+            for value in [parsed, toml::Value::String(text)] {
+                let mut candidate = table.clone();
+                candidate.insert(key.to_string(), value);
+                match Self::deserialize(candidate.clone()) {
+                    Ok(new) => {
+                        cfg = Self {
+                            profile: cfg.profile.take(),
+                            ..new
+                        };
+                        table = candidate;
+                        error = None;
+                        break;
+                    }
+                    Err(e) => error = Some(e.message().to_string()),
+                }
+            }
+            if let Some(e) = error {
+                return Err(format!("invalid {var}: {e}"));
+            }
+        }
+        Ok(cfg)
+    }
+
+    /// Names of the settings that differ between the two configs.
+    pub fn changed_keys(&self, other: &ShurlConfig) -> Vec<String> {
+        let (Ok(toml::Value::Table(old)), Ok(toml::Value::Table(new))) =
+            (toml::Value::try_from(self), toml::Value::try_from(other))
+        else {
+            return vec![];
+        };
+        let mut keys: Vec<String> = old
+            .keys()
+            .chain(new.keys())
+            .filter(|key| old.get(*key) != new.get(*key))
+            .cloned()
+            .collect();
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+
+    /// Checks settings that would otherwise only fail once they're used.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.backends.contains(&Backend::Netlify) && self.backends.contains(&Backend::Cloudflare)
+        {
+            return Err(
+                "the netlify and cloudflare backends both write _redirects, select only one"
+                    .to_string(),
+            );
+        }
+        if !REDIRECT_STATUSES.contains(&self.redirect_status) {
+            return Err(format!(
+                "invalid redirect_status {}: use one of {REDIRECT_STATUSES:?}",
+                self.redirect_status
+            ));
+        }
+        if let Some(base_url) = &self.base_url {
+            Url::parse(base_url).map_err(|e| format!("invalid base_url {base_url}: {e}"))?;
+        }
+        if self.slug_length == Some(0) {
+            return Err("slug_length must be at least 1".to_string());
+        }
+        check_slug_words(self.slug_words, &self.slug_separator)?;
+        for scheme in &self.allowed_schemes {
+            target::check_scheme(scheme).map_err(|e| format!("invalid allowed_schemes: {e}"))?;
+        }
+        for (name, profile) in &self.profiles {
+            if let Some(base_url) = &profile.base_url {
+                Url::parse(base_url)
+                    .map_err(|e| format!("invalid base_url {base_url} of profile `{name}`: {e}"))?;
+            }
+        }
+        if let Some(name) = &self.default_profile {
+            if !self.profiles.contains_key(name) {
+                return Err(format!(
+                    "default_profile `{name}` isn't one of the profiles"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Current value of a setting, its default if not set. Strings are given
+    /// as they are, anything else as TOML. `None` if it has no value.
+    pub fn get(&self, key: &str) -> Result<Option<String>, String> {
+        check_key(key)?;
+        let toml::Value::Table(table) =
+            toml::Value::try_from(self).map_err(|e| format!("failed to serialize config: {e}"))?
+        else {
+            return Ok(None);
+        };
+        Ok(table.get(key).map(|value| match value {
+            toml::Value::String(text) => text.clone(),
+            value => value.to_string(),
+        }))
+    }
+
+    /// Full shareable URL of a slug, if `base_url` is configured.
+    pub fn short_url(&self, slug: &str) -> Option<String> {
+        self.base_url
+            .as_ref()
+            .map(|base_url| format!("{}/{slug}", base_url.trim_end_matches('/')))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// Meta refresh pages, one per link.
+    Html,
+    /// Netlify's `_redirects` file.
+    Netlify,
+    /// Cloudflare Pages' `_redirects` file, which has its own limits.
+    Cloudflare,
+    /// The `redirects` array of `vercel.json`.
+    Vercel,
+    /// `map` blocks for nginx in `shurl.nginx.conf`.
+    Nginx,
+    /// mod_rewrite rules in Apache's `.htaccess`.
+    Apache,
+    /// `redir` directives for Caddy in `shurl.caddy`.
+    Caddy,
+    /// Tab separated `/slug` and target pairs in `shurl.map`, for Apache's
+    /// `RewriteMap` and CDNs taking redirect maps.
+    Map,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SlugStyle {
+    /// Random lowercase letters.
+    Random,
+    /// Derived from a hash of the target, so the same target gets the same
+    /// name wherever it's shortened.
+    Hash,
+    /// Words and a number, e.g. `crisp-otter-42`, easy to read out.
+    Words,
+}
+
+/// Checks `slug_words` and `slug_separator`, which have to give names that
+/// work in URLs and file names.
+pub fn check_slug_words(words: usize, separator: &str) -> Result<(), String> {
+    if words == 0 {
+        return Err("slug_words must be at least 1".to_string());
+    }
+    if !["", "-", "_", "."].contains(&separator) {
+        return Err(format!(
+            "invalid slug_separator `{separator}`: use -, _, . or nothing"
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SlugAlphabet {
+    /// `a` to `z`.
+    Lowercase,
+    /// Digits and both cases of letters, the characters of base62.
+    Alphanumeric,
+    /// Alphanumeric characters but `0`, `O`, `I` and `l`, which are easily
+    /// mistaken for each other.
+    Base58,
+}
+
+impl SlugAlphabet {
+    pub fn chars(self) -> &'static [u8] {
+        match self {
+            SlugAlphabet::Lowercase => b"abcdefghijklmnopqrstuvwxyz",
+            SlugAlphabet::Alphanumeric => {
+                b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz"
+            }
+            SlugAlphabet::Base58 => b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum HttpsPolicy {
+    /// `http://` targets are stored as they are.
+    Allow,
+    /// `http://` targets are stored as `https://`.
+    Upgrade,
+    /// `http://` targets are rejected.
+    Require,
+}
+
+/// Value of the `Referrer-Policy` readers leave the pages of links with.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReferrerPolicy {
+    /// Targets don't learn where readers came from.
+    NoReferrer,
+    /// Targets only learn the origin of the short links.
+    Origin,
+    /// Only the origin, and nothing when leaving https:// for http://.
+    StrictOrigin,
+    /// The whole short URL, except when leaving https:// for http://.
+    NoReferrerWhenDowngrade,
+}
+
+impl ReferrerPolicy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReferrerPolicy::NoReferrer => "no-referrer",
+            ReferrerPolicy::Origin => "origin",
+            ReferrerPolicy::StrictOrigin => "strict-origin",
+            ReferrerPolicy::NoReferrerWhenDowngrade => "no-referrer-when-downgrade",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexOrder {
+    Newest,
+    Slug,
+    /// Under a heading for each tag, links with several tags under each.
+    Tag,
+    /// Under a heading for the domain of each target.
+    Domain,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexSplit {
+    /// Into `index.html`, `index/2.html` and so on.
+    Pages,
+    /// Leaving links in directories to the index of their directory.
+    Namespaces,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum CommitStyle {
+    /// E.g. `Remove abcde`.
+    Plain,
+    /// Conventional Commits, e.g. `chore(links): remove abcde`.
+    Conventional,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    Light,
+    Dark,
+    /// Light or dark as the reader's system is.
+    Auto,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum QrFormat {
+    Png,
+    Svg,
+}
+
+impl QrFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            QrFormat::Png => "png",
+            QrFormat::Svg => "svg",
+        }
+    }
+}
@@ -0,0 +1,217 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+use toml::value::{Date, Datetime, Offset, Time};
+
+/// 9999-12-31T23:59:59Z, the last datetime with the four-digit year of TOML.
+const LAST: i64 = 253_402_300_799;
+
+/// Current time in UTC.
+pub fn now() -> Datetime {
+    from_system_time(SystemTime::now())
+}
+
+pub fn from_system_time(time: SystemTime) -> Datetime {
+    let secs = match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    };
+    from_unix(secs)
+}
+
+/// Converts seconds since the unix epoch into a UTC datetime.
+pub fn from_unix(secs: i64) -> Datetime {
+    let days = secs.div_euclid(86400);
+    let secs_of_day = secs.rem_euclid(86400);
+
+    // Days to civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    Datetime {
+        date: Some(Date {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+        }),
+        time: Some(Time {
+            hour: (secs_of_day / 3600) as u8,
+            minute: (secs_of_day % 3600 / 60) as u8,
+            second: (secs_of_day % 60) as u8,
+            nanosecond: 0,
+        }),
+        offset: Some(Offset::Z),
+    }
+}
+
+/// Converts a datetime back into seconds since the unix epoch. Missing parts
+/// count as midnight UTC.
+pub fn to_unix(datetime: &Datetime) -> i64 {
+    let (year, month, day) = match datetime.date {
+        Some(date) => (
+            i64::from(date.year),
+            i64::from(date.month),
+            i64::from(date.day),
+        ),
+        None => (1970, 1, 1),
+    };
+    let secs_of_day = match datetime.time {
+        Some(time) => {
+            i64::from(time.hour) * 3600 + i64::from(time.minute) * 60 + i64::from(time.second)
+        }
+        None => 0,
+    };
+    let offset = match datetime.offset {
+        Some(Offset::Custom { minutes }) => i64::from(minutes) * 60,
+        _ => 0,
+    };
+
+    // Civil date to days, the inverse of the conversion in `from_unix`.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+
+    days * 86400 + secs_of_day - offset
+}
+
+/// Parses a point in time given on the command line, which needs a time and
+/// an offset to mean the same everywhere, e.g. `2024-05-01T18:00:00Z`.
+pub fn parse_instant(text: &str) -> Result<Datetime, String> {
+    let datetime: Datetime = text
+        .parse()
+        .map_err(|e| format!("invalid datetime {text}: {e}"))?;
+    if datetime.date.is_none() || datetime.time.is_none() || datetime.offset.is_none() {
+        return Err(format!(
+            "invalid datetime {text}: give a date, a time and an offset, e.g. 2024-05-01T18:00:00Z"
+        ));
+    }
+    Ok(datetime)
+}
+
+/// Parses when a link expires, a point in time or a date meaning its
+/// midnight UTC, e.g. `2025-01-01`.
+pub fn parse_expiry(text: &str) -> Result<Datetime, String> {
+    match text.parse::<Datetime>() {
+        Ok(datetime) if datetime.time.is_none() && datetime.offset.is_none() => {
+            Ok(from_unix(to_unix(&datetime)))
+        }
+        _ => parse_instant(text),
+    }
+}
+
+/// Parses a length of time in seconds, a number of minutes, hours, days or
+/// weeks such as `30d`. Lengths ending after the year 9999 are rejected.
+pub fn parse_duration(text: &str) -> Result<i64, String> {
+    let unit = match text.chars().last() {
+        Some('m') => 60,
+        Some('h') => 3600,
+        Some('d') => 86400,
+        Some('w') => 7 * 86400,
+        _ => {
+            return Err(format!(
+                "invalid duration {text}: give a number of minutes, hours, days or weeks, e.g. 30d"
+            ))
+        }
+    };
+    let count: i64 = text[..text.len() - 1]
+        .parse()
+        .ok()
+        .filter(|&count| count > 0)
+        .ok_or_else(|| format!("invalid duration {text}: give a positive number, e.g. 30d"))?;
+    count
+        .checked_mul(unit)
+        .filter(|&secs| to_unix(&now()) <= LAST - secs)
+        .ok_or_else(|| format!("invalid duration {text}: too long, it ends after the year 9999"))
+}
+
+/// Formats the date part only, e.g. `2023-12-20`.
+pub fn format_date(datetime: &Datetime) -> String {
+    match datetime.date {
+        Some(date) => date.to_string(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unix_times_convert_to_dates_and_back() {
+        for (secs, text) in [
+            (0, "1970-01-01T00:00:00Z"),
+            (1_700_000_000, "2023-11-14T22:13:20Z"),
+            // Leap days, also of 2000, a leap year though divisible by 100.
+            (1_709_208_000, "2024-02-29T12:00:00Z"),
+            (951_782_400, "2000-02-29T00:00:00Z"),
+            (951_868_800, "2000-03-01T00:00:00Z"),
+            // 1900 is no leap year, March follows February 28.
+            (-2_203_891_200, "1900-03-01T00:00:00Z"),
+            (-1, "1969-12-31T23:59:59Z"),
+            (-86_400 * 365, "1969-01-01T00:00:00Z"),
+            (LAST, "9999-12-31T23:59:59Z"),
+        ] {
+            let datetime = from_unix(secs);
+            assert_eq!(datetime.to_string(), text);
+            assert_eq!(to_unix(&datetime), secs, "{text}");
+        }
+    }
+
+    #[test]
+    fn offsets_are_taken_off() {
+        let datetime: Datetime = "2024-05-01T20:00:00+02:00".parse().unwrap();
+        assert_eq!(
+            from_unix(to_unix(&datetime)).to_string(),
+            "2024-05-01T18:00:00Z"
+        );
+    }
+
+    #[test]
+    fn expiry_dates_mean_midnight_utc() {
+        let expiry = parse_expiry("2025-01-01").unwrap();
+        assert_eq!(expiry.to_string(), "2025-01-01T00:00:00Z");
+        assert!(parse_expiry("2025-01-01T18:00:00Z").is_ok());
+        assert!(parse_expiry("2025-01-01T18:00:00").is_err());
+        assert!(parse_expiry("tomorrow").is_err());
+    }
+
+    #[test]
+    fn durations_are_checked() {
+        assert_eq!(parse_duration("90m"), Ok(90 * 60));
+        assert_eq!(parse_duration("30d"), Ok(30 * 86400));
+        assert_eq!(parse_duration("2w"), Ok(14 * 86400));
+        for text in ["", "30", "d", "0d", "-1d", "1y"] {
+            assert!(parse_duration(text).is_err(), "{text}");
+        }
+        // Long enough to pass the year 9999, or to overflow.
+        for text in ["10000000d", "9223372036854775807w"] {
+            let e = parse_duration(text).unwrap_err();
+            assert!(e.contains("too long"), "{text}: {e}");
+        }
+    }
+}
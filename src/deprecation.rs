@@ -0,0 +1,139 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Destination domains being migrated away from. `deprecations.toml` keeps
+//! how many links still pointed to each whenever it was checked, so the
+//! progress of a migration shows up in git.
+
+use crate::clipd;
+use crate::date;
+use crate::manifest::{Link, Manifest};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use toml::value::Datetime;
+use url::Url;
+
+pub const DEPRECATIONS_FILE: &str = "deprecations.toml";
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Deprecations {
+    #[serde(default, rename = "domain")]
+    pub domains: Vec<DeprecatedDomain>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DeprecatedDomain {
+    pub domain: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replace_with: Option<String>,
+    #[serde(default, rename = "count")]
+    pub counts: Vec<Count>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Count {
+    pub date: Datetime,
+    /// Links still pointing to the domain.
+    pub remaining: usize,
+}
+
+impl Deprecations {
+    pub fn load(repo_path: &Path) -> Result<Self, String> {
+        let path = repo_path.join(DEPRECATIONS_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("failed to read {DEPRECATIONS_FILE}: {e}"))?;
+        toml::from_str(&content).map_err(|e| format!("failed to parse {DEPRECATIONS_FILE}: {e}"))
+    }
+
+    pub fn save(&self, repo_path: &Path) -> Result<(), String> {
+        let content = toml::to_string_pretty(self)
+            .map_err(|e| format!("failed to serialize {DEPRECATIONS_FILE}: {e}"))?;
+        fs::write(repo_path.join(DEPRECATIONS_FILE), content)
+            .map_err(|e| format!("failed to write {DEPRECATIONS_FILE}: {e}"))
+    }
+
+    /// Records how many links are left on a domain. Returns the domain and
+    /// whether anything changed since it was last recorded.
+    pub fn record(
+        &mut self,
+        domain: &str,
+        replace_with: Option<&str>,
+        remaining: usize,
+    ) -> (&DeprecatedDomain, bool) {
+        let index = match self
+            .domains
+            .iter()
+            .position(|deprecated| deprecated.domain.eq_ignore_ascii_case(domain))
+        {
+            Some(index) => index,
+            None => {
+                self.domains.push(DeprecatedDomain {
+                    domain: domain.to_string(),
+                    replace_with: None,
+                    counts: vec![],
+                });
+                self.domains.len() - 1
+            }
+        };
+
+        let deprecated = &mut self.domains[index];
+        let mut changed = false;
+        if let Some(replace_with) = replace_with {
+            if deprecated.replace_with.as_deref() != Some(replace_with) {
+                deprecated.replace_with = Some(replace_with.to_string());
+                changed = true;
+            }
+        }
+        if deprecated.counts.last().map(|count| count.remaining) != Some(remaining) {
+            deprecated.counts.push(Count {
+                date: date::now(),
+                remaining,
+            });
+            changed = true;
+        }
+        (&self.domains[index], changed)
+    }
+}
+
+/// Links whose target is on the domain or one of its subdomains.
+pub fn affected<'a>(manifest: &'a Manifest, domain: &str) -> Vec<&'a Link> {
+    let domains = [domain.to_string()];
+    manifest
+        .newest_first()
+        .into_iter()
+        .filter(|link| {
+            Url::parse(&link.target).is_ok_and(|url| clipd::matches_domain(&url, &domains))
+        })
+        .collect()
+}
+
+/// The target moved to the new domain, keeping the subdomain, path, query
+/// and fragment.
+pub fn replace_domain(target: &str, domain: &str, replace_with: &str) -> Result<String, String> {
+    let mut url = Url::parse(target).map_err(|e| format!("failed to parse {target}: {e}"))?;
+    let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+    let subdomain = host
+        .strip_suffix(&domain.trim_start_matches('.').to_ascii_lowercase())
+        .unwrap_or_default();
+    let new_host = format!("{subdomain}{replace_with}");
+    url.set_host(Some(&new_host))
+        .map_err(|e| format!("invalid domain `{new_host}`: {e}"))?;
+    Ok(url.to_string())
+}
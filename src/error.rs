@@ -0,0 +1,97 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Errors of the commands. Each kind exits with its own code, so scripts can
+//! tell a typo in a URL from a push that failed.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Debug)]
+pub enum ShurlError {
+    /// The config file can't be read or is invalid.
+    Config(String),
+    /// An argument is invalid, e.g. a URL that doesn't parse.
+    InvalidInput(String),
+    /// What the command works on doesn't exist, e.g. the link to remove.
+    NotFound(String),
+    /// A slug or name is already taken.
+    Conflict(String),
+    /// Opening, committing to or pushing the repository failed.
+    Git(String),
+    /// A request to GitHub, Jira, Cloudflare or a partner failed.
+    Network(String),
+    /// Links break lint rules whose level is `error`.
+    Lint(String),
+    /// Anything else, e.g. a file that couldn't be written.
+    Other(String),
+}
+
+impl ShurlError {
+    /// Code the process exits with. 2 is left to clap for invalid command
+    /// lines.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            ShurlError::Other(_) => 1,
+            ShurlError::Config(_) => 3,
+            ShurlError::InvalidInput(_) => 4,
+            ShurlError::NotFound(_) => 5,
+            ShurlError::Conflict(_) => 6,
+            ShurlError::Git(_) => 7,
+            ShurlError::Network(_) => 8,
+            ShurlError::Lint(_) => 9,
+        }
+    }
+
+    /// Puts what was being done in front of the message, keeping the kind.
+    pub fn context(self, context: &str) -> Self {
+        let message = |message| format!("{context}: {message}");
+        match self {
+            ShurlError::Config(m) => ShurlError::Config(message(m)),
+            ShurlError::InvalidInput(m) => ShurlError::InvalidInput(message(m)),
+            ShurlError::NotFound(m) => ShurlError::NotFound(message(m)),
+            ShurlError::Conflict(m) => ShurlError::Conflict(message(m)),
+            ShurlError::Git(m) => ShurlError::Git(message(m)),
+            ShurlError::Network(m) => ShurlError::Network(message(m)),
+            ShurlError::Lint(m) => ShurlError::Lint(message(m)),
+            ShurlError::Other(m) => ShurlError::Other(message(m)),
+        }
+    }
+}
+
+impl Display for ShurlError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let (ShurlError::Config(message)
+        | ShurlError::InvalidInput(message)
+        | ShurlError::NotFound(message)
+        | ShurlError::Conflict(message)
+        | ShurlError::Git(message)
+        | ShurlError::Network(message)
+        | ShurlError::Lint(message)
+        | ShurlError::Other(message)) = self;
+        f.write_str(message)
+    }
+}
+
+impl Error for ShurlError {}
+
+/// Most modules still report errors as messages, which aren't of any kind
+/// in particular.
+impl From<String> for ShurlError {
+    fn from(message: String) -> Self {
+        ShurlError::Other(message)
+    }
+}
@@ -0,0 +1,160 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Partner shurl repositories whose links are listed on the directory page.
+//!
+//! The partners are recorded in `federation.toml` and a snapshot of each
+//! partner's manifest is kept under `federation/`, so the directory page can
+//! be regenerated without network access and every refresh shows up in git.
+
+use crate::date;
+use crate::manifest::Manifest;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use toml::value::Datetime;
+
+pub const FEDERATION_FILE: &str = "federation.toml";
+pub const SNAPSHOT_DIR: &str = "federation";
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Federation {
+    #[serde(default, rename = "partner")]
+    pub partners: Vec<Partner>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Partner {
+    pub name: String,
+    /// Git URL of the partner's shurl repository.
+    pub url: String,
+    /// Public URL the partner's short links are served from, if known.
+    pub site: Option<String>,
+    pub fetched: Option<Datetime>,
+}
+
+impl Federation {
+    pub fn load(repo_path: &Path) -> Result<Self, String> {
+        let path = repo_path.join(FEDERATION_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("failed to read {FEDERATION_FILE}: {e}"))?;
+        toml::from_str(&content).map_err(|e| format!("failed to parse {FEDERATION_FILE}: {e}"))
+    }
+
+    pub fn save(&self, repo_path: &Path) -> Result<(), String> {
+        let content = toml::to_string_pretty(self)
+            .map_err(|e| format!("failed to serialize {FEDERATION_FILE}: {e}"))?;
+        fs::write(repo_path.join(FEDERATION_FILE), content)
+            .map_err(|e| format!("failed to write {FEDERATION_FILE}: {e}"))
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Partner> {
+        self.partners
+            .iter_mut()
+            .find(|partner| partner.name == name)
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Partner> {
+        let position = self
+            .partners
+            .iter()
+            .position(|partner| partner.name == name)?;
+        Some(self.partners.remove(position))
+    }
+}
+
+impl Partner {
+    /// Whether the snapshot is older than `max_age_hours`.
+    pub fn is_stale(&self, max_age_hours: u64) -> bool {
+        match &self.fetched {
+            Some(fetched) => {
+                let age = date::to_unix(&date::now()) - date::to_unix(fetched);
+                age >= (max_age_hours * 3600) as i64
+            }
+            None => true,
+        }
+    }
+
+    /// URL of one of the partner's short links, if the partner's site is known.
+    pub fn link_url(&self, slug: &str) -> Option<String> {
+        self.site
+            .as_ref()
+            .map(|site| format!("{}/{slug}", site.trim_end_matches('/')))
+    }
+}
+
+/// Derives a partner name from its repository URL, e.g. `team-links` for
+/// `https://github.com/org/team-links.git`.
+pub fn name_from_url(url: &str) -> String {
+    let last = url
+        .trim_end_matches('/')
+        .rsplit(['/', ':'])
+        .next()
+        .unwrap_or(url);
+    last.trim_end_matches(".git").to_string()
+}
+
+pub fn snapshot_path(repo_path: &Path, name: &str) -> PathBuf {
+    repo_path.join(SNAPSHOT_DIR).join(format!("{name}.toml"))
+}
+
+pub fn load_snapshot(repo_path: &Path, name: &str) -> Result<Manifest, String> {
+    let path = snapshot_path(repo_path, name);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(_) => return Ok(Manifest::default()),
+    };
+    toml::from_str(&content).map_err(|e| format!("failed to parse {}: {e}", path.display()))
+}
+
+/// Fetches the partner's manifest and stores it as the partner's snapshot.
+pub fn fetch(repo_path: &Path, partner: &mut Partner) -> Result<usize, String> {
+    let checkout = std::env::temp_dir().join(format!("shurl-federation-{}", partner.name));
+    let _ = fs::remove_dir_all(&checkout);
+
+    // Shell out like `git::push` does, so the user's credentials for private
+    // partner repositories just work.
+    let status = Command::new("git")
+        .arg("clone")
+        .arg("--quiet")
+        .arg("--depth")
+        .arg("1")
+        .arg(&partner.url)
+        .arg(&checkout)
+        .status()
+        .map_err(|e| format!("failed to run git: {e}"))?;
+    if !status.success() {
+        return Err(format!("failed to clone {}", partner.url));
+    }
+
+    let manifest = Manifest::load(&checkout);
+    let _ = fs::remove_dir_all(&checkout);
+    let manifest = manifest?;
+
+    let path = snapshot_path(repo_path, &partner.name);
+    fs::create_dir_all(repo_path.join(SNAPSHOT_DIR))
+        .map_err(|e| format!("failed to create {SNAPSHOT_DIR}: {e}"))?;
+    let content = toml::to_string_pretty(&manifest)
+        .map_err(|e| format!("failed to serialize snapshot of {}: {e}", partner.name))?;
+    fs::write(&path, content).map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+
+    partner.fetched = Some(date::now());
+    Ok(manifest.links.len())
+}
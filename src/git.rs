@@ -0,0 +1,365 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::config::{CommitStyle, ShurlConfig};
+use crate::error::ShurlError;
+use crate::output;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Commits every change in the working tree of the repository, with the
+/// message in the `commit_style` of the config.
+pub fn commit_all(
+    repo: &git2::Repository,
+    cfg: &ShurlConfig,
+    message: &str,
+) -> Result<git2::Oid, ShurlError> {
+    let failed = |e: git2::Error| ShurlError::Git(format!("failed to commit: {e}"));
+    let mut index = repo.index().map_err(failed)?;
+    index
+        .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
+        .map_err(failed)?;
+    // Picks up pages removed when a slug moved to the other layout.
+    index.update_all(["*"].iter(), None).map_err(failed)?;
+    index.write().map_err(failed)?;
+    let tree = repo
+        .find_tree(index.write_tree().map_err(failed)?)
+        .map_err(failed)?;
+    let author = git2::Signature::now(cfg.author(), cfg.author_email()).map_err(failed)?;
+    let committer = git2::Signature::now(&cfg.name, &cfg.email).map_err(failed)?;
+    let message = match cfg.commit_style {
+        CommitStyle::Plain => message.to_string(),
+        CommitStyle::Conventional => conventional(message),
+    };
+    let parent_commit = match repo.head() {
+        Ok(head) => Some(head.peel_to_commit().map_err(failed)?),
+        Err(_) => None,
+    };
+
+    repo.commit(
+        Some("HEAD"),
+        &author,
+        &committer,
+        &message,
+        &tree,
+        &parent_commit.iter().collect::<Vec<_>>(),
+    )
+    .map_err(failed)
+}
+
+/// Turns a plain message such as `Remove abcde` into a conventional
+/// commit, `chore(links): remove abcde`: additions are features, anything
+/// else a chore. Messages already written that way are left alone.
+pub fn conventional(message: &str) -> String {
+    let (summary, body) = message.split_once('\n').unwrap_or((message, ""));
+    if is_conventional(summary) {
+        return message.to_string();
+    }
+    let verb = summary.split(' ').next().unwrap_or_default();
+    let kind = match verb {
+        "Add" | "Restore" | "Federate" => "feat",
+        _ => "chore",
+    };
+    let scope = match verb {
+        "Mark" => "repo",
+        "Rerender" => "pages",
+        _ => "links",
+    };
+    let mut chars = summary.chars();
+    let summary: String = chars
+        .next()
+        .map(|first| first.to_lowercase().chain(chars).collect())
+        .unwrap_or_default();
+    if body.is_empty() {
+        format!("{kind}({scope}): {summary}")
+    } else {
+        format!("{kind}({scope}): {summary}\n{body}")
+    }
+}
+
+/// Whether the line starts with a type such as `feat`, maybe a scope in
+/// parentheses and `!`, then a colon.
+fn is_conventional(summary: &str) -> bool {
+    let Some((head, _)) = summary.split_once(": ") else {
+        return false;
+    };
+    let head = head.strip_suffix('!').unwrap_or(head);
+    let kind = match head.split_once('(') {
+        Some((kind, scope)) if scope.ends_with(')') => kind,
+        Some(_) => return false,
+        None => head,
+    };
+    !kind.is_empty() && kind.chars().all(|c| c.is_ascii_lowercase())
+}
+
+/// The state of the repository before shurl changes it, so that only what
+/// shurl wrote since is undone by [`roll_back`].
+pub struct Snapshot {
+    head: Option<git2::Oid>,
+    /// Tree of what was staged, if it could be written.
+    index: Option<git2::Oid>,
+    /// Paths already changed, staged or untracked, left as they are.
+    dirty: HashSet<String>,
+}
+
+pub fn snapshot(repo: &git2::Repository) -> Result<Snapshot, String> {
+    let failed = |e: git2::Error| format!("failed to read the status: {e}");
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map(|commit| commit.id())
+        .ok();
+    let index = repo.index().and_then(|mut index| index.write_tree()).ok();
+    Ok(Snapshot {
+        head,
+        index,
+        dirty: dirty_paths(repo).map_err(failed)?,
+    })
+}
+
+/// Puts the branch and the index back to the snapshot, and the files
+/// changed since back to their committed version, removing the new ones.
+/// Files that were already changed or untracked aren't touched.
+pub fn roll_back(repo: &git2::Repository, snapshot: &Snapshot) -> Result<(), String> {
+    let failed = |e: git2::Error| format!("failed to roll back: {e}");
+    let head = match snapshot.head {
+        Some(id) => {
+            let commit = repo.find_commit(id).map_err(failed)?;
+            repo.reset(commit.as_object(), git2::ResetType::Soft, None)
+                .map_err(failed)?;
+            Some(commit.tree().map_err(failed)?)
+        }
+        None => {
+            // The commit made was the first one, so the branch goes again.
+            let branch = repo
+                .find_reference("HEAD")
+                .ok()
+                .and_then(|head| head.symbolic_target().map(str::to_string));
+            if let Some(mut branch) = branch.and_then(|name| repo.find_reference(&name).ok()) {
+                branch.delete().map_err(failed)?;
+            }
+            None
+        }
+    };
+
+    let mut index = repo.index().map_err(failed)?;
+    match (snapshot.index, &head) {
+        (Some(tree), _) => index.read_tree(&repo.find_tree(tree).map_err(failed)?),
+        (None, Some(head)) => index.read_tree(head),
+        (None, None) => index.clear(),
+    }
+    .map_err(failed)?;
+    index.write().map_err(failed)?;
+
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| "failed to roll back: the repository is bare".to_string())?;
+    let mut paths: Vec<String> = dirty_paths(repo)
+        .map_err(failed)?
+        .difference(&snapshot.dirty)
+        .cloned()
+        .collect();
+    paths.sort();
+    for path in paths {
+        let committed = head
+            .as_ref()
+            .is_some_and(|head| head.get_path(Path::new(&path)).is_ok());
+        if committed {
+            let mut checkout = git2::build::CheckoutBuilder::new();
+            checkout.force().path(&path);
+            repo.checkout_head(Some(&mut checkout)).map_err(failed)?;
+            continue;
+        }
+        let full_path = workdir.join(&path);
+        if full_path.exists() {
+            fs::remove_file(&full_path).map_err(|e| format!("failed to remove {path}: {e}"))?;
+        }
+        // Directories the batch created go with their last file.
+        let mut dir = full_path.parent();
+        while let Some(parent) = dir.filter(|dir| *dir != workdir) {
+            if fs::remove_dir(parent).is_err() {
+                break;
+            }
+            dir = parent.parent();
+        }
+    }
+    Ok(())
+}
+
+/// Paths that differ between HEAD, the index and the working tree.
+fn dirty_paths(repo: &git2::Repository) -> Result<HashSet<String>, git2::Error> {
+    let mut options = git2::StatusOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true);
+    let statuses = repo.statuses(Some(&mut options))?;
+    Ok(statuses
+        .iter()
+        .filter_map(|entry| entry.path().map(str::to_string))
+        .collect())
+}
+
+/// Number of changes not committed, and of commits not pushed to the branch
+/// of origin, unknown if it was never fetched.
+pub fn pending(repo: &git2::Repository, branch: &str) -> Result<(usize, Option<usize>), String> {
+    let failed = |e: git2::Error| format!("failed to read the status: {e}");
+    let mut options = git2::StatusOptions::new();
+    options.include_untracked(true);
+    let changes = repo.statuses(Some(&mut options)).map_err(failed)?.len();
+    let local = repo.head().ok().and_then(|head| head.target());
+    let remote = repo
+        .refname_to_id(&format!("refs/remotes/origin/{branch}"))
+        .ok();
+    let unpushed = match (local, remote) {
+        (Some(local), Some(remote)) => {
+            Some(repo.graph_ahead_behind(local, remote).map_err(failed)?.0)
+        }
+        _ => None,
+    };
+    Ok((changes, unpushed))
+}
+
+/// Id of the commit HEAD points to.
+pub fn head_id(repo: &git2::Repository) -> Option<String> {
+    let commit = repo.head().ok()?.peel_to_commit().ok()?;
+    Some(commit.id().to_string())
+}
+
+/// Checks that `push` would be allowed, without pushing anything.
+pub fn check_push(repo_path: &Path, branch: &str) -> Result<(), String> {
+    let output = Command::new("git")
+        .args(["push", "--dry-run", "--quiet", "origin", branch])
+        .current_dir(repo_path)
+        .output()
+        .map_err(|e| format!("failed to run git: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "can't push to origin: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+pub fn push(repo_path: &Path, branch: &str) -> Result<(), ShurlError> {
+    // HACK: easier way to push to upstream
+    let mut command = Command::new("git");
+    command.arg("push");
+    if output::is_quiet() {
+        command.arg("--quiet");
+    }
+    let status = command
+        .arg("origin")
+        .arg(branch)
+        .current_dir(repo_path)
+        .status()
+        .map_err(|e| ShurlError::Git(format!("failed to run git: {e}")))?;
+    if !status.success() {
+        return Err(ShurlError::Git(
+            "failed to push to upstream: try running `git push` manually".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Repository in a temporary directory, removed on drop.
+    struct Fixture(PathBuf, git2::Repository);
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("shurl-test-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            let repo = git2::Repository::init(&path).unwrap();
+            Self(path, repo)
+        }
+
+        fn write(&self, path: &str, content: &str) {
+            let path = self.0.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+
+        fn read(&self, path: &str) -> Option<String> {
+            fs::read_to_string(self.0.join(path)).ok()
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn roll_back_keeps_files_it_didnt_write() {
+        let fixture = Fixture::new("roll-back");
+        let cfg = ShurlConfig::default();
+        fixture.write("index.html", "one link");
+        let first = commit_all(&fixture.1, &cfg, "Add redirect").unwrap();
+        fixture.write("notes.txt", "mine");
+
+        let snapshot = snapshot(&fixture.1).unwrap();
+        fixture.write("index.html", "two links");
+        fixture.write("pages/abcde.html", "page");
+        commit_all(&fixture.1, &cfg, "Add 2 redirects").unwrap();
+        roll_back(&fixture.1, &snapshot).unwrap();
+
+        assert_eq!(head_id(&fixture.1), Some(first.to_string()));
+        assert_eq!(fixture.read("index.html").as_deref(), Some("one link"));
+        assert_eq!(fixture.read("notes.txt").as_deref(), Some("mine"));
+        assert!(!fixture.0.join("pages").exists());
+        assert!(dirty_paths(&fixture.1).unwrap().contains("notes.txt"));
+    }
+
+    #[test]
+    fn roll_back_of_the_first_commit_leaves_no_branch() {
+        let fixture = Fixture::new("roll-back-first");
+        fixture.write("notes.txt", "mine");
+
+        let snapshot = snapshot(&fixture.1).unwrap();
+        fixture.write("index.html", "one link");
+        commit_all(&fixture.1, &ShurlConfig::default(), "Add redirect").unwrap();
+        roll_back(&fixture.1, &snapshot).unwrap();
+
+        assert!(fixture.1.head().is_err());
+        assert!(fixture.read("index.html").is_none());
+        assert_eq!(fixture.read("notes.txt").as_deref(), Some("mine"));
+    }
+
+    #[test]
+    fn messages_become_conventional() {
+        assert_eq!(conventional("Remove abcde"), "chore(links): remove abcde");
+        assert_eq!(
+            conventional("Add 3 redirects\n\nRefs: ORG-1"),
+            "feat(links): add 3 redirects\n\nRefs: ORG-1"
+        );
+        assert_eq!(
+            conventional("Rerender pages"),
+            "chore(pages): rerender pages"
+        );
+        assert_eq!(
+            conventional("feat(links): add abcde -> https://example.com/"),
+            "feat(links): add abcde -> https://example.com/"
+        );
+    }
+}
@@ -0,0 +1,100 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::config::ShurlConfig;
+use crate::http::Request;
+use crate::json::{self, Value};
+use std::env;
+
+pub struct GitHub {
+    api_url: String,
+    token: Option<String>,
+}
+
+impl GitHub {
+    /// Uses `github_token` from the config or the `GITHUB_TOKEN` that CI
+    /// provides, and `GITHUB_API_URL` for GitHub Enterprise.
+    pub fn new(cfg: &ShurlConfig) -> Self {
+        Self {
+            api_url: env::var("GITHUB_API_URL")
+                .unwrap_or_else(|_| "https://api.github.com".to_string()),
+            token: cfg
+                .github_token
+                .clone()
+                .or_else(|| env::var("GITHUB_TOKEN").ok())
+                .or_else(|| env::var("GH_TOKEN").ok()),
+        }
+    }
+
+    pub fn get(&self, path: &str) -> Result<Value, String> {
+        self.request("GET", path, None)
+    }
+
+    pub fn post(&self, path: &str, body: &Value) -> Result<Value, String> {
+        self.request("POST", path, Some(body))
+    }
+
+    fn request(&self, method: &str, path: &str, body: Option<&Value>) -> Result<Value, String> {
+        let url = format!("{}{path}", self.api_url.trim_end_matches('/'));
+        let mut request = Request::new(method, &url)
+            .header("Accept", "application/vnd.github+json")
+            .header("X-GitHub-Api-Version", "2022-11-28")
+            .header("User-Agent", "shurl");
+        if let Some(token) = &self.token {
+            request = request.header("Authorization", &format!("Bearer {token}"));
+        }
+        if let Some(body) = body {
+            request = request
+                .header("Content-Type", "application/json")
+                .body(body.to_string());
+        }
+
+        let response = request.send()?;
+        let value = if response.body.trim().is_empty() {
+            Value::Null
+        } else {
+            json::parse(&response.body)?
+        };
+        if !(200..300).contains(&response.status) {
+            let message = value
+                .get("message")
+                .and_then(Value::as_str)
+                .unwrap_or("no message");
+            return Err(format!(
+                "GitHub API {method} {path} failed with {}: {message}",
+                response.status
+            ));
+        }
+        Ok(value)
+    }
+}
+
+/// The `owner/name` of the repository CI runs for.
+pub fn repository_from_env() -> Option<String> {
+    env::var("GITHUB_REPOSITORY").ok()
+}
+
+/// The pull request number CI runs for, from `GITHUB_REF` such as
+/// `refs/pull/42/merge`.
+pub fn pull_request_from_env() -> Option<u64> {
+    env::var("GITHUB_REF")
+        .ok()?
+        .strip_prefix("refs/pull/")?
+        .split('/')
+        .next()?
+        .parse()
+        .ok()
+}
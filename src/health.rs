@@ -0,0 +1,40 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Startup checks for the long-running modes, so a broken setup shows up
+//! right away instead of on the first link.
+
+use crate::config::ShurlConfig;
+use crate::federation::Federation;
+use crate::git;
+use crate::manifest::Manifest;
+use crate::repo_config::RepoConfig;
+use crate::trash::Trash;
+use std::path::Path;
+
+/// Checks the config, that every file shurl reads from the repository
+/// parses, and if `pushes`, that we are allowed to push.
+pub fn self_check(cfg: &ShurlConfig, repo_path: &Path, pushes: bool) -> Result<(), String> {
+    cfg.validate()?;
+    Manifest::load(repo_path)?;
+    Trash::load(repo_path)?;
+    RepoConfig::load(repo_path)?;
+    Federation::load(repo_path)?;
+    if pushes {
+        git::check_push(repo_path, &cfg.branch)?;
+    }
+    Ok(())
+}
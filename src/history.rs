@@ -0,0 +1,291 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! What the git history tells about the links: how the repository grew,
+//! links added per week or month, how many there were and who added them,
+//! everything that happened to a link, and who changed which links. Nothing is tracked for it beyond
+//! the commits themselves.
+
+use crate::date;
+use crate::manifest::{Link, Manifest, MANIFEST_FILE};
+use clap::ValueEnum;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Interval {
+    Week,
+    Month,
+}
+
+pub struct Period {
+    /// The month, e.g. `2024-05`, or the Monday starting the week.
+    pub label: String,
+    pub added: usize,
+    /// Links there were at the end of the period.
+    pub total: usize,
+}
+
+pub struct Growth {
+    /// Oldest first, only periods with commits changing the links.
+    pub periods: Vec<Period>,
+    /// Authors by the number of links they added, most first.
+    pub authors: Vec<(String, usize)>,
+}
+
+pub enum Change {
+    Created {
+        target: String,
+    },
+    /// Added again after it had been removed.
+    Restored {
+        target: String,
+    },
+    Retargeted {
+        from: String,
+        to: String,
+    },
+    RenamedFrom(String),
+    RenamedTo(String),
+    Removed,
+}
+
+/// A commit changing a link.
+pub struct Event {
+    pub commit: String,
+    pub time: i64,
+    pub author: String,
+    /// First line of the commit message.
+    pub summary: String,
+    pub change: Change,
+}
+
+/// Links someone changed, by commit author.
+pub struct Contribution {
+    pub name: String,
+    pub email: String,
+    pub created: Vec<String>,
+    /// Links changed in any way but their slug.
+    pub modified: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// Reads the manifest of every commit of the current branch, oldest first,
+/// counting the links each one added. Commits from before the manifest
+/// existed have no links.
+pub fn growth(repo: &git2::Repository, interval: Interval) -> Result<Growth, String> {
+    let mut periods: Vec<Period> = vec![];
+    let mut authors: BTreeMap<String, usize> = BTreeMap::new();
+    let mut previous: HashSet<String> = HashSet::new();
+    for commit in commits(repo)? {
+        let slugs: HashSet<String> = manifest_at(repo, &commit)?
+            .links
+            .into_iter()
+            .map(|link| link.slug)
+            .collect();
+        let added = slugs.difference(&previous).count();
+        if added == 0 && slugs.len() == previous.len() {
+            continue;
+        }
+
+        let label = label_of(commit.time().seconds(), interval);
+        match periods.last_mut() {
+            Some(period) if period.label == label => {
+                period.added += added;
+                period.total = slugs.len();
+            }
+            _ => periods.push(Period {
+                label,
+                added,
+                total: slugs.len(),
+            }),
+        }
+        if added > 0 {
+            let author = commit.author().name().unwrap_or("unknown").to_string();
+            *authors.entry(author).or_default() += added;
+        }
+        previous = slugs;
+    }
+
+    let mut authors: Vec<_> = authors.into_iter().collect();
+    authors.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(Growth { periods, authors })
+}
+
+/// Every commit of the current branch changing the link, oldest first. A
+/// link that disappears while one with its target and creation date
+/// appears was renamed.
+pub fn events(repo: &git2::Repository, slug: &str) -> Result<Vec<Event>, String> {
+    let mut events = vec![];
+    let mut previous = Manifest::default();
+    let mut existed = false;
+    for commit in commits(repo)? {
+        let manifest = manifest_at(repo, &commit)?;
+        let same_link = |a: &Link, b: &Link| a.target == b.target && a.created == b.created;
+        let change = match (previous.get(slug), manifest.get(slug)) {
+            (None, Some(link)) => Some(
+                match previous
+                    .links
+                    .iter()
+                    .find(|old| same_link(old, link) && manifest.get(&old.slug).is_none())
+                {
+                    Some(old) => Change::RenamedFrom(old.slug.clone()),
+                    None if existed => Change::Restored {
+                        target: link.target.clone(),
+                    },
+                    None => Change::Created {
+                        target: link.target.clone(),
+                    },
+                },
+            ),
+            (Some(old), Some(link)) if old.target != link.target => Some(Change::Retargeted {
+                from: old.target.clone(),
+                to: link.target.clone(),
+            }),
+            (Some(old), None) => Some(
+                match manifest
+                    .links
+                    .iter()
+                    .find(|new| same_link(old, new) && previous.get(&new.slug).is_none())
+                {
+                    Some(new) => Change::RenamedTo(new.slug.clone()),
+                    None => Change::Removed,
+                },
+            ),
+            _ => None,
+        };
+        if let Some(change) = change {
+            existed = true;
+            events.push(Event {
+                commit: commit.id().to_string(),
+                time: commit.time().seconds(),
+                author: commit.author().name().unwrap_or("unknown").to_string(),
+                summary: commit.summary().unwrap_or_default().to_string(),
+                change,
+            });
+        }
+        previous = manifest;
+    }
+    Ok(events)
+}
+
+/// Who created, modified and removed which links in the commits from
+/// `since` on and before `until`, most active first. A renamed link was
+/// removed under its old slug and created under the new one.
+pub fn audit(
+    repo: &git2::Repository,
+    since: Option<i64>,
+    until: Option<i64>,
+) -> Result<Vec<Contribution>, String> {
+    let mut contributions: BTreeMap<(String, String), Contribution> = BTreeMap::new();
+    let mut previous: BTreeMap<String, String> = BTreeMap::new();
+    for commit in commits(repo)? {
+        // Links by slug, serialized to tell whether anything about them
+        // changed.
+        let links: BTreeMap<String, String> = manifest_at(repo, &commit)?
+            .links
+            .into_iter()
+            .map(|link| {
+                let content = toml::to_string(&link).unwrap_or_default();
+                (link.slug, content)
+            })
+            .collect();
+        let time = commit.time().seconds();
+        if since.is_some_and(|since| time < since) || until.is_some_and(|until| time >= until) {
+            previous = links;
+            continue;
+        }
+
+        let author = commit.author();
+        let name = author.name().unwrap_or("unknown").to_string();
+        let email = author.email().unwrap_or_default().to_string();
+        let created = links.keys().filter(|slug| !previous.contains_key(*slug));
+        let modified = links
+            .iter()
+            .filter(|(slug, content)| previous.get(*slug).is_some_and(|old| old != *content))
+            .map(|(slug, _)| slug);
+        let removed = previous.keys().filter(|slug| !links.contains_key(*slug));
+        let (created, modified, removed): (Vec<_>, Vec<_>, Vec<_>) = (
+            created.cloned().collect(),
+            modified.cloned().collect(),
+            removed.cloned().collect(),
+        );
+        if !created.is_empty() || !modified.is_empty() || !removed.is_empty() {
+            let contribution = contributions
+                .entry((name.clone(), email.clone()))
+                .or_insert_with(|| Contribution {
+                    name,
+                    email,
+                    created: vec![],
+                    modified: vec![],
+                    removed: vec![],
+                });
+            contribution.created.extend(created);
+            contribution.modified.extend(modified);
+            contribution.removed.extend(removed);
+        }
+        previous = links;
+    }
+
+    let mut contributions: Vec<Contribution> = contributions.into_values().collect();
+    contributions
+        .sort_by_key(|c| std::cmp::Reverse(c.created.len() + c.modified.len() + c.removed.len()));
+    Ok(contributions)
+}
+
+/// Commits of the current branch following first parents, oldest first.
+fn commits(repo: &git2::Repository) -> Result<Vec<git2::Commit<'_>>, String> {
+    let failed = |e: git2::Error| format!("failed to read the history: {e}");
+    let mut walk = repo.revwalk().map_err(failed)?;
+    walk.push_head().map_err(failed)?;
+    walk.simplify_first_parent().map_err(failed)?;
+    walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)
+        .map_err(failed)?;
+    walk.map(|id| id.and_then(|id| repo.find_commit(id)).map_err(failed))
+        .collect()
+}
+
+fn manifest_at(repo: &git2::Repository, commit: &git2::Commit) -> Result<Manifest, String> {
+    let tree = commit
+        .tree()
+        .map_err(|e| format!("failed to read commit {}: {e}", commit.id()))?;
+    let Ok(entry) = tree.get_path(Path::new(MANIFEST_FILE)) else {
+        return Ok(Manifest::default());
+    };
+    let blob = repo.find_blob(entry.id()).map_err(|e| {
+        format!(
+            "failed to read {MANIFEST_FILE} of commit {}: {e}",
+            commit.id()
+        )
+    })?;
+    // A manifest broken by hand in some commit only leaves a gap.
+    Ok(std::str::from_utf8(blob.content())
+        .ok()
+        .and_then(|content| toml::from_str(content).ok())
+        .unwrap_or_default())
+}
+
+fn label_of(secs: i64, interval: Interval) -> String {
+    match interval {
+        Interval::Month => date::format_date(&date::from_unix(secs))[..7].to_string(),
+        Interval::Week => {
+            // The unix epoch was on a Thursday.
+            let days = secs.div_euclid(86400);
+            let monday = days - (days + 3).rem_euclid(7);
+            date::format_date(&date::from_unix(monday * 86400))
+        }
+    }
+}
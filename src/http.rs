@@ -0,0 +1,167 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! HTTP requests through `curl`, which every CI image and workstation has,
+//! instead of pulling in a TLS stack.
+
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Message of requests that took longer than their timeout.
+pub const TIMED_OUT: &str = "timed out";
+
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+    /// Where a redirect leads, for requests that don't follow redirects.
+    pub location: Option<String>,
+}
+
+pub struct Request<'a> {
+    method: &'a str,
+    url: &'a str,
+    headers: Vec<String>,
+    body: Option<String>,
+    timeout: Option<u64>,
+    follow_redirects: bool,
+}
+
+impl<'a> Request<'a> {
+    pub fn new(method: &'a str, url: &'a str) -> Self {
+        Self {
+            method,
+            url,
+            headers: vec![],
+            body: None,
+            timeout: None,
+            follow_redirects: true,
+        }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push(format!("{name}: {value}"));
+        self
+    }
+
+    pub fn body(mut self, body: String) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    /// Gives up after this many seconds, failing with [`TIMED_OUT`].
+    pub fn timeout(mut self, seconds: u64) -> Self {
+        self.timeout = Some(seconds);
+        self
+    }
+
+    /// Answers redirects with their status and location instead of
+    /// following them.
+    pub fn no_redirects(mut self) -> Self {
+        self.follow_redirects = false;
+        self
+    }
+
+    pub fn send(self) -> Result<Response, String> {
+        let mut command = Command::new("curl");
+        command.arg("--silent").arg("--show-error");
+        if self.follow_redirects {
+            command.arg("--location");
+        }
+        // curl waits for a body after `--request HEAD`.
+        if self.method == "HEAD" {
+            command.arg("--head");
+        } else {
+            command.arg("--request").arg(self.method);
+        }
+        if let Some(seconds) = self.timeout {
+            command.arg("--max-time").arg(seconds.to_string());
+        }
+        command
+            .arg("--write-out")
+            .arg("\n%{redirect_url}\n%{http_code}");
+        // Headers are passed in a config file on stdin so tokens don't show
+        // up in the process list. The body can't share stdin, so it goes
+        // through a temporary file.
+        let mut config = String::new();
+        for header in &self.headers {
+            config.push_str(&format!("header = {}\n", config_quote(header)));
+        }
+        let body_path = std::env::temp_dir().join(format!("shurl-body-{}", std::process::id()));
+        if let Some(body) = &self.body {
+            fs::write(&body_path, body)
+                .map_err(|e| format!("failed to write request body: {e}"))?;
+            config.push_str(&format!(
+                "data-binary = {}\n",
+                config_quote(&format!("@{}", body_path.display()))
+            ));
+        }
+        command
+            .arg("--config")
+            .arg("-")
+            .arg("--url")
+            .arg(self.url)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let result = run(command, &config);
+        if self.body.is_some() {
+            let _ = fs::remove_file(&body_path);
+        }
+        let output = result?;
+
+        let mut lines = output.rsplitn(3, '\n');
+        let (Some(status), Some(location), Some(body)) = (lines.next(), lines.next(), lines.next())
+        else {
+            return Err(format!("unexpected response from {}", self.url));
+        };
+        Ok(Response {
+            status: status.trim().parse().unwrap_or(0),
+            body: body.to_string(),
+            location: Some(location.trim())
+                .filter(|location| !location.is_empty())
+                .map(str::to_string),
+        })
+    }
+}
+
+fn run(mut command: Command, config: &str) -> Result<String, String> {
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("failed to run curl: {e}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(config.as_bytes())
+            .map_err(|e| format!("failed to pass curl config: {e}"))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("failed to run curl: {e}"))?;
+    // curl exits with 28 when the time is up.
+    if output.status.code() == Some(28) {
+        return Err(TIMED_OUT.to_string());
+    }
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Quotes a value for a curl config file.
+fn config_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
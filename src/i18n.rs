@@ -0,0 +1,233 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Text of the redirect, countdown and language pages in the languages
+//! shurl has translations for.
+
+/// What a redirect page says. `{delay}` and `{date}` are replaced with the
+/// seconds until it redirects and the expiry date of its link.
+pub struct Strings {
+    pub redirecting: &'static str,
+    pub taking_you_to: &'static str,
+    pub in_one_second: &'static str,
+    pub in_seconds: &'static str,
+    /// Text before the link to the target, for readers who aren't
+    /// redirected, and the text of the link and after it.
+    pub follow: &'static str,
+    pub link: &'static str,
+    pub follow_end: &'static str,
+    pub expired: &'static str,
+    /// Title of the countdown page of a link without a description.
+    pub starting_soon: &'static str,
+    /// Text before the start of the event and before the time left until
+    /// then, and before the link to the target once it has started.
+    pub starts: &'static str,
+    pub starts_in: &'static str,
+    pub once_started: &'static str,
+    /// Text of the page picking a target by language, before the links to
+    /// them and of the link for everyone else.
+    pub follow_language: &'static str,
+    pub any_language: &'static str,
+}
+
+const TRANSLATIONS: &[(&str, Strings)] = &[
+    (
+        "en",
+        Strings {
+            redirecting: "Redirecting...",
+            taking_you_to: "Taking you to",
+            in_one_second: "in 1 second...",
+            in_seconds: "in {delay} seconds...",
+            follow: "If you are not redirected automatically, follow the",
+            link: "link",
+            follow_end: "",
+            expired: "This link expired on {date}.",
+            starting_soon: "Starting soon",
+            starts: "Starts",
+            starts_in: "in",
+            once_started: "Once it has started, this link takes you to",
+            follow_language: "If you are not redirected automatically, follow the link for your language:",
+            any_language: "Any other language",
+        },
+    ),
+    (
+        "de",
+        Strings {
+            redirecting: "Weiterleitung...",
+            taking_you_to: "Weiterleitung zu",
+            in_one_second: "in 1 Sekunde...",
+            in_seconds: "in {delay} Sekunden...",
+            follow: "Falls Sie nicht automatisch weitergeleitet werden, folgen Sie diesem",
+            link: "Link",
+            follow_end: ".",
+            expired: "Dieser Link ist am {date} abgelaufen.",
+            starting_soon: "Beginnt bald",
+            starts: "Beginnt",
+            starts_in: "in",
+            once_started: "Sobald es begonnen hat, führt dieser Link zu",
+            follow_language: "Falls Sie nicht automatisch weitergeleitet werden, folgen Sie dem Link für Ihre Sprache:",
+            any_language: "Jede andere Sprache",
+        },
+    ),
+    (
+        "es",
+        Strings {
+            redirecting: "Redirigiendo...",
+            taking_you_to: "Te llevamos a",
+            in_one_second: "en 1 segundo...",
+            in_seconds: "en {delay} segundos...",
+            follow: "Si no se te redirige automáticamente, sigue este",
+            link: "enlace",
+            follow_end: ".",
+            expired: "Este enlace caducó el {date}.",
+            starting_soon: "Empieza pronto",
+            starts: "Empieza",
+            starts_in: "en",
+            once_started: "Cuando empiece, este enlace te llevará a",
+            follow_language: "Si no se te redirige automáticamente, sigue el enlace de tu idioma:",
+            any_language: "Cualquier otro idioma",
+        },
+    ),
+    (
+        "fr",
+        Strings {
+            redirecting: "Redirection...",
+            taking_you_to: "Redirection vers",
+            in_one_second: "dans 1 seconde...",
+            in_seconds: "dans {delay} secondes...",
+            follow: "Si vous n'êtes pas redirigé automatiquement, suivez ce",
+            link: "lien",
+            follow_end: ".",
+            expired: "Ce lien a expiré le {date}.",
+            starting_soon: "Ça commence bientôt",
+            starts: "Commence",
+            starts_in: "dans",
+            once_started: "Une fois commencé, ce lien vous mène à",
+            follow_language: "Si vous n'êtes pas redirigé automatiquement, suivez le lien de votre langue :",
+            any_language: "Toute autre langue",
+        },
+    ),
+    (
+        "it",
+        Strings {
+            redirecting: "Reindirizzamento...",
+            taking_you_to: "Ti stiamo portando a",
+            in_one_second: "tra 1 secondo...",
+            in_seconds: "tra {delay} secondi...",
+            follow: "Se non vieni reindirizzato automaticamente, segui questo",
+            link: "link",
+            follow_end: ".",
+            expired: "Questo link è scaduto il {date}.",
+            starting_soon: "Inizia a breve",
+            starts: "Inizia",
+            starts_in: "tra",
+            once_started: "Una volta iniziato, questo link ti porterà a",
+            follow_language: "Se non vieni reindirizzato automaticamente, segui il link della tua lingua:",
+            any_language: "Qualsiasi altra lingua",
+        },
+    ),
+    (
+        "nl",
+        Strings {
+            redirecting: "Doorsturen...",
+            taking_you_to: "We sturen je door naar",
+            in_one_second: "over 1 seconde...",
+            in_seconds: "over {delay} seconden...",
+            follow: "Word je niet automatisch doorgestuurd, volg dan deze",
+            link: "link",
+            follow_end: ".",
+            expired: "Deze link is verlopen op {date}.",
+            starting_soon: "Begint binnenkort",
+            starts: "Begint",
+            starts_in: "over",
+            once_started: "Zodra het begonnen is, brengt deze link je naar",
+            follow_language: "Word je niet automatisch doorgestuurd, volg dan de link voor jouw taal:",
+            any_language: "Elke andere taal",
+        },
+    ),
+    (
+        "pt",
+        Strings {
+            redirecting: "Redirecionando...",
+            taking_you_to: "Levando você para",
+            in_one_second: "em 1 segundo...",
+            in_seconds: "em {delay} segundos...",
+            follow: "Se você não for redirecionado automaticamente, siga este",
+            link: "link",
+            follow_end: ".",
+            expired: "Este link expirou em {date}.",
+            starting_soon: "Começa em breve",
+            starts: "Começa",
+            starts_in: "em",
+            once_started: "Quando começar, este link levará você para",
+            follow_language: "Se você não for redirecionado automaticamente, siga o link do seu idioma:",
+            any_language: "Qualquer outro idioma",
+        },
+    ),
+];
+
+/// Languages there are translations for.
+pub fn languages() -> impl Iterator<Item = &'static str> {
+    TRANSLATIONS.iter().map(|(language, _)| *language)
+}
+
+/// Text of the language, or of its primary language for tags such as
+/// `de-AT` or `pt-BR`.
+pub fn strings(language: &str) -> Result<&'static Strings, String> {
+    let primary = language.split(['-', '_']).next().unwrap_or_default();
+    TRANSLATIONS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(language))
+        .or_else(|| {
+            TRANSLATIONS
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(primary))
+        })
+        .map(|(_, strings)| strings)
+        .ok_or_else(|| {
+            format!(
+                "no translation for `{language}`, there are {}",
+                languages().collect::<Vec<_>>().join(", ")
+            )
+        })
+}
+
+impl Strings {
+    /// How long until the page redirects, e.g. `in 3 seconds...`.
+    pub fn countdown(&self, delay: u32) -> String {
+        match delay {
+            1 => self.in_one_second.to_string(),
+            _ => self.in_seconds.replace("{delay}", &delay.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn languages_fall_back_to_the_primary_language() {
+        assert_eq!(strings("de").unwrap().link, "Link");
+        assert_eq!(strings("pt-BR").unwrap().link, "link");
+        assert_eq!(strings("FR").unwrap().countdown(3), "dans 3 secondes...");
+        assert!(strings("xx").is_err());
+        for (_, strings) in TRANSLATIONS {
+            assert!(strings.in_seconds.contains("{delay}"));
+            assert!(strings.expired.contains("{date}"));
+        }
+    }
+}
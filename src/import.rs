@@ -0,0 +1,256 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Links exported from hosted shorteners, to move them into the manifest:
+//! the CSV exports of bit.ly, YOURLS and Shlink. Columns are found by their
+//! names in the header row, as the exports add and reorder them over time.
+
+use crate::date;
+use toml::value::Datetime;
+
+/// A link of an export.
+pub struct Entry {
+    pub slug: String,
+    pub target: String,
+    pub created: Option<Datetime>,
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    pub clicks: Option<u64>,
+}
+
+/// Names of the columns of an export, the first one with a value winning,
+/// written lowercase without spaces or punctuation.
+struct Columns {
+    slug: &'static [&'static str],
+    target: &'static [&'static str],
+    created: &'static [&'static str],
+    title: &'static [&'static str],
+    tags: &'static [&'static str],
+    clicks: &'static [&'static str],
+}
+
+/// Reads the CSV export of bit.ly's links, custom back-halves winning over
+/// the generated ones.
+pub fn bitly(content: &str) -> Result<Vec<Entry>, String> {
+    parse(
+        content,
+        &Columns {
+            slug: &["custombitlink", "bitlink", "link", "shorturl"],
+            target: &["longurl"],
+            created: &["createdat", "datecreated", "created"],
+            title: &["title"],
+            tags: &["tags"],
+            clicks: &["clicks", "totalclicks"],
+        },
+    )
+}
+
+/// Reads a CSV export of the `yourls_url` table. YOURLS keeps timestamps in
+/// the time of its server, they are taken as UTC.
+pub fn yourls(content: &str) -> Result<Vec<Entry>, String> {
+    parse(
+        content,
+        &Columns {
+            slug: &["keyword"],
+            target: &["url", "longurl"],
+            created: &["timestamp"],
+            title: &["title"],
+            tags: &[],
+            clicks: &["clicks"],
+        },
+    )
+}
+
+/// Reads the CSV export of Shlink's web client.
+pub fn shlink(content: &str) -> Result<Vec<Entry>, String> {
+    parse(
+        content,
+        &Columns {
+            slug: &["shortcode", "shorturl"],
+            target: &["longurl"],
+            created: &["createdat", "datecreated"],
+            title: &["title"],
+            tags: &["tags"],
+            clicks: &["visits", "visitscount"],
+        },
+    )
+}
+
+fn parse(content: &str, columns: &Columns) -> Result<Vec<Entry>, String> {
+    let mut rows = csv(content.trim_start_matches('\u{feff}')).into_iter();
+    let Some((_, header)) = rows.next() else {
+        return Ok(vec![]);
+    };
+    let header: Vec<String> = header
+        .iter()
+        .map(|name| {
+            name.chars()
+                .filter(char::is_ascii_alphanumeric)
+                .collect::<String>()
+                .to_ascii_lowercase()
+        })
+        .collect();
+    let find = |row: &[String], names: &[&str]| {
+        names.iter().find_map(|name| {
+            let i = header.iter().position(|column| column == name)?;
+            let value = row.get(i)?.trim();
+            (!value.is_empty()).then(|| value.to_string())
+        })
+    };
+    for (names, what) in [(columns.slug, "short links"), (columns.target, "targets")] {
+        if !names
+            .iter()
+            .any(|name| header.iter().any(|column| column == name))
+        {
+            return Err(format!("the header has no column of the {what}"));
+        }
+    }
+
+    let mut entries = vec![];
+    for (number, row) in rows {
+        if row.iter().all(|field| field.trim().is_empty()) {
+            continue;
+        }
+        let slug = find(&row, columns.slug)
+            .map(|slug| slug_of(&slug))
+            .filter(|slug| !slug.is_empty())
+            .ok_or_else(|| format!("line {number}: no short link"))?;
+        let target =
+            find(&row, columns.target).ok_or_else(|| format!("line {number}: no target"))?;
+        let created = find(&row, columns.created)
+            .map(|created| parse_date(&created).map_err(|e| format!("line {number}: {e}")))
+            .transpose()?;
+        let clicks = find(&row, columns.clicks)
+            .map(|clicks| {
+                clicks
+                    .parse()
+                    .map_err(|_| format!("line {number}: invalid click count `{clicks}`"))
+            })
+            .transpose()?;
+        let tags = find(&row, columns.tags)
+            .map(|tags| {
+                tags.split([',', '|'])
+                    .map(str::trim)
+                    .filter(|tag| !tag.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        entries.push(Entry {
+            slug,
+            target,
+            created,
+            title: find(&row, columns.title),
+            tags,
+            clicks,
+        });
+    }
+    Ok(entries)
+}
+
+/// The path of a short URL such as `https://bit.ly/3xYz`, or the slug as it
+/// is.
+fn slug_of(short: &str) -> String {
+    let rest = short.split_once("://").map_or(short, |(_, rest)| rest);
+    let path = match rest.split_once('/') {
+        Some((_, path)) if short.contains("://") || rest.contains('.') => path,
+        _ => rest,
+    };
+    path.trim_matches('/').to_string()
+}
+
+/// Dates as the exports write them, e.g. `2021-03-04T10:11:12+0000` or
+/// `2021-03-04 10:11:12`, UTC without an offset.
+fn parse_date(text: &str) -> Result<Datetime, String> {
+    let mut text = text.trim().replacen(' ', "T", 1);
+    let time = text.split_once('T').map_or("", |(_, time)| time);
+    if let Some(sign) = time.rfind(['+', '-']) {
+        let offset = &time[sign + 1..];
+        if offset.len() == 4 && offset.chars().all(|c| c.is_ascii_digit()) {
+            text.insert(text.len() - 2, ':');
+        }
+    } else if !time.is_empty() && !time.ends_with('Z') {
+        text.push('Z');
+    }
+    date::parse_expiry(&text)
+}
+
+/// Rows of the CSV by the number of the line they start on, with fields in
+/// double quotes having commas, quotes doubled and line breaks.
+fn csv(content: &str) -> Vec<(usize, Vec<String>)> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut start = 1;
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push((start, std::mem::take(&mut row)));
+                line += 1;
+                start = line;
+            }
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push((start, row));
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bitly_exports_are_read() {
+        let entries = bitly(
+            "\u{feff}Date Created,Title,Long URL,Bitlink,Custom Bitlink,Clicks,Tags\n\
+            2021-03-04T10:11:12+0000,\"Docs, v2\",https://example.com/docs,bit.ly/3xYz,,42,\"a,b\"\r\n\
+            2021-03-05 08:00:00,,https://example.com/,https://bit.ly/3abc,https://bit.ly/home,0,\n",
+        )
+        .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].slug, "3xYz");
+        assert_eq!(entries[0].title.as_deref(), Some("Docs, v2"));
+        assert_eq!(entries[0].tags, ["a", "b"]);
+        assert_eq!(entries[0].clicks, Some(42));
+        assert_eq!(
+            entries[0].created.as_ref().map(date::to_unix),
+            Some(1614852672)
+        );
+        assert_eq!(entries[1].slug, "home");
+        assert!(entries[1].title.is_none());
+        assert!(bitly("Title,Long URL\nx,https://example.com/\n").is_err());
+    }
+}
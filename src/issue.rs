@@ -0,0 +1,124 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The issue or ticket a link was created for, e.g. `ORG-1234` in Jira or
+//! `owner/repo#123` on GitHub.
+
+use crate::config::ShurlConfig;
+use crate::error::ShurlError;
+use crate::github::GitHub;
+use crate::hash;
+use crate::http::Request;
+use std::env;
+
+pub enum Issue<'a> {
+    Jira { key: &'a str },
+    GitHub { repo: &'a str, number: u64 },
+}
+
+impl<'a> Issue<'a> {
+    pub fn parse(reference: &'a str) -> Result<Self, String> {
+        if let Some((repo, number)) = reference.split_once('#') {
+            let valid_repo = repo
+                .split_once('/')
+                .is_some_and(|(owner, name)| !owner.is_empty() && !name.contains('/'));
+            if let (true, Ok(number)) = (valid_repo, number.parse()) {
+                return Ok(Issue::GitHub { repo, number });
+            }
+        } else if let Some((project, number)) = reference.split_once('-') {
+            let valid_project = project.starts_with(|c: char| c.is_ascii_uppercase())
+                && project
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+            if valid_project && !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) {
+                return Ok(Issue::Jira { key: reference });
+            }
+        }
+        Err(format!(
+            "invalid issue `{reference}`: use a Jira key like ORG-1234 or owner/repo#123 for GitHub"
+        ))
+    }
+
+    /// Web page of the issue. Jira issues need `jira_url` in the config.
+    pub fn url(&self, cfg: &ShurlConfig) -> Option<String> {
+        match self {
+            Issue::Jira { key } => cfg
+                .jira_url
+                .as_ref()
+                .map(|jira_url| format!("{}/browse/{key}", jira_url.trim_end_matches('/'))),
+            Issue::GitHub { repo, number } => {
+                let server = env::var("GITHUB_SERVER_URL")
+                    .unwrap_or_else(|_| "https://github.com".to_string());
+                Some(format!(
+                    "{}/{repo}/issues/{number}",
+                    server.trim_end_matches('/')
+                ))
+            }
+        }
+    }
+
+    /// Checks with the tracker's API that the issue exists.
+    pub fn verify(&self, cfg: &ShurlConfig) -> Result<(), ShurlError> {
+        match self {
+            Issue::Jira { key } => {
+                let jira_url = cfg.jira_url.as_ref().ok_or_else(|| {
+                    ShurlError::Config(
+                        "verifying Jira issues needs `jira_url` in the config".to_string(),
+                    )
+                })?;
+                let url = format!(
+                    "{}/rest/api/2/issue/{key}?fields=summary",
+                    jira_url.trim_end_matches('/')
+                );
+                let mut request = Request::new("GET", &url).header("Accept", "application/json");
+                // Jira Cloud takes the account's email with an API token,
+                // self-hosted Jira a personal access token on its own.
+                let token = cfg
+                    .jira_token
+                    .clone()
+                    .or_else(|| env::var("JIRA_API_TOKEN").ok());
+                match (&cfg.jira_user, token) {
+                    (Some(user), Some(token)) => {
+                        let credentials = hash::base64(format!("{user}:{token}").as_bytes());
+                        request = request.header("Authorization", &format!("Basic {credentials}"));
+                    }
+                    (None, Some(token)) => {
+                        request = request.header("Authorization", &format!("Bearer {token}"));
+                    }
+                    (_, None) => {}
+                }
+                let response = request.send().map_err(|e| {
+                    ShurlError::Network(format!("failed to look up Jira issue {key}: {e}"))
+                })?;
+                match response.status {
+                    200 => Ok(()),
+                    404 => Err(ShurlError::NotFound(format!(
+                        "Jira issue {key} doesn't exist"
+                    ))),
+                    status => Err(ShurlError::Network(format!(
+                        "failed to look up Jira issue {key}: got {status}"
+                    ))),
+                }
+            }
+            Issue::GitHub { repo, number } => GitHub::new(cfg)
+                .get(&format!("/repos/{repo}/issues/{number}"))
+                .map(|_| ())
+                .map_err(|e| {
+                    ShurlError::Network(format!("failed to look up {repo}#{number}: {e}"))
+                }),
+        }
+    }
+}
@@ -0,0 +1,745 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Short links kept in a Git repository: a manifest of links, the pages and
+//! redirect rules generated from it, and a commit for every change.
+//!
+//! [`Shurl`] is the entry point for using shurl from other programs, e.g. a
+//! chat bot creating links:
+//!
+//! ```no_run
+//! use shurl::config::{self, ShurlConfig};
+//! use shurl::{LinkOptions, Shurl};
+//!
+//! let cfg = ShurlConfig::load(&config::config_path())?;
+//! let shurl = Shurl::open(cfg)?;
+//! let link = shurl.add_link("https://example.com/docs", LinkOptions::default())?;
+//! println!("{}", shurl.config().short_url(&link.slug).unwrap_or(link.slug));
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+mod assets;
+pub mod backend;
+pub mod bundle;
+mod cache;
+pub mod ci_docs;
+pub mod clipboard;
+pub mod clipd;
+pub mod completions;
+pub mod config;
+pub mod date;
+pub mod deprecation;
+pub mod error;
+pub mod federation;
+pub mod git;
+pub mod github;
+mod hash;
+pub mod health;
+pub mod history;
+mod http;
+pub mod i18n;
+pub mod import;
+pub mod issue;
+pub mod json;
+pub mod lint;
+pub mod manifest;
+pub mod manpage;
+pub mod output;
+pub mod pages;
+mod png;
+pub mod preview;
+mod provenance;
+pub mod qr;
+pub mod regex;
+pub mod release_links;
+mod reload;
+mod render;
+pub mod repo_config;
+pub mod screenshot;
+pub mod search;
+pub mod serve;
+mod site;
+pub mod slug;
+pub mod stats;
+pub mod tags;
+pub mod target;
+mod template;
+pub mod title;
+pub mod trash;
+pub mod tui;
+pub mod verify;
+pub mod workers_kv;
+
+use crate::bundle::Bundles;
+use crate::config::{CommitStyle, ReferrerPolicy, ShurlConfig, SlugStyle};
+use crate::error::ShurlError;
+use crate::federation::Federation;
+use crate::issue::Issue;
+use crate::manifest::{Link, Manifest};
+use crate::output::Colorize;
+use crate::provenance::Source;
+use crate::repo_config::{RepoConfig, MARKER_FILE};
+use crate::serve::NewLink;
+use crate::slug::NameFormat;
+use crate::trash::{Trash, TrashedLink};
+use rand::RngCore;
+use shellexpand::tilde;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::value::Datetime;
+
+/// A repository of short links, opened with the config of whoever publishes
+/// to it.
+pub struct Shurl {
+    cfg: ShurlConfig,
+    repo: git2::Repository,
+    repo_path: PathBuf,
+    /// Where the names of links created without one come from.
+    rng: RefCell<Box<dyn RngCore + Send>>,
+}
+
+/// Everything about a new link but its target.
+#[derive(Default)]
+pub struct LinkOptions {
+    /// Slug of the link, random if not given.
+    pub name: Option<String>,
+    /// Status code of server-side redirects, `redirect_status` from the
+    /// config if not given.
+    pub status: Option<u16>,
+    /// Issue the link is created for, e.g. `ORG-1234` or `owner/repo#123`.
+    pub issue: Option<String>,
+    /// Tags from the vocabulary in `.shurl.toml`, if it has one.
+    pub tags: Vec<String>,
+    /// When the event the link is for starts, showing a countdown until then.
+    pub starts: Option<Datetime>,
+    /// When the link expires, instead of after the namespace's `expiry_days`.
+    pub expires: Option<Datetime>,
+    /// Seconds its page waits before redirecting, `redirect_delay` from the
+    /// config if not given.
+    pub delay: Option<u32>,
+    /// Whether search engines are asked not to index its page, `noindex`
+    /// from the config if not given.
+    pub noindex: Option<bool>,
+    /// What the target learns of where readers came from,
+    /// `referrer_policy` from the config if not given.
+    pub referrer: Option<ReferrerPolicy>,
+    /// Template of its page in `templates/`, e.g. `interstitial`.
+    pub template: Option<String>,
+    /// Language of the text of its page, `language` from the config if not
+    /// given.
+    pub lang: Option<String>,
+    /// How apps unfurling the link show it, see [`preview::fetch`].
+    pub preview: Option<preview::Preview>,
+    /// Targets for readers of other languages, by language tag.
+    pub locales: BTreeMap<String, String>,
+    /// Whether to take a screenshot of the target for the index,
+    /// `screenshots` from the config if not given.
+    pub screenshot: Option<bool>,
+    /// Whether to replace the link if the name is taken already, rather
+    /// than fail.
+    pub replace: bool,
+    /// What the link is for, e.g. the title of the target page.
+    pub description: Option<String>,
+    /// Whether to name the link after its description, e.g.
+    /// `my-blog-post-title`, if it isn't given a name.
+    pub name_from_description: bool,
+}
+
+/// Links that can't be created, by their position, with why not.
+pub type Rejected = Vec<(usize, String)>;
+
+impl Shurl {
+    /// Opens the repository at `repo_path` from the config. Settings the
+    /// repository has in `.shurl.toml` replace the ones of the config.
+    pub fn open(mut cfg: ShurlConfig) -> Result<Self, ShurlError> {
+        let repo_path = expand_repo_path(&cfg)?;
+        let repo = git2::Repository::open(&repo_path)
+            .map_err(|e| ShurlError::Git(format!("failed to open repository: {e}")))?;
+        RepoConfig::load(&repo_path)
+            .map_err(ShurlError::Config)?
+            .apply_to(&mut cfg);
+        Ok(Self {
+            cfg,
+            repo,
+            repo_path,
+            rng: RefCell::new(Box::new(slug::unseeded())),
+        })
+    }
+
+    /// Opens the repository like [`Shurl::open`], creating it first if
+    /// needed, and marks it as managed by shurl with a commit adding the
+    /// [`MARKER_FILE`]. Returns whether that commit was made, which it isn't
+    /// for repositories marked already.
+    pub fn init(cfg: ShurlConfig) -> Result<(Self, bool), ShurlError> {
+        let repo_path = expand_repo_path(&cfg)?;
+        if git2::Repository::open(&repo_path).is_err() {
+            fs::create_dir_all(&repo_path).map_err(|e| {
+                ShurlError::Git(format!("failed to create {}: {e}", repo_path.display()))
+            })?;
+            git2::Repository::init_opts(
+                &repo_path,
+                git2::RepositoryInitOptions::new().initial_head(&cfg.branch),
+            )
+            .map_err(|e| ShurlError::Git(format!("failed to create repository: {e}")))?;
+        }
+
+        let shurl = Self::open(cfg)?;
+        if repo_config::is_marked(&shurl.repo_path) {
+            return Ok((shurl, false));
+        }
+        fs::write(
+            shurl.repo_path.join(MARKER_FILE),
+            "This repository is managed by shurl, which refuses to change \
+            repositories without this file.\n",
+        )
+        .map_err(|e| ShurlError::Git(format!("failed to write {MARKER_FILE}: {e}")))?;
+        git::commit_all(&shurl.repo, &shurl.cfg, "Mark repository for shurl")?;
+        Ok((shurl, true))
+    }
+
+    /// Replaces where the names of links created without one come from, e.g.
+    /// with [`slug::seeded`] for the same names on every run.
+    pub fn set_rng(&mut self, rng: impl RngCore + Send + 'static) {
+        self.rng = RefCell::new(Box::new(rng));
+    }
+
+    pub fn config(&self) -> &ShurlConfig {
+        &self.cfg
+    }
+
+    /// Replaces the config, e.g. after it was reloaded. The repository stays
+    /// the one opened first, and the config should already have the
+    /// repository's settings, see [`RepoConfig::apply_to`].
+    pub fn set_config(&mut self, cfg: ShurlConfig) {
+        self.cfg = cfg;
+    }
+
+    pub fn repo(&self) -> &git2::Repository {
+        &self.repo
+    }
+
+    pub fn repo_path(&self) -> &Path {
+        &self.repo_path
+    }
+
+    /// Every link, newest first.
+    pub fn list_links(&self) -> Result<Vec<Link>, ShurlError> {
+        let manifest = cache::load_manifest(&self.repo, &self.repo_path)?;
+        Ok(manifest.newest_first().into_iter().cloned().collect())
+    }
+
+    /// Links whose slug, target, description or tags match the query,
+    /// newest first.
+    pub fn search_links(&self, query: &str, regex: bool) -> Result<Vec<Link>, ShurlError> {
+        let query = search::Query::new(query, regex).map_err(ShurlError::InvalidInput)?;
+        Ok(self
+            .list_links()?
+            .into_iter()
+            .filter(|link| query.matches(link))
+            .collect())
+    }
+
+    /// The link to the URL, if it's shortened already.
+    pub fn find_target(&self, url: &str) -> Result<Option<Link>, ShurlError> {
+        let target = target::parse(&self.cfg, url).map_err(ShurlError::InvalidInput)?;
+        let manifest = cache::load_manifest(&self.repo, &self.repo_path)?;
+        Ok(manifest
+            .newest_first()
+            .into_iter()
+            .find(|link| {
+                // Links added before their target was normalized the same way
+                // still count.
+                link.target == target.as_str()
+                    || target::parse(&self.cfg, &link.target).is_ok_and(|url| url == target)
+            })
+            .cloned())
+    }
+
+    /// Creates a link and publishes it.
+    pub fn add_link(&self, url: &str, options: LinkOptions) -> Result<Link, ShurlError> {
+        let mut manifest = Manifest::load(&self.repo_path)?;
+        let name = match (options.name, &options.description) {
+            (None, Some(description)) if options.name_from_description => {
+                slug::title_slug(description, &manifest, &self.repo_path)
+            }
+            (name, _) => name,
+        };
+        let mut link = self.new_link(&manifest, url, name, options.status)?;
+        let mut message = match manifest.get(&link.slug) {
+            Some(existing) if !options.replace => {
+                return Err(ShurlError::Conflict(format!(
+                    "slug `{}` already points to {}, pass --replace to replace it",
+                    link.slug, existing.target
+                )))
+            }
+            Some(_) => format!("Point {} to {}", link.slug, link.target),
+            None => self.add_message(&link),
+        };
+        if let Some(reference) = &options.issue {
+            let parsed = Issue::parse(reference).map_err(ShurlError::InvalidInput)?;
+            if self.cfg.verify_issues {
+                parsed.verify(&self.cfg)?;
+            }
+            message.push_str(&format!("\n\nRefs: {reference}"));
+        }
+        link.issue = options.issue;
+        link.description = options.description;
+        link.preview = options.preview;
+        let repo_config = RepoConfig::load(&self.repo_path).map_err(ShurlError::Config)?;
+        for tag in &options.tags {
+            let tag = tags::check(&repo_config, tag).map_err(ShurlError::InvalidInput)?;
+            if !link.tags.contains(&tag) {
+                link.tags.push(tag);
+            }
+        }
+        link.starts = options.starts;
+        link.delay = options.delay;
+        link.noindex = options.noindex;
+        link.referrer = options.referrer;
+        if let Some(template) = &options.template {
+            repo_config::load_named_template(&self.repo_path, template)
+                .map_err(ShurlError::InvalidInput)?;
+        }
+        link.template = options.template;
+        if let Some(lang) = &options.lang {
+            i18n::strings(lang).map_err(ShurlError::InvalidInput)?;
+        }
+        link.lang = options.lang;
+        if let Some(expires) = options.expires {
+            if date::to_unix(&expires) <= date::to_unix(&link.created) {
+                return Err(ShurlError::InvalidInput(format!(
+                    "invalid expiry {expires}: it has passed already"
+                )));
+            }
+            link.expires = Some(expires);
+        }
+        for (language, target) in options.locales {
+            if !is_language_tag(&language) {
+                return Err(ShurlError::InvalidInput(format!(
+                    "invalid language `{language}`: use a tag such as `de` or `pt-BR`"
+                )));
+            }
+            let url = target::parse(&self.cfg, &target)
+                .map_err(|e| ShurlError::InvalidInput(format!("target for `{language}`: {e}")))?;
+            if let Some(namespace) = repo_config.namespace_for(&link.slug) {
+                namespace
+                    .check(&link.slug, &url)
+                    .map_err(ShurlError::InvalidInput)?;
+            }
+            link.locales.insert(language, url.to_string());
+        }
+        if options.screenshot.unwrap_or(self.cfg.screenshots) {
+            self.capture_screenshot(&link);
+        }
+        manifest.insert(link.clone());
+        self.publish(&manifest, &message)?;
+        Ok(link)
+    }
+
+    /// Creates several links in a single commit only if every one of them
+    /// can be created. Otherwise nothing is written and the error of each is
+    /// returned by its position. If publishing fails, the files written for
+    /// it and the commit are rolled back.
+    pub fn add_links_atomically(
+        &self,
+        links: Vec<NewLink>,
+    ) -> Result<Result<Vec<Link>, Rejected>, ShurlError> {
+        let mut manifest = Manifest::load(&self.repo_path)?;
+        let mut added = vec![];
+        let mut rejected = vec![];
+        for (i, link) in links.into_iter().enumerate() {
+            let result = self
+                .new_link(&manifest, &link.url, link.name, link.status)
+                .and_then(|link| match manifest.get(&link.slug) {
+                    Some(existing) => Err(ShurlError::Conflict(format!(
+                        "slug `{}` already points to {}",
+                        link.slug, existing.target
+                    ))),
+                    None => Ok(link),
+                });
+            match result {
+                Ok(link) => {
+                    manifest.insert(link.clone());
+                    added.push(link);
+                }
+                Err(e) => rejected.push((i, e.to_string())),
+            }
+        }
+        if !rejected.is_empty() {
+            return Ok(Err(rejected));
+        }
+
+        let message = match added.as_slice() {
+            [] => return Ok(Ok(added)),
+            [link] => self.add_message(link),
+            links => format!("Add {} redirects", links.len()),
+        };
+        let snapshot = git::snapshot(&self.repo)?;
+        if self.cfg.screenshots {
+            for link in &added {
+                self.capture_screenshot(link);
+            }
+        }
+        if let Err(e) = self.publish(&manifest, &message) {
+            if let Err(reset) = git::roll_back(&self.repo, &snapshot) {
+                eprintln!("{} {}", "Warning:".yellow(), reset.bold());
+                return Err(e);
+            }
+            return Err(ShurlError::Git(format!(
+                "rolled back the links, publishing them failed: {e}"
+            )));
+        }
+        Ok(Ok(added))
+    }
+
+    /// Creates several links in a single commit. Links that are invalid get an
+    /// error of their own, the others are published together.
+    pub fn add_links(&self, links: Vec<NewLink>) -> Vec<Result<String, String>> {
+        let mut manifest = match Manifest::load(&self.repo_path) {
+            Ok(manifest) => manifest,
+            Err(e) => return links.iter().map(|_| Err(e.clone())).collect(),
+        };
+
+        let mut results = vec![];
+        let mut added = vec![];
+        for link in links {
+            let result = self
+                .new_link(&manifest, &link.url, link.name, link.status)
+                .and_then(|link| match manifest.get(&link.slug) {
+                    Some(existing) => Err(ShurlError::Conflict(format!(
+                        "slug `{}` already points to {}",
+                        link.slug, existing.target
+                    ))),
+                    None => Ok(link),
+                })
+                .map_err(|e| e.to_string());
+            results.push(result.map(|link| {
+                if self.cfg.screenshots {
+                    self.capture_screenshot(&link);
+                }
+                let slug = link.slug.clone();
+                added.push(link.clone());
+                manifest.insert(link);
+                slug
+            }));
+        }
+
+        let message = match added.as_slice() {
+            [] => return results,
+            [link] => self.add_message(link),
+            links => format!("Add {} redirects", links.len()),
+        };
+        if let Err(e) = self.publish(&manifest, &message) {
+            for result in &mut results {
+                if result.is_ok() {
+                    *result = Err(e.to_string());
+                }
+            }
+        }
+        results
+    }
+
+    /// Moves a link to the trash, leaving a page saying it's gone, and
+    /// publishes that.
+    pub fn remove_link(&self, slug: &str) -> Result<Link, ShurlError> {
+        let mut manifest = Manifest::load(&self.repo_path)?;
+        let link = manifest
+            .remove(slug)
+            .ok_or_else(|| ShurlError::NotFound(format!("no link named `{slug}`")))?;
+        let mut trash = Trash::load(&self.repo_path)?;
+        trash.insert(TrashedLink::new(link.clone()));
+        trash.save(&self.repo_path)?;
+
+        self.publish(&manifest, &format!("Remove {slug}"))?;
+        Ok(link)
+    }
+
+    /// Deletes a link for good, along with any earlier link of the same slug
+    /// in the trash, so its short URL answers 404 like it never existed.
+    pub fn delete_link(&self, slug: &str) -> Result<Link, ShurlError> {
+        let mut manifest = Manifest::load(&self.repo_path)?;
+        let link = manifest
+            .remove(slug)
+            .ok_or_else(|| ShurlError::NotFound(format!("no link named `{slug}`")))?;
+        let mut trash = Trash::load(&self.repo_path)?;
+        if trash.take(slug).is_some() {
+            trash.save(&self.repo_path)?;
+        }
+        pages::remove_page(&self.repo_path, slug)?;
+
+        self.publish(&manifest, &format!("Delete {slug}"))?;
+        Ok(link)
+    }
+
+    /// Gives a link another name, checked like the name of a new link, and
+    /// publishes that. Its page and screenshot move along, the page keeping
+    /// its layout. The old short URL stops working.
+    pub fn rename_link(&self, old: &str, new: &str) -> Result<Link, ShurlError> {
+        let mut manifest = Manifest::load(&self.repo_path)?;
+        let link = manifest
+            .remove(old)
+            .ok_or_else(|| ShurlError::NotFound(format!("no link named `{old}`")))?;
+        if let Some(existing) = manifest.get(new) {
+            return Err(ShurlError::Conflict(format!(
+                "slug `{new}` already points to {}",
+                existing.target
+            )));
+        }
+        self.new_link(&manifest, &link.target, Some(new.to_string()), link.status)?;
+
+        let link = Link {
+            slug: new.to_string(),
+            ..link
+        };
+        manifest.insert(link.clone());
+        pages::move_page(&self.repo_path, old, new)?;
+        let shot = self.repo_path.join(screenshot::path(old));
+        if shot.is_file() {
+            let moved = self.repo_path.join(screenshot::path(new));
+            if let Some(parent) = moved.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+            }
+            fs::rename(&shot, &moved)
+                .map_err(|e| format!("failed to move {}: {e}", shot.display()))?;
+        }
+
+        self.publish(&manifest, &format!("Rename {old} to {new}"))?;
+        Ok(link)
+    }
+
+    /// Moves every link past its expiry date to the trash in one commit,
+    /// leaving pages saying they're gone. Only lists them with `dry_run`.
+    pub fn prune_expired(&self, dry_run: bool) -> Result<Vec<Link>, ShurlError> {
+        let mut manifest = Manifest::load(&self.repo_path)?;
+        let (expired, kept): (Vec<Link>, Vec<Link>) =
+            manifest.links.drain(..).partition(Link::is_expired);
+        manifest.links = kept;
+        if expired.is_empty() || dry_run {
+            return Ok(expired);
+        }
+
+        let mut trash = Trash::load(&self.repo_path)?;
+        for link in &expired {
+            trash.insert(TrashedLink::new(link.clone()));
+        }
+        trash.save(&self.repo_path)?;
+
+        let message = match expired.as_slice() {
+            [link] => format!("Remove expired {}", link.slug),
+            links => format!("Remove {} expired links", links.len()),
+        };
+        self.publish(&manifest, &message)?;
+        Ok(expired)
+    }
+
+    /// Checks a link about to be added and fills in everything but its
+    /// target, without adding it to the manifest.
+    pub fn new_link(
+        &self,
+        manifest: &Manifest,
+        url: &str,
+        name: Option<String>,
+        status: Option<u16>,
+    ) -> Result<Link, ShurlError> {
+        let url = target::parse(&self.cfg, url).map_err(ShurlError::InvalidInput)?;
+        if let Some(status) = status {
+            if !backend::REDIRECT_STATUSES.contains(&status) {
+                return Err(ShurlError::InvalidInput(format!(
+                    "invalid redirect status {status}: use one of {:?}",
+                    backend::REDIRECT_STATUSES
+                )));
+            }
+        }
+
+        let slug = match name {
+            Some(name) => {
+                slug::validate(&name).map_err(ShurlError::InvalidInput)?;
+                name
+            }
+            None => {
+                let format = NameFormat::of(&self.cfg);
+                match self.cfg.slug_style {
+                    SlugStyle::Random => slug::new_slug(
+                        &mut **self.rng.borrow_mut(),
+                        format,
+                        manifest,
+                        &self.repo_path,
+                    )
+                    .map_err(ShurlError::Conflict)?,
+                    SlugStyle::Hash => slug::hash_slug(&url, format, manifest, &self.repo_path)
+                        .map_err(ShurlError::Conflict)?,
+                    SlugStyle::Words => {
+                        config::check_slug_words(self.cfg.slug_words, &self.cfg.slug_separator)
+                            .map_err(ShurlError::Config)?;
+                        slug::new_word_slug(
+                            &mut **self.rng.borrow_mut(),
+                            self.cfg.slug_words,
+                            &self.cfg.slug_separator,
+                            manifest,
+                            &self.repo_path,
+                        )
+                        .map_err(ShurlError::Conflict)?
+                    }
+                }
+            }
+        };
+        if Bundles::load(&self.repo_path)?.get(&slug).is_some() {
+            return Err(ShurlError::Conflict(format!("`{slug}` is a bundle")));
+        }
+
+        let mut link = Link {
+            status,
+            owner: Some(self.cfg.author().to_string()),
+            ..Link::new(&slug, url.as_str(), date::now())
+        };
+        let repo_config = RepoConfig::load(&self.repo_path).map_err(ShurlError::Config)?;
+        if let Some(namespace) = repo_config.namespace_for(&slug) {
+            namespace
+                .check(&slug, &url)
+                .map_err(ShurlError::InvalidInput)?;
+            namespace.apply(&mut link);
+        }
+        Ok(link)
+    }
+
+    /// Saves the manifest, regenerates everything derived from it, then
+    /// commits and pushes the result. Returns the id of the commit.
+    pub fn publish(&self, manifest: &Manifest, message: &str) -> Result<git2::Oid, ShurlError> {
+        manifest.save(&self.repo_path)?;
+
+        self.purge_trash(manifest)?;
+        self.refresh_stale_partners();
+        self.build_site(manifest)?;
+
+        let object_id = git::commit_all(&self.repo, &self.cfg, message)?;
+        git::push(&self.repo_path, &self.cfg.branch)?;
+        Ok(object_id)
+    }
+
+    /// Regenerates every page and publishes them, e.g. after the templates or
+    /// `analytics` changed.
+    pub fn rerender(&self) -> Result<git2::Oid, ShurlError> {
+        let manifest = Manifest::load(&self.repo_path)?;
+        self.publish(&manifest, "Rerender pages")
+    }
+
+    /// The page a link would get, see [`site::page`].
+    pub fn page_of(&self, link: &Link) -> Result<String, ShurlError> {
+        Ok(site::page(&self.repo_path, &self.cfg, link)?)
+    }
+
+    /// Message of the commit adding a single link.
+    pub fn add_message(&self, link: &Link) -> String {
+        match self.cfg.commit_style {
+            CommitStyle::Plain => format!("Add redirect to {}", link.target),
+            CommitStyle::Conventional => {
+                format!("feat(links): add {} -> {}", link.slug, link.target)
+            }
+        }
+    }
+
+    /// Regenerates the pages and redirect rules, printing what the backends
+    /// warn about.
+    pub fn build_site(&self, manifest: &Manifest) -> Result<(), ShurlError> {
+        let source = Source {
+            repository: self
+                .repo
+                .find_remote("origin")
+                .ok()
+                .and_then(|remote| remote.url().map(str::to_string)),
+            base_commit: git::head_id(&self.repo),
+        };
+        for warning in site::build(&self.repo_path, &self.cfg, manifest, &source)? {
+            eprintln!("{} {}", "Warning:".yellow(), warning.bold());
+        }
+        Ok(())
+    }
+
+    /// Saves a screenshot of the link's target. The link works without one,
+    /// so failing is only a warning.
+    fn capture_screenshot(&self, link: &Link) {
+        if let Err(e) = screenshot::capture(&self.repo_path, &self.cfg, link) {
+            eprintln!("{} {}", "Warning:".yellow(), e.bold());
+        }
+    }
+
+    /// Deletes the links whose time in the trash is over.
+    fn purge_trash(&self, manifest: &Manifest) -> Result<(), String> {
+        let mut trash = Trash::load(&self.repo_path)?;
+        let expired = trash.take_expired(self.cfg.trash_retention_days);
+        if expired.is_empty() {
+            return Ok(());
+        }
+        for link in &expired {
+            if manifest.get(&link.slug).is_none() {
+                pages::remove_page(&self.repo_path, &link.slug)?;
+            }
+        }
+        trash.save(&self.repo_path)
+    }
+
+    /// Fetches partners whose snapshot is older than the configured interval.
+    /// Failing partners keep their old snapshot and don't stop the run.
+    fn refresh_stale_partners(&self) {
+        let Ok(mut federation) = Federation::load(&self.repo_path) else {
+            return;
+        };
+        let mut changed = false;
+        for partner in &mut federation.partners {
+            if !partner.is_stale(self.cfg.federation_refresh_hours) {
+                continue;
+            }
+            match federation::fetch(&self.repo_path, partner) {
+                Ok(_) => changed = true,
+                Err(e) => eprintln!(
+                    "{} {} {}",
+                    "Warning:".yellow(),
+                    format!("failed to refresh partner {}:", partner.name).bold(),
+                    e
+                ),
+            }
+        }
+        if changed {
+            if let Err(e) = federation.save(&self.repo_path) {
+                eprintln!("{} {}", "Warning:".yellow(), e.bold());
+            }
+        }
+    }
+}
+
+/// `repo_path` of the config with `~` expanded.
+fn expand_repo_path(cfg: &ShurlConfig) -> Result<PathBuf, ShurlError> {
+    let repo_path = cfg
+        .repo_path
+        .to_str()
+        .ok_or_else(|| ShurlError::Config("`repo_path` isn't valid UTF-8".to_string()))?;
+    Ok(PathBuf::from(tilde(repo_path).as_ref()))
+}
+
+/// Whether `tag` looks like a BCP 47 language tag, e.g. `de`, `pt-BR` or
+/// `zh-Hant`.
+fn is_language_tag(tag: &str) -> bool {
+    let mut subtags = tag.split('-');
+    let language = subtags.next().unwrap_or_default();
+    (2..=3).contains(&language.len())
+        && language.bytes().all(|b| b.is_ascii_alphabetic())
+        && subtags.all(|subtag| {
+            (1..=8).contains(&subtag.len()) && subtag.bytes().all(|b| b.is_ascii_alphanumeric())
+        })
+}
@@ -0,0 +1,169 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! `shurl lint`: rules the links of a repository should follow, with levels
+//! set in the `[lint]` table of `.shurl.toml` so CI can gate merges on them.
+
+use crate::date;
+use crate::json::Value;
+use crate::manifest::Manifest;
+use crate::repo_config::RepoConfig;
+use serde::Deserialize;
+use std::fmt::{self, Display, Formatter};
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Off,
+    Warn,
+    Error,
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Level::Off => "off",
+            Level::Warn => "warning",
+            Level::Error => "error",
+        })
+    }
+}
+
+/// Level of every rule, named after the rule.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LintConfig {
+    /// Longest slug `slug_too_long` allows.
+    pub max_slug_length: usize,
+    pub slug_too_long: Level,
+    pub target_not_https: Level,
+    pub missing_description: Level,
+    /// Tags missing from `tags` in `.shurl.toml`, if it lists any.
+    pub unknown_tag: Level,
+    /// Links past their expiry date that are still in the manifest.
+    pub expired: Level,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            max_slug_length: 32,
+            slug_too_long: Level::Warn,
+            target_not_https: Level::Warn,
+            missing_description: Level::Off,
+            unknown_tag: Level::Error,
+            expired: Level::Warn,
+        }
+    }
+}
+
+pub struct Finding<'a> {
+    pub slug: &'a str,
+    pub rule: &'static str,
+    pub level: Level,
+    pub message: String,
+}
+
+impl Finding<'_> {
+    pub fn to_json(&self) -> Value {
+        let mut value = Value::object();
+        value
+            .set("slug", self.slug)
+            .set("rule", self.rule)
+            .set("level", self.level.to_string())
+            .set("message", self.message.as_str());
+        value
+    }
+}
+
+/// Checks every link, in the order of the manifest, leaving out the rules
+/// that are off.
+pub fn check<'a>(repo_config: &RepoConfig, manifest: &'a Manifest) -> Vec<Finding<'a>> {
+    let lint = &repo_config.lint;
+    let now = date::to_unix(&date::now());
+    let mut findings = vec![];
+    for link in &manifest.links {
+        let mut found = |rule, level, message| {
+            if level != Level::Off {
+                findings.push(Finding {
+                    slug: &link.slug,
+                    rule,
+                    level,
+                    message,
+                });
+            }
+        };
+
+        let length = link.slug.chars().count();
+        if length > lint.max_slug_length {
+            found(
+                "slug_too_long",
+                lint.slug_too_long,
+                format!(
+                    "slug is {length} characters long, more than {}",
+                    lint.max_slug_length
+                ),
+            );
+        }
+        if !link.target.starts_with("https://") {
+            found(
+                "target_not_https",
+                lint.target_not_https,
+                format!("{} isn't HTTPS", link.target),
+            );
+        }
+        if link
+            .description
+            .as_deref()
+            .unwrap_or_default()
+            .trim()
+            .is_empty()
+        {
+            found(
+                "missing_description",
+                lint.missing_description,
+                "has no description".to_string(),
+            );
+        }
+        if !repo_config.tags.is_empty() {
+            for tag in link
+                .tags
+                .iter()
+                .filter(|tag| !repo_config.tags.contains(tag))
+            {
+                found(
+                    "unknown_tag",
+                    lint.unknown_tag,
+                    format!("tag `{tag}` isn't one of `tags` in .shurl.toml"),
+                );
+            }
+        }
+        if let Some(expires) = &link.expires {
+            if date::to_unix(expires) <= now {
+                found(
+                    "expired",
+                    lint.expired,
+                    format!(
+                        "expired on {}, remove it with `shurl rm {}` or every expired link with `shurl prune`",
+                        date::format_date(expires),
+                        link.slug
+                    ),
+                );
+            }
+        }
+    }
+    findings
+}
@@ -14,216 +14,3117 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use clap::Parser;
-use owo_colors::OwoColorize;
-use rand::Rng;
-use serde::{Deserialize, Serialize};
-use shellexpand::tilde;
-use std::fs;
-use std::fs::OpenOptions;
-use std::io::{Read, Write};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use shurl::bundle::{Bundle, Bundles};
+use shurl::completions::{Dynamic, Shell};
+use shurl::config::{Backend, QrFormat, ReferrerPolicy, ShurlConfig};
+use shurl::deprecation::Deprecations;
+use shurl::error::ShurlError;
+use shurl::federation::{Federation, Partner};
+use shurl::github::GitHub;
+use shurl::lint::Level;
+use shurl::manifest::{Link, Manifest};
+use shurl::output::{Colorize, Format};
+use shurl::qr::QrCode;
+use shurl::regex::Regex;
+use shurl::repo_config::{self, RepoConfig, MARKER_FILE};
+use shurl::serve::NewLink;
+use shurl::trash::Trash;
+use shurl::workers_kv::WorkersKv;
+use shurl::{
+    backend, ci_docs, clipboard, clipd, completions, config, date, deprecation, federation, git,
+    github, health, history, import, json, lint, manpage, output, pages, preview, release_links,
+    screenshot, serve, slug, stats, tags, target, title, tui, verify, LinkOptions, Shurl,
+};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, ExitCode};
 use toml::to_string_pretty;
-use url::Url;
+use toml::value::Datetime;
 
-#[derive(Serialize, Deserialize)]
-#[serde(default)]
-struct ShurlConfig {
-    repo_path: PathBuf,
-    name: String,
-    email: String,
+/// Small utility to manage short URLs in a Git repository
+#[derive(Parser)]
+#[command(version, subcommand_negates_reqs = true)]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Cmd>,
+    #[command(flatten)]
+    add: AddArgs,
+    /// Print results for people or as JSON for scripts
+    #[arg(long, global = true, value_enum, default_value_t = Format::Human)]
+    format: Format,
+    /// Only print the result, e.g. the short URL of a new link
+    #[arg(short, long, global = true)]
+    quiet: bool,
+    /// Don't color the output, also the case with `NO_COLOR` set
+    #[arg(long, global = true)]
+    no_color: bool,
+    /// Seed of the random names of new links, for the same names on every run
+    #[arg(long, global = true, value_name = "N")]
+    seed: Option<u64>,
+    /// Profile of the config to use, e.g. `work` for its `[profiles.work]` table [default: $SHURL_PROFILE, then `default_profile` from the config]
+    #[arg(long, global = true, value_name = "NAME")]
+    profile: Option<String>,
+    /// Repository to use for this run instead of `repo_path` from the config
+    #[arg(long, global = true, value_name = "PATH")]
+    repo: Option<PathBuf>,
+    /// Author of the commits and owner of new links instead of `name` from the config, e.g. the person a team bot acts for
+    #[arg(long, global = true, value_name = "NAME")]
+    author_name: Option<String>,
+    /// Email of the author of the commits instead of `email` from the config
+    #[arg(long, global = true, value_name = "EMAIL")]
+    author_email: Option<String>,
+    /// Change the repository even without the `.shurl-repo` written by `shurl init`
+    #[arg(long, global = true)]
+    force: bool,
+}
+
+/// The flags of a new link, given with or without the `add` subcommand
+#[derive(clap::Args)]
+struct AddArgs {
+    /// URL to shorten
+    #[arg(required_unless_present_any = ["stdin", "from_file", "interactive"])]
+    url: Option<String>,
+    /// Name of the short link, random if not given
+    short_name: Option<String>,
+    /// Status code of server-side redirects for this link, e.g. 302
+    #[arg(long)]
+    status: Option<u16>,
+    /// Issue or ticket the link is for, e.g. ORG-1234 in Jira or owner/repo#123 on GitHub
+    #[arg(long)]
+    issue: Option<String>,
+    /// Target for readers of a language, e.g. `de=https://docs.example/de/`; the URL is for everyone else
+    #[arg(long = "target", value_name = "LANG=URL", value_parser = parse_locale)]
+    locales: Vec<(String, String)>,
+    /// Start of the event the link is for, e.g. 2024-05-01T18:00:00Z, counted down to until then
+    #[arg(long, value_name = "DATETIME", value_parser = date::parse_instant)]
+    starts: Option<Datetime>,
+    /// When the link expires, e.g. 2025-01-01 or 2025-01-01T12:00:00Z
+    #[arg(long, value_name = "DATE", value_parser = date::parse_expiry)]
+    expires: Option<Datetime>,
+    /// How long until the link expires, e.g. 30d, 12h or 2w
+    #[arg(long, value_name = "DURATION", value_parser = date::parse_duration, conflicts_with = "expires")]
+    ttl: Option<i64>,
+    /// Seconds the page waits before redirecting, showing where the link leads
+    #[arg(long, value_name = "SECONDS")]
+    delay: Option<u32>,
+    /// Ask search engines not to index the page [default: `noindex` from the config]
+    #[arg(long, overrides_with = "index")]
+    noindex: bool,
+    /// Let search engines index the page
+    #[arg(long, overrides_with = "noindex")]
+    index: bool,
+    /// What the target learns of where readers came from [default: `referrer_policy` from the config]
+    #[arg(long, value_name = "POLICY")]
+    referrer: Option<ReferrerPolicy>,
+    /// Template of the page in the templates directory of the repository, e.g. `interstitial` for templates/interstitial.html
+    #[arg(long, value_name = "NAME")]
+    template: Option<String>,
+    /// Language of the text of the page, e.g. de [default: `language` from the config]
+    #[arg(long, value_name = "LANG")]
+    lang: Option<String>,
+    /// Print a QR code of the short URL
+    #[arg(long)]
+    qr: bool,
+    /// Write a QR code of the short URL as PNG
+    #[arg(long, value_name = "FILE")]
+    qr_png: Option<PathBuf>,
+    /// Write a QR code of the short URL as SVG
+    #[arg(long, value_name = "FILE")]
+    qr_svg: Option<PathBuf>,
+    /// Copy the short URL to the clipboard [default: `copy_short_url` from the config]
+    #[arg(long, overrides_with = "no_copy")]
+    copy: bool,
+    /// Don't copy the short URL to the clipboard
+    #[arg(long, overrides_with = "copy")]
+    no_copy: bool,
+    /// Save a screenshot of the target, shown on the index [default: `screenshots` from the config]
+    #[arg(long, overrides_with = "no_screenshot")]
+    screenshot: bool,
+    /// Don't save a screenshot of the target
+    #[arg(long, overrides_with = "screenshot")]
+    no_screenshot: bool,
+    /// Create a link even if the URL is shortened already
+    #[arg(long)]
+    force_new: bool,
+    /// Point the link of a name that's taken to the new target instead of failing
+    #[arg(long)]
+    replace: bool,
+    /// Shorten the URLs of standard input instead, one per line with an optional tab and name, in one commit
+    #[arg(long, conflicts_with_all = PER_LINK)]
+    stdin: bool,
+    /// Shorten the URLs of a file instead, lines as for --stdin, all of them in one commit or none if any can't be
+    #[arg(long, value_name = "FILE", conflicts_with_all = PER_LINK)]
+    from_file: Option<PathBuf>,
+    /// Ask for the URL, the name, a description, tags and the expiry not given as flags, confirming before adding
+    #[arg(short, long, conflicts_with_all = ["stdin", "from_file"])]
+    interactive: bool,
+    /// What the link is for, shown on the index and by `list`
+    #[arg(long, conflicts_with = "fetch_title")]
+    description: Option<String>,
+    /// Tags of the link, e.g. `--tags talks,rust`, from `tags` of `.shurl.toml` if it has them
+    #[arg(long, value_name = "TAGS", value_delimiter = ',')]
+    tags: Vec<String>,
+    /// Fetch the title of the target page as the description of the link
+    #[arg(long)]
+    fetch_title: bool,
+    /// Fetch the OpenGraph title, description and image of the target page for previews of the short link [default: `previews` from the config]
+    #[arg(long)]
+    preview: bool,
+    /// Name the link after its description, e.g. `my-blog-post-title`, fetching the title of the target page without --description
+    #[arg(long, conflicts_with = "short_name")]
+    title_slug: bool,
+    /// Check that the URL answers before adding it, warning if it doesn't, or failing with `strict`
+    #[arg(long, value_enum, value_name = "MODE", num_args = 0..=1, require_equals = true, default_missing_value = "warn")]
+    verify: Option<VerifyMode>,
+}
+
+/// Flags of a single link, which --stdin and --from-file have no way to apply.
+const PER_LINK: &[&str] = &[
+    "url",
+    "short_name",
+    "issue",
+    "locales",
+    "starts",
+    "expires",
+    "ttl",
+    "delay",
+    "noindex",
+    "index",
+    "referrer",
+    "template",
+    "lang",
+    "description",
+    "tags",
+    "fetch_title",
+    "preview",
+    "title_slug",
+    "verify",
+    "qr",
+    "qr_png",
+    "qr_svg",
+    "copy",
+    "no_copy",
+    "screenshot",
+    "no_screenshot",
+    "force_new",
+    "replace",
+];
+
+#[derive(Subcommand)]
+enum Cmd {
+    /// Mark the repository as managed by shurl, creating it if needed
+    Init,
+    /// Add a link, the same as leaving out the subcommand
+    Add(Box<AddArgs>),
+    /// Move a link to the trash, leaving a page saying it's gone
+    #[command(visible_alias = "remove")]
+    Rm {
+        /// Picked from the links if not given
+        slug: Option<String>,
+        /// Delete the link for good, without a page saying it's gone
+        #[arg(long)]
+        hard: bool,
+    },
+    /// Give a link another name, its old short URL no longer working
+    Rename {
+        /// The link and its new name, the link picked from the links if only the new name is given
+        #[arg(required = true, num_args = 1..=2, value_names = ["SLUG", "NEW"])]
+        names: Vec<String>,
+    },
+    /// Change the target, description or tags of a link
+    Update {
+        /// Picked from the links if not given
+        slug: Option<String>,
+        /// URL the link leads to instead
+        #[arg(long, value_name = "URL", required_unless_present_any = ["description", "tags"])]
+        target: Option<String>,
+        /// What the link is for, empty for nothing
+        #[arg(long)]
+        description: Option<String>,
+        /// Tags replacing those of the link, e.g. `--tags talks,rust`, empty for none
+        #[arg(long, value_name = "TAGS", value_delimiter = ',')]
+        tags: Option<Vec<String>>,
+    },
+    /// Open the target of a link in the browser
+    Open {
+        /// Picked from the links if not given
+        slug: Option<String>,
+    },
+    /// List the links, newest first
+    List {
+        /// Only the links in a directory, e.g. `docs` for `docs/install`
+        namespace: Option<String>,
+        /// Only the links with this tag
+        #[arg(long)]
+        tag: Option<String>,
+    },
+    /// Find links by slug, target, description or tag, ignoring case
+    Search {
+        query: String,
+        /// Take the query as a regular expression
+        #[arg(long)]
+        regex: bool,
+    },
+    /// Move the links past their expiry date to the trash
+    Prune {
+        /// Only list the expired links
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Regenerate every page, e.g. after changing `analytics` or a template
+    Rerender,
+    /// Check the links against the rules in the `[lint]` table of `.shurl.toml`
+    Lint,
+    /// Check that the target of every link still answers
+    Verify,
+    /// Manage links removed with `rm`
+    #[command(subcommand)]
+    Trash(TrashCmd),
+    /// Manage partner shurl repositories listed on the directory page
+    #[command(subcommand)]
+    Federate(FederateCmd),
+    /// Tag and untag links, list, rename and merge the tags
+    #[command(subcommand, visible_alias = "tag")]
+    Tags(TagsCmd),
+    /// Share several targets with one short link to a landing page listing them
+    #[command(subcommand)]
+    Bundle(BundleCmd),
+    /// Save screenshots of the targets shown on the index, by default of links without one
+    Screenshot { slugs: Vec<String> },
+    /// Watch the clipboard and shorten copied links of `clipd_domains`
+    Clipd,
+    /// Print or write the redirect rules of a backend without committing
+    Export {
+        /// Backend to render the rules of
+        #[arg(long)]
+        target: Backend,
+        /// File to write instead of printing to stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Create short links for the assets of a GitHub release, e.g. `app-v1.2.3-linux`
+    ReleaseLinks {
+        /// `owner/name` of the repository on GitHub
+        #[arg(long)]
+        repo: String,
+        /// Tag of the release
+        #[arg(long)]
+        tag: String,
+        /// Start of every slug [default: name of the repository]
+        #[arg(long)]
+        prefix: Option<String>,
+    },
+    /// Shorten documentation pages added in a pull request and comment the links on it
+    CiDocs {
+        /// Files added by the pull request, e.g. from `git diff --name-only --diff-filter=A`
+        #[arg(long, num_args = 1.., required = true)]
+        changed_files: Vec<String>,
+        /// `owner/name` of the repository the pull request belongs to [default: $GITHUB_REPOSITORY]
+        #[arg(long)]
+        github_repo: Option<String>,
+        /// Number of the pull request [default: from $GITHUB_REF]
+        #[arg(long)]
+        pr: Option<u64>,
+    },
+    /// Push the links into a Cloudflare Workers KV namespace
+    SyncKv,
+    /// Show the hits `shurl serve` counted for a link, or for every link
+    Stats {
+        slug: Option<String>,
+        /// Show the links added over time and by whom instead, from the git history
+        #[arg(long, conflicts_with = "slug")]
+        history: bool,
+        /// Period the history is counted by
+        #[arg(long, value_enum, default_value = "month", requires = "history")]
+        by: history::Interval,
+    },
+    /// Browse, search and change the links in a table in the terminal
+    Tui,
+    /// Show everything that happened to a link, with the commits that did it
+    History {
+        /// Picked from the links if not given
+        slug: Option<String>,
+    },
+    /// Show who created, modified and removed which links, by commit author
+    Audit {
+        /// Only commits from this date on, e.g. 2024-01-01
+        #[arg(long, value_name = "DATE", value_parser = date::parse_expiry)]
+        since: Option<Datetime>,
+        /// Only commits before this date
+        #[arg(long, value_name = "DATE", value_parser = date::parse_expiry)]
+        until: Option<Datetime>,
+        /// Print CSV with a row per author and the slugs separated by spaces
+        #[arg(long)]
+        csv: bool,
+    },
+    /// Rewrite the targets matching a regex, e.g. `https://old\.cms/(.*)` to `https://new.cms/$1`
+    Rewrite {
+        /// Regex searched for in every target
+        #[arg(long = "match", value_name = "REGEX")]
+        pattern: String,
+        /// Replacement of the first match, `$1` or `${1}` being the text of a group
+        #[arg(long)]
+        replace: String,
+        /// Only show what would change
+        #[arg(long)]
+        dry_run: bool,
+        /// Rewrite without asking first
+        #[arg(long, short, conflicts_with = "dry_run")]
+        yes: bool,
+    },
+    /// List the links to a domain being migrated away from, or move them to another
+    DeprecateDomain {
+        /// Domain the links should stop pointing to, subdomains included
+        domain: String,
+        /// Domain to move the links to, keeping the rest of their targets
+        #[arg(long)]
+        replace_with: Option<String>,
+        /// Ask before moving each link
+        #[arg(long, requires = "replace_with")]
+        review: bool,
+    },
+    /// Create links from a file of another tool or server
+    Import {
+        /// Format of the file
+        #[arg(long, value_enum, default_value_t = ImportSource::Map)]
+        source: ImportSource,
+        file: PathBuf,
+    },
+    /// Print the completion script of a shell, e.g. `shurl completions bash > /etc/bash_completion.d/shurl`
+    Completions { shell: Shell },
+    /// Show or change the settings of the config file
+    #[command(subcommand)]
+    Config(ConfigCmd),
+    /// Print the man page, e.g. `shurl manpage > /usr/share/man/man1/shurl.1`
+    Manpage,
+    /// Print the slugs of the links for completion scripts
+    #[command(hide = true)]
+    CompleteSlugs {
+        /// Slugs of the links in the trash instead
+        #[arg(long, conflicts_with = "bundles")]
+        trash: bool,
+        /// Slugs of the bundles instead
+        #[arg(long)]
+        bundles: bool,
+    },
+    /// Serve the links as HTTP redirects
+    Serve {
+        /// Address to listen on [default: `serve_address` from the config]
+        #[arg(long)]
+        address: Option<String>,
+        /// Port to listen on [default: `serve_port` from the config]
+        #[arg(long)]
+        port: Option<u16>,
+        /// Also create links with `POST /api/links`, authenticated with `api_token`
+        #[arg(long)]
+        api: bool,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum VerifyMode {
+    /// Warn about targets that don't answer
+    Warn,
+    /// Don't add links to targets that don't answer
+    Strict,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ImportSource {
+    /// `from<TAB>to` lines, as in `RewriteMap` files and CDN redirect maps
+    Map,
+    /// CSV export of the links of bit.ly
+    Bitly,
+    /// CSV export of the `yourls_url` table of YOURLS
+    Yourls,
+    /// CSV export of the short URLs of Shlink
+    Shlink,
+}
+
+#[derive(Subcommand)]
+enum TrashCmd {
+    /// List removed links and when they are deleted for good
+    List,
+    /// Put a removed link back
+    Restore { slug: String },
+    /// Delete every removed link for good
+    Empty,
+}
+
+#[derive(Subcommand)]
+enum ConfigCmd {
+    /// Print the value of a setting, its default if not set
+    Get { key: String },
+    /// Change a setting, e.g. `shurl config set base_url https://s.example.com/`
+    Set {
+        key: String,
+        /// TOML value such as `true` or `["html", "netlify"]`, anything else is a string
+        value: String,
+    },
+    /// Open the config in $VISUAL or $EDITOR, replacing it once it's valid
+    Edit,
+    /// Print the location of the config file
+    Path,
+}
+
+#[derive(Subcommand)]
+enum BundleCmd {
+    /// Create a bundle of the given targets
+    Create {
+        slug: String,
+        #[arg(required = true)]
+        urls: Vec<String>,
+        /// Heading of the landing page [default: the slug]
+        #[arg(long)]
+        title: Option<String>,
+    },
+    /// Add targets to a bundle
+    Add {
+        slug: String,
+        #[arg(required = true)]
+        urls: Vec<String>,
+    },
+    /// Remove targets from a bundle
+    Remove {
+        slug: String,
+        #[arg(required = true)]
+        urls: Vec<String>,
+    },
+    /// Delete a bundle and its landing page
+    Delete { slug: String },
+    /// List the bundles
+    List,
+}
+
+#[derive(Subcommand)]
+enum TagsCmd {
+    /// List every tag with the number of links that have it
+    List,
+    /// Tag a link, e.g. `shurl tag add slides talks`
+    Add {
+        slug: String,
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    /// Take tags off a link
+    Rm {
+        slug: String,
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+    /// Rename a tag on every link
+    Rename { old: String, new: String },
+    /// Replace several tags with one on every link, e.g. `doc documentation --into docs`
+    Merge {
+        #[arg(required = true)]
+        tags: Vec<String>,
+        /// Tag to replace them with
+        #[arg(long)]
+        into: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum FederateCmd {
+    /// Add a partner repository and fetch its links
+    Add {
+        /// Git URL of the partner's shurl repository
+        url: String,
+        /// Name shown on the directory page, derived from the URL if not given
+        #[arg(long)]
+        name: Option<String>,
+        /// Public URL the partner's short links are served from
+        #[arg(long)]
+        site: Option<String>,
+    },
+    /// Remove a partner repository
+    Remove { name: String },
+    /// List partner repositories
+    List,
+    /// Fetch the links of all partner repositories again
+    Sync,
+}
+
+/// Publishes the manifest, then prints the commit like every command
+/// changing the repository does.
+fn publish(shurl: &Shurl, manifest: &Manifest, message: &str) -> Result<(), ShurlError> {
+    shurl.publish(manifest, message)?;
+    print_commit(shurl);
+    Ok(())
+}
+
+fn print_commit(shurl: &Shurl) {
+    if let (false, Some(object_id)) = (output::is_quiet(), git::head_id(shurl.repo())) {
+        println!("Created commit with object id: {object_id}");
+    }
+}
+
+/// Prints a link and the commit adding or removing it as JSON.
+fn print_link_json(shurl: &Shurl, link: &Link) {
+    let mut value = output::link(shurl.config(), link);
+    value.set("commit", git::head_id(shurl.repo()));
+    println!("{value}");
+}
+
+/// Prints what to share for a new link, its full URL if `base_url` is set.
+fn print_short_url(cfg: &ShurlConfig, slug: &str) {
+    match cfg.short_url(slug) {
+        Some(short_url) if output::is_quiet() => println!("{short_url}"),
+        Some(short_url) => println!("{}", short_url.bold()),
+        None if output::is_quiet() => println!("{slug}"),
+        None => println!(
+            "{} {}",
+            "Info:".green(),
+            format!("added `{slug}`, set `base_url` in the config to get its full URL").bold()
+        ),
+    }
+}
+
+/// Asks a question on the terminal, returning the answer without the
+/// surrounding whitespace.
+fn ask(question: &str) -> Result<String, ShurlError> {
+    print!("{question} ");
+    io::stdout()
+        .flush()
+        .map_err(|e| format!("failed to write to stdout: {e}"))?;
+    let mut answer = String::new();
+    let read = io::stdin()
+        .read_line(&mut answer)
+        .map_err(|e| format!("failed to read answer: {e}"))?;
+    if read == 0 {
+        return Err(ShurlError::InvalidInput(
+            "no answer, standard input was closed".to_string(),
+        ));
+    }
+    Ok(answer.trim().to_string())
+}
+
+/// Asks for what the flags didn't give about a new link, suggesting a name,
+/// then shows the link and the commit adding it for confirmation. Returns
+/// the arguments completed with the answers, nothing if the link shouldn't
+/// be added after all.
+fn wizard(shurl: &Shurl, mut args: Args) -> Result<Option<Args>, ShurlError> {
+    let cfg = shurl.config();
+    let url = match args.add.url.take() {
+        Some(url) => url,
+        None => loop {
+            let answer = ask("URL to shorten:")?;
+            match target::parse(cfg, &answer) {
+                Ok(_) => break answer,
+                Err(e) => eprintln!("{} {}", "Error:".red(), e.bold()),
+            }
+        },
+    };
+    if !args.add.force_new && args.add.short_name.is_none() {
+        if let Some(link) = shurl.find_target(&url)? {
+            let answer = ask(&format!(
+                "{url} is shortened as `{}` already, create another link? [y/N]",
+                link.slug
+            ))?;
+            if !matches!(answer.as_str(), "y" | "Y") {
+                args.add.url = Some(url);
+                return Ok(Some(args));
+            }
+            args.add.force_new = true;
+        }
+    }
+
+    let manifest = Manifest::load(shurl.repo_path())?;
+    let mut suggested = match args.add.short_name.take() {
+        Some(name) => name,
+        None => shurl.new_link(&manifest, &url, None, None)?.slug,
+    };
+    let mut link = loop {
+        let name = match ask(&format!("Name [{}], r for another:", suggested.bold()))?.as_str() {
+            "r" => {
+                suggested = shurl.new_link(&manifest, &url, None, None)?.slug;
+                continue;
+            }
+            "" => suggested.clone(),
+            name => name.to_string(),
+        };
+        match shurl.new_link(&manifest, &url, Some(name), args.add.status) {
+            Ok(link) if manifest.get(&link.slug).is_some() && !args.add.replace => eprintln!(
+                "{} {}",
+                "Error:".red(),
+                format!("`{}` is taken, pass --replace to replace it", link.slug).bold()
+            ),
+            Ok(link) => break link,
+            Err(e) => eprintln!("{} {}", "Error:".red(), e.to_string().bold()),
+        }
+    };
+
+    // With --fetch-title the title of the page is the description.
+    if args.add.description.is_none() && !args.add.fetch_title {
+        let answer = ask("Description, empty for none:")?;
+        args.add.description = (!answer.is_empty()).then_some(answer);
+    }
+    if args.add.tags.is_empty() {
+        let repo_config = RepoConfig::load(shurl.repo_path()).map_err(ShurlError::Config)?;
+        args.add.tags = loop {
+            let answer = ask("Tags separated by commas, empty for none:")?;
+            let tags: Result<Vec<String>, String> = answer
+                .split(',')
+                .filter(|tag| !tag.trim().is_empty())
+                .map(|tag| tags::check(&repo_config, tag))
+                .collect();
+            match tags {
+                Ok(tags) => break tags,
+                Err(e) => eprintln!("{} {}", "Error:".red(), e.bold()),
+            }
+        };
+    }
+    let now = date::to_unix(&date::now());
+    if let Some(ttl) = args.add.ttl.take() {
+        args.add.expires = Some(date::from_unix(now + ttl));
+    }
+    if args.add.expires.is_none() {
+        args.add.expires = loop {
+            let answer = ask("Expires, e.g. 2025-01-01 or 30d from now, empty for never:")?;
+            if answer.is_empty() {
+                break None;
+            }
+            let expires = date::parse_duration(&answer)
+                .map(|ttl| date::from_unix(now + ttl))
+                .or_else(|_| date::parse_expiry(&answer));
+            match expires {
+                Ok(expires) if date::to_unix(&expires) > now => break Some(expires),
+                Ok(_) => eprintln!(
+                    "{} {}",
+                    "Error:".red(),
+                    format!("{answer} has passed already").bold()
+                ),
+                Err(e) => eprintln!("{} {}", "Error:".red(), e.bold()),
+            }
+        };
+    }
+    link.description.clone_from(&args.add.description);
+    link.tags.clone_from(&args.add.tags);
+    link.expires.clone_from(&args.add.expires);
+
+    println!();
+    let short_url = cfg
+        .short_url(&link.slug)
+        .unwrap_or_else(|| link.slug.clone());
+    println!("{} {}", "Short URL:".bold(), short_url);
+    println!("{} {}", "Target:".bold(), link.target);
+    if let Some(description) = &link.description {
+        println!("{} {description}", "Description:".bold());
+    } else if args.add.fetch_title {
+        println!("{} the title of the target page", "Description:".bold());
+    }
+    if !link.tags.is_empty() {
+        println!("{} {}", "Tags:".bold(), link.tags.join(", "));
+    }
+    if let Some(expires) = &link.expires {
+        println!("{} {expires}", "Expires:".bold());
+    }
+    println!("{} {}", "Commit:".bold(), shurl.add_message(&link));
+    loop {
+        match ask("Add it? [Y/n], p to show its page:")?.as_str() {
+            "p" | "P" => println!("{}", shurl.page_of(&link)?),
+            "n" | "N" => return Ok(None),
+            _ => break,
+        }
+    }
+    args.add.url = Some(url);
+    args.add.short_name = Some(link.slug);
+    Ok(Some(args))
+}
+
+/// Links of lines that are a URL or a URL, a tab and the name, by line
+/// number, skipping blank lines and `#` comments.
+fn parse_link_lines(input: &str, status: Option<u16>) -> Vec<(usize, NewLink)> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| {
+            let (url, name) = match line.split_once('\t') {
+                Some((url, name)) => (url.trim(), Some(name.trim().to_string())),
+                None => (line, None),
+            };
+            let link = NewLink {
+                url: url.to_string(),
+                name,
+                status,
+            };
+            (number, link)
+        })
+        .collect()
+}
+
+/// Creates a link for every line of the file only if all of them can be,
+/// in one commit. Otherwise the error of every line that can't is reported
+/// and the repository is left as it was.
+fn add_from_file(shurl: &Shurl, file: &Path, status: Option<u16>) -> Result<(), ShurlError> {
+    let content =
+        fs::read_to_string(file).map_err(|e| format!("failed to read {}: {e}", file.display()))?;
+    let (numbers, links): (Vec<usize>, Vec<NewLink>) =
+        parse_link_lines(&content, status).into_iter().unzip();
+    if links.is_empty() {
+        return Err(ShurlError::InvalidInput(format!(
+            "no URLs in {}",
+            file.display()
+        )));
+    }
+
+    let rejected = match shurl.add_links_atomically(links)? {
+        Ok(links) => {
+            if output::is_json() {
+                let links: Vec<json::Value> = links
+                    .iter()
+                    .map(|link| output::link(shurl.config(), link))
+                    .collect();
+                println!("{}", json::Value::from(links));
+                return Ok(());
+            }
+            print_commit(shurl);
+            for link in &links {
+                print_short_url(shurl.config(), &link.slug);
+            }
+            return Ok(());
+        }
+        Err(rejected) => rejected,
+    };
+    for (i, e) in &rejected {
+        eprintln!(
+            "{} {}",
+            "Error:".red(),
+            format!("{}:{}: {e}", file.display(), numbers[*i]).bold()
+        );
+    }
+    Err(ShurlError::InvalidInput(format!(
+        "{} of {} lines can't be shortened, so none were",
+        rejected.len(),
+        numbers.len()
+    )))
+}
+
+/// Creates a link for every line of standard input as for `--from-file`,
+/// publishing the links in one commit and reporting those that can't be
+/// created.
+fn add_from_stdin(shurl: &Shurl, status: Option<u16>) -> Result<(), ShurlError> {
+    let mut input = String::new();
+    io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|e| format!("failed to read standard input: {e}"))?;
+    let links: Vec<NewLink> = parse_link_lines(&input, status)
+        .into_iter()
+        .map(|(_, link)| link)
+        .collect();
+    if links.is_empty() {
+        return Err(ShurlError::InvalidInput(
+            "no URLs on standard input".to_string(),
+        ));
+    }
+
+    let urls: Vec<String> = links.iter().map(|link| link.url.clone()).collect();
+    let results = shurl.add_links(links);
+    let failed = results.iter().filter(|result| result.is_err()).count();
+    if output::is_json() {
+        let results: Vec<json::Value> = urls
+            .iter()
+            .zip(&results)
+            .map(|(url, result)| {
+                let mut value = json::Value::object();
+                value.set("target", url.as_str());
+                match result {
+                    Ok(slug) => value
+                        .set("slug", slug.as_str())
+                        .set("short_url", shurl.config().short_url(slug)),
+                    Err(e) => value.set("error", e.as_str()),
+                };
+                value
+            })
+            .collect();
+        println!("{}", json::Value::from(results));
+    } else {
+        if failed < results.len() {
+            print_commit(shurl);
+        }
+        for (url, result) in urls.iter().zip(&results) {
+            match result {
+                Ok(slug) => print_short_url(shurl.config(), slug),
+                Err(e) => eprintln!("{} {}", "Error:".red(), format!("{url}: {e}").bold()),
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(ShurlError::InvalidInput(format!(
+            "{failed} of {} links couldn't be created",
+            results.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Puts a new link's short URL on the clipboard. The link exists either way,
+/// so failing to copy it is only a warning.
+fn copy_short_url(cfg: &ShurlConfig, slug: &str) {
+    let short_url = cfg.short_url(slug).unwrap_or_else(|| slug.to_string());
+    match clipboard::write(&short_url) {
+        Ok(()) if output::is_quiet() => {}
+        Ok(()) => println!(
+            "{} {}",
+            "Info:".green(),
+            format!("copied {short_url} to the clipboard").bold()
+        ),
+        Err(e) => eprintln!("{} {}", "Warning:".yellow(), e.bold()),
+    }
+}
+
+/// Prints and writes the QR code of a new link's short URL.
+fn write_qr(
+    cfg: &ShurlConfig,
+    slug: &str,
+    print: bool,
+    png: Option<PathBuf>,
+    svg: Option<PathBuf>,
+) -> Result<(), ShurlError> {
+    let short_url = cfg.short_url(slug).unwrap_or_else(|| slug.to_string());
+    let code = QrCode::encode(&short_url)?;
+    if print {
+        print!("{}", code.to_terminal());
+    }
+    for (path, format) in [(png, QrFormat::Png), (svg, QrFormat::Svg)] {
+        if let Some(path) = path {
+            fs::write(&path, code.to_format(format))
+                .map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// The slug given, or the one picked from the links in the terminal,
+/// nothing if none was.
+fn slug_or_pick(shurl: &Shurl, slug: Option<String>) -> Result<Option<String>, ShurlError> {
+    match slug {
+        Some(slug) => Ok(Some(slug)),
+        None => tui::pick(&shurl.list_links()?),
+    }
+}
+
+fn rm(shurl: &Shurl, slug: &str, hard: bool) -> Result<(), ShurlError> {
+    let link = if hard {
+        shurl.delete_link(slug)?
+    } else {
+        shurl.remove_link(slug)?
+    };
+    if output::is_json() {
+        print_link_json(shurl, &link);
+        return Ok(());
+    }
+    print_commit(shurl);
+    if output::is_quiet() {
+        return Ok(());
+    }
+    let info = if hard {
+        format!("deleted {slug}")
+    } else {
+        format!("moved {slug} to the trash, undo with `shurl trash restore {slug}`")
+    };
+    println!("{} {}", "Info:".green(), info.bold());
+    Ok(())
+}
+
+/// Changes the target, description or tags of a link in one commit.
+fn update(
+    shurl: &Shurl,
+    slug: &str,
+    target: Option<String>,
+    description: Option<String>,
+    tags: Option<Vec<String>>,
+) -> Result<(), ShurlError> {
+    let cfg = shurl.config();
+    let repo_path = shurl.repo_path();
+    let repo_config = RepoConfig::load(repo_path).map_err(ShurlError::Config)?;
+    let mut manifest = Manifest::load(repo_path)?;
+    let link = manifest
+        .links
+        .iter_mut()
+        .find(|link| link.slug == slug)
+        .ok_or_else(|| ShurlError::NotFound(format!("no link named `{slug}`")))?;
+
+    let mut changed = vec![];
+    if let Some(target) = &target {
+        let url = target::parse(cfg, target).map_err(ShurlError::InvalidInput)?;
+        if let Some(namespace) = repo_config.namespace_for(slug) {
+            namespace
+                .check(slug, &url)
+                .map_err(ShurlError::InvalidInput)?;
+        }
+        link.target = url.to_string();
+        changed.push("target");
+    }
+    if let Some(description) = description {
+        link.description = (!description.is_empty()).then_some(description);
+        changed.push("description");
+    }
+    if let Some(tags) = tags {
+        let mut checked = vec![];
+        for tag in tags.iter().filter(|tag| !tag.trim().is_empty()) {
+            let tag = tags::check(&repo_config, tag).map_err(ShurlError::InvalidInput)?;
+            if !checked.contains(&tag) {
+                checked.push(tag);
+            }
+        }
+        link.tags = checked;
+        changed.push("tags");
+    }
+    let link = link.clone();
+    let message = match changed.as_slice() {
+        ["target"] => format!("Point {slug} to {}", link.target),
+        _ => format!("Update the {} of {slug}", changed.join(" and ")),
+    };
+    shurl.publish(&manifest, &message)?;
+
+    if output::is_json() {
+        print_link_json(shurl, &link);
+        return Ok(());
+    }
+    print_commit(shurl);
+    if !output::is_quiet() {
+        println!("{} {}", "Info:".green(), format!("updated {slug}").bold());
+    }
+    Ok(())
+}
+
+/// Opens the target of a link in the browser.
+fn open(shurl: &Shurl, slug: &str) -> Result<(), ShurlError> {
+    let link = shurl
+        .list_links()?
+        .into_iter()
+        .find(|link| link.slug == slug)
+        .ok_or_else(|| ShurlError::NotFound(format!("no link named `{slug}`")))?;
+    tui::open(&link.target).map_err(ShurlError::Other)?;
+    if output::is_json() {
+        println!("{}", output::link(shurl.config(), &link));
+    } else if !output::is_quiet() {
+        println!(
+            "{} {}",
+            "Info:".green(),
+            format!("opened {}", link.target).bold()
+        );
+    }
+    Ok(())
+}
+
+fn rename(shurl: &Shurl, old: &str, new: &str) -> Result<(), ShurlError> {
+    let link = shurl.rename_link(old, new)?;
+    if output::is_json() {
+        print_link_json(shurl, &link);
+        return Ok(());
+    }
+    print_commit(shurl);
+    if output::is_quiet() {
+        print_short_url(shurl.config(), new);
+        return Ok(());
+    }
+    println!(
+        "{} {}",
+        "Info:".green(),
+        format!("renamed {old} to {new}").bold()
+    );
+    Ok(())
+}
+
+fn prune(shurl: &Shurl, dry_run: bool) -> Result<(), ShurlError> {
+    let expired = shurl.prune_expired(dry_run)?;
+    if output::is_json() {
+        let links: Vec<json::Value> = expired
+            .iter()
+            .map(|link| output::link(shurl.config(), link))
+            .collect();
+        println!("{}", json::Value::from(links));
+        return Ok(());
+    }
+    if expired.is_empty() {
+        if !output::is_quiet() {
+            println!("{} {}", "Info:".green(), "no links have expired".bold());
+        }
+        return Ok(());
+    }
+
+    let width = expired
+        .iter()
+        .map(|link| link.slug.chars().count())
+        .max()
+        .unwrap_or_default();
+    for link in &expired {
+        let expires = link.expires.as_ref().map(date::format_date);
+        println!(
+            "{:width$}  expired {}  {}",
+            link.slug.bold(),
+            expires.unwrap_or_default(),
+            target::display(&link.target)
+        );
+    }
+    if output::is_quiet() {
+        return Ok(());
+    }
+    let links = if expired.len() == 1 { "link" } else { "links" };
+    let info = if dry_run {
+        format!(
+            "would move {} expired {links} to the trash, nothing changed",
+            expired.len()
+        )
+    } else {
+        print_commit(shurl);
+        format!("moved {} expired {links} to the trash", expired.len())
+    };
+    println!("{} {}", "Info:".green(), info.bold());
+    Ok(())
+}
+
+/// Prints the links, then how many of them have each tag.
+fn list(shurl: &Shurl, namespace: Option<&str>, tag: Option<&str>) -> Result<(), ShurlError> {
+    let mut links = shurl.list_links()?;
+    if let Some(namespace) = namespace {
+        let prefix = format!("{}/", namespace.trim_end_matches('/'));
+        links.retain(|link| link.slug.starts_with(&prefix));
+    }
+    if let Some(tag) = tag {
+        links.retain(|link| link.tags.iter().any(|t| t == tag));
+    }
+    if output::is_json() {
+        let links: Vec<json::Value> = links
+            .iter()
+            .map(|link| output::link(shurl.config(), link))
+            .collect();
+        println!("{}", json::Value::from(links));
+        return Ok(());
+    }
+
+    let width = links
+        .iter()
+        .map(|link| link.slug.chars().count())
+        .max()
+        .unwrap_or_default();
+    for link in &links {
+        let tags: Vec<String> = link.tags.iter().map(|tag| format!("#{tag}")).collect();
+        let tags = tags.join(" ");
+        println!(
+            "{:width$}  {}  {}{}{}",
+            link.slug.bold(),
+            date::format_date(&link.created),
+            target::display(&link.target),
+            if tags.is_empty() { "" } else { "  " },
+            tags.dimmed()
+        );
+        if let Some(description) = &link.description {
+            // Lined up with the date.
+            println!("{:width$}  {}", "", description.dimmed());
+        }
+    }
+
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for tag in links.iter().flat_map(|link| &link.tags) {
+        *counts.entry(tag.as_str()).or_default() += 1;
+    }
+    if !counts.is_empty() {
+        let counts: Vec<String> = counts
+            .iter()
+            .map(|(tag, count)| format!("#{tag} {count}"))
+            .collect();
+        println!();
+        println!("{}", counts.join("  ").dimmed());
+    }
+    Ok(())
+}
+
+/// Prints the matching links with their short URLs, or their slugs without
+/// a `base_url`.
+fn search(shurl: &Shurl, query: &str, regex: bool) -> Result<(), ShurlError> {
+    let links = shurl.search_links(query, regex)?;
+    if output::is_json() {
+        let links: Vec<json::Value> = links
+            .iter()
+            .map(|link| output::link(shurl.config(), link))
+            .collect();
+        println!("{}", json::Value::from(links));
+        return Ok(());
+    }
+    if links.is_empty() {
+        return Err(ShurlError::NotFound(format!("no links match `{query}`")));
+    }
+
+    let names: Vec<String> = links
+        .iter()
+        .map(|link| {
+            shurl
+                .config()
+                .short_url(&link.slug)
+                .unwrap_or_else(|| link.slug.clone())
+        })
+        .collect();
+    let width = names
+        .iter()
+        .map(|name| name.chars().count())
+        .max()
+        .unwrap_or_default();
+    for (link, name) in links.iter().zip(&names) {
+        let tags: Vec<String> = link.tags.iter().map(|tag| format!("#{tag}")).collect();
+        let tags = tags.join(" ");
+        println!(
+            "{:width$}  {}{}{}",
+            name.bold(),
+            target::display(&link.target),
+            if tags.is_empty() { "" } else { "  " },
+            tags.dimmed()
+        );
+        if let Some(description) = &link.description {
+            println!("{:width$}  {}", "", description.dimmed());
+        }
+    }
+    Ok(())
+}
+
+/// Prints every finding, failing if any of them is an error.
+fn lint(repo_path: &Path) -> Result<(), ShurlError> {
+    let repo_config = RepoConfig::load(repo_path).map_err(ShurlError::Config)?;
+    let manifest = Manifest::load(repo_path)?;
+    let findings = lint::check(&repo_config, &manifest);
+
+    if output::is_json() {
+        let findings: Vec<json::Value> = findings.iter().map(|f| f.to_json()).collect();
+        println!("{}", json::Value::from(findings));
+    } else {
+        for finding in &findings {
+            let level = match finding.level {
+                Level::Error => finding.level.red().to_string(),
+                _ => finding.level.yellow().to_string(),
+            };
+            println!(
+                "{level}[{}] {}: {}",
+                finding.rule,
+                finding.slug.bold(),
+                finding.message
+            );
+        }
+    }
+
+    let errors = findings
+        .iter()
+        .filter(|finding| finding.level == Level::Error)
+        .count();
+    if errors > 0 {
+        let findings = if errors == 1 { "error" } else { "errors" };
+        return Err(ShurlError::Lint(format!("found {errors} lint {findings}")));
+    }
+    if findings.is_empty() && !output::is_quiet() {
+        println!("{} {}", "Info:".green(), "no lint findings".bold());
+    }
+    Ok(())
+}
+
+/// Requests the target of every link, also the targets for other languages,
+/// and prints those that don't answer or moved, failing if any don't answer.
+fn verify(cfg: &ShurlConfig, repo_path: &Path) -> Result<(), ShurlError> {
+    let manifest = Manifest::load(repo_path)?;
+    let mut targets = vec![];
+    for link in manifest.newest_first() {
+        targets.push((link, None, link.target.as_str()));
+        for (language, target) in &link.locales {
+            targets.push((link, Some(language.as_str()), target.as_str()));
+        }
+    }
+    let urls: Vec<&str> = targets.iter().map(|(_, _, target)| *target).collect();
+    let outcomes = verify::check_all(&urls);
+
+    if output::is_json() {
+        let results: Vec<json::Value> = targets
+            .iter()
+            .zip(&outcomes)
+            .map(|((link, language, target), outcome)| {
+                let mut value = json::Value::object();
+                value
+                    .set("slug", link.slug.as_str())
+                    .set("short_url", cfg.short_url(&link.slug))
+                    .set("language", *language)
+                    .set("target", *target)
+                    .set("result", outcome.kind());
+                match outcome {
+                    verify::Outcome::Moved { status, location } => {
+                        value
+                            .set("status", Some(*status))
+                            .set("location", location.as_str());
+                    }
+                    verify::Outcome::Dead(status) => {
+                        value.set("status", Some(*status));
+                    }
+                    verify::Outcome::Failed(e) => {
+                        value.set("error", e.as_str());
+                    }
+                    verify::Outcome::Ok | verify::Outcome::TimedOut => {}
+                }
+                value
+            })
+            .collect();
+        println!("{}", json::Value::from(results));
+    } else {
+        for ((link, language, target), outcome) in targets.iter().zip(&outcomes) {
+            if outcome.is_ok() {
+                continue;
+            }
+            let slug = match language {
+                Some(language) => format!("{} ({language})", link.slug),
+                None => link.slug.clone(),
+            };
+            let outcome_text = if outcome.answers() {
+                outcome.to_string().yellow().to_string()
+            } else {
+                outcome.to_string().red().to_string()
+            };
+            println!("{}  {target}  {outcome_text}", slug.bold());
+            if let (verify::Outcome::Moved { location, .. }, None) = (outcome, language) {
+                println!(
+                    "    update it with `shurl --replace {location} {}`",
+                    link.slug
+                );
+            }
+        }
+    }
+
+    let dead = outcomes.iter().filter(|outcome| !outcome.answers()).count();
+    if dead > 0 {
+        return Err(ShurlError::Network(format!(
+            "{dead} of {} targets don't answer",
+            outcomes.len()
+        )));
+    }
+    if !output::is_quiet() && !output::is_json() {
+        let moved = outcomes.iter().filter(|outcome| !outcome.is_ok()).count();
+        let message = match moved {
+            0 => format!("all {} targets answer", outcomes.len()),
+            _ => format!("all {} targets answer, {moved} moved", outcomes.len()),
+        };
+        println!("{} {}", "Info:".green(), message.bold());
+    }
+    Ok(())
+}
+
+fn tags(shurl: &Shurl, cmd: TagsCmd) -> Result<(), ShurlError> {
+    let repo_path = shurl.repo_path();
+    let repo_config = RepoConfig::load(repo_path).map_err(ShurlError::Config)?;
+    let mut manifest = Manifest::load(repo_path)?;
+
+    let (tags, into, message) = match cmd {
+        TagsCmd::List => {
+            let counts = tags::counts(&repo_config, &manifest);
+            if output::is_json() {
+                let tags: Vec<json::Value> = counts
+                    .into_iter()
+                    .map(|(tag, count)| {
+                        let mut value = json::Value::object();
+                        value
+                            .set("tag", tag)
+                            .set("count", count as u64)
+                            .set("known", repo_config.check_tag(tag).is_ok());
+                        value
+                    })
+                    .collect();
+                println!("{}", json::Value::from(tags));
+                return Ok(());
+            }
+            for (tag, count) in counts {
+                let unknown = repo_config.check_tag(tag).is_err();
+                println!(
+                    "{count:>8} {}{}",
+                    tag.bold(),
+                    if unknown {
+                        " (not in the vocabulary)"
+                    } else {
+                        ""
+                    }
+                );
+            }
+            return Ok(());
+        }
+        TagsCmd::Add { slug, tags } => {
+            let link = manifest
+                .links
+                .iter_mut()
+                .find(|link| link.slug == slug)
+                .ok_or_else(|| ShurlError::NotFound(format!("no link named `{slug}`")))?;
+            let mut added = vec![];
+            for tag in &tags {
+                let tag = tags::check(&repo_config, tag).map_err(ShurlError::InvalidInput)?;
+                if !link.tags.contains(&tag) && !added.contains(&tag) {
+                    added.push(tag);
+                }
+            }
+            if added.is_empty() {
+                return Err(ShurlError::Conflict(format!(
+                    "`{slug}` already has tag {}",
+                    tags.join(" and ")
+                )));
+            }
+            link.tags.extend(added.iter().cloned());
+            let message = format!("Tag {slug} with {}", added.join(", "));
+            return publish(shurl, &manifest, &message);
+        }
+        TagsCmd::Rm { slug, tags } => {
+            let link = manifest
+                .links
+                .iter_mut()
+                .find(|link| link.slug == slug)
+                .ok_or_else(|| ShurlError::NotFound(format!("no link named `{slug}`")))?;
+            let removed: Vec<String> = link
+                .tags
+                .iter()
+                .filter(|tag| tags.contains(tag))
+                .cloned()
+                .collect();
+            if removed.is_empty() {
+                return Err(ShurlError::NotFound(format!(
+                    "`{slug}` doesn't have tag {}",
+                    tags.join(" or ")
+                )));
+            }
+            link.tags.retain(|tag| !removed.contains(tag));
+            let message = format!("Untag {slug} from {}", removed.join(", "));
+            return publish(shurl, &manifest, &message);
+        }
+        TagsCmd::Rename { old, new } => {
+            if manifest.links.iter().any(|link| link.tags.contains(&new)) {
+                return Err(ShurlError::Conflict(format!(
+                    "links already have tag `{new}`, use `shurl tags merge {old} --into {new}`"
+                )));
+            }
+            let message = format!("Rename tag {old} to {new}");
+            (vec![old], new, message)
+        }
+        TagsCmd::Merge { tags, into } => {
+            let message = format!("Merge tags {} into {into}", tags.join(", "));
+            (tags, into, message)
+        }
+    };
+
+    repo_config
+        .check_tag(&into)
+        .map_err(ShurlError::InvalidInput)?;
+    let changed = tags::replace(&mut manifest, &tags, &into);
+    if changed == 0 {
+        return Err(ShurlError::NotFound(format!(
+            "no link has tag {}",
+            tags.join(" or ")
+        )));
+    }
+    if !output::is_quiet() {
+        let links = if changed == 1 { "link" } else { "links" };
+        println!(
+            "{} {}",
+            "Info:".green(),
+            format!("retagged {changed} {links} with {into}").bold()
+        );
+    }
+    publish(shurl, &manifest, &message)
 }
 
-impl Default for ShurlConfig {
-    fn default() -> Self {
-        Self {
-            repo_path: PathBuf::from("/path_to_valid_and_empty_git_repo"),
-            name: "shurl".to_string(),
-            email: "example@example.com".to_string(),
+fn trash(shurl: &Shurl, cmd: TrashCmd) -> Result<(), ShurlError> {
+    let cfg = shurl.config();
+    let repo_path = shurl.repo_path();
+    let mut trash = Trash::load(repo_path)?;
+    let mut manifest = Manifest::load(repo_path)?;
+
+    let message = match cmd {
+        TrashCmd::List => {
+            for link in &trash.links {
+                let purge_date = link
+                    .purge_date(cfg.trash_retention_days)
+                    .map(|purge_date| date::format_date(&purge_date))
+                    .unwrap_or_else(|| "never".to_string());
+                println!(
+                    "{} {} (removed {}, purged {})",
+                    link.slug.bold(),
+                    link.target,
+                    date::format_date(&link.deleted),
+                    purge_date
+                );
+            }
+            return Ok(());
+        }
+        TrashCmd::Restore { slug } => {
+            if manifest.get(&slug).is_some() {
+                return Err(ShurlError::Conflict(format!(
+                    "`{slug}` has been taken by a new link since"
+                )));
+            }
+            let link = trash.take(&slug).ok_or_else(|| {
+                ShurlError::NotFound(format!("no link named `{slug}` in the trash"))
+            })?;
+            manifest.insert(link.restore());
+            format!("Restore {slug}")
+        }
+        TrashCmd::Empty => {
+            if trash.links.is_empty() {
+                return Err(ShurlError::NotFound(
+                    "the trash is already empty".to_string(),
+                ));
+            }
+            for link in trash.links.drain(..) {
+                if manifest.get(&link.slug).is_none() {
+                    pages::remove_page(repo_path, &link.slug)?;
+                }
+            }
+            "Empty trash".to_string()
+        }
+    };
+
+    trash.save(repo_path)?;
+    publish(shurl, &manifest, &message)
+}
+
+fn export(
+    repo_path: &Path,
+    cfg: &ShurlConfig,
+    target: Backend,
+    output: Option<PathBuf>,
+) -> Result<(), ShurlError> {
+    let manifest = Manifest::load(repo_path)?;
+    let trash = Trash::load(repo_path)?;
+    let gone = trash.gone_slugs(&manifest);
+    let (rules, warnings) = backend::export(target, repo_path, cfg, &manifest, &gone)?;
+    for warning in warnings {
+        eprintln!("{} {}", "Warning:".yellow(), warning.bold());
+    }
+    match output {
+        Some(output) => fs::write(&output, rules)
+            .map_err(|e| format!("failed to write {}: {e}", output.display()).into()),
+        None => {
+            print!("{rules}");
+            Ok(())
+        }
+    }
+}
+
+fn release_links(
+    shurl: &Shurl,
+    github_repo: &str,
+    tag: &str,
+    prefix: Option<String>,
+) -> Result<(), ShurlError> {
+    let cfg = shurl.config();
+    let repo_path = shurl.repo_path();
+    let release = GitHub::new(cfg)
+        .get(&format!("/repos/{github_repo}/releases/tags/{tag}"))
+        .map_err(ShurlError::Network)?;
+    let assets = release_links::assets(&release);
+    if assets.is_empty() {
+        return Err(ShurlError::NotFound(format!(
+            "release {tag} of {github_repo} has no assets"
+        )));
+    }
+
+    let prefix = prefix.unwrap_or_else(|| {
+        github_repo
+            .rsplit('/')
+            .next()
+            .unwrap_or(github_repo)
+            .to_string()
+    });
+    let slugs = release_links::slugs(&prefix, tag, &assets);
+
+    // Every link is checked before any is printed or written.
+    let mut manifest = Manifest::load(repo_path)?;
+    let mut created = vec![];
+    for (asset, slug) in assets.iter().zip(slugs) {
+        let link = shurl
+            .new_link(&manifest, &asset.url, Some(slug.clone()), None)
+            .map_err(|e| e.context(&format!("failed to add a link for {}", asset.name)))?;
+        match manifest.get(&slug) {
+            // Running again for the same release changes nothing.
+            Some(existing) if existing.target == link.target => continue,
+            Some(existing) => {
+                return Err(ShurlError::Conflict(format!(
+                    "slug `{slug}` already points to {}",
+                    existing.target
+                )))
+            }
+            None => {}
+        }
+        manifest.insert(link);
+        created.push((slug, &asset.name));
+    }
+    for (slug, name) in &created {
+        let short = cfg.short_url(slug).unwrap_or_else(|| slug.clone());
+        println!("{} -> {name}", short.bold());
+    }
+
+    if created.is_empty() {
+        println!(
+            "{} {}",
+            "Info:".green(),
+            "all release assets already have short links".bold()
+        );
+        return Ok(());
+    }
+    publish(
+        shurl,
+        &manifest,
+        &format!("Add release links for {github_repo} {tag}"),
+    )
+}
+
+fn ci_docs(
+    shurl: &Shurl,
+    changed_files: &[String],
+    github_repo: Option<String>,
+    pr: Option<u64>,
+) -> Result<(), ShurlError> {
+    let cfg = shurl.config();
+    let repo_path = shurl.repo_path();
+    let doc_pages = ci_docs::doc_pages(cfg, changed_files)?;
+    let mut manifest = Manifest::load(repo_path)?;
+
+    let mut created = vec![];
+    for page in &doc_pages {
+        let target = page.target.to_string();
+        // Pages shortened by an earlier run of the same pull request.
+        if manifest.links.iter().any(|link| link.target == target) {
+            continue;
+        }
+
+        let mut slug = page.slug.clone();
+        let mut suffix = 2;
+        while manifest.get(&slug).is_some() || pages::find_page(repo_path, &slug).is_some() {
+            slug = format!("{}-{suffix}", page.slug);
+            suffix += 1;
+        }
+        let link = shurl
+            .new_link(&manifest, &target, Some(slug.clone()), None)
+            .map_err(|e| e.context(&format!("failed to add a link for {}", page.path)))?;
+        manifest.insert(link);
+        created.push((page, slug));
+    }
+
+    if created.is_empty() {
+        println!(
+            "{} {}",
+            "Info:".green(),
+            "no new documentation pages to shorten".bold()
+        );
+        return Ok(());
+    }
+
+    publish(
+        shurl,
+        &manifest,
+        &format!("Add short links for {} documentation pages", created.len()),
+    )?;
+
+    let body = ci_docs::comment_body(cfg, &created);
+    let github_repo = github_repo.or_else(github::repository_from_env);
+    match (github_repo, pr.or_else(github::pull_request_from_env)) {
+        (Some(github_repo), Some(pr)) => {
+            let mut comment = json::Value::object();
+            comment.set("body", body);
+            GitHub::new(cfg)
+                .post(
+                    &format!("/repos/{github_repo}/issues/{pr}/comments"),
+                    &comment,
+                )
+                .map_err(ShurlError::Network)?;
+            println!(
+                "{} {}",
+                "Info:".green(),
+                format!("commented on {github_repo}#{pr}").bold()
+            );
+        }
+        _ => {
+            println!("{body}");
+            eprintln!(
+                "{} {}",
+                "Warning:".yellow(),
+                "no pull request to comment on, pass --github-repo and --pr".bold()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn federate(shurl: &Shurl, cmd: FederateCmd) -> Result<(), ShurlError> {
+    let cfg = shurl.config();
+    let repo_path = shurl.repo_path();
+    let mut federation = Federation::load(repo_path)?;
+
+    let message = match cmd {
+        FederateCmd::Add { url, name, site } => {
+            let name = name.unwrap_or_else(|| federation::name_from_url(&url));
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+                || name.starts_with('.')
+            {
+                return Err(ShurlError::InvalidInput(format!(
+                    "invalid partner name `{name}`: use letters, digits, `-`, `_` and `.`"
+                )));
+            }
+            if federation.get_mut(&name).is_some() {
+                return Err(ShurlError::Conflict(format!(
+                    "partner `{name}` already exists"
+                )));
+            }
+
+            let mut partner = Partner {
+                name: name.clone(),
+                url,
+                site,
+                fetched: None,
+            };
+            let count = federation::fetch(repo_path, &mut partner).map_err(ShurlError::Network)?;
+            println!(
+                "{} {}",
+                "Info:".green(),
+                format!("fetched {count} links from {name}").bold()
+            );
+            federation.partners.push(partner);
+            format!("Federate with {name}")
+        }
+        FederateCmd::Remove { name } => {
+            if federation.remove(&name).is_none() {
+                return Err(ShurlError::NotFound(format!("no partner named `{name}`")));
+            }
+            let snapshot = federation::snapshot_path(repo_path, &name);
+            if snapshot.exists() {
+                fs::remove_file(&snapshot)
+                    .map_err(|e| format!("failed to remove {}: {e}", snapshot.display()))?;
+            }
+            // Only succeeds once the last snapshot is gone.
+            let _ = fs::remove_dir(repo_path.join(federation::SNAPSHOT_DIR));
+            format!("Stop federating with {name}")
+        }
+        FederateCmd::List => {
+            for partner in &federation.partners {
+                let fetched = partner
+                    .fetched
+                    .as_ref()
+                    .map(date::format_date)
+                    .unwrap_or_else(|| "never".to_string());
+                println!(
+                    "{} {} (fetched {})",
+                    partner.name.bold(),
+                    partner.url,
+                    fetched
+                );
+            }
+            return Ok(());
+        }
+        FederateCmd::Sync => {
+            for partner in &mut federation.partners {
+                let count = federation::fetch(repo_path, partner).map_err(ShurlError::Network)?;
+                println!(
+                    "{} {}",
+                    "Info:".green(),
+                    format!("fetched {count} links from {}", partner.name).bold()
+                );
+            }
+            "Refresh partner links".to_string()
+        }
+    };
+
+    federation.save(repo_path)?;
+    shurl.build_site(&Manifest::load(repo_path)?)?;
+
+    git::commit_all(shurl.repo(), cfg, &message)?;
+    git::push(repo_path, &cfg.branch)?;
+    print_commit(shurl);
+    Ok(())
+}
+
+fn stats(repo_path: &Path, slug: Option<String>) -> Result<(), ShurlError> {
+    let hits = stats::load(repo_path)?;
+    let Some(slug) = slug else {
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for hit in &hits {
+            *counts.entry(hit.slug.as_str()).or_default() += 1;
+        }
+        let mut counts: Vec<_> = counts.into_iter().collect();
+        counts.sort_by_key(|&(_, count)| Reverse(count));
+        for (slug, count) in counts {
+            println!("{count:>8} {}", slug.bold());
+        }
+        return Ok(());
+    };
+
+    let manifest = Manifest::load(repo_path)?;
+    let hits: Vec<_> = hits.iter().filter(|hit| hit.slug == slug).collect();
+    if hits.is_empty() && manifest.get(&slug).is_none() {
+        return Err(ShurlError::NotFound(format!("no link named `{slug}`")));
+    }
+    if let Some(link) = manifest.get(&slug) {
+        println!("{} -> {}", slug.bold(), link.target);
+        if let Some(clicks) = link.clicks {
+            println!("Before import: {clicks}");
+        }
+    }
+
+    let now = date::to_unix(&date::now());
+    let since = |secs: i64| hits.iter().filter(|hit| hit.time > now - secs).count();
+    println!("Total:         {}", hits.len());
+    println!("Last 24 hours: {}", since(86400));
+    println!("Last 7 days:   {}", since(7 * 86400));
+    println!("Last 30 days:  {}", since(30 * 86400));
+    if let (Some(first), Some(last)) = (hits.first(), hits.last()) {
+        println!("First hit:     {}", date::from_unix(first.time));
+        println!("Last hit:      {}", date::from_unix(last.time));
+    }
+
+    let mut referrers: BTreeMap<&str, usize> = BTreeMap::new();
+    for referrer in hits.iter().filter_map(|hit| hit.referrer.as_deref()) {
+        *referrers.entry(referrer).or_default() += 1;
+    }
+    if !referrers.is_empty() {
+        let mut referrers: Vec<_> = referrers.into_iter().collect();
+        referrers.sort_by_key(|&(_, count)| Reverse(count));
+        println!("Top referrers:");
+        for (referrer, count) in referrers.into_iter().take(10) {
+            println!("{count:>8} {referrer}");
         }
     }
+    Ok(())
 }
 
-#[derive(Parser)]
-struct Args {
-    url: String,
-    short_name: Option<String>,
+/// Links added per period with a bar each, how many there were, and the
+/// authors who added the most.
+fn growth(shurl: &Shurl, by: history::Interval) -> Result<(), ShurlError> {
+    let growth = history::growth(shurl.repo(), by).map_err(ShurlError::Git)?;
+    if growth.periods.is_empty() {
+        println!("No links in the history yet.");
+        return Ok(());
+    }
+    let most = growth
+        .periods
+        .iter()
+        .map(|period| period.added)
+        .max()
+        .unwrap_or_default()
+        .max(1);
+    println!(
+        "{:<10} {:>6} {:>6}",
+        "Period".bold(),
+        "Added".bold(),
+        "Total".bold()
+    );
+    for period in &growth.periods {
+        let bar = "#".repeat((period.added * 40).div_ceil(most));
+        println!(
+            "{:<10} {:>6} {:>6} {}",
+            period.label,
+            period.added,
+            period.total,
+            bar.dimmed()
+        );
+    }
+    println!();
+    println!("{}", "Most active authors:".bold());
+    for (author, added) in growth.authors.iter().take(10) {
+        println!("{added:>8} {author}");
+    }
+    Ok(())
 }
 
-fn create_name() -> String {
-    let mut name = String::new();
-    let mut rng = rand::thread_rng();
-    for _ in 0..5 {
-        name.push(rng.gen_range(b'a'..b'z') as char);
+/// Creation, target changes, renames and removal of the link, oldest first.
+fn lifecycle(shurl: &Shurl, slug: &str) -> Result<(), ShurlError> {
+    let events = history::events(shurl.repo(), slug).map_err(ShurlError::Git)?;
+    if events.is_empty() {
+        return Err(ShurlError::NotFound(format!(
+            "no link named `{slug}` in the history"
+        )));
+    }
+    if output::is_json() {
+        let events: Vec<json::Value> = events
+            .iter()
+            .map(|event| {
+                let mut value = json::Value::object();
+                value
+                    .set("commit", event.commit.as_str())
+                    .set("date", date::format_date(&date::from_unix(event.time)))
+                    .set("author", event.author.as_str())
+                    .set("summary", event.summary.as_str());
+                match &event.change {
+                    history::Change::Created { target } => value
+                        .set("change", "created")
+                        .set("target", target.as_str()),
+                    history::Change::Restored { target } => value
+                        .set("change", "restored")
+                        .set("target", target.as_str()),
+                    history::Change::Retargeted { from, to } => value
+                        .set("change", "retargeted")
+                        .set("from", from.as_str())
+                        .set("target", to.as_str()),
+                    history::Change::RenamedFrom(old) => {
+                        value.set("change", "renamed").set("from", old.as_str())
+                    }
+                    history::Change::RenamedTo(new) => {
+                        value.set("change", "renamed").set("to", new.as_str())
+                    }
+                    history::Change::Removed => value.set("change", "removed"),
+                };
+                value
+            })
+            .collect();
+        println!("{}", json::Value::from(events));
+        return Ok(());
+    }
+
+    for event in &events {
+        let change = match &event.change {
+            history::Change::Created { target } => format!("created -> {target}"),
+            history::Change::Restored { target } => format!("restored -> {target}"),
+            history::Change::Retargeted { from, to } => format!("retargeted {from} -> {to}"),
+            history::Change::RenamedFrom(old) => format!("renamed from {old}"),
+            history::Change::RenamedTo(new) => format!("renamed to {new}"),
+            history::Change::Removed => "removed".to_string(),
+        };
+        println!(
+            "{} {}  {}",
+            (&event.commit[..7]).yellow(),
+            date::format_date(&date::from_unix(event.time)),
+            change.bold()
+        );
+        println!(
+            "{}",
+            format!("           {}: {}", event.author, event.summary).dimmed()
+        );
     }
-    name
+    Ok(())
 }
 
-fn main() {
-    let mut cfg_content = String::new();
-    let mut cfg_file = match OpenOptions::new()
-        .create(true)
-        .read(true)
-        .write(true)
-        .append(false)
-        .open(tilde("~/.config/shurl_config.toml").as_ref())
-    {
-        Ok(file) => file,
-        Err(e) => {
+/// Links created, modified and removed by each author, for compliance
+/// reviews of a repository shared by a team.
+fn audit(
+    shurl: &Shurl,
+    since: Option<Datetime>,
+    until: Option<Datetime>,
+    csv: bool,
+) -> Result<(), ShurlError> {
+    let contributions = history::audit(
+        shurl.repo(),
+        since.as_ref().map(date::to_unix),
+        until.as_ref().map(date::to_unix),
+    )
+    .map_err(ShurlError::Git)?;
+    if csv {
+        println!("name,email,created,modified,removed");
+        for c in &contributions {
             println!(
-                "{} {} {}",
-                "Error:".red(),
-                "failed to create config file:".bold(),
-                e.to_string()
+                "{},{},{},{},{}",
+                csv_field(&c.name),
+                csv_field(&c.email),
+                csv_field(&c.created.join(" ")),
+                csv_field(&c.modified.join(" ")),
+                csv_field(&c.removed.join(" "))
             );
-            return;
         }
-    };
+        return Ok(());
+    }
+    if output::is_json() {
+        let slugs = |slugs: &[String]| {
+            json::Value::from(
+                slugs
+                    .iter()
+                    .map(|slug| json::Value::from(slug.as_str()))
+                    .collect::<Vec<_>>(),
+            )
+        };
+        let contributions: Vec<json::Value> = contributions
+            .iter()
+            .map(|c| {
+                let mut value = json::Value::object();
+                value
+                    .set("name", c.name.as_str())
+                    .set("email", c.email.as_str())
+                    .set("created", slugs(&c.created))
+                    .set("modified", slugs(&c.modified))
+                    .set("removed", slugs(&c.removed));
+                value
+            })
+            .collect();
+        println!("{}", json::Value::from(contributions));
+        return Ok(());
+    }
 
-    if let Err(e) = cfg_file.read_to_string(&mut cfg_content) {
+    if contributions.is_empty() {
+        println!("No links were changed in that time.");
+        return Ok(());
+    }
+    println!(
+        "{:>8} {:>8} {:>8}  {}",
+        "Created".bold(),
+        "Modified".bold(),
+        "Removed".bold(),
+        "Author".bold()
+    );
+    for c in &contributions {
         println!(
-            "{} {} {}",
-            "Error:".red(),
-            "failed to read config file:".bold(),
-            e.to_string()
+            "{:>8} {:>8} {:>8}  {} <{}>",
+            c.created.len(),
+            c.modified.len(),
+            c.removed.len(),
+            c.name,
+            c.email
         );
-        return;
     }
+    Ok(())
+}
 
-    if cfg_content.is_empty() {
-        cfg_file
-            .write(to_string_pretty(&ShurlConfig::default()).unwrap().as_ref())
-            .expect("failed to write config file");
+/// Quotes a CSV field if it has to be.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Creates a link for every entry of the file in one commit. Entries that
+/// already exist with the same target are skipped, so a map can be imported
+/// again after it changed.
+fn import(shurl: &Shurl, source: ImportSource, file: &Path) -> Result<(), ShurlError> {
+    let repo_path = shurl.repo_path();
+    let content =
+        fs::read_to_string(file).map_err(|e| format!("failed to read {}: {e}", file.display()))?;
+    let entries = match source {
+        ImportSource::Map => backend::map::parse(&content).map(|entries| {
+            entries
+                .into_iter()
+                .map(|(slug, target)| import::Entry {
+                    slug,
+                    target,
+                    created: None,
+                    title: None,
+                    tags: vec![],
+                    clicks: None,
+                })
+                .collect()
+        }),
+        ImportSource::Bitly => import::bitly(&content),
+        ImportSource::Yourls => import::yourls(&content),
+        ImportSource::Shlink => import::shlink(&content),
+    }
+    .map_err(|e| ShurlError::InvalidInput(format!("{}: {e}", file.display())))?;
+
+    let repo_config = RepoConfig::load(repo_path).map_err(ShurlError::Config)?;
+    let mut manifest = Manifest::load(repo_path)?;
+    let mut created = 0;
+    for entry in entries {
+        let slug = entry.slug;
+        let failed = || format!("failed to import `{slug}`");
+        let mut link = shurl
+            .new_link(&manifest, &entry.target, Some(slug.clone()), None)
+            .map_err(|e| e.context(&failed()))?;
+        if let Some(created) = entry.created {
+            link.created = created;
+        }
+        link.description = entry.title;
+        for tag in &entry.tags {
+            let tag = tags::check(&repo_config, tag)
+                .map_err(|e| ShurlError::InvalidInput(format!("{}: {e}", failed())))?;
+            if !link.tags.contains(&tag) {
+                link.tags.push(tag);
+            }
+        }
+        link.clicks = entry.clicks;
+        match manifest.get(&slug) {
+            Some(existing) if existing.target == link.target => continue,
+            Some(existing) => {
+                return Err(ShurlError::Conflict(format!(
+                    "slug `{slug}` already points to {}",
+                    existing.target
+                )))
+            }
+            None => {}
+        }
+        manifest.insert(link);
+        created += 1;
+    }
+
+    if created == 0 {
         println!(
             "{} {}",
             "Info:".green(),
-            "created config file. Set the default repository path and \
-            run the command again."
-                .bold()
+            "every link of the file already exists".bold()
         );
+        return Ok(());
+    }
+    let redirects = if created == 1 {
+        "redirect"
     } else {
-        let Ok(cfg) = toml::from_str::<ShurlConfig>(&cfg_content) else {
+        "redirects"
+    };
+    publish(
+        shurl,
+        &manifest,
+        &format!(
+            "Import {created} {redirects} from {}",
+            file.file_name().unwrap_or_default().to_string_lossy()
+        ),
+    )
+}
+
+/// Rewrites every target matching the regex in one commit, after showing
+/// the changes.
+fn rewrite(
+    shurl: &Shurl,
+    pattern: &str,
+    replacement: &str,
+    dry_run: bool,
+    yes: bool,
+) -> Result<(), ShurlError> {
+    let repo_path = shurl.repo_path();
+    let regex = Regex::new(pattern).map_err(ShurlError::InvalidInput)?;
+    let mut manifest = Manifest::load(repo_path)?;
+
+    let mut changes = vec![];
+    for link in manifest.newest_first() {
+        let Some(target) = regex.replace(&link.target, replacement) else {
+            continue;
+        };
+        if target == link.target {
+            continue;
+        }
+        let target = target::parse(shurl.config(), &target)
+            .map_err(|e| {
+                ShurlError::InvalidInput(format!("`{}` would point to {target}: {e}", link.slug))
+            })?
+            .to_string();
+        changes.push((link.slug.clone(), link.target.clone(), target));
+    }
+    if changes.is_empty() {
+        println!(
+            "{} {}",
+            "Info:".green(),
+            format!("no targets match {pattern}").bold()
+        );
+        return Ok(());
+    }
+
+    let width = changes
+        .iter()
+        .map(|(slug, _, _)| slug.chars().count())
+        .max()
+        .unwrap_or_default();
+    for (slug, old, new) in &changes {
+        println!("{:width$}  {old}", slug.bold());
+        println!("{:width$}  {} {new}", "", "->".green());
+    }
+    let count = changes.len();
+    let targets = if count == 1 { "target" } else { "targets" };
+    if dry_run {
+        println!(
+            "{} {}",
+            "Info:".green(),
+            format!("would rewrite {count} {targets}, nothing changed").bold()
+        );
+        return Ok(());
+    }
+    if !yes {
+        let answer = ask(&format!("Rewrite {count} {targets}? [y/N]"))?;
+        if !matches!(answer.as_str(), "y" | "Y") {
+            println!("{} {}", "Info:".green(), "nothing changed".bold());
+            return Ok(());
+        }
+    }
+
+    for (slug, _, target) in changes {
+        if let Some(link) = manifest.links.iter_mut().find(|link| link.slug == slug) {
+            link.target = target;
+        }
+    }
+    publish(
+        shurl,
+        &manifest,
+        &format!("Rewrite {count} {targets} matching {pattern}"),
+    )
+}
+
+/// Lists or moves the links to a deprecated domain and records how many are
+/// left, committing when that changed.
+fn deprecate_domain(
+    shurl: &Shurl,
+    domain: &str,
+    replace_with: Option<String>,
+    review: bool,
+) -> Result<(), ShurlError> {
+    let repo_path = shurl.repo_path();
+    let mut manifest = Manifest::load(repo_path)?;
+    let affected: Vec<(String, String)> = deprecation::affected(&manifest, domain)
+        .into_iter()
+        .map(|link| (link.slug.clone(), link.target.clone()))
+        .collect();
+
+    let mut moved = 0;
+    for (slug, target) in &affected {
+        let Some(replace_with) = &replace_with else {
+            println!("{} -> {target}", slug.bold());
+            continue;
+        };
+        let new_target = deprecation::replace_domain(target, domain, replace_with)
+            .map_err(ShurlError::InvalidInput)?;
+        if review {
+            match ask(&format!(
+                "{} {target} -> {new_target}? [y/N/q]",
+                slug.bold()
+            ))?
+            .as_str()
+            {
+                "y" | "Y" => {}
+                "q" | "Q" => break,
+                _ => continue,
+            }
+        } else {
+            println!("{} {target} -> {new_target}", slug.bold());
+        }
+        if let Some(link) = manifest.links.iter_mut().find(|link| link.slug == *slug) {
+            link.target = new_target;
+            moved += 1;
+        }
+    }
+
+    let remaining = affected.len() - moved;
+    let mut deprecations = Deprecations::load(repo_path)?;
+    let (deprecated, changed) = deprecations.record(domain, replace_with.as_deref(), remaining);
+    println!(
+        "{} {}",
+        "Info:".green(),
+        format!("{remaining} links left on {domain}").bold()
+    );
+    for count in &deprecated.counts {
+        println!("  {}  {}", date::format_date(&count.date), count.remaining);
+    }
+    if !changed {
+        return Ok(());
+    }
+
+    deprecations.save(repo_path)?;
+    let message = match &replace_with {
+        Some(replace_with) if moved > 0 => {
+            let links = if moved == 1 { "link" } else { "links" };
+            format!("Move {moved} {links} from {domain} to {replace_with}")
+        }
+        _ => format!("Track links to deprecated {domain}: {remaining} left"),
+    };
+    publish(shurl, &manifest, &message)
+}
+
+fn sync_kv(cfg: &ShurlConfig, repo_path: &Path) -> Result<(), ShurlError> {
+    let manifest = Manifest::load(repo_path)?;
+    let summary = WorkersKv::new(cfg)
+        .map_err(ShurlError::Config)?
+        .sync(cfg, &manifest)
+        .map_err(ShurlError::Network)?;
+    println!(
+        "{} {}",
+        "Info:".green(),
+        format!(
+            "wrote {} links to Workers KV, deleted {} stale keys",
+            summary.written, summary.deleted
+        )
+        .bold()
+    );
+    Ok(())
+}
+
+/// Subcommands whose arguments are completed with the slugs of the repository.
+const SLUG_COMPLETIONS: &[Dynamic] = &[
+    // The name of the link replaced with `--replace`.
+    Dynamic {
+        path: "add",
+        source: "shurl complete-slugs",
+    },
+    Dynamic {
+        path: "rename",
+        source: "shurl complete-slugs",
+    },
+    Dynamic {
+        path: "update",
+        source: "shurl complete-slugs",
+    },
+    Dynamic {
+        path: "open",
+        source: "shurl complete-slugs",
+    },
+    Dynamic {
+        path: "rm",
+        source: "shurl complete-slugs",
+    },
+    Dynamic {
+        path: "stats",
+        source: "shurl complete-slugs",
+    },
+    Dynamic {
+        path: "screenshot",
+        source: "shurl complete-slugs",
+    },
+    Dynamic {
+        path: "history",
+        source: "shurl complete-slugs",
+    },
+    Dynamic {
+        path: "tags add",
+        source: "shurl complete-slugs",
+    },
+    Dynamic {
+        path: "tags rm",
+        source: "shurl complete-slugs",
+    },
+    Dynamic {
+        path: "trash restore",
+        source: "shurl complete-slugs --trash",
+    },
+    Dynamic {
+        path: "bundle add",
+        source: "shurl complete-slugs --bundles",
+    },
+    Dynamic {
+        path: "bundle remove",
+        source: "shurl complete-slugs --bundles",
+    },
+    Dynamic {
+        path: "bundle delete",
+        source: "shurl complete-slugs --bundles",
+    },
+];
+
+fn main() -> ExitCode {
+    let mut args = Args::parse();
+    match args.command.take() {
+        Some(Cmd::Add(add)) => args.add = *add,
+        command => args.command = command,
+    }
+    output::init_color(args.no_color);
+    output::set_format(args.format);
+    output::set_quiet(args.quiet);
+
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{} {}", "Error:".red(), e.bold());
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+/// Loads the config and opens the repository, then runs the command.
+fn run(mut args: Args) -> Result<(), ShurlError> {
+    // Scripts and the man page are generated while packaging, without a
+    // config.
+    if let Some(Cmd::Completions { shell }) = args.command {
+        let script = completions::generate(shell, &mut Args::command(), SLUG_COMPLETIONS);
+        print!("{script}");
+        return Ok(());
+    }
+    if let Some(Cmd::Manpage) = args.command {
+        print!("{}", manpage::generate(&mut Args::command()));
+        return Ok(());
+    }
+
+    let cfg_path = config::config_path();
+    if let Some(legacy) = config::migrate_legacy_config().map_err(ShurlError::Config)? {
+        // On stderr, so it doesn't end up in what scripts read.
+        if !output::is_quiet() {
+            eprintln!(
+                "{} {}",
+                "Info:".green(),
+                format!(
+                    "moved the config from {} to {}",
+                    legacy.display(),
+                    cfg_path.display()
+                )
+                .bold()
+            );
+        }
+    }
+    if let Some(dir) = cfg_path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|e| ShurlError::Config(format!("failed to create {}: {e}", dir.display())))?;
+    }
+    let mut cfg_content = String::new();
+    let mut cfg_file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .write(true)
+        .truncate(false)
+        .open(&cfg_path)
+        .map_err(|e| ShurlError::Config(format!("failed to create config file: {e}")))?;
+    cfg_file
+        .read_to_string(&mut cfg_content)
+        .map_err(|e| ShurlError::Config(format!("failed to read config file: {e}")))?;
+
+    if cfg_content.is_empty() {
+        let content = to_string_pretty(&ShurlConfig::default())
+            .map_err(|e| ShurlError::Config(format!("failed to serialize config: {e}")))?;
+        cfg_file
+            .write_all(content.as_bytes())
+            .map_err(|e| ShurlError::Config(format!("failed to write config file: {e}")))?;
+        if !matches!(args.command, Some(Cmd::Config(_))) {
             println!(
                 "{} {}",
-                "Error:".red(),
-                "failed to parse config file".bold()
+                "Info:".green(),
+                "created config file. Set the default repository path and \
+                run the command again."
+                    .bold()
             );
-            return;
-        };
-        let args = Args::parse();
+            return Ok(());
+        }
+        cfg_content = content;
+    }
+    // Works on configs that can't be used, to fix them.
+    let profile = args
+        .profile
+        .take()
+        .or_else(|| env::var("SHURL_PROFILE").ok());
+    if let Some(Cmd::Config(cmd)) = args.command {
+        return configure(cmd, &cfg_content, profile.as_deref());
+    }
 
-        let url = match Url::parse(&args.url) {
-            Ok(url) => url,
-            Err(e) => {
-                eprintln!(
-                    "{} {} {}",
-                    "Error:".red(),
-                    "failed to parse url:".bold(),
-                    e.to_string()
-                );
-                return;
+    let mut cfg = toml::from_str::<ShurlConfig>(&cfg_content)
+        .map_err(|e| ShurlError::Config(format!("failed to parse config file: {e}")))?
+        .with_profile(profile.as_deref())
+        .and_then(ShurlConfig::with_env)
+        .map_err(ShurlError::Config)?;
+    if let Some(repo) = args.repo.take() {
+        cfg.repo_path = repo;
+    }
+    cfg.author_name = args.author_name.take();
+    cfg.author_email = args.author_email.take();
+    if let Some(Cmd::Init) = args.command {
+        return init(cfg);
+    }
+    let mut shurl = Shurl::open(cfg)?;
+    if changes_repo(&args) && !args.force && !repo_config::is_marked(shurl.repo_path()) {
+        return Err(ShurlError::Config(format!(
+            "{} has no {MARKER_FILE}, so it may not be a shurl repository: \
+            run `shurl init` to mark it, or pass --force",
+            shurl.repo_path().display()
+        )));
+    }
+    let cfg = shurl.config().clone();
+    if let Some(seed) = args.seed {
+        shurl.set_rng(slug::seeded(seed));
+    }
+    let repo_path = shurl.repo_path().to_path_buf();
+    let repo_path = repo_path.as_path();
+
+    match args.command {
+        Some(Cmd::Rm { slug, hard }) => slug_or_pick(&shurl, slug)
+            .and_then(|slug| slug.map_or(Ok(()), |slug| rm(&shurl, &slug, hard))),
+        Some(Cmd::List { namespace, tag }) => list(&shurl, namespace.as_deref(), tag.as_deref()),
+        Some(Cmd::Search { query, regex }) => search(&shurl, &query, regex),
+        Some(Cmd::Prune { dry_run }) => prune(&shurl, dry_run),
+        Some(Cmd::Rerender) => {
+            shurl.rerender()?;
+            print_commit(&shurl);
+            Ok(())
+        }
+        Some(Cmd::Lint) => lint(repo_path),
+        Some(Cmd::Verify) => verify(shurl.config(), repo_path),
+        Some(Cmd::Trash(cmd)) => trash(&shurl, cmd),
+        Some(Cmd::Federate(cmd)) => federate(&shurl, cmd),
+        Some(Cmd::Tags(cmd)) => tags(&shurl, cmd),
+        Some(Cmd::Bundle(cmd)) => bundle(&shurl, cmd),
+        Some(Cmd::Screenshot { slugs }) => screenshots(&shurl, slugs),
+        Some(Cmd::Completions { .. } | Cmd::Manpage | Cmd::Config(_) | Cmd::Init) => {
+            unreachable!("handled before the repository is opened")
+        }
+        Some(Cmd::CompleteSlugs { trash, bundles }) => complete_slugs(repo_path, trash, bundles),
+        Some(Cmd::Export { target, output }) => export(repo_path, &cfg, target, output),
+        Some(Cmd::Import { source, file }) => import(&shurl, source, &file),
+        Some(Cmd::ReleaseLinks {
+            repo: github_repo,
+            tag,
+            prefix,
+        }) => release_links(&shurl, &github_repo, &tag, prefix),
+        Some(Cmd::CiDocs {
+            changed_files,
+            github_repo,
+            pr,
+        }) => ci_docs(&shurl, &changed_files, github_repo, pr),
+        Some(Cmd::Stats {
+            history: true, by, ..
+        }) => growth(&shurl, by),
+        Some(Cmd::Stats { slug, .. }) => stats(repo_path, slug),
+        Some(Cmd::SyncKv) => sync_kv(&cfg, repo_path),
+        Some(Cmd::Update {
+            slug,
+            target,
+            description,
+            tags,
+        }) => slug_or_pick(&shurl, slug).and_then(|slug| {
+            slug.map_or(Ok(()), |slug| {
+                update(&shurl, &slug, target, description, tags)
+            })
+        }),
+        Some(Cmd::Open { slug }) => slug_or_pick(&shurl, slug)
+            .and_then(|slug| slug.map_or(Ok(()), |slug| open(&shurl, &slug))),
+        Some(Cmd::Rename { mut names }) => {
+            let new = names.pop().expect("the new name is required");
+            slug_or_pick(&shurl, names.pop())
+                .and_then(|old| old.map_or(Ok(()), |old| rename(&shurl, &old, &new)))
+        }
+        Some(Cmd::History { slug }) => slug_or_pick(&shurl, slug)
+            .and_then(|slug| slug.map_or(Ok(()), |slug| lifecycle(&shurl, &slug))),
+        Some(Cmd::Tui) => tui::run(&shurl),
+        Some(Cmd::Audit { since, until, csv }) => audit(&shurl, since, until, csv),
+        Some(Cmd::Rewrite {
+            pattern,
+            replace,
+            dry_run,
+            yes,
+        }) => rewrite(&shurl, &pattern, &replace, dry_run, yes),
+        Some(Cmd::DeprecateDomain {
+            domain,
+            replace_with,
+            review,
+        }) => deprecate_domain(&shurl, &domain, replace_with, review),
+        Some(Cmd::Serve { address, port, api }) => {
+            let address = address.as_deref().unwrap_or(&cfg.serve_address);
+            let port = port.unwrap_or(cfg.serve_port);
+            if api {
+                // Only the thread committing the links uses the repository.
+                serve::run(
+                    &cfg,
+                    repo_path,
+                    address,
+                    port,
+                    Some(move |cfg: &ShurlConfig, links| {
+                        shurl.set_config(cfg.clone());
+                        shurl.add_links(links)
+                    }),
+                )
+            } else {
+                serve::run(
+                    &cfg,
+                    repo_path,
+                    address,
+                    port,
+                    None::<fn(&ShurlConfig, Vec<NewLink>) -> _>,
+                )
             }
-        };
+            .map_err(ShurlError::from)
+        }
+        Some(Cmd::Clipd) => health::self_check(&cfg, repo_path, true)
+            .and_then(|()| {
+                clipd::run(&cfg, repo_path, |cfg, url| {
+                    shurl.set_config(cfg.clone());
+                    // Copying a link again gets the same short URL.
+                    if let Some(link) =
+                        shurl.find_target(url.as_str()).map_err(|e| e.to_string())?
+                    {
+                        return Ok(link.slug);
+                    }
+                    shurl
+                        .add_link(url.as_str(), LinkOptions::default())
+                        .map(|link| link.slug)
+                        .map_err(|e| e.to_string())
+                })
+            })
+            .map_err(ShurlError::from),
+        // `main` moves the flags of `add` to the top level already.
+        None | Some(Cmd::Add(_)) => add(&shurl, args),
+    }
+}
+
+/// Whether the command writes to the repository, so it has to be marked.
+fn changes_repo(args: &Args) -> bool {
+    match &args.command {
+        None | Some(Cmd::Add(_)) => true,
+        Some(Cmd::Trash(cmd)) => !matches!(cmd, TrashCmd::List),
+        Some(Cmd::Federate(cmd)) => !matches!(cmd, FederateCmd::List),
+        Some(Cmd::Tags(cmd)) => !matches!(cmd, TagsCmd::List),
+        Some(Cmd::Bundle(cmd)) => !matches!(cmd, BundleCmd::List),
+        Some(Cmd::Rewrite { dry_run, .. } | Cmd::Prune { dry_run }) => !dry_run,
+        Some(Cmd::DeprecateDomain { replace_with, .. }) => replace_with.is_some(),
+        Some(Cmd::Serve { api, .. }) => *api,
+        Some(
+            Cmd::Rm { .. }
+            | Cmd::Rename { .. }
+            | Cmd::Update { .. }
+            | Cmd::Rerender
+            | Cmd::Screenshot { .. }
+            | Cmd::Clipd
+            | Cmd::Tui
+            | Cmd::Import { .. }
+            | Cmd::ReleaseLinks { .. }
+            | Cmd::CiDocs { .. },
+        ) => true,
+        Some(
+            Cmd::Init
+            | Cmd::List { .. }
+            | Cmd::Search { .. }
+            | Cmd::Lint
+            | Cmd::Verify
+            | Cmd::Export { .. }
+            | Cmd::SyncKv
+            | Cmd::Stats { .. }
+            | Cmd::History { .. }
+            | Cmd::Open { .. }
+            | Cmd::Audit { .. }
+            | Cmd::Completions { .. }
+            | Cmd::Config(_)
+            | Cmd::Manpage
+            | Cmd::CompleteSlugs { .. },
+        ) => false,
+    }
+}
+
+/// Marks the repository, creating it if needed, and pushes the commit if it
+/// has somewhere to go.
+fn init(cfg: ShurlConfig) -> Result<(), ShurlError> {
+    let (shurl, marked) = Shurl::init(cfg)?;
+    if !marked {
+        if !output::is_quiet() {
+            println!(
+                "{} {}",
+                "Info:".green(),
+                format!("{} is marked already", shurl.repo_path().display()).bold()
+            );
+        }
+        return Ok(());
+    }
+    if shurl.repo().find_remote("origin").is_ok() {
+        git::push(shurl.repo_path(), &shurl.config().branch)?;
+    }
+    print_commit(&shurl);
+    Ok(())
+}
 
-        let expanded_repo_path = tilde(cfg.repo_path.to_str().unwrap()).to_string();
-        let repo_path = Path::new(&expanded_repo_path);
-        let repo = match git2::Repository::open(repo_path) {
-            Ok(repo) => repo,
-            Err(e) => {
+/// Runs a `config` subcommand. Settings are read and changed in the profile,
+/// `default_profile` if not given.
+fn configure(cmd: ConfigCmd, content: &str, profile: Option<&str>) -> Result<(), ShurlError> {
+    let path = config::config_path();
+    match cmd {
+        ConfigCmd::Get { key } => {
+            let cfg = toml::from_str::<ShurlConfig>(content)
+                .map_err(|e| ShurlError::Config(format!("failed to parse config file: {e}")))?
+                .with_profile(profile)
+                .and_then(ShurlConfig::with_env)
+                .map_err(ShurlError::Config)?;
+            match cfg.get(&key).map_err(ShurlError::InvalidInput)? {
+                Some(value) => println!("{value}"),
+                None => return Err(ShurlError::NotFound(format!("`{key}` isn't set"))),
+            }
+        }
+        ConfigCmd::Set { key, value } => {
+            config::check_key(&key).map_err(ShurlError::InvalidInput)?;
+            let default_profile = toml::from_str::<ShurlConfig>(content)
+                .ok()
+                .and_then(|cfg| cfg.default_profile);
+            let profile = profile.or(default_profile.as_deref());
+            config::set_key(&path, profile, &key, &value).map_err(ShurlError::Config)?;
+            if !output::is_quiet() {
+                println!("{} {}", "Info:".green(), format!("set {key}").bold());
+            }
+        }
+        ConfigCmd::Edit => edit_config(&path)?,
+        ConfigCmd::Path => println!("{}", path.display()),
+    }
+    Ok(())
+}
+
+/// Opens a copy of the config in the user's editor until it's valid, and
+/// only then replaces the config with it.
+fn edit_config(path: &Path) -> Result<(), ShurlError> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let copy = path.with_extension("toml.edit");
+    fs::copy(path, &copy).map_err(|e| format!("failed to copy {}: {e}", path.display()))?;
+    let discard = |message: String| {
+        let _ = fs::remove_file(&copy);
+        ShurlError::Config(message)
+    };
+
+    loop {
+        // Editors are often set with arguments, e.g. `code --wait`.
+        let mut words = editor.split_whitespace();
+        let program = words.next().unwrap_or("vi");
+        let status = Command::new(program)
+            .args(words)
+            .arg(&copy)
+            .status()
+            .map_err(|e| discard(format!("failed to run {editor}: {e}")))?;
+        if !status.success() {
+            return Err(discard(format!(
+                "{editor} exited with {status}, the config is unchanged"
+            )));
+        }
+
+        let content = fs::read_to_string(&copy)
+            .map_err(|e| discard(format!("failed to read {}: {e}", copy.display())))?;
+        let checked = toml::from_str::<ShurlConfig>(&content)
+            .map_err(|e| e.to_string())
+            .and_then(|cfg| cfg.validate());
+        let Err(e) = checked else {
+            for key in config::unknown_keys(&content) {
                 eprintln!(
-                    "{} {} {}",
-                    "Error:".red(),
-                    "failed to open repository:".bold(),
-                    e.to_string()
+                    "{} {}",
+                    "Warning:".yellow(),
+                    format!("unknown config key `{key}` is ignored").bold()
                 );
-                return;
             }
+            fs::rename(&copy, path).map_err(|e| discard(format!("failed to save config: {e}")))?;
+            return Ok(());
         };
 
-        let file_content = format!(
-            "<html>
-    <head>
-        <meta http-equiv=\"refresh\" content=\"0; URL={url}\" />
-    </head>
-    <body>
-        <p>Redirecting...</p>
-        <p>If you are not redirected automatically, follow the <a href=\"{url}\">link</a></p>
-    </body>
-</html>"
-        );
-        let file_name = match args.short_name {
-            Some(name) => repo_path.join(name + ".html"),
-            None => {
-                // We're using 5 characters long short names. May clash?
-                let mut possible_file_name = repo_path.join(&(create_name() + ".html"));
-                while possible_file_name.exists() {
-                    possible_file_name = repo_path.join(&(create_name() + ".html"))
+        eprintln!("{} {}", "Error:".red(), e.bold());
+        print!("Edit again? [Y/n] ");
+        io::stdout()
+            .flush()
+            .map_err(|e| format!("failed to write to stdout: {e}"))?;
+        let mut answer = String::new();
+        io::stdin()
+            .read_line(&mut answer)
+            .map_err(|e| format!("failed to read answer: {e}"))?;
+        if matches!(answer.trim(), "n" | "N") {
+            return Err(discard("the config is unchanged".to_string()));
+        }
+    }
+}
+
+fn bundle(shurl: &Shurl, cmd: BundleCmd) -> Result<(), ShurlError> {
+    let cfg = shurl.config();
+    let repo_path = shurl.repo_path();
+    let manifest = Manifest::load(repo_path)?;
+    let mut bundles = Bundles::load(repo_path)?;
+    let no_bundle = |slug: &str| ShurlError::NotFound(format!("no bundle named `{slug}`"));
+
+    // Only a new bundle has its short URL printed.
+    let (created, message) = match cmd {
+        BundleCmd::Create { slug, urls, title } => {
+            slug::validate(&slug).map_err(ShurlError::InvalidInput)?;
+            if manifest.get(&slug).is_some() || bundles.get(&slug).is_some() {
+                return Err(ShurlError::Conflict(format!("`{slug}` is already taken")));
+            }
+            let mut bundle = Bundle::new(slug.clone(), title, cfg.author().to_string());
+            bundle.add(parse_urls(cfg, &urls)?);
+            bundles.bundles.push(bundle);
+            let message = format!("Add bundle {slug}");
+            (Some(slug), message)
+        }
+        BundleCmd::Add { slug, urls } => {
+            let bundle = bundles.get_mut(&slug).ok_or_else(|| no_bundle(&slug))?;
+            if bundle.add(parse_urls(cfg, &urls)?) == 0 {
+                return Err(ShurlError::Conflict(format!(
+                    "every target is in `{slug}` already"
+                )));
+            }
+            (None, format!("Add targets to bundle {slug}"))
+        }
+        BundleCmd::Remove { slug, urls } => {
+            let bundle = bundles.get_mut(&slug).ok_or_else(|| no_bundle(&slug))?;
+            let targets = parse_urls(cfg, &urls)?;
+            let before = bundle.targets.len();
+            bundle.targets.retain(|target| !targets.contains(target));
+            if bundle.targets.len() == before {
+                return Err(ShurlError::NotFound(format!(
+                    "none of the targets is in `{slug}`"
+                )));
+            }
+            if bundle.targets.is_empty() {
+                return Err(ShurlError::InvalidInput(format!(
+                    "that would leave `{slug}` empty, delete it with `shurl bundle delete {slug}`"
+                )));
+            }
+            (None, format!("Remove targets from bundle {slug}"))
+        }
+        BundleCmd::Delete { slug } => {
+            bundles.remove(&slug).ok_or_else(|| no_bundle(&slug))?;
+            pages::remove_page(repo_path, &slug)?;
+            (None, format!("Delete bundle {slug}"))
+        }
+        BundleCmd::List => {
+            if output::is_json() {
+                let bundles: Vec<json::Value> = bundles
+                    .bundles
+                    .iter()
+                    .map(|bundle| {
+                        let targets: Vec<json::Value> = bundle
+                            .targets
+                            .iter()
+                            .map(|target| json::Value::from(target.as_str()))
+                            .collect();
+                        let mut value = json::Value::object();
+                        value
+                            .set("slug", bundle.slug.as_str())
+                            .set("short_url", cfg.short_url(&bundle.slug))
+                            .set("title", bundle.title.as_deref())
+                            .set("targets", targets)
+                            .set("created", bundle.created.to_string());
+                        value
+                    })
+                    .collect();
+                println!("{}", json::Value::from(bundles));
+                return Ok(());
+            }
+            for bundle in &bundles.bundles {
+                println!("{} ({} targets)", bundle.slug.bold(), bundle.targets.len());
+                for target in &bundle.targets {
+                    println!("    {target}");
                 }
-                possible_file_name
+            }
+            return Ok(());
+        }
+    };
+
+    bundles.save(repo_path)?;
+    publish(shurl, &manifest, &message)?;
+    if let Some(slug) = created {
+        print_short_url(cfg, &slug);
+    }
+    Ok(())
+}
+
+/// Checks the URLs of bundle targets, normalized like link targets.
+fn parse_urls(cfg: &ShurlConfig, urls: &[String]) -> Result<Vec<String>, ShurlError> {
+    urls.iter()
+        .map(|url| {
+            target::parse(cfg, url)
+                .map(|url| url.to_string())
+                .map_err(|e| ShurlError::InvalidInput(format!("{url}: {e}")))
+        })
+        .collect()
+}
+
+/// Prints one slug per line, quietly skipping a broken manifest so
+/// completion doesn't print errors into the command line.
+fn complete_slugs(repo_path: &Path, trash: bool, bundles: bool) -> Result<(), ShurlError> {
+    let slugs: Vec<String> = if bundles {
+        Bundles::load(repo_path)
+            .map(|bundles| {
+                bundles
+                    .bundles
+                    .into_iter()
+                    .map(|bundle| bundle.slug)
+                    .collect()
+            })
+            .unwrap_or_default()
+    } else if trash {
+        Trash::load(repo_path)
+            .map(|trash| trash.links.into_iter().map(|link| link.slug).collect())
+            .unwrap_or_default()
+    } else {
+        Manifest::load(repo_path)
+            .map(|manifest| manifest.links.into_iter().map(|link| link.slug).collect())
+            .unwrap_or_default()
+    };
+    for slug in slugs {
+        println!("{slug}");
+    }
+    Ok(())
+}
+
+/// Takes screenshots of the given links, or of the links without one, and
+/// publishes them. Links whose screenshot fails are skipped.
+fn screenshots(shurl: &Shurl, slugs: Vec<String>) -> Result<(), ShurlError> {
+    let cfg = shurl.config();
+    let repo_path = shurl.repo_path();
+    let manifest = Manifest::load(repo_path)?;
+    let links: Vec<&Link> = if slugs.is_empty() {
+        manifest
+            .links
+            .iter()
+            .filter(|link| !repo_path.join(screenshot::path(&link.slug)).is_file())
+            .collect()
+    } else {
+        slugs
+            .iter()
+            .map(|slug| {
+                manifest
+                    .get(slug)
+                    .ok_or_else(|| ShurlError::NotFound(format!("no link named `{slug}`")))
+            })
+            .collect::<Result<_, _>>()?
+    };
+    if links.is_empty() {
+        if !output::is_quiet() {
+            println!(
+                "{} {}",
+                "Info:".green(),
+                "every link has a screenshot".bold()
+            );
+        }
+        return Ok(());
+    }
+
+    let mut taken = vec![];
+    for link in links {
+        match screenshot::capture(repo_path, cfg, link) {
+            Ok(()) => taken.push(link.slug.as_str()),
+            Err(e) => eprintln!("{} {}", "Warning:".yellow(), e.bold()),
+        }
+    }
+    let message = match taken.as_slice() {
+        [] => return Err(ShurlError::Other("no screenshot was taken".to_string())),
+        [slug] => format!("Add screenshot of {slug}"),
+        slugs => format!("Add screenshots of {} links", slugs.len()),
+    };
+    publish(shurl, &manifest, &message)
+}
+
+/// Adds the link given on the command line, then prints, copies and writes
+/// the QR codes of its short URL as asked.
+fn add(shurl: &Shurl, mut args: Args) -> Result<(), ShurlError> {
+    if args.add.interactive {
+        args = match wizard(shurl, args)? {
+            Some(args) => args,
+            None => {
+                println!("{} {}", "Info:".green(), "nothing was added".bold());
+                return Ok(());
             }
         };
+    }
+    if args.add.stdin {
+        return add_from_stdin(shurl, args.add.status);
+    }
+    if let Some(file) = &args.add.from_file {
+        return add_from_file(shurl, file, args.add.status);
+    }
+    let cfg = shurl.config();
+    let wants_qr = args.add.qr || args.add.qr_png.is_some() || args.add.qr_svg.is_some();
+    let copy = !args.add.no_copy && (args.add.copy || cfg.copy_short_url);
+    if wants_qr && cfg.base_url.is_none() {
+        return Err(ShurlError::Config(
+            "QR codes need `base_url` in the config".to_string(),
+        ));
+    }
+    if copy && cfg.base_url.is_none() {
+        return Err(ShurlError::Config(
+            "copying the short URL needs `base_url` in the config".to_string(),
+        ));
+    }
 
-        fs::write(&file_name, file_content).expect("Failed to write file for redirection to url");
-
-        let mut index_file = match OpenOptions::new()
-            .create(true)
-            .read(true)
-            .write(true)
-            .append(true)
-            .open(repo_path.join("index.html"))
-        {
-            Ok(file) => file,
-            Err(e) => {
+    let url = args.add.url.expect("url is required without a subcommand");
+    // Links given a name are meant to be another one.
+    let existing = match (&args.add.short_name, args.add.force_new) {
+        (None, false) => shurl.find_target(&url)?,
+        _ => None,
+    };
+    if let Some(link) = existing {
+        if output::is_json() {
+            let mut value = output::link(cfg, &link);
+            value.set("existing", true);
+            println!("{value}");
+        } else {
+            if !output::is_quiet() {
                 println!(
-                    "{} {} {}",
-                    "Error:".red(),
-                    "failed to create config file:".bold(),
-                    e.to_string()
+                    "{} {}",
+                    "Info:".green(),
+                    format!(
+                        "{} is shortened as `{}` already, pass --force-new for another link",
+                        link.target, link.slug
+                    )
+                    .bold()
                 );
-                return;
             }
-        };
+            // Without `base_url` the message has the slug already.
+            if cfg.short_url(&link.slug).is_some() || output::is_quiet() {
+                print_short_url(cfg, &link.slug);
+            }
+        }
+        if copy {
+            copy_short_url(cfg, &link.slug);
+        }
+        if wants_qr {
+            write_qr(
+                cfg,
+                &link.slug,
+                args.add.qr,
+                args.add.qr_png,
+                args.add.qr_svg,
+            )?;
+        }
+        return Ok(());
+    }
 
-        let file_name = file_name.iter().last().unwrap().to_str().unwrap();
-        index_file
-            .write(format!("\n{url}: <a href=\"./{file_name}\">./{file_name}</a><br/>",).as_ref())
-            .expect("Failed to write to index.html");
-
-        let mut index = repo.index().unwrap();
-        index
-            .add_all(["*"].iter(), git2::IndexAddOption::DEFAULT, None)
-            .unwrap();
-        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
-        let head = repo.head();
-        let parent_commit;
-
-        let object_id = repo
-            .commit(
-                Some("HEAD"),
-                &git2::Signature::now(&cfg.name, &cfg.email).unwrap(),
-                &git2::Signature::now(&cfg.name, &cfg.email).unwrap(),
-                format!("Add redirect to {}", url).as_ref(),
-                &tree,
-                &match head {
-                    Ok(head) => {
-                        parent_commit = head.peel_to_commit().unwrap();
-                        vec![&parent_commit]
-                    },
-                    Err(_) => vec![],
-                },
-            )
-            .expect("Failed to create commit");
+    if let Some(mode) = args.add.verify {
+        match verify::check(&url) {
+            verify::Outcome::Ok => {}
+            // The target still answers, just somewhere else.
+            outcome @ verify::Outcome::Moved { .. } => {
+                eprintln!("{} {url} {outcome}", "Warning:".yellow());
+            }
+            outcome if mode == VerifyMode::Strict => {
+                return Err(ShurlError::InvalidInput(format!(
+                    "{url} {outcome}, leave out --verify=strict to add it anyway"
+                )));
+            }
+            outcome => eprintln!("{} {url} {outcome}", "Warning:".yellow()),
+        }
+    }
 
-        println!("Created commit with object id: {}", object_id);
+    // Fetched once for both the title and the preview.
+    let wants_title =
+        args.add.description.is_none() && (args.add.fetch_title || args.add.title_slug);
+    let wants_preview = args.add.preview || cfg.previews;
+    let page = if wants_title || wants_preview {
+        title::fetch_page(&url).map_err(|e| {
+            eprintln!(
+                "{} {e}, adding the link without a title or preview",
+                "Warning:".yellow()
+            );
+        })
+    } else {
+        Err(())
+    };
 
-        // HACK: easier way to push to upstream
-        Command::new("git")
-            .arg("push")
-            .arg("origin")
-            .arg("master")
-            .current_dir(repo_path)
-            .status()
-            .expect("Failed to push to upstream: try running `git push` manually");
+    let description = if args.add.description.is_some() {
+        args.add.description
+    } else if wants_title {
+        page.as_deref().ok().and_then(|page| {
+            let title = title::extract(page);
+            if title.is_none() {
+                eprintln!(
+                    "{} {url} has no title, adding the link without one",
+                    "Warning:".yellow()
+                );
+            }
+            title
+        })
+    } else {
+        None
+    };
+    let preview = match (&page, url::Url::parse(&url)) {
+        (Ok(page), Ok(base)) if wants_preview => {
+            let preview = preview::extract(page, &base);
+            if preview.is_empty() {
+                eprintln!(
+                    "{} {url} has nothing to preview, adding the link without a preview",
+                    "Warning:".yellow()
+                );
+            }
+            (!preview.is_empty()).then_some(preview)
+        }
+        _ => None,
+    };
+
+    let link = shurl.add_link(
+        &url,
+        LinkOptions {
+            name: args.add.short_name,
+            status: args.add.status,
+            issue: args.add.issue,
+            tags: args.add.tags,
+            starts: args.add.starts,
+            expires: args.add.expires.or_else(|| {
+                args.add
+                    .ttl
+                    .map(|ttl| date::from_unix(date::to_unix(&date::now()) + ttl))
+            }),
+            delay: args.add.delay,
+            noindex: (args.add.noindex || args.add.index).then_some(args.add.noindex),
+            referrer: args.add.referrer,
+            template: args.add.template,
+            lang: args.add.lang,
+            locales: args.add.locales.into_iter().collect(),
+            screenshot: (args.add.screenshot || args.add.no_screenshot)
+                .then_some(args.add.screenshot),
+            replace: args.add.replace,
+            description,
+            name_from_description: args.add.title_slug,
+            preview,
+        },
+    )?;
+    if output::is_json() {
+        print_link_json(shurl, &link);
+    } else {
+        print_commit(shurl);
+        print_short_url(cfg, &link.slug);
+    }
+    if copy {
+        copy_short_url(cfg, &link.slug);
+    }
+    if wants_qr {
+        write_qr(
+            cfg,
+            &link.slug,
+            args.add.qr,
+            args.add.qr_png,
+            args.add.qr_svg,
+        )?;
+    }
+    Ok(())
+}
+
+/// Splits a `--target` of the form `LANG=URL`, the rest is checked when the
+/// link is added.
+fn parse_locale(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(language, url)| (language.to_string(), url.to_string()))
+        .ok_or_else(|| format!("`{value}` isn't of the form LANG=URL"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batches_reject_the_flags_of_a_single_link() {
+        Args::command().debug_assert();
+        for batch in [&["--stdin"][..], &["--from-file", "links.tsv"]] {
+            for flag in [&["--delay", "5"][..], &["--force-new"], &["--verify"]] {
+                let argv = ["shurl"].iter().chain(batch).chain(flag);
+                assert!(Args::try_parse_from(argv).is_err(), "{batch:?} {flag:?}");
+            }
+            let argv = ["shurl", "--status", "302"].iter().chain(batch);
+            assert!(Args::try_parse_from(argv).is_ok(), "{batch:?}");
+        }
+    }
+
+    #[test]
+    fn add_takes_the_same_flags() {
+        let args = Args::try_parse_from(["shurl", "add", "--stdin", "--status", "302"]).unwrap();
+        assert!(
+            matches!(&args.command, Some(Cmd::Add(add)) if add.stdin && add.status == Some(302))
+        );
+        assert!(Args::try_parse_from(["shurl", "add", "--stdin", "--delay", "5"]).is_err());
+    }
+
+    /// Paths of the subcommands of `command` taking a slug, e.g. `tags add`.
+    fn slug_commands(command: &clap::Command, path: &str, found: &mut Vec<String>) {
+        for sub in command.get_subcommands() {
+            let path = format!("{path} {}", sub.get_name()).trim().to_string();
+            let takes_slug = sub.get_positionals().any(|arg| {
+                arg.get_id().as_str().starts_with("slug")
+                    || arg
+                        .get_value_names()
+                        .is_some_and(|names| names.iter().any(|name| name.starts_with("SLUG")))
+            });
+            if takes_slug {
+                found.push(path.clone());
+            }
+            slug_commands(sub, &path, found);
+        }
+    }
+
+    #[test]
+    fn every_command_taking_a_slug_completes_slugs() {
+        let mut expected = vec!["add".to_string()];
+        slug_commands(&Args::command(), "", &mut expected);
+        // Named after a slug not taken yet.
+        expected.retain(|path| path != "bundle create");
+        for path in ["history", "tags add", "tags rm", "rename", "rm"] {
+            assert!(expected.iter().any(|found| found == path), "{path}");
+        }
+        let script = completions::generate(Shell::Bash, &mut Args::command(), SLUG_COMPLETIONS);
+        for path in expected {
+            let case = format!("        'shurl {path}')\n");
+            let start = script.find(&case).unwrap_or_else(|| panic!("{path}"));
+            let end = start + script[start..].find("\n            ;;\n").unwrap();
+            assert!(
+                script[start..end].contains("shurl complete-slugs"),
+                "{path} isn't completed with slugs"
+            );
+        }
     }
 }
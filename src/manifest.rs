@@ -0,0 +1,216 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::config::ReferrerPolicy;
+use crate::date;
+use crate::preview::Preview;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use toml::value::Datetime;
+
+pub const MANIFEST_FILE: &str = "links.toml";
+
+/// Every short link in the repository. Pages such as `index.html` are
+/// generated from this, so it is the source of truth.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Manifest {
+    #[serde(default, rename = "link")]
+    pub links: Vec<Link>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Link {
+    pub slug: String,
+    pub target: String,
+    pub created: Datetime,
+    /// Status code for server-side redirect backends, `redirect_status` from
+    /// the config if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// When the link stops being valid, set from the namespace's
+    /// `expiry_days` in `.shurl.toml`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<Datetime>,
+    /// When the event the link is for starts. Until then the link shows a
+    /// countdown, then it redirects to the target.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub starts: Option<Datetime>,
+    /// Targets for readers of other languages, by language tag such as `de`
+    /// or `pt-BR`. Everyone else goes to `target`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub locales: BTreeMap<String, String>,
+    /// Who created the link, `name` from the config of whoever ran shurl.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Issue or ticket the link was created for, e.g. `ORG-1234`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issue: Option<String>,
+    /// Seconds its page waits before redirecting, `redirect_delay` from the
+    /// config if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delay: Option<u32>,
+    /// Whether search engines are asked not to index its page, `noindex`
+    /// from the config if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub noindex: Option<bool>,
+    /// What the target learns of where readers came from,
+    /// `referrer_policy` from the config if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub referrer: Option<ReferrerPolicy>,
+    /// Template of its page in `templates/`, e.g. `interstitial`, instead of
+    /// the one of its namespace or the repository.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// Language of the text of its page, `language` from the config if not
+    /// set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+    /// What the link is for, shown to people looking for it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// How apps unfurling the link show it, taken from the target page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<Preview>,
+    /// How often the link was followed on the shortener it was imported
+    /// from, before shurl counted anything.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clicks: Option<u64>,
+}
+
+impl Link {
+    /// A link of the name to the target, everything else left to the config.
+    pub fn new(slug: &str, target: &str, created: Datetime) -> Self {
+        Self {
+            slug: slug.to_string(),
+            target: target.to_string(),
+            created,
+            status: None,
+            expires: None,
+            starts: None,
+            locales: BTreeMap::new(),
+            owner: None,
+            issue: None,
+            delay: None,
+            noindex: None,
+            referrer: None,
+            template: None,
+            lang: None,
+            description: None,
+            tags: vec![],
+            preview: None,
+            clicks: None,
+        }
+    }
+
+    /// Whether the link's event hasn't started yet, so it has to be left to
+    /// its countdown page rather than redirect.
+    pub fn is_counting_down(&self) -> bool {
+        self.starts
+            .as_ref()
+            .is_some_and(|starts| date::to_unix(starts) > date::to_unix(&date::now()))
+    }
+
+    /// Whether the link is past its expiry date.
+    pub fn is_expired(&self) -> bool {
+        self.expires
+            .as_ref()
+            .is_some_and(|expires| date::to_unix(expires) <= date::to_unix(&date::now()))
+    }
+
+    /// Whether the link is left to its page rather than to server-side
+    /// redirects, because the page counts down to its event or picks the
+    /// target of the reader's language.
+    pub fn needs_page(&self) -> bool {
+        self.is_counting_down() || !self.locales.is_empty()
+    }
+}
+
+impl Manifest {
+    /// Loads the manifest of the repository. Repositories created before the
+    /// manifest existed only have an `index.html`, so the links are recovered
+    /// from it instead.
+    pub fn load(repo_path: &Path) -> Result<Self, String> {
+        let manifest_path = repo_path.join(MANIFEST_FILE);
+        if manifest_path.exists() {
+            let content = fs::read_to_string(&manifest_path)
+                .map_err(|e| format!("failed to read {MANIFEST_FILE}: {e}"))?;
+            return toml::from_str(&content)
+                .map_err(|e| format!("failed to parse {MANIFEST_FILE}: {e}"));
+        }
+
+        match fs::read_to_string(repo_path.join("index.html")) {
+            Ok(content) => Ok(Self::from_legacy_index(repo_path, &content)),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub fn save(&self, repo_path: &Path) -> Result<(), String> {
+        let content = toml::to_string_pretty(self)
+            .map_err(|e| format!("failed to serialize {MANIFEST_FILE}: {e}"))?;
+        fs::write(repo_path.join(MANIFEST_FILE), content)
+            .map_err(|e| format!("failed to write {MANIFEST_FILE}: {e}"))
+    }
+
+    pub fn get(&self, slug: &str) -> Option<&Link> {
+        self.links.iter().find(|link| link.slug == slug)
+    }
+
+    /// Adds a link, replacing any existing link with the same slug.
+    pub fn insert(&mut self, link: Link) {
+        self.links.retain(|existing| existing.slug != link.slug);
+        self.links.push(link);
+    }
+
+    pub fn remove(&mut self, slug: &str) -> Option<Link> {
+        let index = self.links.iter().position(|link| link.slug == slug)?;
+        Some(self.links.remove(index))
+    }
+
+    pub fn newest_first(&self) -> Vec<&Link> {
+        let mut links: Vec<&Link> = self.links.iter().collect();
+        links.sort_by(|a, b| b.created.cmp(&a.created).then_with(|| a.slug.cmp(&b.slug)));
+        links
+    }
+
+    /// Parses lines of the form `{url}: <a href="./{slug}.html">./{slug}.html</a><br/>`
+    /// which older versions appended to `index.html`. Later lines win for
+    /// duplicated slugs.
+    fn from_legacy_index(repo_path: &Path, content: &str) -> Self {
+        let mut manifest = Self::default();
+        for line in content.lines() {
+            let Some((target, rest)) = line.rsplit_once(": <a href=\"./") else {
+                continue;
+            };
+            let Some((file_name, _)) = rest.split_once('"') else {
+                continue;
+            };
+            let Some(slug) = file_name.strip_suffix(".html") else {
+                continue;
+            };
+
+            let created = fs::metadata(repo_path.join(file_name))
+                .and_then(|metadata| metadata.modified())
+                .map(date::from_system_time)
+                .unwrap_or_else(|_| date::now());
+            manifest.insert(Link::new(slug, target.trim(), created));
+        }
+        manifest
+    }
+}
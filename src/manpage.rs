@@ -0,0 +1,260 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! roff man page of the command line and the config, generated from the
+//! clap definition so packagers can ship it.
+
+use crate::config::{self, ShurlConfig, KEYS, LEGACY_CONFIG_FILE};
+use clap::{Arg, Command};
+
+/// Exit codes, as returned by [`ShurlError::exit_code`].
+const EXIT_CODES: &[(u8, &str)] = &[
+    (0, "Success."),
+    (1, "Any other error."),
+    (2, "Invalid command line."),
+    (
+        3,
+        "The config is missing, invalid or lacks a needed setting.",
+    ),
+    (4, "An argument such as a URL is invalid."),
+    (5, "The link, bundle or partner doesn't exist."),
+    (6, "A slug or name is taken."),
+    (7, "Committing or pushing failed."),
+    (
+        8,
+        "A request to GitHub, Jira, Cloudflare or a partner failed.",
+    ),
+    (9, "shurl lint found errors."),
+];
+
+/// Writes the man page of `command`, in section 1.
+pub fn generate(command: &mut Command) -> String {
+    command.build();
+    let name = command.get_name().to_string();
+    let mut out = format!(
+        ".TH {} 1 \"\" \"{name} {}\" \"User Commands\"\n",
+        name.to_uppercase(),
+        command.get_version().unwrap_or_default()
+    );
+
+    out.push_str(".SH NAME\n");
+    let about = command
+        .get_about()
+        .map(|about| about.to_string())
+        .unwrap_or_default();
+    out.push_str(&format!("{name} \\- {}\n", escape(&about)));
+
+    out.push_str(".SH SYNOPSIS\n");
+    out.push_str(&format!(
+        "\\fB{name}\\fR [\\fIOPTIONS\\fR] {}\n",
+        usage(command).join(" ")
+    ));
+    out.push_str(".br\n");
+    out.push_str(&format!(
+        "\\fB{name}\\fR \\fICOMMAND\\fR [\\fIOPTIONS\\fR]\n"
+    ));
+
+    out.push_str(".SH OPTIONS\n");
+    options(command, &mut out);
+
+    out.push_str(".SH COMMANDS\n");
+    for sub in visible_subcommands(command) {
+        subcommand(sub, &name, &mut out);
+    }
+
+    out.push_str(".SH CONFIGURATION\n");
+    out.push_str(&format!(
+        "Settings are read from \\fI$XDG_CONFIG_HOME/shurl/config.toml\\fR, or \\fI~/.config/shurl/config.toml\\fR \
+        without it, created on first run. On macOS it's \\fI~/Library/Application Support/shurl/config.toml\\fR \
+        and on Windows \\fI%APPDATA%\\\\shurl\\\\config.toml\\fR. A config at \\fI{}\\fR, where older versions kept it, is moved there.\n",
+        escape(LEGACY_CONFIG_FILE)
+    ));
+    let defaults = toml::Value::try_from(ShurlConfig::default()).ok();
+    let default_of = |key: &str| {
+        defaults
+            .as_ref()
+            .and_then(|defaults| defaults.get(key))
+            .map(|value| value.to_string())
+    };
+    for (key, description) in KEYS {
+        out.push_str(&format!(".TP\n\\fB{}\\fR", escape(key)));
+        if let Some(default) = default_of(key) {
+            out.push_str(&format!(" = {}", escape(&default)));
+        }
+        out.push_str(&format!("\n{}\n", escape(description)));
+    }
+
+    out.push_str(".SH ENVIRONMENT\n");
+    out.push_str(&format!(
+        "Every setting but {} can be replaced with a variable of its name in capitals after SHURL_, \
+        e.g. \\fBSHURL_BASE_URL\\fR, taking precedence over the config and its profile.\n",
+        escape(
+            &KEYS
+                .iter()
+                .filter(|(key, _)| config::env_var(key).is_none())
+                .map(|(key, _)| *key)
+                .collect::<Vec<_>>()
+                .join(" and ")
+        )
+    ));
+    out.push_str(
+        ".TP\n\\fBSHURL_PROFILE\\fR\nProfile used without --profile, before default_profile.\n",
+    );
+
+    out.push_str(".SH FILES\n");
+    for (file, description) in [
+        ("links.toml", "Every short link, the source of truth of the generated pages."),
+        (".shurl.toml", "Namespaces, the tag vocabulary, lint rules and settings replacing those of the config, shared by everyone using the repository."),
+        (".shurl-repo", "Written by shurl init. Commands changing the repository refuse to run without it unless given --force."),
+        ("trash.toml", "Links removed with shurl rm."),
+        ("bundles.toml", "Bundles created with shurl bundle."),
+        ("federation.toml", "Partner repositories listed on the directory page."),
+        ("provenance.json", "SLSA provenance of the generated files, written on every publish."),
+    ] {
+        out.push_str(&format!(".TP\n\\fI{file}\\fR\n{}\n", escape(description)));
+    }
+
+    out.push_str(".SH EXIT STATUS\n");
+    for (code, description) in EXIT_CODES {
+        out.push_str(&format!(".TP\n{code}\n{}\n", escape(description)));
+    }
+    out
+}
+
+fn visible_subcommands(command: &Command) -> impl Iterator<Item = &Command> {
+    command
+        .get_subcommands()
+        .filter(|sub| !sub.is_hide_set() && sub.get_name() != "help")
+}
+
+fn subcommand(command: &Command, parent: &str, out: &mut String) {
+    let path = format!("{parent} {}", command.get_name());
+    out.push_str(&format!(".SS \"{path}\"\n"));
+    let mut synopsis = vec![format!("\\fB{path}\\fR")];
+    synopsis.extend(usage(command));
+    if command.has_subcommands() {
+        synopsis.push("\\fICOMMAND\\fR".to_string());
+    }
+    out.push_str(&format!("{}\n", synopsis.join(" ")));
+    if let Some(about) = command.get_about() {
+        out.push_str(&format!(".PP\n{}\n", escape(&about.to_string())));
+    }
+    // Global options are documented once, under OPTIONS.
+    let own: Vec<&Arg> = command
+        .get_arguments()
+        .filter(|arg| !arg.is_global_set() && arg.get_id().as_str() != "help")
+        .collect();
+    if !own.is_empty() {
+        out.push_str(".RS\n");
+        for arg in own {
+            option(arg, out);
+        }
+        out.push_str(".RE\n");
+    }
+    for sub in visible_subcommands(command) {
+        subcommand(sub, &path, out);
+    }
+}
+
+fn options(command: &Command, out: &mut String) {
+    for arg in command.get_arguments() {
+        option(arg, out);
+    }
+}
+
+fn option(arg: &Arg, out: &mut String) {
+    let help = arg
+        .get_long_help()
+        .or(arg.get_help())
+        .map(|help| help.to_string())
+        .unwrap_or_default();
+    // Positional arguments without help are explained by the synopsis.
+    if arg.is_hide_set() || (arg.is_positional() && help.is_empty()) {
+        return;
+    }
+    let mut spellings = vec![];
+    if let Some(short) = arg.get_short() {
+        spellings.push(format!("\\fB\\-{short}\\fR"));
+    }
+    if let Some(long) = arg.get_long() {
+        spellings.push(format!("\\fB\\-\\-{}\\fR", escape(long)));
+    }
+    let mut term = spellings.join(", ");
+    if arg.get_action().takes_values() {
+        let value = value_name(arg);
+        if term.is_empty() {
+            term = format!("\\fI{value}\\fR");
+        } else {
+            term.push_str(&format!(" \\fI{value}\\fR"));
+        }
+    }
+    out.push_str(&format!(".TP\n{term}\n"));
+    out.push_str(&format!("{}\n", escape(&help)));
+    let possible: Vec<String> = arg
+        .get_possible_values()
+        .iter()
+        .filter(|value| !value.is_hide_set())
+        .map(|value| value.get_name().to_string())
+        .collect();
+    if !possible.is_empty() {
+        out.push_str(&format!(".br\nOne of: {}\n", escape(&possible.join(", "))));
+    }
+}
+
+fn value_name(arg: &Arg) -> String {
+    arg.get_value_names()
+        .and_then(|names| names.first())
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| arg.get_id().as_str().to_uppercase())
+}
+
+/// Positional arguments, with brackets around the optional ones.
+fn usage(command: &Command) -> Vec<String> {
+    command
+        .get_positionals()
+        .filter(|arg| !arg.is_hide_set())
+        .map(|arg| {
+            let mut name = format!("\\fI{}\\fR", value_name(arg));
+            if arg
+                .get_num_args()
+                .is_some_and(|range| range.max_values() > 1)
+            {
+                name.push_str("...");
+            }
+            if arg.is_required_set() {
+                name
+            } else {
+                format!("[{name}]")
+            }
+        })
+        .collect()
+}
+
+/// Escapes text for roff, including lines starting like a request.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\e")
+        .replace('-', "\\-")
+        .lines()
+        .map(|line| {
+            if line.starts_with(['.', '\'']) {
+                format!("\\&{line}")
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
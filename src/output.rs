@@ -0,0 +1,135 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! How commands print their results: colored text for people or, with
+//! `--format json`, one JSON document on stdout for scripts. `--quiet`
+//! leaves only the result, and colors are left out for `--no-color`,
+//! `NO_COLOR` and when stdout isn't a terminal.
+
+use crate::config::ShurlConfig;
+use crate::json::Value;
+use crate::manifest::Link;
+use clap::ValueEnum;
+use owo_colors::{OwoColorize, Style};
+use std::env;
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static JSON: AtomicBool = AtomicBool::new(false);
+static QUIET: AtomicBool = AtomicBool::new(false);
+static COLOR: AtomicBool = AtomicBool::new(true);
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum Format {
+    #[default]
+    Human,
+    Json,
+}
+
+pub fn set_format(format: Format) {
+    JSON.store(format == Format::Json, Ordering::Relaxed);
+}
+
+/// Whether stdout is reserved for JSON, so progress messages must not go
+/// there.
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Whether stdout only gets the result of a command, with `--quiet` or
+/// because it's JSON. Warnings and errors still go to stderr.
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed) || is_json()
+}
+
+/// Turns colors off for `--no-color`, a non-empty `NO_COLOR` (see
+/// <https://no-color.org>) or output that isn't a terminal.
+pub fn init_color(no_color: bool) {
+    let enabled = !no_color
+        && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+        && io::stdout().is_terminal();
+    COLOR.store(enabled, Ordering::Relaxed);
+}
+
+/// The colors used in messages, written only while colors are on. Use this
+/// instead of `OwoColorize`.
+pub trait Colorize: Display + Sized {
+    fn red(&self) -> Painted<'_, Self> {
+        Painted(self, Style::new().red())
+    }
+
+    fn green(&self) -> Painted<'_, Self> {
+        Painted(self, Style::new().green())
+    }
+
+    fn yellow(&self) -> Painted<'_, Self> {
+        Painted(self, Style::new().yellow())
+    }
+
+    fn bold(&self) -> Painted<'_, Self> {
+        Painted(self, Style::new().bold())
+    }
+
+    fn dimmed(&self) -> Painted<'_, Self> {
+        Painted(self, Style::new().dimmed())
+    }
+}
+
+impl<T: Display> Colorize for T {}
+
+pub struct Painted<'a, T>(&'a T, Style);
+
+impl<T: Display> Display for Painted<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if COLOR.load(Ordering::Relaxed) {
+            self.0.style(self.1).fmt(f)
+        } else {
+            self.0.fmt(f)
+        }
+    }
+}
+
+/// A link as scripts see it.
+pub fn link(cfg: &ShurlConfig, link: &Link) -> Value {
+    let mut locales = Value::object();
+    for (language, target) in &link.locales {
+        locales.set(language, target.as_str());
+    }
+    let mut value = Value::object();
+    value
+        .set("slug", link.slug.as_str())
+        .set("short_url", cfg.short_url(&link.slug))
+        .set("target", link.target.as_str())
+        .set("created", link.created.to_string())
+        .set("status", link.status)
+        .set("expires", link.expires.as_ref().map(ToString::to_string))
+        .set("starts", link.starts.as_ref().map(ToString::to_string))
+        .set("locales", locales)
+        .set("owner", link.owner.as_deref())
+        .set("issue", link.issue.as_deref())
+        .set("description", link.description.as_deref())
+        .set(
+            "tags",
+            Value::Array(link.tags.iter().map(|tag| tag.as_str().into()).collect()),
+        )
+        .set("clicks", link.clicks);
+    value
+}
@@ -0,0 +1,148 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where the redirect page of a slug lives in the repository.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// `slug.html`, served at `/slug.html`.
+    Flat,
+    /// `slug/index.html`, served at `/slug` by GitHub Pages and most other hosts.
+    Directory,
+}
+
+impl Layout {
+    pub fn from_config(clean_urls: bool) -> Self {
+        if clean_urls {
+            Layout::Directory
+        } else {
+            Layout::Flat
+        }
+    }
+
+    pub fn page_path(self, repo_path: &Path, slug: &str) -> PathBuf {
+        match self {
+            Layout::Flat => repo_path.join(format!("{slug}.html")),
+            Layout::Directory => repo_path.join(slug).join("index.html"),
+        }
+    }
+
+    /// Link to the page relative to the repository root.
+    pub fn href(self, slug: &str) -> String {
+        match self {
+            Layout::Flat => format!("./{slug}.html"),
+            Layout::Directory => format!("./{slug}/"),
+        }
+    }
+
+    fn other(self) -> Self {
+        match self {
+            Layout::Flat => Layout::Directory,
+            Layout::Directory => Layout::Flat,
+        }
+    }
+}
+
+/// Finds the existing page of a slug in either layout. Repositories may mix
+/// both when `clean_urls` was switched on after links were already created.
+pub fn find_page(repo_path: &Path, slug: &str) -> Option<(Layout, PathBuf)> {
+    [Layout::Flat, Layout::Directory]
+        .into_iter()
+        .find_map(|layout| {
+            let path = layout.page_path(repo_path, slug);
+            path.is_file().then_some((layout, path))
+        })
+}
+
+/// Writes the page of a slug in the given layout, removing a page left over
+/// in the other layout so a slug never has two pages.
+pub fn write_page(
+    repo_path: &Path,
+    slug: &str,
+    layout: Layout,
+    content: &str,
+) -> Result<(), String> {
+    let path = layout.page_path(repo_path, slug);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+    }
+    fs::write(&path, content).map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+
+    remove_page_in(repo_path, slug, layout.other())
+}
+
+/// Moves the page of a slug to another slug, keeping its layout so the page
+/// of the new slug is written in the same one.
+pub fn move_page(repo_path: &Path, from: &str, to: &str) -> Result<(), String> {
+    let Some((layout, path)) = find_page(repo_path, from) else {
+        return Ok(());
+    };
+    let content =
+        fs::read_to_string(&path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    write_page(repo_path, to, layout, &content)?;
+    remove_page(repo_path, from)
+}
+
+/// Removes the page of a slug in whichever layout it has.
+pub fn remove_page(repo_path: &Path, slug: &str) -> Result<(), String> {
+    remove_page_in(repo_path, slug, Layout::Flat)?;
+    remove_page_in(repo_path, slug, Layout::Directory)
+}
+
+fn remove_page_in(repo_path: &Path, slug: &str, layout: Layout) -> Result<(), String> {
+    let path = layout.page_path(repo_path, slug);
+    if !path.is_file() {
+        return Ok(());
+    }
+    fs::remove_file(&path).map_err(|e| format!("failed to remove {}: {e}", path.display()))?;
+
+    if layout == Layout::Directory {
+        // Only removes the directory if nothing else was put in it.
+        let _ = fs::remove_dir(repo_path.join(slug));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moved_pages_keep_their_layout() {
+        let repo_path =
+            std::env::temp_dir().join(format!("shurl-test-move-page-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&repo_path);
+        write_page(&repo_path, "old", Layout::Directory, "page").unwrap();
+        write_page(&repo_path, "flat", Layout::Flat, "flat page").unwrap();
+
+        move_page(&repo_path, "old", "docs/new").unwrap();
+        assert!(find_page(&repo_path, "old").is_none());
+        assert!(!repo_path.join("old").exists());
+        let (layout, path) = find_page(&repo_path, "docs/new").unwrap();
+        assert!(layout == Layout::Directory);
+        assert_eq!(fs::read_to_string(path).unwrap(), "page");
+
+        move_page(&repo_path, "flat", "new").unwrap();
+        assert!(find_page(&repo_path, "new").is_some_and(|(layout, _)| layout == Layout::Flat));
+        // Slugs without a page have nothing to move.
+        move_page(&repo_path, "none", "other").unwrap();
+        assert!(find_page(&repo_path, "other").is_none());
+        let _ = fs::remove_dir_all(&repo_path);
+    }
+}
@@ -0,0 +1,132 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Just enough PNG to write black and white images, uncompressed.
+
+/// Encodes a 1-bit grayscale image, `dark(x, y)` telling which pixels are
+/// black.
+pub fn encode_bilevel(width: u32, height: u32, dark: impl Fn(u32, u32) -> bool) -> Vec<u8> {
+    let mut raw = vec![];
+    for y in 0..height {
+        // Filter type of the scanline: none.
+        raw.push(0);
+        let mut byte = 0u8;
+        for x in 0..width {
+            let bit = u8::from(!dark(x, y));
+            byte |= bit << (7 - x % 8);
+            if x % 8 == 7 || x + 1 == width {
+                raw.push(byte);
+                byte = 0;
+            }
+        }
+    }
+
+    let mut header = vec![];
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // Bit depth 1, grayscale, deflate, standard filters, no interlacing.
+    header.extend_from_slice(&[1, 0, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Wraps the data in a zlib stream of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(0xffff).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        out.push(u8::from(blocks.peek().is_none()));
+        let len = block.len() as u16;
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash;
+
+    #[test]
+    fn checksums_match_known_values() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        assert_eq!(adler32(b""), 1);
+    }
+
+    #[test]
+    fn stored_blocks_split_at_their_limit() {
+        assert_eq!(hash::hex(&zlib_stored(&[])), "7801010000ffff00000001");
+        let data = vec![7; 0x1_0000];
+        let stream = zlib_stored(&data);
+        // Header, two blocks with their headers, Adler-32.
+        assert_eq!(stream.len(), 2 + 5 + 0xffff + 5 + 1 + 4);
+        assert_eq!(stream[2..7], [0, 0xff, 0xff, 0, 0]);
+        assert_eq!(stream[0xffff + 7..0xffff + 12], [1, 1, 0, 0xfe, 0xff]);
+    }
+
+    /// The same image as written by zlib and Python's `zlib.crc32`.
+    #[test]
+    fn bilevel_image_matches_reference() {
+        let png = encode_bilevel(2, 2, |x, y| x == y);
+        let expected = concat!(
+            "89504e470d0a1a0a0000000d494844520000000200000002",
+            "01000000005acd30890000000f494441547801010400fbff",
+            "00400080014400c1c3e62e9e0000000049454e44ae426082",
+        );
+        assert_eq!(hash::hex(&png), expected);
+    }
+}
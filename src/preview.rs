@@ -0,0 +1,184 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Previews of target pages from their OpenGraph and Twitter card tags, so
+//! chat apps unfurling a short link show the page it leads to.
+
+use crate::title;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Longest description kept, in characters.
+const MAX_DESCRIPTION_LENGTH: usize = 300;
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Preview {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Absolute URL of the image.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+}
+
+impl Preview {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.description.is_none() && self.image.is_none()
+    }
+}
+
+/// Fetches the target and reads the preview of the page.
+pub fn fetch(url: &str) -> Result<Preview, String> {
+    let html = title::fetch_page(url)?;
+    let base = Url::parse(url).map_err(|e| format!("invalid URL {url}: {e}"))?;
+    let preview = extract(&html, &base);
+    if preview.is_empty() {
+        return Err(format!("{url} has nothing to preview"));
+    }
+    Ok(preview)
+}
+
+/// The preview of a page at `base`. OpenGraph tags win over Twitter card
+/// tags, which win over the `<title>` and the `description` meta tag.
+pub fn extract(html: &str, base: &Url) -> Preview {
+    let tags = meta_tags(html);
+    let first = |keys: &[&str]| {
+        keys.iter().find_map(|key| {
+            tags.iter()
+                .find(|(name, content)| name == key && !content.trim().is_empty())
+                .map(|(_, content)| content.as_str())
+        })
+    };
+
+    let title = first(&["og:title", "twitter:title"])
+        .and_then(|title| title::clean(title, 200))
+        .or_else(|| title::extract(html));
+    let description = first(&["og:description", "twitter:description", "description"])
+        .and_then(|description| title::clean(description, MAX_DESCRIPTION_LENGTH));
+    // Only images a browser would load from anywhere, however the page
+    // wrote them.
+    let image = first(&[
+        "og:image:secure_url",
+        "og:image",
+        "og:image:url",
+        "twitter:image",
+    ])
+    .and_then(|image| title::clean(image, usize::MAX))
+    .and_then(|image| base.join(&image).ok())
+    .filter(|image| matches!(image.scheme(), "http" | "https"))
+    .map(String::from);
+    Preview {
+        title,
+        description,
+        image,
+    }
+}
+
+/// The `property` or `name` of every `<meta>` tag with its `content`, the
+/// name lowercase. Entities in the content are left for the caller.
+fn meta_tags(html: &str) -> Vec<(String, String)> {
+    // Tags are ASCII, so positions in the lowercase copy are the same.
+    let lower = html.to_ascii_lowercase();
+    let mut tags = vec![];
+    let mut from = 0;
+    while let Some(open) = lower[from..].find("<meta").map(|i| from + i) {
+        let Some(close) = lower[open..].find('>').map(|i| open + i) else {
+            break;
+        };
+        let attributes = parse_attributes(&html[open + 5..close]);
+        let name = attributes
+            .iter()
+            .find(|(key, _)| key == "property" || key == "name")
+            .map(|(_, value)| value.to_ascii_lowercase());
+        let content = attributes
+            .iter()
+            .find(|(key, _)| key == "content")
+            .map(|(_, value)| value.clone());
+        if let (Some(name), Some(content)) = (name, content) {
+            tags.push((name, content));
+        }
+        from = close;
+    }
+    tags
+}
+
+/// Attributes of a tag, names lowercase, values quoted with `"` or `'` or
+/// not at all.
+pub(crate) fn parse_attributes(text: &str) -> Vec<(String, String)> {
+    let mut attributes = vec![];
+    let mut rest = text.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+    while !rest.is_empty() {
+        let name_end = rest
+            .find(|c: char| c == '=' || c.is_whitespace() || c == '/')
+            .unwrap_or(rest.len());
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+        let value = match rest.strip_prefix('=') {
+            Some(after) => {
+                let after = after.trim_start();
+                let (value, len) = match after.chars().next() {
+                    Some(quote @ ('"' | '\'')) => match after[1..].find(quote) {
+                        Some(end) => (&after[1..end + 1], end + 2),
+                        None => (&after[1..], after.len()),
+                    },
+                    _ => {
+                        let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                        (&after[..end], end)
+                    }
+                };
+                rest = &after[len..];
+                value.to_string()
+            }
+            None => String::new(),
+        };
+        if !name.is_empty() {
+            attributes.push((name, value));
+        }
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+    }
+    attributes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_graph_wins_and_images_are_resolved() {
+        let html = r#"<head><title>Page</title>
+            <meta name="twitter:title" content="Card title">
+            <META property='og:title' content='Rust &amp; You'/>
+            <meta name=description content="About &quot;it&quot;">
+            <meta property="og:image" content="/img/cover.png" />
+            </head>"#;
+        let base = Url::parse("https://example.com/posts/1").unwrap();
+        let preview = extract(html, &base);
+        assert_eq!(preview.title.as_deref(), Some("Rust & You"));
+        assert_eq!(preview.description.as_deref(), Some("About \"it\""));
+        assert_eq!(
+            preview.image.as_deref(),
+            Some("https://example.com/img/cover.png")
+        );
+
+        let preview = extract(
+            r#"<title>Plain</title><meta property="og:image" content="javascript:alert(1)">"#,
+            &base,
+        );
+        assert_eq!(preview.title.as_deref(), Some("Plain"));
+        assert_eq!(preview.image, None);
+    }
+}
@@ -0,0 +1,171 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! SLSA provenance of the generated site: an in-toto statement of which
+//! files a build produced, from which sources and with which version of
+//! shurl, so the deployed site can be checked against the repository.
+//!
+//! The statement is committed along with what it describes, so it can't name
+//! its own commit. It names the commit the build was made on top of, and the
+//! sources by their digests.
+
+use crate::bundle::BUNDLES_FILE;
+use crate::config::ShurlConfig;
+use crate::date;
+use crate::federation::FEDERATION_FILE;
+use crate::hash;
+use crate::json::Value;
+use crate::manifest::MANIFEST_FILE;
+use crate::repo_config::REPO_CONFIG_FILE;
+use crate::trash::TRASH_DIR;
+use clap::ValueEnum;
+use std::fs;
+use std::path::Path;
+use toml::value::Datetime;
+use url::Url;
+
+pub const PROVENANCE_FILE: &str = "provenance.json";
+const STATEMENT_TYPE: &str = "https://in-toto.io/Statement/v1";
+const PREDICATE_TYPE: &str = "https://slsa.dev/provenance/v1";
+const BUILD_TYPE: &str = "https://github.com/mdgaziur/shurl/build/v1";
+const BUILDER_ID: &str = "https://github.com/mdgaziur/shurl";
+
+/// Where the sources of a build are kept in git.
+pub struct Source {
+    /// URL of the `origin` remote.
+    pub repository: Option<String>,
+    /// Commit the build was made on top of, none in a repository without
+    /// commits.
+    pub base_commit: Option<String>,
+}
+
+/// Writes the provenance of a build that generated `files` and started at
+/// `started`.
+pub fn write(
+    repo_path: &Path,
+    cfg: &ShurlConfig,
+    source: &Source,
+    files: &[String],
+    started: &Datetime,
+) -> Result<(), String> {
+    let mut subjects = vec![];
+    for file in files {
+        subjects.push(file_digest(repo_path, file)?);
+    }
+
+    let mut parameters = Value::object();
+    parameters
+        .set(
+            "backends",
+            cfg.backends
+                .iter()
+                .filter_map(|backend| backend.to_possible_value())
+                .map(|value| Value::from(value.get_name()))
+                .collect::<Vec<_>>(),
+        )
+        .set("clean_urls", cfg.clean_urls)
+        .set("base_url", cfg.base_url.as_deref());
+
+    let mut dependencies = vec![];
+    if let Some(commit) = &source.base_commit {
+        let mut digest = Value::object();
+        digest.set("gitCommit", commit.as_str());
+        let mut dependency = Value::object();
+        dependency
+            .set(
+                "uri",
+                source
+                    .repository
+                    .as_deref()
+                    .map(|url| format!("git+{}", without_credentials(url))),
+            )
+            .set("digest", digest);
+        dependencies.push(dependency);
+    }
+    let sources = [
+        MANIFEST_FILE.to_string(),
+        REPO_CONFIG_FILE.to_string(),
+        format!("{TRASH_DIR}/links.toml"),
+        BUNDLES_FILE.to_string(),
+        FEDERATION_FILE.to_string(),
+    ];
+    for file in sources {
+        if repo_path.join(&file).is_file() {
+            dependencies.push(file_digest(repo_path, &file)?);
+        }
+    }
+
+    let mut definition = Value::object();
+    definition
+        .set("buildType", BUILD_TYPE)
+        .set("externalParameters", parameters)
+        .set("resolvedDependencies", dependencies);
+
+    let mut version = Value::object();
+    version.set("shurl", env!("CARGO_PKG_VERSION"));
+    let mut builder = Value::object();
+    builder.set("id", BUILDER_ID).set("version", version);
+    let mut metadata = Value::object();
+    metadata
+        .set("startedOn", started.to_string())
+        .set("finishedOn", date::now().to_string());
+    let mut run_details = Value::object();
+    run_details
+        .set("builder", builder)
+        .set("metadata", metadata);
+
+    let mut predicate = Value::object();
+    predicate
+        .set("buildDefinition", definition)
+        .set("runDetails", run_details);
+    let mut statement = Value::object();
+    statement
+        .set("_type", STATEMENT_TYPE)
+        .set("subject", subjects)
+        .set("predicateType", PREDICATE_TYPE)
+        .set("predicate", predicate);
+
+    fs::write(
+        repo_path.join(PROVENANCE_FILE),
+        statement.to_pretty_string() + "\n",
+    )
+    .map_err(|e| format!("failed to write {PROVENANCE_FILE}: {e}"))
+}
+
+/// Remote URL without a token or password in it, which HTTPS remotes often
+/// have. Other remotes, e.g. `git@github.com:owner/repo.git`, are left as
+/// they are.
+fn without_credentials(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut url) => {
+            let _ = url.set_username("");
+            let _ = url.set_password(None);
+            url.to_string()
+        }
+        Err(_) => url.to_string(),
+    }
+}
+
+/// Resource descriptor of a file of the repository with its SHA-256 digest.
+fn file_digest(repo_path: &Path, file: &str) -> Result<Value, String> {
+    let content =
+        fs::read(repo_path.join(file)).map_err(|e| format!("failed to read {file}: {e}"))?;
+    let mut digest = Value::object();
+    digest.set("sha256", hash::hex(&hash::sha256(&content)));
+    let mut descriptor = Value::object();
+    descriptor.set("name", file).set("digest", digest);
+    Ok(descriptor)
+}
@@ -0,0 +1,648 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! QR codes of short URLs, in byte mode with medium error correction,
+//! following ISO/IEC 18004.
+
+use crate::config::{QrFormat, ShurlConfig};
+use crate::manifest::Manifest;
+use crate::png;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where `qr_assets` saves the codes of every link, relative to the
+/// repository root.
+pub const QR_DIR: &str = "assets/qr";
+
+/// Error correction codewords per block for medium error correction, by
+/// version.
+const ECC_CODEWORDS_PER_BLOCK: [usize; 41] = [
+    0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26, 26, 28, 28,
+    28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28,
+];
+/// Error correction blocks for medium error correction, by version.
+const ECC_BLOCKS: [usize; 41] = [
+    0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21, 23,
+    25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49,
+];
+/// Light modules around the code, scanners need them to find it.
+const QUIET_ZONE: usize = 4;
+/// Pixels per module in PNG images.
+const PNG_SCALE: usize = 8;
+
+pub struct QrCode {
+    size: usize,
+    modules: Vec<bool>,
+    /// Modules of the finder, timing, alignment, format and version
+    /// patterns, which data and masks leave alone.
+    function: Vec<bool>,
+}
+
+impl QrCode {
+    /// Encodes the text in the smallest version it fits in.
+    pub fn encode(text: &str) -> Result<Self, String> {
+        let data = text.as_bytes();
+        let count_bits = |version: usize| if version <= 9 { 8 } else { 16 };
+        let version = (1..=40)
+            .find(|&version| {
+                4 + count_bits(version) + data.len() * 8 <= data_codewords(version) * 8
+            })
+            .ok_or_else(|| format!("`{text}` is too long for a QR code"))?;
+
+        let capacity = data_codewords(version) * 8;
+        let mut bits = vec![];
+        push_bits(&mut bits, 0b0100, 4);
+        push_bits(&mut bits, data.len() as u32, count_bits(version));
+        for &byte in data {
+            push_bits(&mut bits, byte.into(), 8);
+        }
+        let terminator = (capacity - bits.len()).min(4);
+        push_bits(&mut bits, 0, terminator);
+        let padding = bits.len().next_multiple_of(8) - bits.len();
+        push_bits(&mut bits, 0, padding);
+        for pad in [0xec, 0x11].into_iter().cycle() {
+            if bits.len() >= capacity {
+                break;
+            }
+            push_bits(&mut bits, pad, 8);
+        }
+        let bytes: Vec<u8> = bits
+            .chunks(8)
+            .map(|byte| byte.iter().fold(0, |acc, &bit| (acc << 1) | u8::from(bit)))
+            .collect();
+
+        let mut code = Self::with_function_patterns(version);
+        code.draw_codewords(&add_error_correction(&bytes, version));
+
+        // Masks only change data modules, so each is tried and undone again.
+        let mask = (0..8)
+            .min_by_key(|&mask| {
+                code.apply_mask(mask);
+                code.draw_format(mask);
+                let penalty = code.penalty();
+                code.apply_mask(mask);
+                penalty
+            })
+            .unwrap_or(0);
+        code.apply_mask(mask);
+        code.draw_format(mask);
+        Ok(code)
+    }
+
+    /// Renders the code with half block characters, two modules per line,
+    /// light modules lit so it works on dark terminals.
+    pub fn to_terminal(&self) -> String {
+        let border = 2;
+        let range = -border..self.size as isize + border;
+        let mut out = String::new();
+        for y in range.clone().step_by(2) {
+            for x in range.clone() {
+                out.push(match (self.is_dark(x, y), self.is_dark(x, y + 1)) {
+                    (false, false) => '█',
+                    (false, true) => '▀',
+                    (true, false) => '▄',
+                    (true, true) => ' ',
+                });
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    pub fn to_svg(&self) -> String {
+        let width = self.size + 2 * QUIET_ZONE;
+        let mut path = String::new();
+        for y in 0..self.size {
+            for x in 0..self.size {
+                if self.modules[y * self.size + x] {
+                    path.push_str(&format!("M{},{}h1v1h-1z", x + QUIET_ZONE, y + QUIET_ZONE));
+                }
+            }
+        }
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {width} {width}\" shape-rendering=\"crispEdges\">\n\
+             <rect width=\"{width}\" height=\"{width}\" fill=\"#fff\"/>\n\
+             <path d=\"{path}\" fill=\"#000\"/>\n\
+             </svg>\n"
+        )
+    }
+
+    pub fn to_png(&self) -> Vec<u8> {
+        let width = ((self.size + 2 * QUIET_ZONE) * PNG_SCALE) as u32;
+        png::encode_bilevel(width, width, |x, y| {
+            let module = |pixel: u32| (pixel as usize / PNG_SCALE) as isize - QUIET_ZONE as isize;
+            self.is_dark(module(x), module(y))
+        })
+    }
+
+    pub fn to_format(&self, format: QrFormat) -> Vec<u8> {
+        match format {
+            QrFormat::Png => self.to_png(),
+            QrFormat::Svg => self.to_svg().into_bytes(),
+        }
+    }
+
+    /// Whether a module is dark, everything outside of the code is light.
+    fn is_dark(&self, x: isize, y: isize) -> bool {
+        let range = 0..self.size as isize;
+        range.contains(&x)
+            && range.contains(&y)
+            && self.modules[y as usize * self.size + x as usize]
+    }
+
+    fn get(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y * self.size + x] = dark;
+        self.function[y * self.size + x] = true;
+    }
+
+    fn with_function_patterns(version: usize) -> Self {
+        let size = version * 4 + 17;
+        let mut code = Self {
+            size,
+            modules: vec![false; size * size],
+            function: vec![false; size * size],
+        };
+
+        for i in 0..size {
+            code.set_function(6, i, i % 2 == 0);
+            code.set_function(i, 6, i % 2 == 0);
+        }
+
+        // Finder patterns with their separators, in three corners.
+        for (cx, cy) in [(3, 3), (size - 4, 3), (3, size - 4)] {
+            for dy in -4isize..=4 {
+                for dx in -4isize..=4 {
+                    let (x, y) = (cx as isize + dx, cy as isize + dy);
+                    if (0..size as isize).contains(&x) && (0..size as isize).contains(&y) {
+                        let distance = dx.abs().max(dy.abs());
+                        code.set_function(x as usize, y as usize, distance != 2 && distance != 4);
+                    }
+                }
+            }
+        }
+
+        let positions = alignment_positions(version);
+        let last = positions.len().saturating_sub(1);
+        for (i, &x) in positions.iter().enumerate() {
+            for (j, &y) in positions.iter().enumerate() {
+                // Those would overlap the finder patterns.
+                if (i == 0 && (j == 0 || j == last)) || (i == last && j == 0) {
+                    continue;
+                }
+                for dy in -2isize..=2 {
+                    for dx in -2isize..=2 {
+                        let (x, y) = ((x as isize + dx) as usize, (y as isize + dy) as usize);
+                        code.set_function(x, y, dx.abs().max(dy.abs()) != 1);
+                    }
+                }
+            }
+        }
+
+        // Reserves the format modules, they're drawn for real once the mask
+        // is chosen.
+        code.draw_format(0);
+
+        if version >= 7 {
+            let mut remainder = version as u32;
+            for _ in 0..12 {
+                remainder = (remainder << 1) ^ ((remainder >> 11) * 0x1f25);
+            }
+            let bits = (version as u32) << 12 | remainder;
+            for i in 0..18 {
+                let dark = (bits >> i) & 1 != 0;
+                let (a, b) = (size - 11 + i % 3, i / 3);
+                code.set_function(a, b, dark);
+                code.set_function(b, a, dark);
+            }
+        }
+        code
+    }
+
+    fn draw_format(&mut self, mask: u32) {
+        // The error correction level is medium, whose bits are 0.
+        let data = mask;
+        let mut remainder = data;
+        for _ in 0..10 {
+            remainder = (remainder << 1) ^ ((remainder >> 9) * 0x537);
+        }
+        let bits = (data << 10 | remainder) ^ 0x5412;
+        let bit = |i: usize| (bits >> i) & 1 != 0;
+
+        let size = self.size;
+        for i in 0..6 {
+            self.set_function(8, i, bit(i));
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(i));
+        }
+
+        for i in 0..8 {
+            self.set_function(size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set_function(8, size - 15 + i, bit(i));
+        }
+        self.set_function(8, size - 8, true);
+    }
+
+    /// Places the codewords in the zigzag of two module wide columns, from
+    /// the bottom right corner.
+    fn draw_codewords(&mut self, codewords: &[u8]) {
+        let size = self.size;
+        let mut i = 0;
+        let mut right = size - 1;
+        loop {
+            if right == 6 {
+                right = 5;
+            }
+            for vertical in 0..size {
+                for j in 0..2 {
+                    let x = right - j;
+                    let upward = (right + 1) & 2 == 0;
+                    let y = if upward {
+                        size - 1 - vertical
+                    } else {
+                        vertical
+                    };
+                    if !self.function[y * size + x] && i < codewords.len() * 8 {
+                        self.modules[y * size + x] = (codewords[i / 8] >> (7 - i % 8)) & 1 != 0;
+                        i += 1;
+                    }
+                }
+            }
+            if right < 2 {
+                break;
+            }
+            right -= 2;
+        }
+    }
+
+    /// Flips the data modules selected by the mask, so applying it twice
+    /// undoes it.
+    fn apply_mask(&mut self, mask: u32) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let flip = match mask {
+                    0 => (x + y) % 2 == 0,
+                    1 => y % 2 == 0,
+                    2 => x % 3 == 0,
+                    3 => (x + y) % 3 == 0,
+                    4 => (x / 3 + y / 2) % 2 == 0,
+                    5 => x * y % 2 + x * y % 3 == 0,
+                    6 => (x * y % 2 + x * y % 3) % 2 == 0,
+                    _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+                };
+                if flip && !self.function[y * self.size + x] {
+                    self.modules[y * self.size + x] ^= true;
+                }
+            }
+        }
+    }
+
+    /// How hard the code is to scan, for picking the mask.
+    fn penalty(&self) -> usize {
+        let size = self.size;
+        let mut penalty = 0;
+        let lines = |horizontal: bool| {
+            (0..size).map(move |a| {
+                (0..size)
+                    .map(|b| {
+                        if horizontal {
+                            self.get(b, a)
+                        } else {
+                            self.get(a, b)
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            })
+        };
+        const FINDER_LIKE: [bool; 11] = [
+            true, false, true, true, true, false, true, false, false, false, false,
+        ];
+
+        for line in lines(true).chain(lines(false)) {
+            // Runs of five or more modules of the same color.
+            let mut run = 1;
+            for i in 1..=size {
+                if i < size && line[i] == line[i - 1] {
+                    run += 1;
+                    continue;
+                }
+                if run >= 5 {
+                    penalty += run - 2;
+                }
+                run = 1;
+            }
+            // Patterns that look like a finder pattern.
+            for window in line.windows(11) {
+                if window == FINDER_LIKE || window.iter().rev().eq(FINDER_LIKE.iter()) {
+                    penalty += 40;
+                }
+            }
+        }
+
+        // Blocks of 2x2 modules of the same color.
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let color = self.get(x, y);
+                if self.get(x + 1, y) == color
+                    && self.get(x, y + 1) == color
+                    && self.get(x + 1, y + 1) == color
+                {
+                    penalty += 3;
+                }
+            }
+        }
+
+        // Imbalance of dark and light modules.
+        let total = size * size;
+        let dark = self.modules.iter().filter(|&&dark| dark).count();
+        let k = (dark * 20)
+            .abs_diff(total * 10)
+            .div_ceil(total)
+            .saturating_sub(1);
+        penalty + k * 10
+    }
+}
+
+fn push_bits(bits: &mut Vec<bool>, value: u32, count: usize) {
+    bits.extend((0..count).rev().map(|i| (value >> i) & 1 != 0));
+}
+
+/// Modules available for data and error correction in a version.
+fn raw_data_modules(version: usize) -> usize {
+    let mut modules = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let alignments = version / 7 + 2;
+        modules -= (25 * alignments - 10) * alignments - 55;
+        if version >= 7 {
+            modules -= 36;
+        }
+    }
+    modules
+}
+
+fn data_codewords(version: usize) -> usize {
+    raw_data_modules(version) / 8 - ECC_CODEWORDS_PER_BLOCK[version] * ECC_BLOCKS[version]
+}
+
+fn alignment_positions(version: usize) -> Vec<usize> {
+    if version == 1 {
+        return vec![];
+    }
+    let count = version / 7 + 2;
+    let step = (version * 8 + count * 3 + 5) / (count * 4 - 4) * 2;
+    let last = version * 4 + 17 - 7;
+    let mut positions = vec![6];
+    positions.extend((0..count - 1).rev().map(|i| last - i * step));
+    positions
+}
+
+/// Splits the data into blocks, appends the error correction codewords of
+/// each and interleaves them.
+fn add_error_correction(data: &[u8], version: usize) -> Vec<u8> {
+    let blocks_count = ECC_BLOCKS[version];
+    let ecc_len = ECC_CODEWORDS_PER_BLOCK[version];
+    let raw_codewords = raw_data_modules(version) / 8;
+    let short_blocks = blocks_count - raw_codewords % blocks_count;
+    let short_len = raw_codewords / blocks_count;
+
+    let divisor = reed_solomon_divisor(ecc_len);
+    let mut blocks = vec![];
+    let mut rest = data;
+    for i in 0..blocks_count {
+        let len = short_len - ecc_len + usize::from(i >= short_blocks);
+        let (block_data, remaining) = rest.split_at(len);
+        rest = remaining;
+        let mut block = block_data.to_vec();
+        // Short blocks get a placeholder so all blocks line up.
+        if i < short_blocks {
+            block.push(0);
+        }
+        block.extend(reed_solomon_remainder(block_data, &divisor));
+        blocks.push(block);
+    }
+
+    let mut out = vec![];
+    for i in 0..blocks[0].len() {
+        for (j, block) in blocks.iter().enumerate() {
+            if i != short_len - ecc_len || j >= short_blocks {
+                out.push(block[i]);
+            }
+        }
+    }
+    out
+}
+
+fn reed_solomon_divisor(degree: usize) -> Vec<u8> {
+    let mut divisor = vec![0; degree];
+    divisor[degree - 1] = 1;
+    let mut root = 1;
+    for _ in 0..degree {
+        for j in 0..degree {
+            divisor[j] = gf_multiply(divisor[j], root);
+            if j + 1 < degree {
+                divisor[j] ^= divisor[j + 1];
+            }
+        }
+        root = gf_multiply(root, 2);
+    }
+    divisor
+}
+
+fn reed_solomon_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut remainder = vec![0; divisor.len()];
+    for &byte in data {
+        let factor = byte ^ remainder.remove(0);
+        remainder.push(0);
+        for (r, &d) in remainder.iter_mut().zip(divisor) {
+            *r ^= gf_multiply(d, factor);
+        }
+    }
+    remainder
+}
+
+/// Multiplication in GF(2^8) modulo x^8 + x^4 + x^3 + x^2 + 1.
+fn gf_multiply(x: u8, y: u8) -> u8 {
+    let mut z = 0u32;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x11d);
+        z ^= ((u32::from(y) >> i) & 1) * u32::from(x);
+    }
+    z as u8
+}
+
+/// Saves the code of every link's short URL under [`QR_DIR`] in the formats
+/// of `qr_assets`, and removes codes of links that no longer exist. Returns
+/// the paths written, relative to the repository root.
+pub fn write_assets(
+    repo_path: &Path,
+    cfg: &ShurlConfig,
+    manifest: &Manifest,
+) -> Result<Vec<String>, String> {
+    let mut written = vec![];
+    if !cfg.qr_assets.is_empty() {
+        for link in &manifest.links {
+            let Some(short_url) = cfg.short_url(&link.slug) else {
+                break;
+            };
+            let code = QrCode::encode(&short_url)?;
+            for &format in &cfg.qr_assets {
+                let path = format!("{QR_DIR}/{}.{}", link.slug, format.extension());
+                let full_path = repo_path.join(&path);
+                let content = code.to_format(format);
+                if fs::read(&full_path).ok().as_deref() != Some(content.as_slice()) {
+                    if let Some(parent) = full_path.parent() {
+                        fs::create_dir_all(parent)
+                            .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+                    }
+                    fs::write(&full_path, content)
+                        .map_err(|e| format!("failed to write {path}: {e}"))?;
+                }
+                written.push(path);
+            }
+        }
+    }
+
+    let keep: HashSet<PathBuf> = written.iter().map(|path| repo_path.join(path)).collect();
+    remove_stale(&repo_path.join(QR_DIR), &keep)?;
+    Ok(written)
+}
+
+/// Removes files not in `keep` and directories left empty.
+fn remove_stale(dir: &Path, keep: &HashSet<PathBuf>) -> Result<(), String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(());
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            remove_stale(&path, keep)?;
+        } else if !keep.contains(&path) {
+            fs::remove_file(&path)
+                .map_err(|e| format!("failed to remove {}: {e}", path.display()))?;
+        }
+    }
+    if fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_none()) {
+        fs::remove_dir(dir).map_err(|e| format!("failed to remove {}: {e}", dir.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The example of ISO/IEC 18004 Annex I, `01234567` as version 1-M.
+    #[test]
+    fn error_correction_matches_the_spec_example() {
+        let data = [
+            0x10, 0x20, 0x0c, 0x56, 0x61, 0x80, 0xec, 0x11, 0xec, 0x11, 0xec, 0x11, 0xec, 0x11,
+            0xec, 0x11,
+        ];
+        assert_eq!(
+            reed_solomon_remainder(&data, &reed_solomon_divisor(10)),
+            [0xa5, 0x24, 0xd4, 0xc1, 0xed, 0x36, 0xc7, 0x87, 0x2c, 0x55]
+        );
+        // One block, so the codewords are the data and then the remainder.
+        let codewords = add_error_correction(&data, 1);
+        assert_eq!(codewords[..16], data);
+        assert_eq!(
+            codewords[16..],
+            [0xa5, 0x24, 0xd4, 0xc1, 0xed, 0x36, 0xc7, 0x87, 0x2c, 0x55]
+        );
+    }
+
+    #[test]
+    fn capacities_and_alignment_match_the_spec_tables() {
+        assert_eq!(data_codewords(1), 16);
+        assert_eq!(data_codewords(3), 44);
+        assert_eq!(data_codewords(10), 216);
+        assert_eq!(data_codewords(40), 2334);
+        assert_eq!(alignment_positions(1), Vec::<usize>::new());
+        assert_eq!(alignment_positions(7), [6, 22, 38]);
+        assert_eq!(alignment_positions(32), [6, 34, 60, 86, 112, 138]);
+        assert_eq!(alignment_positions(36), [6, 24, 50, 76, 102, 128, 154]);
+        assert_eq!(alignment_positions(39), [6, 26, 54, 82, 110, 138, 166]);
+    }
+
+    /// A short URL, needing version 3 and getting mask 2. Checked by
+    /// decoding it: format bits `101111001111100`, the Reed-Solomon
+    /// remainder and the text.
+    #[test]
+    fn short_url_matches_golden_matrix() {
+        let expected = [
+            "#######..#..#..##.###.#######",
+            "#.....#..####.....#.#.#.....#",
+            "#.###.#.###...#.#..##.#.###.#",
+            "#.###.#.##..#...##.#..#.###.#",
+            "#.###.#.#.#.####..##..#.###.#",
+            "#.....#.###########.#.#.....#",
+            "#######.#.#.#.#.#.#.#.#######",
+            "........#.####.#..#.#........",
+            "#.#####..##...##.#.#..#####..",
+            "##...#...#..#..##.#######...#",
+            ".#.##.#.#..##....##.##..#....",
+            "###.....#.###.#.#..##....#.#.",
+            ".....##.#.#.....##.#...#.##..",
+            ".#.#......######..#######...#",
+            "#....###.##.#####...#.#####..",
+            "###......#####.#..##...##..#.",
+            ".#....##.#....##.#.......##..",
+            "##.#.#...##.#..##.###.###.#.#",
+            "#.###.##.............#....#..",
+            "#.####.##.....#.#..###.#...#.",
+            "#..##.#...##....#.#######.###",
+            "........#..#####.##.#...#####",
+            "#######..#.#.#####.##.#.###..",
+            "#.....#.###.##.#.##.#...#...#",
+            "#.###.#.#.###.##....#####.###",
+            "#.###.#.##.##..##.##.....##..",
+            "#.###.#.##..#.....##.##.####.",
+            "#.....#....#..#.#...#.##.#.#.",
+            "#######.##......#.##.#..###..",
+        ];
+        let code = QrCode::encode("https://s.example.com/abcde").unwrap();
+        let rows: Vec<String> = (0..code.size)
+            .map(|y| {
+                (0..code.size)
+                    .map(|x| if code.get(x, y) { '#' } else { '.' })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(rows, expected);
+    }
+
+    #[test]
+    fn smallest_version_is_picked() {
+        assert_eq!(QrCode::encode(&"a".repeat(14)).unwrap().size, 21);
+        assert_eq!(QrCode::encode(&"a".repeat(15)).unwrap().size, 25);
+        // Through the versions with seven alignment patterns in a row.
+        for len in (1600..=2331).step_by(90) {
+            assert!(QrCode::encode(&"a".repeat(len)).is_ok(), "{len}");
+        }
+        assert!(QrCode::encode(&"a".repeat(2331)).is_ok());
+        assert!(QrCode::encode(&"a".repeat(2332)).is_err());
+    }
+}
@@ -0,0 +1,625 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A small regular expression engine for rewriting targets.
+//!
+//! Supports literals, `.`, classes such as `[a-z]` and `[^/]`, `\d`, `\w`
+//! and `\s`, groups `(...)` and `(?:...)`, alternation, the quantifiers
+//! `*`, `+`, `?` and `{n,m}` (lazy with a trailing `?`), and the anchors `^`
+//! and `$`. Matching is leftmost-first like most engines, and backtracking
+//! remembers failed states so no pattern takes exponential time: each
+//! instruction is tried at most once per position of the text.
+
+/// Upper bound of `{n,m}`, which is expanded into copies of the group.
+const MAX_REPEAT: u32 = 1000;
+
+/// Most instructions a pattern compiles to. Nested repetitions multiply, and
+/// the states remembered while matching grow with the program.
+const MAX_PROGRAM: usize = 10_000;
+
+pub struct Regex {
+    program: Vec<Inst>,
+    /// Capture groups, including the whole match as group 0.
+    groups: usize,
+}
+
+enum Inst {
+    Char(char),
+    Any,
+    Class(Class),
+    /// Tries the first branch, then the second.
+    Split(usize, usize),
+    Jump(usize),
+    Save(usize),
+    Start,
+    End,
+    Match,
+}
+
+#[derive(Clone)]
+struct Class {
+    negated: bool,
+    items: Vec<ClassItem>,
+}
+
+#[derive(Clone)]
+enum ClassItem {
+    Range(char, char),
+    Class(Class),
+}
+
+impl Class {
+    fn matches(&self, c: char) -> bool {
+        let found = self.items.iter().any(|item| match item {
+            ClassItem::Range(start, end) => (*start..=*end).contains(&c),
+            ClassItem::Class(class) => class.matches(c),
+        });
+        found != self.negated
+    }
+}
+
+enum Node {
+    Empty,
+    Char(char),
+    Any,
+    Class(Class),
+    Start,
+    End,
+    Group(Box<Node>, Option<usize>),
+    Concat(Vec<Node>),
+    Alternation(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: u32,
+        max: Option<u32>,
+        greedy: bool,
+    },
+}
+
+enum Job {
+    Explore(usize, usize),
+    Restore(usize, Option<usize>),
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+            groups: 1,
+        };
+        let node = parser.alternation()?;
+        if parser.pos < parser.chars.len() {
+            return Err(format!("invalid regex `{pattern}`: unmatched `)`"));
+        }
+
+        let mut program = vec![Inst::Save(0)];
+        compile(&node, &mut program).map_err(|()| {
+            format!("invalid regex `{pattern}`: too large, repeat fewer or smaller groups")
+        })?;
+        program.push(Inst::Save(1));
+        program.push(Inst::Match);
+        Ok(Self {
+            program,
+            groups: parser.groups,
+        })
+    }
+
+    /// Byte ranges of the groups of the first match, `None` for groups
+    /// that didn't take part in it.
+    pub fn captures(&self, text: &str) -> Option<Vec<Option<(usize, usize)>>> {
+        let chars: Vec<char> = text.chars().collect();
+        let offsets: Vec<usize> = text
+            .char_indices()
+            .map(|(offset, _)| offset)
+            .chain([text.len()])
+            .collect();
+
+        // Whether a state leads to a match doesn't depend on where the
+        // search started, so failed states stay failed for every start.
+        let mut visited = vec![false; self.program.len() * (chars.len() + 1)];
+        for start in 0..=chars.len() {
+            let mut slots = vec![None; self.groups * 2];
+            if self.run(&chars, start, &mut visited, &mut slots) {
+                return Some(
+                    slots
+                        .chunks(2)
+                        .map(|slot| match (slot[0], slot[1]) {
+                            (Some(start), Some(end)) => Some((offsets[start], offsets[end])),
+                            _ => None,
+                        })
+                        .collect(),
+                );
+            }
+        }
+        None
+    }
+
+    /// Replaces the first match, `$1` or `${1}` in the replacement being the
+    /// text of a group and `$$` a dollar sign. `None` if nothing matches.
+    pub fn replace(&self, text: &str, replacement: &str) -> Option<String> {
+        let captures = self.captures(text)?;
+        let group = |index: usize| match captures.get(index) {
+            Some(Some((start, end))) => &text[*start..*end],
+            _ => "",
+        };
+
+        let (start, end) = captures[0]?;
+        let mut out = text[..start].to_string();
+        let mut rest = replacement;
+        while let Some(dollar) = rest.find('$') {
+            out.push_str(&rest[..dollar]);
+            rest = &rest[dollar + 1..];
+            if let Some(after) = rest.strip_prefix('$') {
+                out.push('$');
+                rest = after;
+            } else if let Some((name, after)) = rest
+                .strip_prefix('{')
+                .and_then(|braced| braced.split_once('}'))
+                .filter(|(name, _)| name.parse::<usize>().is_ok())
+            {
+                out.push_str(group(name.parse().unwrap_or_default()));
+                rest = after;
+            } else {
+                let digits =
+                    rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+                match rest[..digits].parse() {
+                    Ok(index) => out.push_str(group(index)),
+                    Err(_) => out.push('$'),
+                }
+                rest = &rest[digits..];
+            }
+        }
+        out.push_str(rest);
+        out.push_str(&text[end..]);
+        Some(out)
+    }
+
+    fn run(
+        &self,
+        input: &[char],
+        start: usize,
+        visited: &mut [bool],
+        slots: &mut [Option<usize>],
+    ) -> bool {
+        let mut jobs = vec![Job::Explore(0, start)];
+        while let Some(job) = jobs.pop() {
+            let (mut pc, mut pos) = match job {
+                Job::Explore(pc, pos) => (pc, pos),
+                Job::Restore(slot, value) => {
+                    slots[slot] = value;
+                    continue;
+                }
+            };
+            loop {
+                let state = pc * (input.len() + 1) + pos;
+                if visited[state] {
+                    break;
+                }
+                visited[state] = true;
+
+                match &self.program[pc] {
+                    Inst::Char(c) if input.get(pos) == Some(c) => {
+                        pc += 1;
+                        pos += 1;
+                    }
+                    Inst::Any if pos < input.len() => {
+                        pc += 1;
+                        pos += 1;
+                    }
+                    Inst::Class(class) if input.get(pos).is_some_and(|&c| class.matches(c)) => {
+                        pc += 1;
+                        pos += 1;
+                    }
+                    Inst::Split(first, second) => {
+                        jobs.push(Job::Explore(*second, pos));
+                        pc = *first;
+                    }
+                    Inst::Jump(target) => pc = *target,
+                    Inst::Save(slot) => {
+                        jobs.push(Job::Restore(*slot, slots[*slot]));
+                        slots[*slot] = Some(pos);
+                        pc += 1;
+                    }
+                    Inst::Start if pos == 0 => pc += 1,
+                    Inst::End if pos == input.len() => pc += 1,
+                    Inst::Match => return true,
+                    _ => break,
+                }
+            }
+        }
+        false
+    }
+}
+
+/// Fails once the program grows past [`MAX_PROGRAM`].
+fn compile(node: &Node, program: &mut Vec<Inst>) -> Result<(), ()> {
+    if program.len() > MAX_PROGRAM {
+        return Err(());
+    }
+    match node {
+        Node::Empty => {}
+        Node::Char(c) => program.push(Inst::Char(*c)),
+        Node::Any => program.push(Inst::Any),
+        Node::Class(class) => program.push(Inst::Class(class.clone())),
+        Node::Start => program.push(Inst::Start),
+        Node::End => program.push(Inst::End),
+        Node::Group(node, index) => match index {
+            Some(index) => {
+                program.push(Inst::Save(index * 2));
+                compile(node, program)?;
+                program.push(Inst::Save(index * 2 + 1));
+            }
+            None => compile(node, program)?,
+        },
+        Node::Concat(nodes) => {
+            for node in nodes {
+                compile(node, program)?;
+            }
+        }
+        Node::Alternation(nodes) => {
+            let mut jumps = vec![];
+            for (i, node) in nodes.iter().enumerate() {
+                if i + 1 == nodes.len() {
+                    compile(node, program)?;
+                    break;
+                }
+                let split = program.len();
+                program.push(Inst::Split(split + 1, 0));
+                compile(node, program)?;
+                jumps.push(program.len());
+                program.push(Inst::Jump(0));
+                let next = program.len();
+                program[split] = Inst::Split(split + 1, next);
+            }
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Inst::Jump(end);
+            }
+        }
+        Node::Repeat {
+            node,
+            min,
+            max,
+            greedy,
+        } => {
+            let split = |body: usize, exit: usize| {
+                if *greedy {
+                    Inst::Split(body, exit)
+                } else {
+                    Inst::Split(exit, body)
+                }
+            };
+            for _ in 0..*min {
+                compile(node, program)?;
+            }
+            match max {
+                None => {
+                    let start = program.len();
+                    program.push(Inst::Jump(0));
+                    compile(node, program)?;
+                    program.push(Inst::Jump(start));
+                    let exit = program.len();
+                    program[start] = split(start + 1, exit);
+                }
+                Some(max) => {
+                    let mut splits = vec![];
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        program.push(Inst::Jump(0));
+                        compile(node, program)?;
+                    }
+                    let exit = program.len();
+                    for start in splits {
+                        program[start] = split(start + 1, exit);
+                    }
+                }
+            }
+        }
+    }
+    if program.len() > MAX_PROGRAM {
+        return Err(());
+    }
+    Ok(())
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    /// Capture groups seen so far, including the whole match.
+    groups: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn error(&self, message: &str) -> String {
+        let pattern: String = self.chars.iter().collect();
+        format!("invalid regex `{pattern}`: {message}")
+    }
+
+    fn alternation(&mut self) -> Result<Node, String> {
+        let mut alternatives = vec![self.concat()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.concat()?);
+        }
+        Ok(if alternatives.len() == 1 {
+            alternatives.remove(0)
+        } else {
+            Node::Alternation(alternatives)
+        })
+    }
+
+    fn concat(&mut self) -> Result<Node, String> {
+        let mut nodes = vec![];
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantifier(atom)?);
+        }
+        Ok(match nodes.len() {
+            0 => Node::Empty,
+            1 => nodes.remove(0),
+            _ => Node::Concat(nodes),
+        })
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Some('(') => {
+                let index = if self.chars[self.pos..].starts_with(&['?', ':']) {
+                    self.pos += 2;
+                    None
+                } else {
+                    self.groups += 1;
+                    Some(self.groups - 1)
+                };
+                let node = self.alternation()?;
+                if self.next() != Some(')') {
+                    return Err(self.error("unclosed group"));
+                }
+                Ok(Node::Group(Box::new(node), index))
+            }
+            Some('[') => self.class().map(Node::Class),
+            Some('.') => Ok(Node::Any),
+            Some('^') => Ok(Node::Start),
+            Some('$') => Ok(Node::End),
+            Some('\\') => match self.escape()? {
+                ClassItem::Range(c, _) => Ok(Node::Char(c)),
+                ClassItem::Class(class) => Ok(Node::Class(class)),
+            },
+            Some(c @ ('*' | '+' | '?' | '{')) => {
+                Err(self.error(&format!("nothing to repeat before `{c}`")))
+            }
+            Some(c) => Ok(Node::Char(c)),
+            None => Ok(Node::Empty),
+        }
+    }
+
+    fn quantifier(&mut self, node: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => {
+                let Some(close) = self.chars[self.pos..].iter().position(|&c| c == '}') else {
+                    return Err(self.error("unclosed `{`"));
+                };
+                let inside: String = self.chars[self.pos + 1..self.pos + close].iter().collect();
+                let bound = |text: &str| {
+                    text.trim()
+                        .parse::<u32>()
+                        .map_err(|_| self.error(&format!("invalid repetition `{{{inside}}}`")))
+                };
+                let (min, max) = match inside.split_once(',') {
+                    Some((min, "")) => (bound(min)?, None),
+                    Some((min, max)) => (bound(min)?, Some(bound(max)?)),
+                    None => (bound(&inside)?, Some(bound(&inside)?)),
+                };
+                if max.is_some_and(|max| max < min) || min.max(max.unwrap_or(0)) > MAX_REPEAT {
+                    return Err(self.error(&format!("invalid repetition `{{{inside}}}`")));
+                }
+                self.pos += close;
+                (min, max)
+            }
+            _ => return Ok(node),
+        };
+        self.pos += 1;
+        let greedy = if self.peek() == Some('?') {
+            self.pos += 1;
+            false
+        } else {
+            true
+        };
+        if matches!(self.peek(), Some('*' | '+' | '?' | '{')) {
+            return Err(self.error("repeated quantifier"));
+        }
+        Ok(Node::Repeat {
+            node: Box::new(node),
+            min,
+            max,
+            greedy,
+        })
+    }
+
+    fn class(&mut self) -> Result<Class, String> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut items = vec![];
+        let mut first = true;
+        loop {
+            let item = match self.next() {
+                None => return Err(self.error("unclosed `[`")),
+                Some(']') if !first => break,
+                Some('\\') => self.escape()?,
+                Some(c) => ClassItem::Range(c, c),
+            };
+            first = false;
+            // A `-` between two characters makes a range.
+            let is_range = self.peek() == Some('-') && self.chars.get(self.pos + 1) != Some(&']');
+            match item {
+                ClassItem::Range(start, _) if is_range => {
+                    self.pos += 1;
+                    let end = match self.next() {
+                        Some('\\') => match self.escape()? {
+                            ClassItem::Range(end, _) => end,
+                            ClassItem::Class(_) => return Err(self.error("invalid class range")),
+                        },
+                        Some(end) => end,
+                        None => return Err(self.error("unclosed `[`")),
+                    };
+                    if end < start {
+                        return Err(self.error(&format!("invalid class range `{start}-{end}`")));
+                    }
+                    items.push(ClassItem::Range(start, end));
+                }
+                item => items.push(item),
+            }
+        }
+        Ok(Class { negated, items })
+    }
+
+    /// The character or class after a backslash.
+    fn escape(&mut self) -> Result<ClassItem, String> {
+        let shorthand = |negated: bool, ranges: &[(char, char)]| {
+            ClassItem::Class(Class {
+                negated,
+                items: ranges
+                    .iter()
+                    .map(|&(start, end)| ClassItem::Range(start, end))
+                    .collect(),
+            })
+        };
+        const DIGIT: &[(char, char)] = &[('0', '9')];
+        const WORD: &[(char, char)] = &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+        const SPACE: &[(char, char)] = &[('\t', '\r'), (' ', ' ')];
+        Ok(match self.next() {
+            Some('d') => shorthand(false, DIGIT),
+            Some('D') => shorthand(true, DIGIT),
+            Some('w') => shorthand(false, WORD),
+            Some('W') => shorthand(true, WORD),
+            Some('s') => shorthand(false, SPACE),
+            Some('S') => shorthand(true, SPACE),
+            Some('n') => ClassItem::Range('\n', '\n'),
+            Some('t') => ClassItem::Range('\t', '\t'),
+            Some(c) if !c.is_ascii_alphanumeric() => ClassItem::Range(c, c),
+            Some(c) => return Err(self.error(&format!("unknown escape `\\{c}`"))),
+            None => return Err(self.error("trailing backslash")),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    /// The text of the first match.
+    fn find<'a>(pattern: &str, text: &'a str) -> Option<&'a str> {
+        let (start, end) = Regex::new(pattern).unwrap().captures(text)?[0]?;
+        Some(&text[start..end])
+    }
+
+    #[test]
+    fn alternation_is_leftmost_first() {
+        assert_eq!(find("cat|dog", "hotdog"), Some("dog"));
+        assert_eq!(find("a|ab", "ab"), Some("a"));
+        assert_eq!(find("ab|a", "ab"), Some("ab"));
+        assert_eq!(find("x(|y)z", "xz"), Some("xz"));
+    }
+
+    #[test]
+    fn classes_and_escapes() {
+        assert_eq!(find("[a-c]+", "xxbcay"), Some("bca"));
+        assert_eq!(find("[^/]+$", "https://a/b/page"), Some("page"));
+        assert_eq!(find("[-a]+", "b-a-"), Some("-a-"));
+        assert_eq!(find(r"\d+", "v12.3"), Some("12"));
+        assert_eq!(find(r"\w+", "  foo_9 "), Some("foo_9"));
+        assert_eq!(find(r"\s", "a\tb"), Some("\t"));
+        assert_eq!(find(r"\D\W", "1a."), Some("a."));
+        assert_eq!(find(r"\.com", "examplexcom.com"), Some(".com"));
+        assert_eq!(find(r"[\d.]+", "v1.2.3-rc"), Some("1.2.3"));
+        assert!(Regex::new(r"\q").is_err());
+        assert!(Regex::new("[z-a]").is_err());
+        assert!(Regex::new("[abc").is_err());
+    }
+
+    #[test]
+    fn anchors() {
+        assert_eq!(find("^http:", "http://a"), Some("http:"));
+        assert_eq!(find("^a", "ba"), None);
+        assert_eq!(find("a$", "aba"), Some("a"));
+        assert_eq!(find("^$", ""), Some(""));
+        assert_eq!(find("^b$", "ab"), None);
+    }
+
+    #[test]
+    fn quantifiers() {
+        assert_eq!(find("a*", "aaab"), Some("aaa"));
+        assert_eq!(find("a*?", "aaab"), Some(""));
+        assert_eq!(find("a+?", "aaab"), Some("a"));
+        assert_eq!(find("colou?r", "color"), Some("color"));
+        assert_eq!(find("a{2}", "aaaa"), Some("aa"));
+        assert_eq!(find("a{2,}", "aaaa"), Some("aaaa"));
+        assert_eq!(find("a{1,3}", "aaaa"), Some("aaa"));
+        assert_eq!(find("a{1,3}?", "aaaa"), Some("a"));
+        assert_eq!(find("(ab)+", "ababa"), Some("abab"));
+        assert!(Regex::new("*a").is_err());
+        assert!(Regex::new("a**").is_err());
+        assert!(Regex::new("a{3,1}").is_err());
+        assert!(Regex::new("a{1001}").is_err());
+        assert!(Regex::new("(a").is_err());
+        assert!(Regex::new("a)").is_err());
+    }
+
+    #[test]
+    fn replacements_use_groups() {
+        let regex = Regex::new("^http://([^/]+)/(.*)$").unwrap();
+        assert_eq!(
+            regex.replace("http://old.example/a/b", "https://$1/v2/${2}"),
+            Some("https://old.example/v2/a/b".to_string())
+        );
+        let regex = Regex::new("(?:x)(y)?z").unwrap();
+        assert_eq!(regex.replace("xz!", "[$1$$]"), Some("[$]!".to_string()));
+        assert_eq!(regex.replace("nothing", "x"), None);
+    }
+
+    #[test]
+    fn hostile_patterns_finish() {
+        let started = Instant::now();
+        let text = format!("{}!", "a".repeat(5000));
+        assert_eq!(find("(a+)+$", &text), None);
+        assert_eq!(find("(a|aa)*b", &text), None);
+        assert_eq!(find("(a*)*(a*)*c", &text), None);
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        assert!(Regex::new("((a{1000}){1000}){1000}").is_err());
+        assert!(Regex::new("(a{10}){500}").is_ok());
+    }
+}
@@ -0,0 +1,140 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! `shurl release-links`: predictable short links for the assets of a
+//! GitHub release, e.g. `app-v1.2.3-linux`.
+
+use crate::json::Value;
+
+pub struct ReleaseAsset {
+    pub name: String,
+    pub url: String,
+}
+
+/// Signatures and checksums aren't worth a link of their own.
+const SKIPPED_SUFFIXES: &[&str] = &[
+    ".sha256",
+    ".sha512",
+    ".sha256sum",
+    ".sig",
+    ".asc",
+    ".minisig",
+    ".pem",
+];
+
+const OPERATING_SYSTEMS: &[(&str, &str)] = &[
+    ("linux", "linux"),
+    ("windows", "windows"),
+    ("win64", "windows"),
+    ("win32", "windows"),
+    ("darwin", "macos"),
+    ("macos", "macos"),
+    ("apple", "macos"),
+    ("osx", "macos"),
+    ("freebsd", "freebsd"),
+];
+
+const ARCHITECTURES: &[(&str, &str)] = &[
+    ("x86_64", "x86_64"),
+    ("amd64", "x86_64"),
+    ("aarch64", "aarch64"),
+    ("arm64", "aarch64"),
+    ("armv7", "armv7"),
+    ("i686", "i686"),
+];
+
+/// Reads the assets of a release from the GitHub API response.
+pub fn assets(release: &Value) -> Vec<ReleaseAsset> {
+    release
+        .get("assets")
+        .and_then(Value::as_array)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|asset| {
+            Some(ReleaseAsset {
+                name: asset.get("name")?.as_str()?.to_string(),
+                url: asset.get("browser_download_url")?.as_str()?.to_string(),
+            })
+        })
+        .filter(|asset| {
+            let name = asset.name.to_ascii_lowercase();
+            !SKIPPED_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+        })
+        .collect()
+}
+
+/// Picks a slug for every asset: `{prefix}-{tag}-{os}` where the operating
+/// system is enough to tell assets apart, `{os}-{arch}` where it isn't, and
+/// the asset name itself as a last resort.
+pub fn slugs(prefix: &str, tag: &str, assets: &[ReleaseAsset]) -> Vec<String> {
+    let labels: Vec<(Option<&str>, Option<&str>)> = assets
+        .iter()
+        .map(|asset| {
+            let name = asset.name.to_ascii_lowercase();
+            (find(&name, OPERATING_SYSTEMS), find(&name, ARCHITECTURES))
+        })
+        .collect();
+
+    let mut slugs: Vec<String> = assets
+        .iter()
+        .zip(&labels)
+        .map(|(asset, (os, arch))| {
+            let same_os = labels.iter().filter(|(other, _)| other == os).count();
+            let same_platform = labels
+                .iter()
+                .filter(|(other_os, other_arch)| other_os == os && other_arch == arch)
+                .count();
+            let label = match (os, arch) {
+                (Some(os), _) if same_os == 1 => os.to_string(),
+                (Some(os), Some(arch)) if same_platform == 1 => format!("{os}-{arch}"),
+                _ => slugify(&asset.name),
+            };
+            format!("{}-{}-{label}", slugify(prefix), slugify(tag))
+        })
+        .collect();
+
+    // Asset names are unique within a release, but slugifying them might
+    // still collide.
+    for i in 1..slugs.len() {
+        let mut suffix = 2;
+        let base = slugs[i].clone();
+        while slugs[..i].contains(&slugs[i]) {
+            slugs[i] = format!("{base}-{suffix}");
+            suffix += 1;
+        }
+    }
+    slugs
+}
+
+fn find(name: &str, table: &[(&str, &'static str)]) -> Option<&'static str> {
+    table
+        .iter()
+        .find(|(needle, _)| name.contains(needle))
+        .map(|(_, label)| *label)
+}
+
+/// Keeps letters, digits, `.` and `_`, turning everything else into `-`.
+fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() || matches!(c, '.' | '_') {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_matches('-').to_string()
+}
@@ -0,0 +1,129 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Hot reloading of the config and `.shurl.toml` for the long-running
+//! modes, so tokens and policies can change without a restart.
+
+use crate::config::{self, ShurlConfig};
+use crate::output::Colorize;
+use crate::repo_config::{RepoConfig, REPO_CONFIG_FILE};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Settings only read once at startup.
+const RESTART_KEYS: &[&str] = &["repo_path", "serve_address", "serve_port"];
+
+/// Notices changes to a file by its modification time.
+struct FileWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl FileWatcher {
+    fn new(path: PathBuf) -> Self {
+        let modified = modified(&path);
+        Self { path, modified }
+    }
+
+    fn changed(&mut self) -> bool {
+        let modified = modified(&self.path);
+        let changed = modified != self.modified;
+        self.modified = modified;
+        changed
+    }
+}
+
+/// When the file was last changed, nothing if it doesn't exist.
+pub fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+pub struct Reloader {
+    config: FileWatcher,
+    repo_config: FileWatcher,
+    repo_path: PathBuf,
+}
+
+impl Reloader {
+    pub fn new(repo_path: &Path) -> Self {
+        Self {
+            config: FileWatcher::new(config::config_path()),
+            repo_config: FileWatcher::new(repo_path.join(REPO_CONFIG_FILE)),
+            repo_path: repo_path.to_path_buf(),
+        }
+    }
+
+    /// Checks both files for changes and logs them. Returns the new config
+    /// if it changed and is valid, a broken config keeps the current one.
+    pub fn poll(&mut self, current: &ShurlConfig) -> Option<ShurlConfig> {
+        // Most of `.shurl.toml` is read whenever it's needed, it's checked
+        // so mistakes show up in the log right away. The settings it
+        // replaces in the config are applied again below.
+        let repo_config_changed = self.repo_config.changed();
+        if repo_config_changed {
+            match RepoConfig::load(&self.repo_path) {
+                Ok(_) => info(&format!("reloaded {REPO_CONFIG_FILE}")),
+                Err(e) => warn(&e),
+            }
+        }
+
+        if !self.config.changed() && !repo_config_changed {
+            return None;
+        }
+        let mut new = match ShurlConfig::load(&self.config.path).and_then(|new| {
+            new.validate()?;
+            let mut new = new.with_profile(current.profile.as_deref())?.with_env()?;
+            RepoConfig::load(&self.repo_path)?.apply_to(&mut new);
+            Ok(new)
+        }) {
+            Ok(new) => new,
+            Err(e) => {
+                warn(&format!("keeping the previous config: {e}"));
+                return None;
+            }
+        };
+
+        let changed = current.changed_keys(&new);
+        let (restart, applied): (Vec<_>, Vec<_>) = changed
+            .into_iter()
+            .partition(|key| RESTART_KEYS.contains(&key.as_str()));
+        if !restart.is_empty() {
+            warn(&format!(
+                "changes to {} only apply after a restart",
+                restart.join(", ")
+            ));
+            new.repo_path = current.repo_path.clone();
+            new.serve_address = current.serve_address.clone();
+            new.serve_port = current.serve_port;
+        }
+        if applied.is_empty() {
+            return None;
+        }
+        info(&format!("config changed: {}", applied.join(", ")));
+        Some(new)
+    }
+}
+
+fn info(message: &str) {
+    println!("{} {}", "Info:".green(), message.bold());
+}
+
+fn warn(message: &str) {
+    eprintln!("{} {}", "Warning:".yellow(), message.bold());
+}
@@ -0,0 +1,955 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::assets::Asset;
+use crate::bundle::{Bundle, Bundles};
+use crate::config::{ReferrerPolicy, ShurlConfig};
+use crate::date;
+use crate::federation::Partner;
+use crate::hash;
+use crate::i18n::{self, Strings};
+use crate::json;
+use crate::manifest::{Link, Manifest};
+use crate::preview;
+use crate::target;
+use crate::template::{self, Context};
+use toml::value::Datetime;
+use url::Url;
+
+const REDIRECT_TEMPLATE: &str = include_str!("templates/redirect.html");
+const INDEX_TEMPLATE: &str = include_str!("templates/index.html");
+const GONE_TEMPLATE: &str = include_str!("templates/gone.html");
+const NOT_FOUND_TEMPLATE: &str = include_str!("templates/not_found.html");
+const DIRECTORY_TEMPLATE: &str = include_str!("templates/directory.html");
+const BUNDLE_TEMPLATE: &str = include_str!("templates/bundle.html");
+const NAMESPACE_TEMPLATE: &str = include_str!("templates/namespace.html");
+const COUNTDOWN_TEMPLATE: &str = include_str!("templates/countdown.html");
+const COUNTDOWN_SCRIPT: &str = include_str!("templates/countdown.js");
+const COUNTDOWN_STYLE: &str = include_str!("templates/countdown.css");
+const LOCALE_TEMPLATE: &str = include_str!("templates/locale.html");
+const LOCALE_SCRIPT: &str = include_str!("templates/locale.js");
+const INDEX_SCRIPT: &str = include_str!("templates/index.js");
+const REDIRECT_SCRIPT: &str = include_str!("templates/redirect.js");
+const NOT_FOUND_SCRIPT: &str = include_str!("templates/not_found.js");
+const SITEMAP_TEMPLATE: &str = include_str!("templates/sitemap.xml");
+const FEED_TEMPLATE: &str = include_str!("templates/feed.xml");
+
+/// Settings of the pages of links, from the config.
+pub struct PageOptions {
+    /// Whether pages of links that expire say they have instead of
+    /// redirecting, once they have.
+    pub expired_notice: bool,
+    /// Seconds before redirecting, for links without a delay of their own.
+    pub delay: u32,
+    /// Whether search engines are asked not to index the pages, for links
+    /// that don't say.
+    pub noindex: bool,
+    /// Whether the pages name the target as their canonical URL.
+    pub canonical: bool,
+    /// Referrer policy of the pages, for links without one of their own.
+    pub referrer: Option<ReferrerPolicy>,
+    /// Whether the pages add the reader's query string and fragment to the
+    /// target.
+    pub passthrough: bool,
+    /// Language of the text of the pages, for links without one of their
+    /// own.
+    pub language: String,
+}
+
+impl PageOptions {
+    pub fn of(cfg: &ShurlConfig) -> Self {
+        Self {
+            expired_notice: cfg.expired_notice,
+            delay: cfg.redirect_delay,
+            noindex: cfg.noindex,
+            canonical: cfg.canonical,
+            referrer: cfg.referrer_policy,
+            passthrough: cfg.query_passthrough,
+            language: cfg.language.clone(),
+        }
+    }
+}
+
+/// Redirect pages load nothing but their inline script, allowed by its hash,
+/// and the stylesheets of the site.
+const REDIRECT_CSP: &str = "default-src 'none'; base-uri 'none'; form-action 'none'";
+/// The index only loads its own stylesheets, which are pinned with SRI hashes,
+/// and the screenshots of the links, besides its inline script filtering
+/// them, which also fetches `links.json`.
+const INDEX_CSP: &str =
+    "default-src 'none'; style-src 'self'; img-src 'self'; base-uri 'none'; form-action 'none'";
+
+/// Renders a link's redirect page, with the built-in template unless its
+/// namespace has one of its own. Links to an event get a countdown instead,
+/// links with targets per language a page picking one. Besides the meta
+/// refresh a script redirects, for browsers ignoring it, and says the link
+/// has expired instead once it has with `expired_notice`.
+pub fn redirect_page(
+    link: &Link,
+    custom_template: Option<&str>,
+    stylesheets: &[Asset],
+    root: &str,
+    options: &PageOptions,
+) -> Result<String, String> {
+    // However they got into the manifest, such targets would run in the
+    // reader's browser on the origin of the short links.
+    for target in std::iter::once(&link.target).chain(link.locales.values()) {
+        if let Ok(url) = Url::parse(target) {
+            target::check_scheme(url.scheme())
+                .map_err(|e| format!("refusing to write the page of `{}`: {e}", link.slug))?;
+        }
+    }
+    let language = link.lang.as_deref().unwrap_or(&options.language);
+    let strings = i18n::strings(language)
+        .map_err(|e| format!("can't write the page of `{}`: {e}", link.slug))?;
+    if let Some(starts) = &link.starts {
+        return countdown_page(link, starts, language, strings, options);
+    }
+    if !link.locales.is_empty() {
+        return locale_page(link, language, strings, options);
+    }
+    let delay = link.delay.unwrap_or(options.delay);
+    let mut context = Context::new();
+    context
+        .insert("lang", language)
+        .insert("redirecting", strings.redirecting)
+        .insert("taking_you_to", strings.taking_you_to)
+        .insert("countdown", strings.countdown(delay))
+        .insert("follow", strings.follow)
+        .insert("link_text", strings.link)
+        .insert("follow_end", strings.follow_end)
+        .insert("expired", strings.expired);
+    context
+        .insert("slug", link.slug.as_str())
+        .insert("target", link.target.as_str())
+        .insert("target_text", target::display(&link.target))
+        .insert("created", date::format_date(&link.created))
+        .insert(
+            "expires",
+            link.expires
+                .as_ref()
+                .map(date::format_date)
+                .unwrap_or_default(),
+        )
+        .insert("owner", link.owner.as_deref().unwrap_or_default())
+        .insert(
+            "description",
+            link.description.as_deref().unwrap_or_default(),
+        )
+        .insert("tags", link.tags.join(","))
+        .insert("jsonld", link_json_ld(link))
+        .insert("delay", delay.to_string())
+        .insert("interstitial", delay > 0)
+        .insert("script", REDIRECT_SCRIPT)
+        .insert("stylesheets", stylesheet_rows(stylesheets))
+        .insert("root", root)
+        .insert(
+            "csp",
+            format!(
+                "{REDIRECT_CSP}; script-src '{}'{}",
+                hash::integrity(REDIRECT_SCRIPT.as_bytes()),
+                if stylesheets.is_empty() {
+                    ""
+                } else {
+                    "; style-src 'self'"
+                }
+            ),
+        );
+    if let Some(preview) = &link.preview {
+        let card = if preview.image.is_some() {
+            "summary_large_image"
+        } else {
+            "summary"
+        };
+        context
+            .insert(
+                "preview_title",
+                preview.title.as_deref().unwrap_or_default(),
+            )
+            .insert(
+                "preview_description",
+                preview.description.as_deref().unwrap_or_default(),
+            )
+            .insert(
+                "preview_image",
+                preview.image.as_deref().unwrap_or_default(),
+            )
+            .insert("twitter_card", card);
+    }
+    // Either needs the script to decide where to go, so the meta refresh
+    // is left to browsers running none.
+    let expires_at = link.expires.as_ref().filter(|_| options.expired_notice);
+    if let Some(expires) = expires_at {
+        context.insert("expires_at", expires.to_string());
+    }
+    context
+        .insert("passthrough", options.passthrough)
+        .insert("scripted", expires_at.is_some() || options.passthrough);
+    insert_meta(&mut context, link, options);
+    template::render(custom_template.unwrap_or(REDIRECT_TEMPLATE), &context)
+}
+
+/// What the pages of links tell search engines and targets: whether to
+/// leave them out, with `canonical` that the target is what they stand for,
+/// and the referrer policy readers leave with.
+fn insert_meta(context: &mut Context, link: &Link, options: &PageOptions) {
+    context.insert("noindex", link.noindex.unwrap_or(options.noindex));
+    if options.canonical {
+        context.insert("canonical", link.target.as_str());
+    }
+    if let Some(referrer) = link.referrer.or(options.referrer) {
+        context.insert("referrer", referrer.as_str());
+    }
+}
+
+/// Page counting down to the start of a link's event, redirecting once it
+/// has started. The countdown runs in the browser, so the page doesn't have
+/// to be regenerated when the event starts.
+fn countdown_page(
+    link: &Link,
+    starts: &Datetime,
+    language: &str,
+    strings: &Strings,
+    options: &PageOptions,
+) -> Result<String, String> {
+    // The inline script and style are allowed by their hashes.
+    let csp = format!(
+        "default-src 'none'; script-src '{}'; style-src '{}'; base-uri 'none'; form-action 'none'",
+        hash::integrity(COUNTDOWN_SCRIPT.as_bytes()),
+        hash::integrity(COUNTDOWN_STYLE.as_bytes())
+    );
+    let mut context = Context::new();
+    context
+        .insert("lang", language)
+        .insert("starts_text", strings.starts)
+        .insert("starts_in", strings.starts_in)
+        .insert("once_started", strings.once_started);
+    context
+        .insert("slug", link.slug.as_str())
+        .insert("target", link.target.as_str())
+        .insert("target_text", target::display(&link.target))
+        .insert("starts", starts.to_string())
+        .insert("created", date::format_date(&link.created))
+        .insert("owner", link.owner.as_deref().unwrap_or_default())
+        .insert(
+            "title",
+            link.description.as_deref().unwrap_or(strings.starting_soon),
+        )
+        .insert("jsonld", link_json_ld(link))
+        .insert("csp", csp)
+        .insert("style", COUNTDOWN_STYLE)
+        .insert("script", COUNTDOWN_SCRIPT);
+    insert_meta(&mut context, link, options);
+    template::render(COUNTDOWN_TEMPLATE, &context)
+}
+
+/// Page sending readers to the target of the first of their browser's
+/// languages that has one, and everyone else to the link's target.
+fn locale_page(
+    link: &Link,
+    language: &str,
+    strings: &Strings,
+    options: &PageOptions,
+) -> Result<String, String> {
+    let mut locales = json::Value::object();
+    let mut versions = vec![];
+    for (language, target) in &link.locales {
+        locales.set(language, target.as_str());
+        let mut row = Context::new();
+        row.insert("language", language.as_str())
+            .insert("target", target.as_str());
+        versions.push(row);
+    }
+
+    // The inline script is allowed by its hash.
+    let csp = format!(
+        "default-src 'none'; script-src '{}'; base-uri 'none'; form-action 'none'",
+        hash::integrity(LOCALE_SCRIPT.as_bytes())
+    );
+    let mut context = Context::new();
+    context
+        .insert("lang", language)
+        .insert("redirecting", strings.redirecting)
+        .insert("follow_language", strings.follow_language)
+        .insert("any_language", strings.any_language);
+    context
+        .insert("slug", link.slug.as_str())
+        .insert("target", link.target.as_str())
+        .insert("target_text", target::display(&link.target))
+        .insert("locales", locales.to_string())
+        .insert("versions", versions)
+        .insert("created", date::format_date(&link.created))
+        .insert(
+            "expires",
+            link.expires
+                .as_ref()
+                .map(date::format_date)
+                .unwrap_or_default(),
+        )
+        .insert("owner", link.owner.as_deref().unwrap_or_default())
+        .insert("jsonld", link_json_ld(link))
+        .insert("csp", csp)
+        .insert("script", LOCALE_SCRIPT);
+    insert_meta(&mut context, link, options);
+    template::render(LOCALE_TEMPLATE, &context)
+}
+
+/// schema.org description of a link, so crawlers and scanners can inventory
+/// the links without access to the repository.
+fn link_json_ld(link: &Link) -> String {
+    let mut value = json::Value::object();
+    value
+        .set("@context", "https://schema.org")
+        .set("@type", "WebPage")
+        .set("identifier", link.slug.as_str())
+        .set("relatedLink", link.target.as_str())
+        .set("dateCreated", link.created.to_string());
+    if let Some(expires) = &link.expires {
+        value.set("expires", expires.to_string());
+    }
+    if let Some(description) = &link.description {
+        value.set("description", description.as_str());
+    }
+    if !link.tags.is_empty() {
+        value.set("keywords", link.tags.join(","));
+    }
+    if let Some(owner) = &link.owner {
+        let mut author = json::Value::object();
+        author.set("@type", "Person").set("name", owner.as_str());
+        value.set("author", author);
+    }
+    // Keeps `</script>` in a value from ending the script element.
+    value.to_string().replace('<', "\\u003c")
+}
+
+/// Page left in place of a removed link, pointing to the index for finding
+/// other links. Without a slug it's the page shared by all removed links.
+pub fn gone_page(
+    slug: Option<&str>,
+    index_href: &str,
+    custom_template: Option<&str>,
+) -> Result<String, String> {
+    let mut context = Context::new();
+    context
+        .insert("slug", slug.unwrap_or_default())
+        .insert("index_href", index_href)
+        .insert("csp", REDIRECT_CSP);
+    template::render(custom_template.unwrap_or(GONE_TEMPLATE), &context)
+}
+
+/// The `404.html` of the site, for paths that aren't a link. `root` is an
+/// absolute URL or path of the index, since the page is served at any depth.
+/// With `search` it has a box for going to a link, suggesting the `links`.
+pub fn not_found_page(
+    links: &[&Link],
+    stylesheets: &[Asset],
+    root: &str,
+    index: bool,
+    search: bool,
+    custom_template: Option<&str>,
+) -> Result<String, String> {
+    let slugs: Vec<Context> = links
+        .iter()
+        .map(|link| {
+            let mut row = Context::new();
+            row.insert("slug", link.slug.as_str())
+                .insert("target_text", target::display(&link.target));
+            row
+        })
+        .collect();
+    let csp = if search {
+        format!(
+            "default-src 'none'; style-src 'self'; script-src '{}'; base-uri 'none'; form-action 'none'",
+            hash::integrity(NOT_FOUND_SCRIPT.as_bytes())
+        )
+    } else {
+        INDEX_CSP.to_string()
+    };
+    let mut context = Context::new();
+    context
+        .insert("root", root)
+        .insert("index", index)
+        .insert("search", search)
+        .insert("slugs", slugs)
+        .insert("script", NOT_FOUND_SCRIPT)
+        .insert("stylesheets", stylesheet_rows(stylesheets))
+        .insert("csp", csp);
+    template::render(custom_template.unwrap_or(NOT_FOUND_TEMPLATE), &context)
+}
+
+/// `sitemap.xml` listing the URLs, each with the date it last changed if
+/// known.
+pub fn sitemap(urls: &[(String, Option<String>)]) -> Result<String, String> {
+    let urls: Vec<Context> = urls
+        .iter()
+        .map(|(loc, lastmod)| {
+            let mut row = Context::new();
+            row.insert("loc", loc.as_str())
+                .insert("lastmod", lastmod.as_deref().unwrap_or_default());
+            row
+        })
+        .collect();
+    let mut context = Context::new();
+    context.insert("urls", urls);
+    template::render(SITEMAP_TEMPLATE, &context)
+}
+
+/// `robots.txt` keeping crawlers off the `disallowed` paths, with the URL
+/// of the sitemap if there is one.
+pub fn robots_txt(disallowed: &[String], sitemap: Option<&str>) -> String {
+    let mut out = String::from("User-agent: *\n");
+    for path in disallowed {
+        out.push_str(&format!("Disallow: {path}\n"));
+    }
+    if disallowed.is_empty() {
+        out.push_str("Disallow:\n");
+    }
+    if let Some(sitemap) = sitemap {
+        out.push_str(&format!("\nSitemap: {sitemap}\n"));
+    }
+    out
+}
+
+/// Atom feed of the links, leading to their targets. `root` is the URL of
+/// the index, the links' URLs are under it.
+pub fn feed(links: &[&Link], root: &str, author: &str) -> Result<String, String> {
+    let entries: Vec<Context> = links
+        .iter()
+        .map(|link| {
+            let mut row = Context::new();
+            row.insert(
+                "title",
+                link.description.as_deref().unwrap_or(link.slug.as_str()),
+            )
+            .insert("short_url", format!("{root}{}", link.slug))
+            .insert("target", link.target.as_str())
+            .insert("created", link.created.to_string())
+            .insert("owner", link.owner.as_deref().unwrap_or_default())
+            .insert(
+                "description",
+                link.description.as_deref().unwrap_or_default(),
+            )
+            .insert("tags", tag_rows(&link.tags));
+            row
+        })
+        .collect();
+    let updated = links
+        .first()
+        .map(|link| link.created.to_string())
+        .unwrap_or_else(|| date::now().to_string());
+    let mut context = Context::new();
+    context
+        .insert("root", root)
+        .insert("author", author)
+        .insert("updated", updated)
+        .insert("entries", entries);
+    template::render(FEED_TEMPLATE, &context)
+}
+
+/// Landing page of a bundle, listing its targets. `root` leads from the page
+/// back to the index.
+pub fn bundle_page(bundle: &Bundle, stylesheets: &[Asset], root: &str) -> Result<String, String> {
+    let targets: Vec<Context> = bundle
+        .targets
+        .iter()
+        .map(|target| {
+            let mut row = Context::new();
+            row.insert("target", target.as_str())
+                .insert("target_text", target::display(target));
+            row
+        })
+        .collect();
+
+    let mut context = Context::new();
+    context
+        .insert("csp", INDEX_CSP)
+        .insert("stylesheets", stylesheet_rows(stylesheets))
+        .insert("root", root)
+        .insert("title", bundle.title.as_deref().unwrap_or(&bundle.slug))
+        .insert("created", date::format_date(&bundle.created))
+        .insert("owner", bundle.owner.as_deref().unwrap_or_default())
+        .insert("targets", targets);
+    template::render(BUNDLE_TEMPLATE, &context)
+}
+
+/// Index of the links in a directory such as `docs/`, newest first, and of
+/// the directories in it. `root` leads from the page back to the index,
+/// `href` gives the location of a page relative to the index.
+pub fn namespace_page(
+    name: &str,
+    links: &[&Link],
+    namespaces: &[&str],
+    stylesheets: &[Asset],
+    root: &str,
+    href: impl Fn(&str) -> String,
+) -> Result<String, String> {
+    let rows: Vec<Context> = links
+        .iter()
+        .map(|link| {
+            let mut row = Context::new();
+            row.insert("slug", link.slug.as_str())
+                .insert(
+                    "href",
+                    format!("{root}{}", href(&link.slug).trim_start_matches("./")),
+                )
+                .insert("target", link.target.as_str())
+                .insert("target_text", target::display(&link.target))
+                .insert(
+                    "description",
+                    link.description.as_deref().unwrap_or_default(),
+                )
+                .insert("tags", tag_rows(&link.tags))
+                .insert("created", date::format_date(&link.created));
+            row
+        })
+        .collect();
+
+    let mut context = Context::new();
+    context
+        .insert("csp", INDEX_CSP)
+        .insert("stylesheets", stylesheet_rows(stylesheets))
+        .insert("root", root)
+        .insert("name", name)
+        .insert("has_namespaces", !namespaces.is_empty())
+        .insert("namespaces", namespace_rows(namespaces, root))
+        .insert("has_links", !rows.is_empty())
+        .insert("links", rows);
+    template::render(NAMESPACE_TEMPLATE, &context)
+}
+
+/// Renders page `page` of the `pages` of the index: its links in order,
+/// under the heading of their group if they have one, links to the other
+/// pages, and on the first page the top directories and the bundles. `href`
+/// gives the location of a page relative to the first page of the index.
+/// `issue_href` gives the page of an issue links were created for, and
+/// `screenshot` the screenshot of a link's target.
+#[allow(clippy::too_many_arguments)]
+pub fn index_page(
+    entries: &[(String, &Link)],
+    page: usize,
+    pages: usize,
+    bundles: &Bundles,
+    stylesheets: &[Asset],
+    has_directory: bool,
+    namespaces: &[&str],
+    href: impl Fn(&str) -> Option<String>,
+    issue_href: impl Fn(&str) -> Option<String>,
+    screenshot: impl Fn(&Link) -> Option<String>,
+) -> Result<String, String> {
+    // Later pages are in `index/`.
+    let root = if page == 1 { "./" } else { "../" };
+    let href =
+        |slug: &str| href(slug).map(|href| format!("{root}{}", href.trim_start_matches("./")));
+    let groups: Vec<Context> = entries
+        .chunk_by(|(a, _), (b, _)| a == b)
+        .map(|entries| {
+            let links: Vec<&Link> = entries.iter().map(|(_, link)| *link).collect();
+            let mut group = Context::new();
+            group
+                .insert("heading", entries[0].0.as_str())
+                .insert("links", link_rows(&links, href, &issue_href, &screenshot));
+            group
+        })
+        .collect();
+    let page_href = |page: usize| match page {
+        1 => root.to_string(),
+        _ => format!("{root}index/{page}.html"),
+    };
+    let bundles: Vec<Context> = bundles
+        .bundles
+        .iter()
+        .filter(|_| page == 1)
+        .map(|bundle| {
+            let mut row = Context::new();
+            row.insert("slug", bundle.slug.as_str())
+                .insert("href", href(&bundle.slug).unwrap_or_default())
+                .insert("title", bundle.title.as_deref().unwrap_or_default())
+                .insert("count", bundle.targets.len().to_string())
+                .insert("created", date::format_date(&bundle.created));
+            row
+        })
+        .collect();
+
+    let mut context = Context::new();
+    context
+        .insert(
+            "csp",
+            format!(
+                "{INDEX_CSP}; connect-src 'self'; script-src '{}'",
+                hash::integrity(INDEX_SCRIPT.as_bytes())
+            ),
+        )
+        .insert("script", INDEX_SCRIPT)
+        .insert("stylesheets", stylesheet_rows(stylesheets))
+        .insert("root", root)
+        .insert("has_directory", has_directory && page == 1)
+        .insert("has_namespaces", !namespaces.is_empty() && page == 1)
+        .insert("namespaces", namespace_rows(namespaces, root))
+        .insert("has_links", !entries.is_empty())
+        .insert(
+            "has_issues",
+            entries.iter().any(|(_, link)| link.issue.is_some()),
+        )
+        .insert("groups", groups)
+        .insert("has_pages", pages > 1)
+        .insert("page", page.to_string())
+        .insert("pages", pages.to_string())
+        .insert(
+            "previous",
+            if page > 1 {
+                page_href(page - 1)
+            } else {
+                String::new()
+            },
+        )
+        .insert(
+            "next",
+            if page < pages {
+                page_href(page + 1)
+            } else {
+                String::new()
+            },
+        )
+        .insert("has_bundles", !bundles.is_empty())
+        .insert("bundles", bundles);
+    template::render(INDEX_TEMPLATE, &context)
+}
+
+/// Renders the directory page listing our own links followed by the links
+/// of every partner repository, each under its own heading.
+pub fn directory_page(
+    manifest: &Manifest,
+    partners: &[(Partner, Manifest)],
+    stylesheets: &[Asset],
+    href: impl Fn(&str) -> Option<String>,
+) -> Result<String, String> {
+    let mut sources = vec![];
+
+    let links = link_rows(&manifest.newest_first(), href, |_| None, |_| None);
+    let mut own = Context::new();
+    own.insert("name", "This repository")
+        .insert("has_links", !links.is_empty())
+        .insert("links", links);
+    sources.push(own);
+
+    for (partner, manifest) in partners {
+        let links = link_rows(
+            &manifest.newest_first(),
+            |slug| partner.link_url(slug),
+            |_| None,
+            |_| None,
+        );
+        let mut source = Context::new();
+        source
+            .insert("name", partner.name.as_str())
+            .insert("url", partner.url.as_str())
+            .insert(
+                "fetched",
+                partner
+                    .fetched
+                    .as_ref()
+                    .map(date::format_date)
+                    .unwrap_or_default(),
+            )
+            .insert("has_links", !links.is_empty())
+            .insert("links", links);
+        sources.push(source);
+    }
+
+    let mut context = Context::new();
+    context
+        .insert("csp", INDEX_CSP)
+        .insert("stylesheets", stylesheet_rows(stylesheets))
+        .insert("sources", sources);
+    template::render(DIRECTORY_TEMPLATE, &context)
+}
+
+fn link_rows(
+    links: &[&Link],
+    href: impl Fn(&str) -> Option<String>,
+    issue_href: impl Fn(&str) -> Option<String>,
+    screenshot: impl Fn(&Link) -> Option<String>,
+) -> Vec<Context> {
+    links
+        .iter()
+        .map(|link| {
+            let mut row = Context::new();
+            row.insert("slug", link.slug.as_str())
+                .insert("href", href(&link.slug).unwrap_or_default())
+                .insert("target", link.target.as_str())
+                .insert("target_text", target::display(&link.target))
+                .insert(
+                    "description",
+                    link.description.as_deref().unwrap_or_default(),
+                )
+                .insert("tags", tag_rows(&link.tags))
+                .insert("created", date::format_date(&link.created))
+                .insert("issue", link.issue.as_deref().unwrap_or_default())
+                .insert(
+                    "issue_href",
+                    link.issue
+                        .as_deref()
+                        .and_then(&issue_href)
+                        .unwrap_or_default(),
+                )
+                .insert("screenshot", screenshot(link).unwrap_or_default());
+            row
+        })
+        .collect()
+}
+
+fn tag_rows(tags: &[String]) -> Vec<Context> {
+    tags.iter()
+        .map(|tag| {
+            let mut row = Context::new();
+            row.insert("tag", tag.as_str());
+            row
+        })
+        .collect()
+}
+
+fn namespace_rows(namespaces: &[&str], root: &str) -> Vec<Context> {
+    namespaces
+        .iter()
+        .map(|name| {
+            let mut row = Context::new();
+            row.insert("name", *name)
+                .insert("href", format!("{root}{name}/"));
+            row
+        })
+        .collect()
+}
+
+/// Puts the analytics snippet at the end of the page's `<head>`, or of its
+/// `<body>` or the page itself for templates without one, allowing
+/// in the page's Content Security Policy what it loads: the origins of its
+/// scripts, which it may send to as well, its inline scripts by their hash
+/// and the origins of its images. The snippet is a template getting the
+/// `slug` and `target` of the link, both empty on the index.
+pub fn with_analytics(
+    page: &str,
+    snippet: &str,
+    slug: &str,
+    target: &str,
+) -> Result<String, String> {
+    let mut context = Context::new();
+    context.insert("slug", slug).insert("target", target);
+    let snippet = template::render(snippet, &context)?;
+
+    let mut scripts = vec![];
+    let mut origins = vec![];
+    let mut images = vec![];
+    let lower = snippet.to_ascii_lowercase();
+    for (open, tag) in [("<script", "script"), ("<img", "img")] {
+        let mut from = 0;
+        while let Some(start) = lower[from..].find(open).map(|i| from + i) {
+            let Some(end) = lower[start..].find('>').map(|i| start + i) else {
+                break;
+            };
+            let attributes = preview::parse_attributes(&snippet[start + open.len()..end]);
+            let src = attributes
+                .iter()
+                .find(|(name, _)| name == "src")
+                .map(|(_, value)| value.as_str());
+            match (src, tag) {
+                (Some(src), _) => {
+                    let origin = match Url::parse(src) {
+                        Ok(url) => url.origin().ascii_serialization(),
+                        Err(_) => "'self'".to_string(),
+                    };
+                    let sources = if tag == "script" {
+                        &mut origins
+                    } else {
+                        &mut images
+                    };
+                    if !sources.contains(&origin) {
+                        sources.push(origin);
+                    }
+                }
+                (None, "script") => {
+                    let body_end = lower[end..]
+                        .find("</script")
+                        .map_or(lower.len(), |i| end + i);
+                    let body = &snippet[end + 1..body_end];
+                    scripts.push(format!("'{}'", hash::integrity(body.as_bytes())));
+                }
+                _ => {}
+            }
+            from = end;
+        }
+    }
+    scripts.extend(origins.iter().cloned());
+    images.extend(origins.iter().cloned());
+
+    let mut page = page.to_string();
+    if let Some((start, end)) = csp_content(&page) {
+        let csp = page[start..end]
+            .replace("&#39;", "'")
+            .replace("&quot;", "\"")
+            .replace("&amp;", "&");
+        let csp = extend_csp(
+            &csp,
+            &[
+                ("script-src", &scripts),
+                ("connect-src", &origins),
+                ("img-src", &images),
+            ],
+        );
+        page.replace_range(start..end, &template::escape_html(&csp));
+    }
+    let lower = page.to_ascii_lowercase();
+    match lower.find("</head>").or_else(|| lower.rfind("</body>")) {
+        Some(end) => page.insert_str(end, &format!("{}\n    ", snippet.trim())),
+        None => {
+            page.push_str(snippet.trim());
+            page.push('\n');
+        }
+    }
+    Ok(page)
+}
+
+/// Range of the policy in the page's Content Security Policy meta tag, if it
+/// has one with the policy in double quotes.
+fn csp_content(page: &str) -> Option<(usize, usize)> {
+    let lower = page.to_ascii_lowercase();
+    let mut from = 0;
+    while let Some(start) = lower[from..].find("<meta").map(|i| from + i) {
+        let end = start + lower[start..].find('>')?;
+        let tag = &lower[start..end];
+        if tag.contains("content-security-policy") {
+            let value = start + tag.find("content=\"")? + "content=\"".len();
+            return Some((value, value + lower[value..].find('"')?));
+        }
+        from = end;
+    }
+    None
+}
+
+/// Adds sources to directives of a policy, adding the directives it lacks.
+fn extend_csp(csp: &str, additions: &[(&str, &Vec<String>)]) -> String {
+    let mut directives: Vec<Vec<String>> = csp
+        .split(';')
+        .map(|directive| directive.split_whitespace().map(str::to_string).collect())
+        .filter(|directive: &Vec<String>| !directive.is_empty())
+        .collect();
+    for (name, sources) in additions {
+        if sources.is_empty() {
+            continue;
+        }
+        match directives
+            .iter_mut()
+            .find(|directive| directive[0] == *name)
+        {
+            Some(directive) => {
+                directive.retain(|source| source != "'none'");
+                for source in sources.iter() {
+                    if !directive.contains(source) {
+                        directive.push(source.clone());
+                    }
+                }
+            }
+            None => {
+                let mut directive = vec![name.to_string()];
+                directive.extend(sources.iter().cloned());
+                directives.push(directive);
+            }
+        }
+    }
+    directives
+        .iter()
+        .map(|directive| directive.join(" "))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+fn stylesheet_rows(stylesheets: &[Asset]) -> Vec<Context> {
+    stylesheets
+        .iter()
+        .map(|asset| {
+            let mut stylesheet = Context::new();
+            stylesheet
+                .insert("path", asset.path.as_str())
+                .insert("integrity", asset.integrity.as_str());
+            stylesheet
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ShurlConfig;
+
+    fn page(link: &Link) -> String {
+        let options = PageOptions::of(&ShurlConfig::default());
+        redirect_page(link, None, &[], "./", &options).unwrap()
+    }
+
+    fn link() -> Link {
+        Link::new(
+            "talk",
+            "https://example.com/talk",
+            date::from_unix(1_700_000_000),
+        )
+    }
+
+    #[test]
+    fn countdown_pages_are_translated() {
+        let link = Link {
+            starts: Some(date::from_unix(1_800_000_000)),
+            lang: Some("de".to_string()),
+            ..link()
+        };
+        let page = page(&link);
+        assert!(page.contains(r#"<html lang="de""#));
+        assert!(page.contains("<title>Beginnt bald</title>"));
+        assert!(page.contains("Sobald es begonnen hat, führt dieser Link zu"));
+        assert!(page.contains(r#"data-in="in""#));
+        assert!(!page.contains("Starting soon"));
+    }
+
+    #[test]
+    fn analytics_go_at_the_end_of_the_head() {
+        let snippet = r#"<script src="https://stats.example.com/s.js"></script>"#;
+        let page = "<!DOCTYPE html>\n<html><head><title>t</title></head><body></body></html>";
+        let page = with_analytics(page, snippet, "", "").unwrap();
+        assert!(page.starts_with("<!DOCTYPE html>"));
+        assert!(page
+            .contains("<title>t</title><script src=\"https://stats.example.com/s.js\"></script>"));
+
+        // Templates without a `<head>` get them at the end of the body or
+        // the page, never before the doctype.
+        let page = with_analytics("<!DOCTYPE html>\n<p>hi</p></BODY>", snippet, "", "").unwrap();
+        assert!(page.starts_with("<!DOCTYPE html>\n<p>hi</p><script"));
+        assert!(page.ends_with("</BODY>"));
+        let page = with_analytics("<!DOCTYPE html>\n<p>hi</p>", snippet, "", "").unwrap();
+        assert!(page.starts_with("<!DOCTYPE html>\n<p>hi</p><script"));
+    }
+
+    #[test]
+    fn locale_pages_are_translated() {
+        let mut link = Link {
+            lang: Some("fr".to_string()),
+            ..link()
+        };
+        link.locales
+            .insert("de".to_string(), "https://example.com/de".to_string());
+        let page = page(&link);
+        assert!(page.contains(r#"<html lang="fr""#));
+        assert!(page.contains("Toute autre langue"));
+        assert!(!page.contains("Any other language"));
+    }
+}
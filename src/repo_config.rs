@@ -0,0 +1,427 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Settings kept in the repository itself, shared by everyone who publishes
+//! to it.
+
+use crate::clipd;
+use crate::config::{
+    self, CommitStyle, HttpsPolicy, IndexOrder, IndexSplit, ReferrerPolicy, ShurlConfig,
+    SlugAlphabet, SlugStyle, Theme,
+};
+use crate::date;
+use crate::lint::LintConfig;
+use crate::manifest::Link;
+use crate::target;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use url::Url;
+
+pub const REPO_CONFIG_FILE: &str = ".shurl.toml";
+/// Marks a repository as managed by shurl. Commands changing the repository
+/// refuse to run without it, so a wrong `repo_path` doesn't fill an
+/// unrelated project with pages and commits.
+pub const MARKER_FILE: &str = ".shurl-repo";
+
+/// Templates of redirect pages links choose by name, e.g.
+/// `templates/interstitial.html` for `--template interstitial`.
+pub const TEMPLATES_DIR: &str = "templates";
+
+/// Reads the template of [`TEMPLATES_DIR`] a link chose.
+pub fn load_named_template(repo_path: &Path, name: &str) -> Result<String, String> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!(
+            "invalid template name `{name}`: use letters, digits, `-` and `_`"
+        ));
+    }
+    let path = format!("{TEMPLATES_DIR}/{name}.html");
+    fs::read_to_string(repo_path.join(&path))
+        .map_err(|e| format!("failed to read template {path}: {e}"))
+}
+
+/// Whether the repository has a [`MARKER_FILE`], written by `shurl init`.
+pub fn is_marked(repo_path: &Path) -> bool {
+    repo_path.join(MARKER_FILE).is_file()
+}
+
+#[derive(Deserialize, Default)]
+pub struct RepoConfig {
+    #[serde(default, rename = "namespace")]
+    pub namespaces: Vec<Namespace>,
+    /// Tags links may have, any tag if empty.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub lint: LintConfig,
+    /// Redirect page template of links outside namespaces with a template of
+    /// their own, relative to the repository root.
+    pub template: Option<String>,
+    /// Template of the pages left in place of removed links, relative to the
+    /// repository root.
+    pub gone_template: Option<String>,
+    /// What the `404.html` of the site shows.
+    #[serde(default)]
+    pub not_found: NotFoundConfig,
+    /// Replaces `base_url` from the config of whoever publishes.
+    pub base_url: Option<String>,
+    /// Replaces `branch` from the config of whoever publishes.
+    pub branch: Option<String>,
+    /// Replaces `slug_style` from the config of whoever publishes, e.g. so
+    /// everyone derives the same names from hashes.
+    pub slug_style: Option<SlugStyle>,
+    pub slug_length: Option<usize>,
+    pub slug_alphabet: Option<SlugAlphabet>,
+    pub slug_words: Option<usize>,
+    pub slug_separator: Option<String>,
+    /// Replace `allowed_schemes` and `https_policy` from the config of
+    /// whoever publishes, so a policy holds for everyone.
+    pub allowed_schemes: Option<Vec<String>>,
+    pub https_policy: Option<HttpsPolicy>,
+    /// Replaces `strip_params` from the config of whoever publishes.
+    pub strip_params: Option<Vec<String>>,
+    /// Replace `feed_size`, `sitemap_pages`, `noindex`, `referrer_policy`,
+    /// `canonical`, `robots_txt`, `query_passthrough`, `language`,
+    /// `redirect_delay` and `expired_notice` from the config of whoever
+    /// publishes.
+    pub feed_size: Option<usize>,
+    pub sitemap_pages: Option<bool>,
+    pub noindex: Option<bool>,
+    pub referrer_policy: Option<ReferrerPolicy>,
+    pub canonical: Option<bool>,
+    pub robots_txt: Option<bool>,
+    pub query_passthrough: Option<bool>,
+    pub language: Option<String>,
+    pub redirect_delay: Option<u32>,
+    pub expired_notice: Option<bool>,
+    /// Replaces `analytics` from the config of whoever publishes, so every
+    /// publish keeps counting.
+    pub analytics: Option<String>,
+    /// Replace `index_order`, `index_page_size`, `index_split`, `theme` and
+    /// `custom_css` from the config of whoever publishes, so the pages keep
+    /// their look.
+    pub index_order: Option<IndexOrder>,
+    pub index_page_size: Option<usize>,
+    pub index_split: Option<IndexSplit>,
+    pub theme: Option<Theme>,
+    pub custom_css: Option<String>,
+    /// Replaces `commit_style` from the config of whoever publishes, for
+    /// repositories checking their commit messages.
+    pub commit_style: Option<CommitStyle>,
+}
+
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotFoundConfig {
+    /// Template replacing the built-in one, relative to the repository root.
+    pub template: Option<String>,
+    /// Whether the page links to the index.
+    pub index: bool,
+    /// Whether the page has a box for going to a link, suggesting the
+    /// existing ones.
+    pub search: bool,
+}
+
+impl Default for NotFoundConfig {
+    fn default() -> Self {
+        Self {
+            template: None,
+            index: true,
+            search: true,
+        }
+    }
+}
+
+impl NotFoundConfig {
+    pub fn load_template(&self, repo_path: &Path) -> Result<Option<String>, String> {
+        let Some(template) = &self.template else {
+            return Ok(None);
+        };
+        fs::read_to_string(repo_path.join(template))
+            .map(Some)
+            .map_err(|e| format!("failed to read template {template}: {e}"))
+    }
+}
+
+/// Settings for every slug matching `pattern`, e.g. `events/*`. `*` matches
+/// within one path segment and `**` matches across segments.
+#[derive(Deserialize)]
+pub struct Namespace {
+    pub pattern: String,
+    /// Redirect page template, relative to the repository root.
+    pub template: Option<String>,
+    #[serde(default)]
+    pub name_policy: NamePolicy,
+    /// New links expire this many days after they are created.
+    pub expiry_days: Option<u32>,
+    /// Targets must be on one of these domains or their subdomains. Any
+    /// domain is allowed if empty.
+    #[serde(default)]
+    pub allowed_domains: Vec<String>,
+}
+
+/// Which names are allowed for the last segment of a slug.
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum NamePolicy {
+    #[default]
+    Any,
+    /// Lowercase letters and digits only.
+    Lowercase,
+    /// Lowercase words of letters and digits joined by single hyphens.
+    KebabCase,
+}
+
+impl RepoConfig {
+    /// Loads `.shurl.toml` from the repository, defaults if there is none.
+    pub fn load(repo_path: &Path) -> Result<Self, String> {
+        let path = repo_path.join(REPO_CONFIG_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("failed to read {REPO_CONFIG_FILE}: {e}"))?;
+        let repo_config: Self = toml::from_str(&content)
+            .map_err(|e| format!("failed to parse {REPO_CONFIG_FILE}: {e}"))?;
+        if let Some(base_url) = &repo_config.base_url {
+            Url::parse(base_url)
+                .map_err(|e| format!("invalid base_url {base_url} in {REPO_CONFIG_FILE}: {e}"))?;
+        }
+        config::check_slug_words(
+            repo_config.slug_words.unwrap_or(1),
+            repo_config.slug_separator.as_deref().unwrap_or_default(),
+        )
+        .map_err(|e| format!("{e} in {REPO_CONFIG_FILE}"))?;
+        for scheme in repo_config.allowed_schemes.iter().flatten() {
+            target::check_scheme(scheme)
+                .map_err(|e| format!("invalid allowed_schemes in {REPO_CONFIG_FILE}: {e}"))?;
+        }
+        if repo_config.slug_length == Some(0) {
+            return Err(format!(
+                "slug_length in {REPO_CONFIG_FILE} must be at least 1"
+            ));
+        }
+        Ok(repo_config)
+    }
+
+    /// Replaces the settings of the user's config the repository has, so
+    /// shurl behaves the same for everyone publishing to it.
+    pub fn apply_to(&self, cfg: &mut ShurlConfig) {
+        if let Some(base_url) = &self.base_url {
+            cfg.base_url = Some(base_url.clone());
+        }
+        if let Some(branch) = &self.branch {
+            cfg.branch = branch.clone();
+        }
+        if let Some(slug_style) = self.slug_style {
+            cfg.slug_style = slug_style;
+        }
+        if let Some(slug_length) = self.slug_length {
+            cfg.slug_length = Some(slug_length);
+        }
+        if let Some(slug_alphabet) = self.slug_alphabet {
+            cfg.slug_alphabet = Some(slug_alphabet);
+        }
+        if let Some(slug_words) = self.slug_words {
+            cfg.slug_words = slug_words;
+        }
+        if let Some(slug_separator) = &self.slug_separator {
+            cfg.slug_separator = slug_separator.clone();
+        }
+        if let Some(allowed_schemes) = &self.allowed_schemes {
+            cfg.allowed_schemes = allowed_schemes.clone();
+        }
+        if let Some(https_policy) = self.https_policy {
+            cfg.https_policy = https_policy;
+        }
+        if let Some(strip_params) = &self.strip_params {
+            cfg.strip_params = strip_params.clone();
+        }
+        if let Some(feed_size) = self.feed_size {
+            cfg.feed_size = feed_size;
+        }
+        if let Some(sitemap_pages) = self.sitemap_pages {
+            cfg.sitemap_pages = sitemap_pages;
+        }
+        if let Some(noindex) = self.noindex {
+            cfg.noindex = noindex;
+        }
+        if let Some(referrer_policy) = self.referrer_policy {
+            cfg.referrer_policy = Some(referrer_policy);
+        }
+        if let Some(canonical) = self.canonical {
+            cfg.canonical = canonical;
+        }
+        if let Some(robots_txt) = self.robots_txt {
+            cfg.robots_txt = robots_txt;
+        }
+        if let Some(query_passthrough) = self.query_passthrough {
+            cfg.query_passthrough = query_passthrough;
+        }
+        if let Some(language) = &self.language {
+            cfg.language = language.clone();
+        }
+        if let Some(redirect_delay) = self.redirect_delay {
+            cfg.redirect_delay = redirect_delay;
+        }
+        if let Some(expired_notice) = self.expired_notice {
+            cfg.expired_notice = expired_notice;
+        }
+        if let Some(analytics) = &self.analytics {
+            cfg.analytics = Some(analytics.clone());
+        }
+        if let Some(index_order) = self.index_order {
+            cfg.index_order = index_order;
+        }
+        if let Some(index_page_size) = self.index_page_size {
+            cfg.index_page_size = index_page_size;
+        }
+        if let Some(index_split) = self.index_split {
+            cfg.index_split = index_split;
+        }
+        if let Some(theme) = self.theme {
+            cfg.theme = theme;
+        }
+        if let Some(custom_css) = &self.custom_css {
+            cfg.custom_css = Some(custom_css.clone());
+        }
+        if let Some(commit_style) = self.commit_style {
+            cfg.commit_style = commit_style;
+        }
+    }
+
+    /// Reads the redirect template of links outside namespaces, if there is
+    /// one.
+    pub fn load_template(&self, repo_path: &Path) -> Result<Option<String>, String> {
+        let Some(template) = &self.template else {
+            return Ok(None);
+        };
+        fs::read_to_string(repo_path.join(template))
+            .map(Some)
+            .map_err(|e| format!("failed to read template {template}: {e}"))
+    }
+
+    /// Reads the template of pages of removed links, if there is one.
+    pub fn load_gone_template(&self, repo_path: &Path) -> Result<Option<String>, String> {
+        let Some(template) = &self.gone_template else {
+            return Ok(None);
+        };
+        fs::read_to_string(repo_path.join(template))
+            .map(Some)
+            .map_err(|e| format!("failed to read template {template}: {e}"))
+    }
+
+    /// Checks that the tag is in the vocabulary, if there is one.
+    pub fn check_tag(&self, tag: &str) -> Result<(), String> {
+        if self.tags.is_empty() || self.tags.iter().any(|known| known == tag) {
+            return Ok(());
+        }
+        Err(format!(
+            "tag `{tag}` isn't one of `tags` in {REPO_CONFIG_FILE}: use one of {}",
+            self.tags.join(", ")
+        ))
+    }
+
+    /// The first namespace whose pattern matches the slug.
+    pub fn namespace_for(&self, slug: &str) -> Option<&Namespace> {
+        self.namespaces
+            .iter()
+            .find(|namespace| glob_matches(&namespace.pattern, slug))
+    }
+}
+
+impl Namespace {
+    /// Checks a new link against the name policy and allowed domains.
+    pub fn check(&self, slug: &str, url: &Url) -> Result<(), String> {
+        let name = slug.rsplit('/').next().unwrap_or(slug);
+        let is_word = |word: &str| {
+            !word.is_empty()
+                && word
+                    .bytes()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit())
+        };
+        let rule = match self.name_policy {
+            NamePolicy::Any => None,
+            NamePolicy::Lowercase => (!is_word(name)).then_some("lowercase letters and digits"),
+            NamePolicy::KebabCase => {
+                (!name.split('-').all(is_word)).then_some("lowercase words joined by hyphens")
+            }
+        };
+        if let Some(rule) = rule {
+            return Err(format!(
+                "invalid name `{slug}`: namespace `{}` only allows {rule}",
+                self.pattern
+            ));
+        }
+
+        if !self.allowed_domains.is_empty() && !clipd::matches_domain(url, &self.allowed_domains) {
+            return Err(format!(
+                "namespace `{}` only allows links to {}",
+                self.pattern,
+                self.allowed_domains.join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    /// Fills in the namespace defaults the link doesn't set itself.
+    pub fn apply(&self, link: &mut Link) {
+        if let (None, Some(days)) = (&link.expires, self.expiry_days) {
+            let expires = date::to_unix(&link.created) + i64::from(days) * 86400;
+            link.expires = Some(date::from_unix(expires));
+        }
+    }
+
+    /// Reads the namespace's redirect template, if it has one.
+    pub fn load_template(&self, repo_path: &Path) -> Result<Option<String>, String> {
+        let Some(template) = &self.template else {
+            return Ok(None);
+        };
+        fs::read_to_string(repo_path.join(template))
+            .map(Some)
+            .map_err(|e| {
+                format!(
+                    "failed to read template {template} of namespace `{}`: {e}",
+                    self.pattern
+                )
+            })
+    }
+}
+
+fn glob_matches(pattern: &str, slug: &str) -> bool {
+    if let Some(rest) = pattern.strip_prefix("**") {
+        return (0..=slug.len())
+            .filter(|&i| slug.is_char_boundary(i))
+            .any(|i| glob_matches(rest, &slug[i..]));
+    }
+    if let Some(rest) = pattern.strip_prefix('*') {
+        let segment_end = slug.find('/').unwrap_or(slug.len());
+        return (0..=segment_end)
+            .filter(|&i| slug.is_char_boundary(i))
+            .any(|i| glob_matches(rest, &slug[i..]));
+    }
+    match (pattern.chars().next(), slug.chars().next()) {
+        (Some(p), Some(s)) if p == s => {
+            glob_matches(&pattern[p.len_utf8()..], &slug[s.len_utf8()..])
+        }
+        (None, None) => true,
+        _ => false,
+    }
+}
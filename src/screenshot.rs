@@ -0,0 +1,127 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Screenshots of link targets, shown when hovering a link on the index so
+//! links can be recognized at a glance.
+
+use crate::config::ShurlConfig;
+use crate::manifest::Link;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+
+pub const SHOTS_DIR: &str = "shots";
+
+/// Headless Chrome builds tried in order when `screenshot_command` isn't set.
+const CHROME_COMMANDS: &[&str] = &[
+    "chromium",
+    "chromium-browser",
+    "google-chrome",
+    "google-chrome-stable",
+    "chrome",
+];
+
+/// Arguments of Chrome, with the placeholders of `screenshot_command`.
+const CHROME_ARGS: &[&str] = &[
+    "--headless",
+    "--disable-gpu",
+    "--hide-scrollbars",
+    "--window-size=1280,800",
+    "--screenshot={output}",
+    "{url}",
+];
+
+/// Path of a link's screenshot, relative to the repository root.
+pub fn path(slug: &str) -> String {
+    format!("{SHOTS_DIR}/{slug}.png")
+}
+
+/// Saves a screenshot of the link's target with the configured renderer,
+/// replacing any earlier one.
+pub fn capture(repo_path: &Path, cfg: &ShurlConfig, link: &Link) -> Result<(), String> {
+    let relative = path(&link.slug);
+    let output = repo_path.join(&relative);
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+    }
+    let output_path = output.to_string_lossy();
+    let fill = |arg: &str| {
+        arg.replace("{url}", &link.target)
+            .replace("{output}", &output_path)
+    };
+
+    let status = match cfg.screenshot_command.split_first() {
+        Some((program, args)) => run(program, args.iter().map(|arg| fill(arg)))
+            .map_err(|e| format!("failed to run {program}: {e}"))?,
+        None => CHROME_COMMANDS
+            .iter()
+            .find_map(|program| run(program, CHROME_ARGS.iter().map(|arg| fill(arg))).ok())
+            .ok_or_else(|| {
+                "failed to take a screenshot: install Chromium or Chrome, or set \
+                 `screenshot_command` in the config"
+                    .to_string()
+            })?,
+    };
+    if !status.success() {
+        return Err(format!(
+            "failed to take a screenshot of {}: the renderer exited with {status}",
+            link.target
+        ));
+    }
+    if !output.is_file() {
+        return Err(format!("the renderer didn't write {relative}"));
+    }
+    Ok(())
+}
+
+fn run(program: &str, args: impl Iterator<Item = String>) -> std::io::Result<ExitStatus> {
+    Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+}
+
+/// Removes the screenshots of slugs not in `keep`, and directories left
+/// empty.
+pub fn remove_stale(repo_path: &Path, keep: &HashSet<&str>) -> Result<(), String> {
+    remove_stale_in(&repo_path.join(SHOTS_DIR), "", keep)
+}
+
+fn remove_stale_in(dir: &Path, prefix: &str, keep: &HashSet<&str>) -> Result<(), String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(());
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if path.is_dir() {
+            remove_stale_in(&path, &format!("{prefix}{name}/"), keep)?;
+            continue;
+        }
+        let slug = format!("{prefix}{}", name.strip_suffix(".png").unwrap_or(&name));
+        if !keep.contains(slug.as_str()) {
+            fs::remove_file(&path)
+                .map_err(|e| format!("failed to remove {}: {e}", path.display()))?;
+        }
+    }
+    // Only removes the directory if nothing is left in it.
+    let _ = fs::remove_dir(dir);
+    Ok(())
+}
@@ -0,0 +1,64 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Finding links by what they're called, where they lead, what they're for
+//! and how they're tagged.
+
+use crate::manifest::Link;
+use crate::regex::Regex;
+use crate::target;
+
+pub enum Query {
+    /// Text anywhere in a field, ignoring case.
+    Text(String),
+    /// A regular expression matching part of a field, ignoring case.
+    Regex(Regex),
+}
+
+impl Query {
+    pub fn new(query: &str, regex: bool) -> Result<Self, String> {
+        let query = query.to_lowercase();
+        if regex {
+            // The fields are lowercased too, so the pattern matches either
+            // case.
+            Regex::new(&query).map(Query::Regex)
+        } else {
+            Ok(Query::Text(query))
+        }
+    }
+
+    /// Whether the slug, a target, the description or a tag of the link
+    /// matches. Targets match as stored and as shown, e.g. with the domain
+    /// in Unicode.
+    pub fn matches(&self, link: &Link) -> bool {
+        let targets = std::iter::once(&link.target).chain(link.locales.values());
+        let mut fields = vec![link.slug.clone()];
+        for target in targets {
+            fields.push(target.clone());
+            fields.push(target::display(target));
+        }
+        fields.extend(link.description.clone());
+        fields.extend(link.tags.iter().cloned());
+
+        fields.iter().any(|field| {
+            let field = field.to_lowercase();
+            match self {
+                Query::Text(text) => field.contains(text.as_str()),
+                Query::Regex(regex) => regex.captures(&field).is_some(),
+            }
+        })
+    }
+}
@@ -0,0 +1,135 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! `POST /api/links` creates a link, commits and pushes it, for minting links
+//! from scripts or a phone. Requests need `Authorization: Bearer <api_token>`.
+//!
+//! The body is a JSON object with `url` and optionally `name` and `status`,
+//! the answer has the `slug` and, with `base_url` set, the `short_url`. It's
+//! sent once the link was committed as part of a batch, see [`super::queue`].
+
+use super::queue::Queued;
+use super::{NewLink, Request, Response, Server};
+use crate::config::ShurlConfig;
+use crate::json::{self, Value};
+use std::env;
+use std::sync::mpsc::{self, Sender};
+
+/// `api_token` from the config or `$SHURL_API_TOKEN`.
+pub fn token(cfg: &ShurlConfig) -> Option<String> {
+    cfg.api_token
+        .clone()
+        .or_else(|| env::var("SHURL_API_TOKEN").ok())
+        .filter(|token| !token.is_empty())
+}
+
+pub fn respond(server: &Server, queue: &Sender<Queued>, path: &str, request: &Request) -> Response {
+    if path != "/api/links" {
+        return error(404, "no such endpoint");
+    }
+    if request.method != "POST" {
+        let mut response = error(405, "use POST");
+        response
+            .headers
+            .push(("Allow".to_string(), "POST".to_string()));
+        return response;
+    }
+    let cfg = server.cfg();
+    if !authorized(&cfg, request) {
+        let mut response = error(401, "missing or wrong bearer token");
+        response
+            .headers
+            .push(("WWW-Authenticate".to_string(), "Bearer".to_string()));
+        return response;
+    }
+
+    let new_link = match parse_new_link(&request.body) {
+        Ok(new_link) => new_link,
+        Err(e) => return error(400, &e),
+    };
+    if let Some(name) = &new_link.name {
+        if server.links().manifest.get(name).is_some() {
+            return error(409, &format!("`{name}` already exists"));
+        }
+    }
+
+    let (reply, result) = mpsc::channel();
+    if queue.send((new_link, reply)).is_err() {
+        return error(500, "the link queue stopped");
+    }
+    let slug = match result.recv() {
+        Ok(Ok(slug)) => slug,
+        Ok(Err(e)) => return error(400, &e),
+        Err(_) => return error(500, "the link queue stopped"),
+    };
+
+    let mut body = Value::object();
+    body.set("slug", slug.as_str())
+        .set("short_url", cfg.short_url(&slug));
+    Response::json(201, &body)
+}
+
+fn authorized(cfg: &ShurlConfig, request: &Request) -> bool {
+    let (Some(token), Some(given)) = (
+        token(cfg),
+        request
+            .header("authorization")
+            .and_then(|value| value.strip_prefix("Bearer ")),
+    ) else {
+        return false;
+    };
+    // Compares every byte so the time taken doesn't reveal the token.
+    token.len() == given.len()
+        && token
+            .bytes()
+            .zip(given.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn parse_new_link(body: &str) -> Result<NewLink, String> {
+    let value = json::parse(body).map_err(|e| format!("invalid JSON body: {e}"))?;
+    let url = value
+        .get("url")
+        .and_then(Value::as_str)
+        .ok_or_else(|| "`url` is required".to_string())?;
+    let name = match value.get("name") {
+        None | Some(Value::Null) => None,
+        Some(name) => Some(
+            name.as_str()
+                .ok_or_else(|| "`name` must be a string".to_string())?
+                .to_string(),
+        ),
+    };
+    let status = match value.get("status") {
+        None | Some(Value::Null) => None,
+        Some(Value::Number(status)) if status.fract() == 0.0 && (0.0..=999.0).contains(status) => {
+            Some(*status as u16)
+        }
+        Some(_) => return Err("`status` must be a status code".to_string()),
+    };
+    Ok(NewLink {
+        url: url.to_string(),
+        name,
+        status,
+    })
+}
+
+fn error(status: u16, message: &str) -> Response {
+    let mut body = Value::object();
+    body.set("error", message);
+    Response::json(status, &body)
+}
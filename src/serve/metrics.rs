@@ -0,0 +1,120 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Prometheus metrics of `shurl serve`, exposed at `/metrics`.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds of the latency histogram buckets, in seconds.
+const BUCKETS: [f64; 10] = [
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 1.0,
+];
+
+#[derive(Default)]
+pub struct Metrics {
+    redirects: Mutex<BTreeMap<String, u64>>,
+    not_found: AtomicU64,
+    gone: AtomicU64,
+    /// Requests that took at most the matching bound of `BUCKETS`, the
+    /// cumulative counts are added up when rendering.
+    buckets: [AtomicU64; BUCKETS.len()],
+    requests: AtomicU64,
+    duration_micros: AtomicU64,
+}
+
+impl Metrics {
+    pub fn redirect(&self, slug: &str) {
+        *self
+            .redirects
+            .lock()
+            .unwrap()
+            .entry(slug.to_string())
+            .or_default() += 1;
+    }
+
+    pub fn not_found(&self) {
+        self.not_found.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn gone(&self) {
+        self.gone.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(bucket) = BUCKETS.iter().position(|&bound| seconds <= bound) {
+            self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.duration_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// The metrics in Prometheus' text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::from(
+            "# HELP shurl_redirects_total Redirects served per slug.\n\
+            # TYPE shurl_redirects_total counter\n",
+        );
+        for (slug, count) in self.redirects.lock().unwrap().iter() {
+            out.push_str(&format!(
+                "shurl_redirects_total{{slug=\"{}\"}} {count}\n",
+                escape_label(slug)
+            ));
+        }
+
+        out.push_str(&format!(
+            "# HELP shurl_not_found_total Requests for paths without a link.\n\
+            # TYPE shurl_not_found_total counter\n\
+            shurl_not_found_total {}\n\
+            # HELP shurl_gone_total Requests for removed links.\n\
+            # TYPE shurl_gone_total counter\n\
+            shurl_gone_total {}\n",
+            self.not_found.load(Ordering::Relaxed),
+            self.gone.load(Ordering::Relaxed)
+        ));
+
+        out.push_str(
+            "# HELP shurl_request_duration_seconds Time taken to answer requests.\n\
+            # TYPE shurl_request_duration_seconds histogram\n",
+        );
+        let mut cumulative = 0;
+        for (bound, count) in BUCKETS.iter().zip(&self.buckets) {
+            cumulative += count.load(Ordering::Relaxed);
+            out.push_str(&format!(
+                "shurl_request_duration_seconds_bucket{{le=\"{bound}\"}} {cumulative}\n"
+            ));
+        }
+        let requests = self.requests.load(Ordering::Relaxed);
+        out.push_str(&format!(
+            "shurl_request_duration_seconds_bucket{{le=\"+Inf\"}} {requests}\n\
+            shurl_request_duration_seconds_sum {}\n\
+            shurl_request_duration_seconds_count {requests}\n",
+            self.duration_micros.load(Ordering::Relaxed) as f64 / 1e6
+        ));
+        out
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,609 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A small HTTP server answering with real redirects straight from the
+//! manifest, for hosting without a static site platform and for testing.
+//! With `--api` it also creates links, see [`api`].
+//!
+//! `/healthz` answers as long as the server runs. `/readyz` and everything
+//! else answer 503 until the startup self-check passed, except for the
+//! Prometheus metrics at `/metrics`.
+//!
+//! Changes to the config are picked up while running, see [`Reloader`], and
+//! links added or removed with the CLI meanwhile are served right away.
+//!
+//! A fixed number of workers answer the connections, so clients opening
+//! many of them can't use up the threads of the machine.
+
+mod api;
+mod metrics;
+mod queue;
+
+use crate::backend;
+use crate::config::ShurlConfig;
+use crate::health;
+use crate::json;
+use crate::manifest::{Manifest, MANIFEST_FILE};
+use crate::output::Colorize;
+use crate::reload::{self, Reloader};
+use crate::render;
+use crate::repo_config::RepoConfig;
+use crate::stats::Recorder;
+use crate::target;
+use crate::trash::{self, Trash};
+use metrics::Metrics;
+use queue::Queued;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender, TrySendError};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Longest request line or header line we accept.
+const MAX_LINE: usize = 8192;
+/// Most header lines we accept.
+const MAX_HEADERS: usize = 100;
+/// Largest request body we accept.
+const MAX_BODY: usize = 64 * 1024;
+/// Connections answered at once.
+const WORKERS: usize = 16;
+/// Connections waiting for a worker, more are answered 503 right away.
+const MAX_WAITING: usize = 64;
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// Header names are lowercase.
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Response {
+    fn new(status: u16, content_type: &str, body: String) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".to_string(), content_type.to_string())],
+            body,
+        }
+    }
+
+    fn json(status: u16, body: &json::Value) -> Self {
+        Self::new(status, "application/json", body.to_string() + "\n")
+    }
+
+    fn redirect(status: u16, location: &str) -> Self {
+        let mut response = Self::new(status, "text/plain; charset=utf-8", String::new());
+        response
+            .headers
+            .push(("Location".to_string(), location.to_string()));
+        response
+    }
+}
+
+/// A link to create through the API.
+pub struct NewLink {
+    pub url: String,
+    pub name: Option<String>,
+    pub status: Option<u16>,
+}
+
+/// The links being served. Reloaded after the API changed them, and when
+/// the files they're loaded from changed.
+struct Links {
+    manifest: Manifest,
+    gone: Vec<String>,
+    /// When `links.toml` and the trash were changed as they were loaded.
+    modified: [Option<SystemTime>; 2],
+}
+
+impl Links {
+    fn load(repo_path: &Path) -> Result<Self, String> {
+        let modified = Self::modified(repo_path);
+        let manifest = Manifest::load(repo_path)?;
+        let gone = Trash::load(repo_path)?
+            .gone_slugs(&manifest)
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        Ok(Self {
+            manifest,
+            gone,
+            modified,
+        })
+    }
+
+    fn modified(repo_path: &Path) -> [Option<SystemTime>; 2] {
+        [
+            reload::modified(&repo_path.join(MANIFEST_FILE)),
+            reload::modified(&trash::trash_path(repo_path)),
+        ]
+    }
+}
+
+struct Server<'a> {
+    cfg: RwLock<Arc<ShurlConfig>>,
+    repo_path: &'a Path,
+    links: RwLock<Links>,
+    hits: Recorder,
+    metrics: Metrics,
+    ready: AtomicBool,
+    /// Links waiting to be created, if the API is enabled.
+    queue: Option<Sender<Queued>>,
+}
+
+/// Serves the links until the process is stopped. `create` enables the API:
+/// it's given batches of links to create in one commit and returns the slug
+/// of each, or why it couldn't be created.
+pub fn run<F>(
+    cfg: &ShurlConfig,
+    repo_path: &Path,
+    address: &str,
+    port: u16,
+    create: Option<F>,
+) -> Result<(), String>
+where
+    F: FnMut(&ShurlConfig, Vec<NewLink>) -> Vec<Result<String, String>> + Send,
+{
+    cfg.validate()?;
+    if create.is_some() && api::token(cfg).is_none() {
+        return Err("set `api_token` in the config or $SHURL_API_TOKEN for --api".to_string());
+    }
+    let (queue, queued) = mpsc::channel();
+    let server = Server {
+        cfg: RwLock::new(Arc::new(cfg.clone())),
+        repo_path,
+        links: RwLock::new(Links::load(repo_path)?),
+        hits: Recorder::open(repo_path)?,
+        metrics: Metrics::default(),
+        ready: AtomicBool::new(false),
+        queue: create.is_some().then_some(queue),
+    };
+    let listener = TcpListener::bind((address, port))
+        .map_err(|e| format!("failed to listen on {address}:{port}: {e}"))?;
+    println!(
+        "{} {}",
+        "Info:".green(),
+        format!(
+            "serving {} links on http://{address}:{port}/",
+            server.links().manifest.links.len()
+        )
+        .bold()
+    );
+
+    let (connections, waiting) = mpsc::sync_channel::<TcpStream>(MAX_WAITING);
+    let waiting = Mutex::new(waiting);
+    thread::scope(|scope| {
+        let server = &server;
+        scope.spawn(move || server.become_ready());
+        scope.spawn(move || server.watch_config());
+        if let Some(create) = create {
+            scope.spawn(move || queue::run(server, queued, create));
+        }
+
+        let waiting = &waiting;
+        for _ in 0..WORKERS {
+            scope.spawn(move || loop {
+                let Ok(stream) = waiting.lock().unwrap().recv() else {
+                    return;
+                };
+                if let Err(e) = server.handle(stream) {
+                    eprintln!("{} {}", "Warning:".yellow(), e.bold());
+                }
+            });
+        }
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            if let Err(TrySendError::Full(mut stream)) = connections.try_send(stream) {
+                let _ = stream.set_write_timeout(Some(Duration::from_secs(1)));
+                let _ = write_response(&mut stream, &busy("too many connections\n"), false);
+            }
+        }
+    });
+    Ok(())
+}
+
+impl Server<'_> {
+    /// The current config. Requests keep the one they started with even if
+    /// it's reloaded meanwhile.
+    fn cfg(&self) -> Arc<ShurlConfig> {
+        self.cfg.read().unwrap().clone()
+    }
+
+    /// The links, loaded again first if `links.toml` or the trash changed
+    /// since, e.g. as a link was added with the CLI.
+    fn links(&self) -> RwLockReadGuard<'_, Links> {
+        let modified = Links::modified(self.repo_path);
+        if self.links.read().unwrap().modified != modified {
+            let mut links = self.links.write().unwrap();
+            match Links::load(self.repo_path) {
+                Ok(new) => *links = new,
+                // Likely written right now, it's changed again once done.
+                Err(e) => {
+                    eprintln!("{} {}", "Warning:".yellow(), e.bold());
+                    links.modified = modified;
+                }
+            }
+        }
+        self.links.read().unwrap()
+    }
+
+    fn watch_config(&self) {
+        let mut reloader = Reloader::new(self.repo_path);
+        loop {
+            thread::sleep(Duration::from_secs(2));
+            if let Some(cfg) = reloader.poll(&self.cfg()) {
+                *self.cfg.write().unwrap() = Arc::new(cfg);
+            }
+        }
+    }
+
+    /// Runs the self-check until it passes. The API needs to push, so that
+    /// is checked too when it's enabled.
+    fn become_ready(&self) {
+        loop {
+            match health::self_check(&self.cfg(), self.repo_path, self.queue.is_some()) {
+                Ok(()) => {
+                    self.ready.store(true, Ordering::Relaxed);
+                    println!("{} {}", "Info:".green(), "ready".bold());
+                    return;
+                }
+                Err(e) => {
+                    eprintln!(
+                        "{} {}",
+                        "Warning:".yellow(),
+                        format!("not ready, checking again in 30 seconds: {e}").bold()
+                    );
+                    thread::sleep(Duration::from_secs(30));
+                }
+            }
+        }
+    }
+
+    fn handle(&self, mut stream: TcpStream) -> Result<(), String> {
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .map_err(|e| format!("failed to set timeout: {e}"))?;
+
+        let response = match read_request(&mut BufReader::new(&stream)) {
+            Ok(request) => {
+                let start = Instant::now();
+                let response = self.respond(&request);
+                self.metrics.observe(start.elapsed());
+                println!("{} {} {}", request.method, request.path, response.status);
+                write_response(&mut stream, &response, request.method == "HEAD")
+            }
+            Err(e) => write_response(
+                &mut stream,
+                &Response::new(400, "text/plain; charset=utf-8", format!("{e}\n")),
+                false,
+            ),
+        };
+        response.map_err(|e| format!("failed to write response: {e}"))
+    }
+
+    fn respond(&self, request: &Request) -> Response {
+        let path = request.path.split(['?', '#']).next().unwrap_or_default();
+        let ready = self.ready.load(Ordering::Relaxed);
+        match path {
+            "/healthz" => return Response::new(200, "text/plain", "ok\n".to_string()),
+            "/metrics" => {
+                return Response::new(200, "text/plain; version=0.0.4", self.metrics.render())
+            }
+            "/readyz" if ready => return Response::new(200, "text/plain", "ready\n".to_string()),
+            _ if !ready => return busy("not ready\n"),
+            _ => {}
+        }
+        if let Some(queue) = &self.queue {
+            if path.starts_with("/api/") {
+                return api::respond(self, queue, path, request);
+            }
+        }
+
+        if request.method != "GET" && request.method != "HEAD" {
+            let mut response = Response::new(
+                405,
+                "text/plain; charset=utf-8",
+                "method not allowed\n".to_string(),
+            );
+            response
+                .headers
+                .push(("Allow".to_string(), "GET, HEAD".to_string()));
+            return response;
+        }
+
+        if path == "/robots.txt" {
+            if let Ok(robots) = fs::read_to_string(self.repo_path.join("robots.txt")) {
+                return Response::new(200, "text/plain; charset=utf-8", robots);
+            }
+        }
+        if path == "/" {
+            if let Ok(index) = fs::read_to_string(self.repo_path.join("index.html")) {
+                return Response::new(200, "text/html; charset=utf-8", index);
+            }
+        }
+        let slug = path.trim_start_matches('/').trim_end_matches('/');
+        let slug = slug.strip_suffix(".html").unwrap_or(slug);
+        let cfg = self.cfg();
+        let links = self.links();
+        if let Some(link) = links.manifest.get(slug) {
+            if link.needs_page() {
+                let page =
+                    render::redirect_page(link, None, &[], "/", &render::PageOptions::of(&cfg))
+                        .unwrap_or_default();
+                return Response::new(200, "text/html; charset=utf-8", page);
+            }
+            let referrer = request.header("referer").filter(|_| cfg.stats_referrers);
+            if let Err(e) = self.hits.record(slug, referrer) {
+                eprintln!("{} {}", "Warning:".yellow(), e.bold());
+            }
+            self.metrics.redirect(slug);
+            let target = match request.path.split_once('?') {
+                Some((_, query)) if cfg.query_passthrough => {
+                    target::with_query(&link.target, query.split('#').next().unwrap_or_default())
+                }
+                _ => link.target.clone(),
+            };
+            let mut response = Response::redirect(backend::status_of(&cfg, link), &target);
+            if let Some(referrer) = link.referrer.or(cfg.referrer_policy) {
+                response
+                    .headers
+                    .push(("Referrer-Policy".to_string(), referrer.as_str().to_string()));
+            }
+            return response;
+        }
+        if links.gone.iter().any(|gone| gone == slug) {
+            self.metrics.gone();
+            let template = RepoConfig::load(self.repo_path)
+                .and_then(|repo_config| repo_config.load_gone_template(self.repo_path))
+                .unwrap_or_default();
+            let page = render::gone_page(Some(slug), "/", template.as_deref()).unwrap_or_default();
+            return Response::new(410, "text/html; charset=utf-8", page);
+        }
+
+        self.metrics.not_found();
+        let not_found = fs::read_to_string(self.repo_path.join("404.html"))
+            .or_else(|_| render::not_found_page(&[], &[], "/", true, false, None))
+            .unwrap_or_default();
+        Response::new(404, "text/html; charset=utf-8", not_found)
+    }
+}
+
+/// 503 asking to come back in 30 seconds.
+fn busy(message: &str) -> Response {
+    let mut response = Response::new(503, "text/plain", message.to_string());
+    response
+        .headers
+        .push(("Retry-After".to_string(), "30".to_string()));
+    response
+}
+
+fn read_request(reader: &mut impl BufRead) -> Result<Request, String> {
+    let request_line = read_line(reader)?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err("malformed request line".to_string());
+    };
+    let mut request = Request {
+        method: method.to_string(),
+        path: target.to_string(),
+        headers: vec![],
+        body: String::new(),
+    };
+
+    loop {
+        let line = read_line(reader)?;
+        if line.is_empty() {
+            break;
+        }
+        if request.headers.len() == MAX_HEADERS {
+            return Err("too many headers".to_string());
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            request
+                .headers
+                .push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+    }
+
+    if let Some(length) = request.header("content-length") {
+        let length: usize = length
+            .parse()
+            .map_err(|_| "invalid content-length".to_string())?;
+        if length > MAX_BODY {
+            return Err("request body is too large".to_string());
+        }
+        let mut body = vec![0; length];
+        reader
+            .read_exact(&mut body)
+            .map_err(|e| format!("failed to read request body: {e}"))?;
+        request.body = String::from_utf8(body).map_err(|_| "body is not utf-8".to_string())?;
+    }
+    Ok(request)
+}
+
+fn read_line(reader: &mut impl BufRead) -> Result<String, String> {
+    let mut line = vec![];
+    let read = reader
+        .by_ref()
+        .take(MAX_LINE as u64)
+        .read_until(b'\n', &mut line)
+        .map_err(|e| format!("failed to read request: {e}"))?;
+    if read == MAX_LINE && line.last() != Some(&b'\n') {
+        return Err("request line or header is too long".to_string());
+    }
+    if read == 0 || line.last() != Some(&b'\n') {
+        return Err("incomplete request".to_string());
+    }
+    let line = String::from_utf8(line).map_err(|_| "request is not utf-8".to_string())?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn write_response(stream: &mut TcpStream, response: &Response, head: bool) -> std::io::Result<()> {
+    let mut out = format!(
+        "HTTP/1.1 {} {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        reason(response.status),
+        response.body.len()
+    );
+    for (name, value) in &response.headers {
+        out.push_str(&format!("{name}: {value}\r\n"));
+    }
+    out.push_str("\r\n");
+    if !head {
+        out.push_str(&response.body);
+    }
+    stream.write_all(out.as_bytes())?;
+    stream.flush()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        301 => "Moved Permanently",
+        302 => "Found",
+        303 => "See Other",
+        307 => "Temporary Redirect",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        404 => "Not Found",
+        401 => "Unauthorized",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        410 => "Gone",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(request: &[u8]) -> Result<Request, String> {
+        read_request(&mut BufReader::new(request))
+    }
+
+    #[test]
+    fn requests_are_parsed() {
+        let request = parse(
+            b"POST /api/links?x=1 HTTP/1.1\r\nHost: s.example.com\r\nContent-Type:application/json\r\nContent-Length: 2\r\n\r\n{}",
+        )
+        .unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/api/links?x=1");
+        assert_eq!(request.header("host"), Some("s.example.com"));
+        assert_eq!(request.header("content-type"), Some("application/json"));
+        assert_eq!(request.body, "{}");
+
+        let request = parse(b"GET / HTTP/1.0\n\n").unwrap();
+        assert_eq!(request.header("content-length"), None);
+        assert_eq!(request.body, "");
+    }
+
+    #[test]
+    fn bad_request_lines_fail() {
+        assert_eq!(
+            parse(b"GET\r\n\r\n").err().unwrap(),
+            "malformed request line"
+        );
+        assert_eq!(parse(b"\r\n\r\n").err().unwrap(), "malformed request line");
+        assert_eq!(parse(b"").err().unwrap(), "incomplete request");
+        assert_eq!(
+            parse(b"GET / HTTP/1.1\r\nHost: a").err().unwrap(),
+            "incomplete request"
+        );
+        assert_eq!(
+            parse(b"GET /\xff HTTP/1.1\r\n\r\n").err().unwrap(),
+            "request is not utf-8"
+        );
+    }
+
+    #[test]
+    fn oversized_headers_fail() {
+        let long = format!("GET / HTTP/1.1\r\nX-Long: {}\r\n\r\n", "a".repeat(MAX_LINE));
+        assert_eq!(
+            parse(long.as_bytes()).err().unwrap(),
+            "request line or header is too long"
+        );
+        let long_path = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE));
+        assert!(parse(long_path.as_bytes()).is_err());
+
+        let many = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "X-A: b\r\n".repeat(MAX_HEADERS + 1)
+        );
+        assert_eq!(parse(many.as_bytes()).err().unwrap(), "too many headers");
+        let enough = format!("GET / HTTP/1.1\r\n{}\r\n", "X-A: b\r\n".repeat(MAX_HEADERS));
+        assert!(parse(enough.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn bodies_are_limited() {
+        let too_large = format!(
+            "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY + 1
+        );
+        assert_eq!(
+            parse(too_large.as_bytes()).err().unwrap(),
+            "request body is too large"
+        );
+        assert_eq!(
+            parse(b"POST / HTTP/1.1\r\nContent-Length: -1\r\n\r\n")
+                .err()
+                .unwrap(),
+            "invalid content-length"
+        );
+        assert!(parse(b"POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort")
+            .err()
+            .unwrap()
+            .starts_with("failed to read request body"));
+        assert_eq!(
+            parse(b"POST / HTTP/1.1\r\nContent-Length: 1\r\n\r\n\xff")
+                .err()
+                .unwrap(),
+            "body is not utf-8"
+        );
+
+        let largest = format!(
+            "POST / HTTP/1.1\r\nContent-Length: {MAX_BODY}\r\n\r\n{}",
+            "a".repeat(MAX_BODY)
+        );
+        assert_eq!(parse(largest.as_bytes()).unwrap().body.len(), MAX_BODY);
+    }
+}
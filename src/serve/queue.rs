@@ -0,0 +1,79 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Links created through the API are committed by a single thread, in
+//! batches of up to `api_batch_size` links or whatever arrived within
+//! `api_batch_ms` of the first one. Bursts of requests then make one commit
+//! and one push instead of racing each other for the repository.
+//!
+//! Each batch is created with the config current when it started.
+
+use super::{Links, NewLink, Server};
+use crate::config::ShurlConfig;
+use crate::output::Colorize;
+use std::collections::HashSet;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+/// A link to create and where to send its slug.
+pub type Queued = (NewLink, Sender<Result<String, String>>);
+
+pub fn run<F>(server: &Server, queued: Receiver<Queued>, mut create: F)
+where
+    F: FnMut(&ShurlConfig, Vec<NewLink>) -> Vec<Result<String, String>>,
+{
+    while let Ok(first) = queued.recv() {
+        let cfg = server.cfg();
+        let batch_size = cfg.api_batch_size.max(1);
+        let wait = Duration::from_millis(cfg.api_batch_ms);
+        let mut batch = vec![first];
+        let deadline = Instant::now() + wait;
+        while batch.len() < batch_size {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match queued.recv_timeout(timeout) {
+                Ok(next) => batch.push(next),
+                Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        // Two requests for the same name in one batch: the first one wins,
+        // like it would have if they were committed one after the other.
+        let mut names = HashSet::new();
+        let mut links = vec![];
+        let mut replies = vec![];
+        for (link, reply) in batch {
+            match &link.name {
+                Some(name) if !names.insert(name.clone()) => {
+                    let _ = reply.send(Err(format!("`{name}` already exists")));
+                }
+                _ => {
+                    links.push(link);
+                    replies.push(reply);
+                }
+            }
+        }
+
+        let results = create(&cfg, links);
+        // Serve the new links before answering, so they work right away.
+        match Links::load(server.repo_path) {
+            Ok(links) => *server.links.write().unwrap() = links,
+            Err(e) => eprintln!("{} {}", "Warning:".yellow(), e.bold()),
+        }
+        for (reply, result) in replies.into_iter().zip(results) {
+            let _ = reply.send(result);
+        }
+    }
+}
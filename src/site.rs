@@ -0,0 +1,789 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use crate::assets;
+use crate::backend;
+use crate::bundle::Bundles;
+use crate::config::{Backend, IndexOrder, IndexSplit, ShurlConfig};
+use crate::date;
+use crate::federation::{self, Federation};
+use crate::hash;
+use crate::issue::Issue;
+use crate::json;
+use crate::manifest::{Link, Manifest};
+use crate::output;
+use crate::pages::{self, Layout};
+use crate::provenance::{self, Source};
+use crate::qr;
+use crate::render;
+use crate::repo_config::{self, RepoConfig};
+use crate::screenshot;
+use crate::trash::Trash;
+use shellexpand::tilde;
+use std::collections::hash_map::{Entry, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::path::Path;
+use url::Url;
+
+pub const DIRECTORY_FILE: &str = "directory.html";
+pub const NOT_FOUND_FILE: &str = "404.html";
+pub const SITEMAP_FILE: &str = "sitemap.xml";
+pub const FEED_FILE: &str = "feed.xml";
+pub const ROBOTS_FILE: &str = "robots.txt";
+pub const LINKS_JSON_FILE: &str = "links.json";
+/// Pages of the index after the first, e.g. `index/2.html`.
+pub const INDEX_PAGES_DIR: &str = "index";
+/// Maps every generated file to the integrity hash of its content, so a
+/// deploy can be checked against the build it came from.
+pub const BUILD_MANIFEST_FILE: &str = "build-manifest.json";
+
+/// Regenerates every file derived from the manifest, so all backends always
+/// agree with it and with each other, and records the provenance of the
+/// result. Returns warnings for the user.
+pub fn build(
+    repo_path: &Path,
+    cfg: &ShurlConfig,
+    manifest: &Manifest,
+    source: &Source,
+) -> Result<Vec<String>, String> {
+    let started = date::now();
+    let default_layout = Layout::from_config(cfg.clean_urls);
+    // The index of a directory such as `docs/` is at `docs/index.html`, so
+    // it isn't the page of a link named `docs`.
+    let previous_namespaces = previous_namespaces(repo_path);
+    // Links keep the layout they were created with so already shared URLs
+    // don't break when `clean_urls` is toggled.
+    let layout_of = |slug: &str| match pages::find_page(repo_path, slug) {
+        Some((Layout::Directory, _)) if previous_namespaces.contains(slug) => default_layout,
+        Some((layout, _)) => layout,
+        None => default_layout,
+    };
+
+    let trash = Trash::load(repo_path)?;
+    let gone = trash.gone_slugs(manifest);
+
+    let mut generated = vec![];
+    let custom_css = match &cfg.custom_css {
+        Some(path) => Some(
+            fs::read_to_string(repo_path.join(tilde(path).as_ref()))
+                .map_err(|e| format!("failed to read custom_css {path}: {e}"))?,
+        ),
+        None => None,
+    };
+    let stylesheets = assets::write_stylesheets(repo_path, cfg.theme, custom_css.as_deref())?;
+    generated.extend(stylesheets.iter().map(|asset| asset.path.clone()));
+
+    let repo_config = RepoConfig::load(repo_path)?;
+    let mut templates = HashMap::new();
+    let mut named_templates = HashMap::new();
+    let default_template = repo_config.load_template(repo_path)?;
+    let page_options = render::PageOptions::of(cfg);
+    let gone_template = repo_config.load_gone_template(repo_path)?;
+    for link in &manifest.links {
+        // Links to an event need their countdown page whatever the backends,
+        // links with targets per language the page picking one.
+        if !cfg.writes_html_pages() && link.starts.is_none() && link.locales.is_empty() {
+            continue;
+        }
+        let template = match (&link.template, repo_config.namespace_for(&link.slug)) {
+            (Some(name), _) => Some(&*match named_templates.entry(name.as_str()) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    entry.insert(repo_config::load_named_template(repo_path, name)?)
+                }
+            }),
+            (None, Some(namespace)) => match templates.entry(namespace.pattern.as_str()) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(namespace.load_template(repo_path)?),
+            }
+            .as_ref(),
+            (None, None) => None,
+        };
+        let template = template.or(default_template.as_ref());
+        let layout = layout_of(&link.slug);
+        let mut content = render::redirect_page(
+            link,
+            template.map(String::as_str),
+            &stylesheets,
+            &root_href(&link.slug, layout),
+            &page_options,
+        )?;
+        if let Some(analytics) = &cfg.analytics {
+            content = render::with_analytics(&content, analytics, &link.slug, &link.target)?;
+        }
+        pages::write_page(repo_path, &link.slug, layout, &content)?;
+        let page_path = layout.page_path(Path::new(""), &link.slug);
+        generated.push(page_path.to_string_lossy().replace('\\', "/"));
+    }
+
+    if cfg.writes_html_pages() {
+        for slug in &gone {
+            if !cfg.gone_backends.contains(&Backend::Html) {
+                pages::remove_page(repo_path, slug)?;
+                continue;
+            }
+            let layout = layout_of(slug);
+            let content = render::gone_page(
+                Some(slug),
+                &root_href(slug, layout),
+                gone_template.as_deref(),
+            )?;
+            pages::write_page(repo_path, slug, layout, &content)?;
+            let page_path = layout.page_path(Path::new(""), slug);
+            generated.push(page_path.to_string_lossy().replace('\\', "/"));
+        }
+    }
+
+    let mut warnings = backend::write_all(repo_path, cfg, manifest, &gone)?;
+    generated.extend(
+        cfg.backends
+            .iter()
+            .filter_map(|&backend| backend::output_file(backend))
+            .map(str::to_string),
+    );
+
+    // Shared by the backends answering 410 themselves, which can't tell
+    // which page a removed link had.
+    let gone_path = repo_path.join(backend::GONE_FILE);
+    let server_gone = cfg
+        .gone_backends
+        .iter()
+        .any(|&backend| backend::supports_gone(backend) && cfg.backends.contains(&backend));
+    if server_gone && !gone.is_empty() {
+        let index_href = cfg.base_url.as_deref().unwrap_or("/");
+        fs::write(
+            &gone_path,
+            render::gone_page(None, index_href, gone_template.as_deref())?,
+        )
+        .map_err(|e| format!("failed to write {}: {e}", backend::GONE_FILE))?;
+        generated.push(backend::GONE_FILE.to_string());
+    } else if gone_path.exists() {
+        fs::remove_file(&gone_path)
+            .map_err(|e| format!("failed to remove {}: {e}", backend::GONE_FILE))?;
+    }
+
+    if !cfg.qr_assets.is_empty() && cfg.base_url.is_none() {
+        warnings.push("`qr_assets` needs `base_url` to know the short URLs".to_string());
+    }
+    generated.extend(qr::write_assets(repo_path, cfg, manifest)?);
+
+    // Removed links keep their screenshot until they leave the trash, in
+    // case they're restored.
+    let shot_slugs: HashSet<&str> = manifest
+        .links
+        .iter()
+        .map(|link| link.slug.as_str())
+        .chain(trash.links.iter().map(|link| link.slug.as_str()))
+        .collect();
+    screenshot::remove_stale(repo_path, &shot_slugs)?;
+
+    let href = |slug: &str| Some(layout_of(slug).href(slug));
+
+    let bundles = Bundles::load(repo_path)?;
+    for bundle in &bundles.bundles {
+        let layout = layout_of(&bundle.slug);
+        let content = render::bundle_page(bundle, &stylesheets, &root_href(&bundle.slug, layout))?;
+        pages::write_page(repo_path, &bundle.slug, layout, &content)?;
+        let page_path = layout.page_path(Path::new(""), &bundle.slug);
+        generated.push(page_path.to_string_lossy().replace('\\', "/"));
+    }
+
+    // Every directory slugs are in, such as `docs` and `docs/v2` for
+    // `docs/v2/install`, gets an index of what's in it.
+    let all_namespaces: BTreeSet<&str> = manifest
+        .links
+        .iter()
+        .flat_map(|link| link.slug.match_indices('/').map(|(i, _)| &link.slug[..i]))
+        .collect();
+    let mut namespaces = vec![];
+    for &name in &all_namespaces {
+        let index_path = format!("{name}/index.html");
+        if generated.contains(&index_path) {
+            warnings.push(format!(
+                "`{name}` has a page at {index_path}, so the links in `{name}/` have no index"
+            ));
+            continue;
+        }
+        let links: Vec<_> = manifest
+            .newest_first()
+            .into_iter()
+            .filter(|link| parent_of(&link.slug) == Some(name))
+            .collect();
+        let children: Vec<&str> = all_namespaces
+            .iter()
+            .copied()
+            .filter(|&child| parent_of(child) == Some(name))
+            .collect();
+        let root = "../".repeat(name.matches('/').count() + 1);
+        let content =
+            render::namespace_page(name, &links, &children, &stylesheets, &root, |slug| {
+                layout_of(slug).href(slug)
+            })?;
+        let path = repo_path.join(&index_path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+        }
+        fs::write(&path, content).map_err(|e| format!("failed to write {index_path}: {e}"))?;
+        generated.push(index_path);
+        namespaces.push(name);
+    }
+    for name in &previous_namespaces {
+        let index_path = format!("{name}/index.html");
+        if !namespaces.contains(&name.as_str()) && !generated.contains(&index_path) {
+            let _ = fs::remove_file(repo_path.join(&index_path));
+            // Only removes the directory if nothing else was put in it.
+            let _ = fs::remove_dir(repo_path.join(name));
+        }
+    }
+
+    let federation = Federation::load(repo_path)?;
+    let directory_path = repo_path.join(DIRECTORY_FILE);
+    if federation.partners.is_empty() {
+        if directory_path.exists() {
+            fs::remove_file(&directory_path)
+                .map_err(|e| format!("failed to remove {DIRECTORY_FILE}: {e}"))?;
+        }
+    } else {
+        let mut partners = vec![];
+        for partner in &federation.partners {
+            let snapshot = federation::load_snapshot(repo_path, &partner.name)?;
+            partners.push((partner.clone(), snapshot));
+        }
+        let content = render::directory_page(manifest, &partners, &stylesheets, href)?;
+        fs::write(&directory_path, content)
+            .map_err(|e| format!("failed to write {DIRECTORY_FILE}: {e}"))?;
+        generated.push(DIRECTORY_FILE.to_string());
+    }
+
+    let top_namespaces: Vec<&str> = namespaces
+        .iter()
+        .copied()
+        .filter(|name| !name.contains('/'))
+        .collect();
+    let mut index_links = manifest.newest_first();
+    let split = cfg.index_page_size > 0 && index_links.len() > cfg.index_page_size;
+    if split && cfg.index_split == IndexSplit::Namespaces {
+        index_links.retain(|link| {
+            !parent_of(&link.slug).is_some_and(|parent| namespaces.contains(&parent))
+        });
+    }
+    let entries = index_entries(index_links, cfg.index_order);
+    let pages: Vec<&[(String, &Link)]> = match cfg.index_split {
+        IndexSplit::Pages if split => entries.chunks(cfg.index_page_size).collect(),
+        _ => vec![&entries],
+    };
+    for (i, entries) in pages.iter().enumerate() {
+        let content = render::index_page(
+            entries,
+            i + 1,
+            pages.len(),
+            &bundles,
+            &stylesheets,
+            !federation.partners.is_empty(),
+            &top_namespaces,
+            href,
+            |reference| Issue::parse(reference).ok()?.url(cfg),
+            |link| {
+                let path = screenshot::path(&link.slug);
+                repo_path.join(&path).is_file().then_some(path)
+            },
+        )?;
+        let content = match &cfg.analytics {
+            Some(analytics) => render::with_analytics(&content, analytics, "", "")?,
+            None => content,
+        };
+        let path = match i {
+            0 => "index.html".to_string(),
+            _ => format!("{INDEX_PAGES_DIR}/{}.html", i + 1),
+        };
+        if i == 1 {
+            fs::create_dir_all(repo_path.join(INDEX_PAGES_DIR))
+                .map_err(|e| format!("failed to create {INDEX_PAGES_DIR}: {e}"))?;
+        }
+        fs::write(repo_path.join(&path), content)
+            .map_err(|e| format!("failed to write {path}: {e}"))?;
+        generated.push(path);
+    }
+    // Pages past the last one, from when there were more links.
+    if let Ok(entries) = fs::read_dir(repo_path.join(INDEX_PAGES_DIR)) {
+        for entry in entries.flatten() {
+            let path = format!("{INDEX_PAGES_DIR}/{}", entry.file_name().to_string_lossy());
+            if path.ends_with(".html") && !generated.contains(&path) {
+                fs::remove_file(entry.path())
+                    .map_err(|e| format!("failed to remove {path}: {e}"))?;
+            }
+        }
+        let _ = fs::remove_dir(repo_path.join(INDEX_PAGES_DIR));
+    }
+
+    // The index as scripts see it, newest first, also for the filter of the
+    // index to find links on its other pages. `href` leads from the index
+    // to the page of the link.
+    let links: Vec<json::Value> = manifest
+        .newest_first()
+        .iter()
+        .map(|link| {
+            let mut value = output::link(cfg, link);
+            let href = layout_of(&link.slug).href(&link.slug);
+            value.set("href", href.trim_start_matches("./"));
+            value
+        })
+        .collect();
+    fs::write(
+        repo_path.join(LINKS_JSON_FILE),
+        format!("{}\n", json::Value::from(links)),
+    )
+    .map_err(|e| format!("failed to write {LINKS_JSON_FILE}: {e}"))?;
+    generated.push(LINKS_JSON_FILE.to_string());
+
+    // Absolute, since the pages using it are served at any depth or read
+    // from elsewhere.
+    let site_root = match &cfg.base_url {
+        Some(base_url) => format!("{}/", base_url.trim_end_matches('/')),
+        None => "/".to_string(),
+    };
+
+    // Served by GitHub Pages and most other hosts for every path that isn't
+    // a file. One written by hand is left alone.
+    if cfg.writes_html_pages() {
+        let not_found_path = repo_path.join(NOT_FOUND_FILE);
+        if not_found_path.exists() && !was_generated(repo_path, NOT_FOUND_FILE) {
+            warnings.push(format!(
+                "{NOT_FOUND_FILE} wasn't written by shurl, so it's left as it is"
+            ));
+        } else {
+            let not_found = &repo_config.not_found;
+            let content = render::not_found_page(
+                &manifest.newest_first(),
+                &stylesheets,
+                &site_root,
+                not_found.index,
+                not_found.search,
+                not_found.load_template(repo_path)?.as_deref(),
+            )?;
+            fs::write(&not_found_path, content)
+                .map_err(|e| format!("failed to write {NOT_FOUND_FILE}: {e}"))?;
+            generated.push(NOT_FOUND_FILE.to_string());
+        }
+    } else if was_generated(repo_path, NOT_FOUND_FILE) {
+        fs::remove_file(repo_path.join(NOT_FOUND_FILE))
+            .map_err(|e| format!("failed to remove {NOT_FOUND_FILE}: {e}"))?;
+    }
+
+    let feed_path = repo_path.join(FEED_FILE);
+    if cfg.feed_size > 0 && cfg.base_url.is_some() {
+        let newest = manifest.newest_first();
+        let links = &newest[..newest.len().min(cfg.feed_size)];
+        fs::write(&feed_path, render::feed(links, &site_root, &cfg.name)?)
+            .map_err(|e| format!("failed to write {FEED_FILE}: {e}"))?;
+        generated.push(FEED_FILE.to_string());
+    } else if was_generated(repo_path, FEED_FILE) {
+        fs::remove_file(&feed_path).map_err(|e| format!("failed to remove {FEED_FILE}: {e}"))?;
+    }
+
+    // Sitemaps need full URLs, so there's none without `base_url`.
+    let sitemap_path = repo_path.join(SITEMAP_FILE);
+    if cfg.writes_html_pages() && cfg.base_url.is_some() {
+        let newest = manifest.newest_first();
+        let mut urls = vec![(
+            site_root.clone(),
+            newest.first().map(|link| date::format_date(&link.created)),
+        )];
+        if !federation.partners.is_empty() {
+            urls.push((format!("{site_root}{DIRECTORY_FILE}"), None));
+        }
+        urls.extend(
+            namespaces
+                .iter()
+                .map(|name| (format!("{site_root}{name}/"), None)),
+        );
+        if cfg.sitemap_pages {
+            for link in newest
+                .iter()
+                .filter(|link| !link.noindex.unwrap_or(cfg.noindex))
+            {
+                let href = layout_of(&link.slug).href(&link.slug);
+                urls.push((
+                    format!("{site_root}{}", href.trim_start_matches("./")),
+                    Some(date::format_date(&link.created)),
+                ));
+            }
+        }
+        fs::write(&sitemap_path, render::sitemap(&urls)?)
+            .map_err(|e| format!("failed to write {SITEMAP_FILE}: {e}"))?;
+        generated.push(SITEMAP_FILE.to_string());
+    } else if was_generated(repo_path, SITEMAP_FILE) {
+        fs::remove_file(&sitemap_path)
+            .map_err(|e| format!("failed to remove {SITEMAP_FILE}: {e}"))?;
+    }
+
+    // Crawlers only read the one at the root of the host, and one written
+    // by hand is left alone.
+    let robots_path = repo_path.join(ROBOTS_FILE);
+    if cfg.robots_txt && cfg.writes_html_pages() {
+        let root_path = site_root
+            .split_once("://")
+            .and_then(|(_, rest)| rest.find('/').map(|slash| &rest[slash..]))
+            .unwrap_or(&site_root);
+        if root_path != "/" {
+            warnings.push(format!(
+                "crawlers only read {ROBOTS_FILE} at the root of the host, not under {root_path}"
+            ));
+        }
+        if robots_path.exists() && !was_generated(repo_path, ROBOTS_FILE) {
+            warnings.push(format!(
+                "{ROBOTS_FILE} wasn't written by shurl, so it's left as it is"
+            ));
+        } else {
+            let disallowed: Vec<String> = manifest
+                .newest_first()
+                .iter()
+                .filter(|link| link.noindex.unwrap_or(cfg.noindex))
+                .map(|link| {
+                    let href = layout_of(&link.slug).href(&link.slug);
+                    format!("{root_path}{}", href.trim_start_matches("./"))
+                })
+                .collect();
+            let sitemap = cfg
+                .base_url
+                .is_some()
+                .then(|| format!("{site_root}{SITEMAP_FILE}"));
+            fs::write(
+                &robots_path,
+                render::robots_txt(&disallowed, sitemap.as_deref()),
+            )
+            .map_err(|e| format!("failed to write {ROBOTS_FILE}: {e}"))?;
+            generated.push(ROBOTS_FILE.to_string());
+        }
+    } else if was_generated(repo_path, ROBOTS_FILE) {
+        fs::remove_file(&robots_path)
+            .map_err(|e| format!("failed to remove {ROBOTS_FILE}: {e}"))?;
+    }
+
+    // Pages of earlier builds this one didn't write, such as those of links
+    // needing no page anymore or of index pages that moved.
+    for file in previous_files(repo_path) {
+        if file.ends_with(".html") && !generated.contains(&file) {
+            remove_stale(repo_path, &file)?;
+        }
+    }
+
+    let mut files = write_build_manifest(repo_path, generated, &namespaces)?;
+    files.push(BUILD_MANIFEST_FILE.to_string());
+    provenance::write(repo_path, cfg, source, &files, &started)?;
+    Ok(warnings)
+}
+
+/// The links of the index in the order of `order`, each with the heading of
+/// the group it's listed under, empty if they aren't grouped.
+fn index_entries(links: Vec<&Link>, order: IndexOrder) -> Vec<(String, &Link)> {
+    match order {
+        IndexOrder::Newest => links
+            .into_iter()
+            .map(|link| (String::new(), link))
+            .collect(),
+        IndexOrder::Slug => {
+            let mut links = links;
+            links.sort_by(|a, b| a.slug.cmp(&b.slug));
+            links
+                .into_iter()
+                .map(|link| (String::new(), link))
+                .collect()
+        }
+        IndexOrder::Tag => {
+            let mut groups: BTreeMap<String, Vec<&Link>> = BTreeMap::new();
+            let mut untagged = vec![];
+            for link in links {
+                if link.tags.is_empty() {
+                    untagged.push(link);
+                }
+                for tag in &link.tags {
+                    groups.entry(format!("#{tag}")).or_default().push(link);
+                }
+            }
+            let mut entries = flatten_groups(groups);
+            entries.extend(
+                untagged
+                    .into_iter()
+                    .map(|link| ("Untagged".to_string(), link)),
+            );
+            entries
+        }
+        IndexOrder::Domain => {
+            let mut groups: BTreeMap<String, Vec<&Link>> = BTreeMap::new();
+            for link in links {
+                let domain = Url::parse(&link.target)
+                    .ok()
+                    .and_then(|url| url.host_str().map(str::to_string))
+                    .map(|host| host.trim_start_matches("www.").to_string())
+                    .unwrap_or_else(|| link.target.clone());
+                groups.entry(domain).or_default().push(link);
+            }
+            flatten_groups(groups)
+        }
+    }
+}
+
+fn flatten_groups(groups: BTreeMap<String, Vec<&Link>>) -> Vec<(String, &Link)> {
+    groups
+        .into_iter()
+        .flat_map(|(heading, links)| links.into_iter().map(move |link| (heading.clone(), link)))
+        .collect()
+}
+
+/// The page the link would get, with its template and analytics but
+/// without stylesheets, rendered without writing anything, e.g. to show it
+/// before the link is created.
+pub fn page(repo_path: &Path, cfg: &ShurlConfig, link: &Link) -> Result<String, String> {
+    let repo_config = RepoConfig::load(repo_path)?;
+    let template = match (&link.template, repo_config.namespace_for(&link.slug)) {
+        (Some(name), _) => Some(repo_config::load_named_template(repo_path, name)?),
+        (None, Some(namespace)) => namespace.load_template(repo_path)?,
+        (None, None) => None,
+    };
+    let template = match template {
+        Some(template) => Some(template),
+        None => repo_config.load_template(repo_path)?,
+    };
+    let layout = Layout::from_config(cfg.clean_urls);
+    let content = render::redirect_page(
+        link,
+        template.as_deref(),
+        &[],
+        &root_href(&link.slug, layout),
+        &render::PageOptions::of(cfg),
+    )?;
+    match &cfg.analytics {
+        Some(analytics) => render::with_analytics(&content, analytics, &link.slug, &link.target),
+        None => Ok(content),
+    }
+}
+
+/// The directory a slug is in, e.g. `docs` for `docs/install`.
+fn parent_of(slug: &str) -> Option<&str> {
+    slug.rsplit_once('/').map(|(parent, _)| parent)
+}
+
+/// Relative location of the index from the page of a slug.
+fn root_href(slug: &str, layout: Layout) -> String {
+    let depth = slug.matches('/').count() + usize::from(layout == Layout::Directory);
+    if depth == 0 {
+        "./".to_string()
+    } else {
+        "../".repeat(depth)
+    }
+}
+
+/// Directories the last build wrote an index for, so it can be told apart
+/// from a page and removed once the directory has no links left.
+fn previous_namespaces(repo_path: &Path) -> HashSet<String> {
+    let Ok(content) = fs::read_to_string(repo_path.join(BUILD_MANIFEST_FILE)) else {
+        return HashSet::new();
+    };
+    let Ok(build) = json::parse(&content) else {
+        return HashSet::new();
+    };
+    build
+        .get("namespaces")
+        .and_then(json::Value::as_array)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|name| name.as_str().map(str::to_string))
+        .collect()
+}
+
+/// Files the previous build generated.
+fn previous_files(repo_path: &Path) -> Vec<String> {
+    let Ok(content) = fs::read_to_string(repo_path.join(BUILD_MANIFEST_FILE)) else {
+        return vec![];
+    };
+    match json::parse(&content)
+        .ok()
+        .and_then(|build| build.get("files").cloned())
+    {
+        Some(json::Value::Object(files)) => files.into_iter().map(|(file, _)| file).collect(),
+        _ => vec![],
+    }
+}
+
+/// Removes a file of an earlier build, and the directories it leaves empty.
+fn remove_stale(repo_path: &Path, file: &str) -> Result<(), String> {
+    match fs::remove_file(repo_path.join(file)) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(format!("failed to remove {file}: {e}")),
+    }
+    let mut dir = Path::new(file).parent();
+    while let Some(parent) = dir.filter(|parent| !parent.as_os_str().is_empty()) {
+        // Stops at the first directory with anything else in it.
+        if fs::remove_dir(repo_path.join(parent)).is_err() {
+            break;
+        }
+        dir = parent.parent();
+    }
+    Ok(())
+}
+
+/// Whether the previous build generated the file.
+fn was_generated(repo_path: &Path, file: &str) -> bool {
+    fs::read_to_string(repo_path.join(BUILD_MANIFEST_FILE))
+        .ok()
+        .and_then(|content| json::parse(&content).ok())
+        .is_some_and(|build| {
+            build
+                .get("files")
+                .and_then(|files| files.get(file))
+                .is_some()
+        })
+}
+
+/// Writes the integrity hash of every generated file and the directories
+/// with an index, returning the files.
+fn write_build_manifest(
+    repo_path: &Path,
+    mut files: Vec<String>,
+    namespaces: &[&str],
+) -> Result<Vec<String>, String> {
+    files.sort();
+    files.dedup();
+
+    let mut hashes = json::Value::object();
+    for file in &files {
+        let content =
+            fs::read(repo_path.join(file)).map_err(|e| format!("failed to read {file}: {e}"))?;
+        hashes.set(file, hash::integrity(&content));
+    }
+    let mut build = json::Value::object();
+    build.set("files", hashes);
+    if !namespaces.is_empty() {
+        build.set(
+            "namespaces",
+            json::Value::Array(namespaces.iter().map(|&name| name.into()).collect()),
+        );
+    }
+
+    let path = repo_path.join(BUILD_MANIFEST_FILE);
+    let content = build.to_pretty_string() + "\n";
+    if fs::read_to_string(&path).ok().as_deref() != Some(content.as_str()) {
+        fs::write(&path, content)
+            .map_err(|e| format!("failed to write {BUILD_MANIFEST_FILE}: {e}"))?;
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Empty directory standing in for a repository, removed on drop.
+    struct Fixture(PathBuf);
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("shurl-test-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+
+        fn build(&self, cfg: &ShurlConfig, manifest: &Manifest) {
+            let source = Source {
+                repository: None,
+                base_commit: None,
+            };
+            build(&self.0, cfg, manifest, &source).unwrap();
+        }
+
+        fn read(&self, file: &str) -> String {
+            fs::read_to_string(self.0.join(file)).unwrap()
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Links named `a`, `b` and so on, created a minute apart in that order.
+    fn manifest(slugs: &[&str]) -> Manifest {
+        let mut manifest = Manifest::default();
+        for (i, slug) in slugs.iter().enumerate() {
+            let created = date::from_unix(1_700_000_000 + 60 * i as i64);
+            manifest.insert(Link::new(slug, "https://example.com/", created));
+        }
+        manifest
+    }
+
+    #[test]
+    fn split_index_lets_the_filter_find_every_link() {
+        let fixture = Fixture::new("split-index");
+        let cfg = ShurlConfig {
+            index_page_size: 2,
+            ..ShurlConfig::default()
+        };
+        fixture.build(&cfg, &manifest(&["a", "b", "c", "d", "e"]));
+
+        let first = fixture.read("index.html");
+        let last = fixture.read("index/3.html");
+        assert!(first.contains("./e.html") && first.contains("./d.html"));
+        assert!(!first.contains("c.html"));
+        assert!(last.contains("../a.html") && !last.contains("b.html"));
+        assert!(!fixture.0.join("index/4.html").exists());
+        // Each page fetches the links of all of them.
+        assert!(first.contains(r#"data-root="./""#));
+        assert!(last.contains(r#"data-root="../""#));
+        assert!(first.contains("connect-src &#39;self&#39;"));
+
+        let links = json::parse(&fixture.read(LINKS_JSON_FILE)).unwrap();
+        let hrefs: Vec<&str> = links
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|link| link.get("href").and_then(json::Value::as_str).unwrap())
+            .collect();
+        assert_eq!(hrefs, ["e.html", "d.html", "c.html", "b.html", "a.html"]);
+    }
+
+    #[test]
+    fn pages_no_build_writes_anymore_are_removed() {
+        let fixture = Fixture::new("stale-pages");
+        fs::write(fixture.0.join("notes.html"), "mine").unwrap();
+        let cfg = ShurlConfig {
+            index_page_size: 1,
+            ..ShurlConfig::default()
+        };
+        fixture.build(&cfg, &manifest(&["a", "b", "docs/c"]));
+        assert!(fixture.0.join("index/3.html").is_file());
+        assert!(fixture.0.join("docs/c.html").is_file());
+
+        // One page of the index with no HTML pages for the links.
+        let cfg = ShurlConfig {
+            backends: vec![Backend::Netlify],
+            ..ShurlConfig::default()
+        };
+        fixture.build(&cfg, &manifest(&["a", "b", "docs/c"]));
+        for stale in ["index", "a.html", "docs/c.html"] {
+            assert!(!fixture.0.join(stale).exists(), "{stale}");
+        }
+        assert!(fixture.0.join("index.html").is_file());
+        assert_eq!(fixture.read("notes.html"), "mine");
+    }
+}
@@ -0,0 +1,554 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Names of links created without one.
+//!
+//! Random names come from whatever generator the caller hands in, so they
+//! can be made the same on every run with [`seeded`], e.g. for tests or demo
+//! repositories. Names derived from a hash of the target are the same
+//! everywhere anyway.
+
+use crate::config::{ShurlConfig, SlugAlphabet, SlugStyle};
+use crate::hash;
+use crate::manifest::Manifest;
+use crate::pages;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::path::Path;
+use url::Url;
+
+/// EFF's short wordlist of 1296 words of at most five letters, chosen to be
+/// easy to tell apart when read out. Licensed CC BY 3.0 by the Electronic
+/// Frontier Foundation.
+const WORDS: &str = include_str!("words.txt");
+
+/// Names taken by what shurl generates or serves itself, and what for. A
+/// link can't start with them, with or without an extension, since
+/// `sitemap.xml` would be the sitemap with clean URLs.
+const RESERVED: &[(&str, &str)] = &[
+    ("index", "the index page"),
+    ("404", "the page of unknown links"),
+    ("gone", "the page of removed links"),
+    ("directory", "the directory page"),
+    ("assets", "stylesheets and QR codes"),
+    ("shots", "screenshots"),
+    ("federation", "snapshots of partner repositories"),
+    ("robots", "robots.txt"),
+    ("sitemap", "the sitemap"),
+    ("feed", "the feed of new links"),
+    ("links", "links.toml and links.json"),
+    ("provenance", "the provenance of the generated files"),
+    ("build-manifest", "the build manifest"),
+    ("bundles", "the bundles"),
+    ("deprecations", "deprecated domains"),
+    ("_redirects", "the redirects of Netlify and Cloudflare"),
+    ("vercel", "the redirects of Vercel"),
+    ("shurl", "the nginx, Caddy and map outputs"),
+    ("templates", "templates chosen with --template"),
+    ("api", "the API of shurl serve"),
+    ("healthz", "health checks of shurl serve"),
+    ("readyz", "health checks of shurl serve"),
+    ("metrics", "metrics of shurl serve"),
+];
+
+/// Checks a name given for a link or bundle. Names are paths of the
+/// repository and the site, so only characters that need no escaping in
+/// URLs are allowed, `/` separating directories, and no part of them may
+/// lead out of their directory or to hidden files such as `.git`.
+pub fn validate(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("names can't be empty".to_string());
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|&c| !(c.is_ascii_alphanumeric() || "-_.~/".contains(c)))
+    {
+        return Err(format!(
+            "invalid name `{name}`: `{c}` isn't allowed, use letters, digits, `-`, `_`, `.`, `~` and `/`"
+        ));
+    }
+    for part in name.split('/') {
+        if part.is_empty() {
+            return Err(format!(
+                "invalid name `{name}`: names can't start or end with `/` or have `//`"
+            ));
+        }
+        if part.starts_with('.') {
+            return Err(format!(
+                "invalid name `{name}`: parts of names can't start with `.`"
+            ));
+        }
+        // `docs/index.html` would be the page of the directory `docs/`.
+        if stem(part).eq_ignore_ascii_case("index") {
+            return Err(format!(
+                "invalid name `{name}`: `index` is reserved for index pages"
+            ));
+        }
+    }
+    let first = stem(name.split('/').next().unwrap_or_default());
+    if let Some((reserved, purpose)) = RESERVED
+        .iter()
+        .find(|(reserved, _)| reserved.eq_ignore_ascii_case(first))
+    {
+        return Err(format!(
+            "invalid name `{name}`: `{reserved}` is reserved for {purpose}"
+        ));
+    }
+    Ok(())
+}
+
+/// The part of a path segment before its first `.`.
+fn stem(part: &str) -> &str {
+    part.split('.').next().unwrap_or(part)
+}
+
+/// How long names are and which characters they're made of.
+#[derive(Clone, Copy)]
+pub struct NameFormat {
+    /// For names derived from hashes, the length unless a longer one is
+    /// needed to get past a link to another target.
+    pub length: usize,
+    pub alphabet: SlugAlphabet,
+}
+
+impl NameFormat {
+    /// Five lowercase letters.
+    pub const RANDOM: Self = Self {
+        length: 5,
+        alphabet: SlugAlphabet::Lowercase,
+    };
+    /// Seven characters of base62.
+    pub const HASH: Self = Self {
+        length: 7,
+        alphabet: SlugAlphabet::Alphanumeric,
+    };
+
+    /// Format of `slug_style` with `slug_length` and `slug_alphabet` of the
+    /// config applied.
+    pub fn of(cfg: &ShurlConfig) -> Self {
+        let default = match cfg.slug_style {
+            // Names of words don't have a length and alphabet.
+            SlugStyle::Random | SlugStyle::Words => Self::RANDOM,
+            SlugStyle::Hash => Self::HASH,
+        };
+        Self {
+            length: cfg.slug_length.unwrap_or(default.length).max(1),
+            alphabet: cfg.slug_alphabet.unwrap_or(default.alphabet),
+        }
+    }
+}
+
+/// Generator giving the same names for the same seed. ChaCha gives the same
+/// numbers on every platform and version of `rand`, unlike `StdRng`.
+pub fn seeded(seed: u64) -> ChaCha8Rng {
+    ChaCha8Rng::seed_from_u64(seed)
+}
+
+/// Generator seeded by the operating system, for names that differ on every
+/// run.
+pub fn unseeded() -> ChaCha8Rng {
+    ChaCha8Rng::from_entropy()
+}
+
+/// A name of characters picked uniformly from the alphabet.
+pub fn random_name(rng: &mut dyn RngCore, format: NameFormat) -> String {
+    let chars = format.alphabet.chars();
+    // Sampled as `u8` so names stay the same as before alphabets existed.
+    (0..format.length)
+        .map(|_| chars[usize::from(rng.gen_range(0..chars.len() as u8))] as char)
+        .collect()
+}
+
+/// Words picked uniformly from the list and a number below 100, joined with
+/// the separator.
+pub fn word_name(rng: &mut dyn RngCore, words: usize, separator: &str) -> String {
+    let list: Vec<&str> = WORDS.lines().collect();
+    let mut parts: Vec<String> = (0..words)
+        .map(|_| list[rng.gen_range(0..list.len())].to_string())
+        .collect();
+    parts.push(rng.gen_range(0..100).to_string());
+    parts.join(separator)
+}
+
+/// A random name that's neither in the manifest, taken by a file nor reserved.
+/// Fails when none is found, e.g. as every name of the length is taken.
+pub fn new_slug(
+    rng: &mut dyn RngCore,
+    format: NameFormat,
+    manifest: &Manifest,
+    repo_path: &Path,
+) -> Result<String, String> {
+    free_name(manifest, repo_path, || random_name(rng, format)).ok_or_else(|| {
+        format!(
+            "found no free name of {} characters, raise `slug_length` in the config",
+            format.length
+        )
+    })
+}
+
+/// A name of random words that's neither in the manifest nor taken by a
+/// file. Fails when none is found.
+pub fn new_word_slug(
+    rng: &mut dyn RngCore,
+    words: usize,
+    separator: &str,
+    manifest: &Manifest,
+    repo_path: &Path,
+) -> Result<String, String> {
+    free_name(manifest, repo_path, || word_name(rng, words, separator)).ok_or_else(|| {
+        format!("found no free name of {words} words, raise `slug_words` in the config")
+    })
+}
+
+/// Most words of a title kept in a name made from it.
+const MAX_TITLE_WORDS: usize = 6;
+
+/// Name made from a title, e.g. `my-blog-post-title`: its first words in
+/// lowercase ASCII letters and digits, joined by hyphens. Latin letters lose
+/// their accents. Empty if the title has no such words.
+pub fn slugify(title: &str) -> String {
+    let folded: String = title.chars().map(fold_accent).collect();
+    folded
+        .split(|c: char| !c.is_ascii_alphanumeric() && c != '\'')
+        .map(|word| word.replace('\'', "").to_ascii_lowercase())
+        .filter(|word| !word.is_empty())
+        .take(MAX_TITLE_WORDS)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// The ASCII letter of accented Latin letters, `ß` and the like as the
+/// letters they're written as.
+fn fold_accent(c: char) -> String {
+    let folded = match c {
+        'à'..='å' | 'À'..='Å' => "a",
+        'æ' | 'Æ' => "ae",
+        'ç' | 'Ç' => "c",
+        'è'..='ë' | 'È'..='Ë' => "e",
+        'ì'..='ï' | 'Ì'..='Ï' => "i",
+        'ñ' | 'Ñ' => "n",
+        'ò'..='ö' | 'ø' | 'Ò'..='Ö' | 'Ø' => "o",
+        'ù'..='ü' | 'Ù'..='Ü' => "u",
+        'ý' | 'ÿ' | 'Ý' => "y",
+        'ß' => "ss",
+        'œ' | 'Œ' => "oe",
+        _ => return c.to_string(),
+    };
+    folded.to_string()
+}
+
+/// A name made from a title that's neither in the manifest nor taken by a
+/// file, with `-2`, `-3` and so on added if needed. `None` if the title has
+/// nothing to make a name from, or every numbered name tried is taken.
+pub fn title_slug(title: &str, manifest: &Manifest, repo_path: &Path) -> Option<String> {
+    let base = slugify(title);
+    if base.is_empty() {
+        return None;
+    }
+    let mut attempt = 0;
+    free_name(manifest, repo_path, || {
+        attempt += 1;
+        match attempt {
+            1 => base.clone(),
+            n => format!("{base}-{n}"),
+        }
+    })
+}
+
+/// Names tried before giving up. Random names of a few characters could
+/// all be taken, and drawing them again would never end. Missing the last
+/// free one of an alphabet of 26 this often is about as likely as never.
+const MAX_ATTEMPTS: usize = 10_000;
+
+fn free_name(
+    manifest: &Manifest,
+    repo_path: &Path,
+    mut next: impl FnMut() -> String,
+) -> Option<String> {
+    (0..MAX_ATTEMPTS).map(|_| next()).find(|name| {
+        // Random letters can spell a reserved name such as `index`.
+        manifest.get(name).is_none()
+            && pages::find_page(repo_path, name).is_none()
+            && validate(name).is_ok()
+    })
+}
+
+/// Name derived from the SHA-256 of the target, the shortest start of it in
+/// the alphabet that isn't taken by a link to another target or a file.
+/// Fails when the target is shortened under that name already.
+pub fn hash_slug(
+    url: &Url,
+    format: NameFormat,
+    manifest: &Manifest,
+    repo_path: &Path,
+) -> Result<String, String> {
+    let digest = hash::to_base(
+        &hash::sha256(url.as_str().as_bytes()),
+        format.alphabet.chars(),
+    );
+    for length in format.length.min(digest.len())..=digest.len() {
+        let name = &digest[..length];
+        match manifest.get(name) {
+            Some(link) if link.target == url.as_str() => {
+                return Err(format!("{url} is shortened as `{name}` already"));
+            }
+            Some(_) => continue,
+            None if pages::find_page(repo_path, name).is_some() || validate(name).is_err() => {
+                continue
+            }
+            None => return Ok(name.to_string()),
+        }
+    }
+    Err(format!("every name derived from {url} is taken"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::date;
+    use crate::hash;
+    use crate::manifest::Link;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Empty directory standing in for a repository, removed on drop.
+    struct Fixture(PathBuf);
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("shurl-test-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn link(slug: &str) -> Link {
+        link_to(slug, "https://example.com/")
+    }
+
+    fn link_to(slug: &str, target: &str) -> Link {
+        Link::new(slug, target, date::now())
+    }
+
+    fn names(rng: &mut dyn RngCore, count: usize) -> Vec<String> {
+        (0..count)
+            .map(|_| random_name(rng, NameFormat::RANDOM))
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_gives_same_names() {
+        assert_eq!(names(&mut seeded(42), 10), names(&mut seeded(42), 10));
+        assert_ne!(names(&mut seeded(42), 10), names(&mut seeded(43), 10));
+    }
+
+    #[test]
+    fn seeded_names_stay_the_same() {
+        // Demo repositories built with `--seed` rely on these not changing.
+        assert_eq!(names(&mut seeded(1), 3), ["okzcf", "pkfvh", "xsmls"]);
+    }
+
+    #[test]
+    fn names_are_five_lowercase_letters() {
+        for name in names(&mut unseeded(), 100) {
+            assert_eq!(name.len(), 5);
+            assert!(name.bytes().all(|b| b.is_ascii_lowercase()), "{name}");
+        }
+    }
+
+    #[test]
+    fn names_use_the_whole_alphabet() {
+        let format = NameFormat {
+            length: 12,
+            alphabet: SlugAlphabet::Base58,
+        };
+        let mut seen = std::collections::BTreeSet::new();
+        for _ in 0..200 {
+            let name = random_name(&mut unseeded(), format);
+            assert_eq!(name.len(), 12);
+            seen.extend(name.bytes());
+        }
+        assert_eq!(
+            seen.into_iter().collect::<Vec<_>>(),
+            SlugAlphabet::Base58.chars()
+        );
+    }
+
+    #[test]
+    fn new_slug_skips_taken_names() {
+        let fixture = Fixture::new("new-slug");
+        let taken = names(&mut seeded(7), 2);
+        let mut manifest = Manifest::default();
+        manifest.insert(link(&taken[0]));
+        fs::write(fixture.0.join(format!("{}.html", taken[1])), "").unwrap();
+
+        let slug = new_slug(&mut seeded(7), NameFormat::RANDOM, &manifest, &fixture.0).unwrap();
+        assert!(!taken.contains(&slug), "{slug}");
+        assert_eq!(slug, names(&mut seeded(7), 3)[2]);
+    }
+
+    #[test]
+    fn new_slug_fails_once_every_name_is_taken() {
+        let fixture = Fixture::new("new-slug-exhausted");
+        let format = NameFormat {
+            length: 1,
+            alphabet: SlugAlphabet::Lowercase,
+        };
+        let mut manifest = Manifest::default();
+        for c in 'a'..='y' {
+            manifest.insert(link(&c.to_string()));
+        }
+        let slug = new_slug(&mut seeded(7), format, &manifest, &fixture.0);
+        assert_eq!(slug.as_deref(), Ok("z"));
+
+        manifest.insert(link("z"));
+        let error = new_slug(&mut seeded(7), format, &manifest, &fixture.0).unwrap_err();
+        assert!(error.contains("slug_length"), "{error}");
+    }
+
+    #[test]
+    fn hash_slugs_stay_the_same() {
+        let fixture = Fixture::new("hash-slug");
+        let url = Url::parse("https://example.com/docs").unwrap();
+        let slug = hash_slug(&url, NameFormat::HASH, &Manifest::default(), &fixture.0).unwrap();
+        // Links shortened on other machines rely on these not changing.
+        assert_eq!(slug, "qeoLCyp");
+    }
+
+    #[test]
+    fn hash_slug_gets_past_other_targets() {
+        let fixture = Fixture::new("hash-slug-taken");
+        let url = Url::parse("https://example.com/docs").unwrap();
+        let slug = hash_slug(&url, NameFormat::HASH, &Manifest::default(), &fixture.0).unwrap();
+
+        let mut manifest = Manifest::default();
+        manifest.insert(link(&slug));
+        let longer = hash_slug(&url, NameFormat::HASH, &manifest, &fixture.0).unwrap();
+        assert_eq!(longer.len(), slug.len() + 1);
+        assert!(longer.starts_with(&slug), "{longer}");
+
+        manifest.insert(link_to(&longer, url.as_str()));
+        assert!(hash_slug(&url, NameFormat::HASH, &manifest, &fixture.0).is_err());
+    }
+
+    #[test]
+    fn word_names_stay_the_same() {
+        let mut rng = seeded(1);
+        let names: Vec<String> = (0..2).map(|_| word_name(&mut rng, 2, "-")).collect();
+        assert_eq!(names, ["grub-book-21", "grain-tummy-71"]);
+        assert_eq!(word_name(&mut seeded(1), 3, "_"), "grub_book_plaza_38");
+    }
+
+    #[test]
+    fn word_list_is_complete() {
+        let words: Vec<&str> = WORDS.lines().collect();
+        assert_eq!(words.len(), 1296);
+        assert!(words.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(words
+            .iter()
+            .all(|word| word.len() <= 5 && word.bytes().all(|b| b.is_ascii_lowercase())));
+        // Names of word slugs change with any edit of the list.
+        assert_eq!(
+            hash::hex(&hash::sha256(WORDS.as_bytes())),
+            "e2b39449cf03a561df0ea3996f1819708ee09a775677e625bf783ffbaa4485c4"
+        );
+    }
+
+    #[test]
+    fn validate_accepts_paths_of_safe_names() {
+        for name in [
+            "docs",
+            "events/2024-rustconf",
+            "app-v1.2.3-source.tar.gz",
+            "a_b~c",
+            "indexes",
+        ] {
+            assert!(validate(name).is_ok(), "{name}");
+        }
+    }
+
+    #[test]
+    fn validate_rejects_unsafe_and_reserved_names() {
+        for name in [
+            "",
+            "../evil",
+            "docs/../../evil",
+            "/etc/passwd",
+            "docs/",
+            "a//b",
+            ".git/config",
+            "docs/.hidden",
+            "a b",
+            "a?b",
+            "caf\u{e9}",
+            "back\\slash",
+            "index",
+            "docs/index",
+            "404",
+            "Assets/logo",
+            "robots",
+            "sitemap",
+            "api/links",
+            "sitemap.xml",
+            "links.json",
+            "_redirects",
+            "feed.xml",
+            "robots.txt",
+            "404.html",
+            "docs/index.html",
+            "shurl.map",
+        ] {
+            assert!(validate(name).is_err(), "{name}");
+        }
+    }
+
+    #[test]
+    fn titles_give_readable_names() {
+        assert_eq!(slugify("My Blog Post: Title!"), "my-blog-post-title");
+        assert_eq!(slugify("Don't Panic — A Guide"), "dont-panic-a-guide");
+        assert_eq!(
+            slugify("one two three four five six seven"),
+            "one-two-three-four-five-six"
+        );
+        assert_eq!(slugify("Café déjà vu, Straße"), "cafe-deja-vu-strasse");
+        assert_eq!(slugify("— · —"), "");
+    }
+
+    #[test]
+    fn title_slug_skips_taken_names() {
+        let fixture = Fixture::new("title-slug");
+        let mut manifest = Manifest::default();
+        manifest.insert(link("release-notes"));
+        manifest.insert(link("release-notes-2"));
+        assert_eq!(
+            title_slug("Release notes", &manifest, &fixture.0).as_deref(),
+            Some("release-notes-3")
+        );
+        assert_eq!(
+            title_slug("Index", &manifest, &fixture.0).as_deref(),
+            Some("index-2")
+        );
+        assert_eq!(title_slug("…", &manifest, &fixture.0), None);
+    }
+}
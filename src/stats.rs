@@ -0,0 +1,100 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Hits counted by `shurl serve`. They are stored next to the repository's
+//! git data rather than in it, so counting doesn't create commits.
+
+use crate::date;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub struct Hit {
+    /// Seconds since the unix epoch.
+    pub time: i64,
+    pub slug: String,
+    pub referrer: Option<String>,
+}
+
+/// One line per hit: time, slug and referrer separated by tabs.
+pub fn hits_path(repo_path: &Path) -> PathBuf {
+    repo_path.join(".git").join("shurl").join("hits.log")
+}
+
+/// Appends hits to the log, shared by the server's threads.
+pub struct Recorder {
+    file: Mutex<File>,
+}
+
+impl Recorder {
+    pub fn open(repo_path: &Path) -> Result<Self, String> {
+        let path = hits_path(repo_path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("failed to open {}: {e}", path.display()))?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn record(&self, slug: &str, referrer: Option<&str>) -> Result<(), String> {
+        let referrer: String = referrer
+            .unwrap_or_default()
+            .chars()
+            .filter(|c| !c.is_control())
+            .collect();
+        let line = format!("{}\t{slug}\t{referrer}\n", date::to_unix(&date::now()));
+        self.file
+            .lock()
+            .unwrap()
+            .write_all(line.as_bytes())
+            .map_err(|e| format!("failed to record hit: {e}"))
+    }
+}
+
+/// Every recorded hit, oldest first. Lines that can't be read are skipped.
+pub fn load(repo_path: &Path) -> Result<Vec<Hit>, String> {
+    let path = hits_path(repo_path);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(_) if !path.exists() => return Ok(vec![]),
+        Err(e) => return Err(format!("failed to read {}: {e}", path.display())),
+    };
+
+    let mut hits = vec![];
+    for line in content.lines() {
+        let mut fields = line.splitn(3, '\t');
+        let (Some(Ok(time)), Some(slug)) = (fields.next().map(str::parse), fields.next()) else {
+            continue;
+        };
+        hits.push(Hit {
+            time,
+            slug: slug.to_string(),
+            referrer: fields
+                .next()
+                .filter(|referrer| !referrer.is_empty())
+                .map(str::to_string),
+        });
+    }
+    Ok(hits)
+}
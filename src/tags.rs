@@ -0,0 +1,107 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Tags of the links, kept to the vocabulary in `tags` of `.shurl.toml` so
+//! the same topic doesn't end up as `docs`, `doc` and `documentation`.
+
+use crate::manifest::Manifest;
+use crate::repo_config::RepoConfig;
+use std::collections::BTreeMap;
+
+/// The tag without surrounding whitespace, if it's a single word of letters,
+/// digits, `-`, `_` and `.` from the vocabulary.
+pub fn check(repo_config: &RepoConfig, tag: &str) -> Result<String, String> {
+    let tag = tag.trim();
+    if tag.is_empty() || tag.chars().any(char::is_whitespace) {
+        return Err(format!("invalid tag `{tag}`: tags are single words"));
+    }
+    if let Some(c) = tag
+        .chars()
+        .find(|&c| !(c.is_ascii_alphanumeric() || "-_.".contains(c)))
+    {
+        return Err(format!(
+            "invalid tag `{tag}`: `{c}` isn't allowed, only letters, digits, `-`, `_` and `.`"
+        ));
+    }
+    repo_config.check_tag(tag)?;
+    Ok(tag.to_string())
+}
+
+/// Number of links with each tag. Tags of the vocabulary no link has yet
+/// are counted as 0.
+pub fn counts<'a>(repo_config: &'a RepoConfig, manifest: &'a Manifest) -> BTreeMap<&'a str, usize> {
+    let mut counts: BTreeMap<&str, usize> = repo_config
+        .tags
+        .iter()
+        .map(|tag| (tag.as_str(), 0))
+        .collect();
+    for tag in manifest.links.iter().flat_map(|link| &link.tags) {
+        *counts.entry(tag.as_str()).or_default() += 1;
+    }
+    counts
+}
+
+/// Replaces each of the tags with `into` on every link, keeping a link from
+/// getting `into` twice. Returns how many links changed.
+pub fn replace(manifest: &mut Manifest, tags: &[String], into: &str) -> usize {
+    let mut changed = 0;
+    for link in &mut manifest.links {
+        if !link.tags.iter().any(|tag| tags.contains(tag)) {
+            continue;
+        }
+        let mut replaced = vec![];
+        for tag in link.tags.drain(..) {
+            let tag = if tags.contains(&tag) {
+                into.to_string()
+            } else {
+                tag
+            };
+            if !replaced.contains(&tag) {
+                replaced.push(tag);
+            }
+        }
+        link.tags = replaced;
+        changed += 1;
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_keeps_tags_to_slug_like_words() {
+        let repo_config = RepoConfig::default();
+        assert_eq!(
+            check(&repo_config, " rust-1.0_beta ").unwrap(),
+            "rust-1.0_beta"
+        );
+        for tag in ["", "two words", "a\"b", "<b>", "a,b", "a/b", "café"] {
+            assert!(check(&repo_config, tag).is_err(), "{tag}");
+        }
+    }
+
+    #[test]
+    fn check_keeps_tags_to_the_vocabulary() {
+        let repo_config = RepoConfig {
+            tags: vec!["talks".to_string()],
+            ..RepoConfig::default()
+        };
+        assert!(check(&repo_config, "talks").is_ok());
+        assert!(check(&repo_config, "docs").is_err());
+    }
+}
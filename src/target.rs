@@ -0,0 +1,294 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Checks of link targets before they're stored, the same wherever a target
+//! comes from.
+
+use crate::config::{HttpsPolicy, ShurlConfig};
+use url::{form_urlencoded, Url};
+
+/// Schemes running code or carrying content of their own in the reader's
+/// browser, never allowed whatever `allowed_schemes` says.
+pub const BLOCKED_SCHEMES: &[&str] = &["javascript", "data", "vbscript"];
+
+/// Parses a target, checking its scheme against `allowed_schemes` and
+/// upgrading or rejecting `http` as `https_policy` says, then normalizes it.
+pub fn parse(cfg: &ShurlConfig, target: &str) -> Result<Url, String> {
+    // Parsing lowercases the host, leaves out default ports and resolves
+    // `.` and `..` in the path already.
+    let mut url = Url::parse(target).map_err(|e| format!("failed to parse url: {e}"))?;
+    check_scheme(url.scheme())?;
+    if url.scheme() == "http" {
+        match cfg.https_policy {
+            HttpsPolicy::Allow => {}
+            HttpsPolicy::Upgrade => {
+                // Only fails for schemes without a host, which http isn't.
+                let _ = url.set_scheme("https");
+            }
+            HttpsPolicy::Require => {
+                return Err(format!(
+                    "{url} isn't https, which https_policy = \"require\" asks for"
+                ))
+            }
+        }
+    }
+    if !cfg
+        .allowed_schemes
+        .iter()
+        .any(|scheme| scheme.eq_ignore_ascii_case(url.scheme()))
+    {
+        return Err(format!(
+            "{}: URLs aren't allowed, allowed_schemes has {}",
+            url.scheme(),
+            cfg.allowed_schemes.join(", ")
+        ));
+    }
+    strip_params(&mut url, &cfg.strip_params);
+    if url.fragment() == Some("") {
+        url.set_fragment(None);
+    }
+    Ok(url)
+}
+
+/// The target as people read it, for `list` and the index: the host in
+/// Unicode rather than punycode, and percent-encoded characters outside
+/// ASCII decoded. Targets are stored and redirected to encoded, as
+/// [`parse`] gives them.
+pub fn display(target: &str) -> String {
+    let Ok(url) = Url::parse(target) else {
+        return target.to_string();
+    };
+    let Some(host) = url.host_str().filter(|host| host.contains("xn--")) else {
+        return decode_non_ascii(target);
+    };
+    let (unicode, result) = idna::domain_to_unicode(host);
+    if result.is_err() {
+        return decode_non_ascii(target);
+    }
+    // The host comes right after the scheme and any user info, so the first
+    // occurrence is the host itself.
+    match target.find(host) {
+        Some(start) => decode_non_ascii(&format!(
+            "{}{unicode}{}",
+            &target[..start],
+            &target[start + host.len()..]
+        )),
+        None => decode_non_ascii(target),
+    }
+}
+
+/// Decodes runs of percent-encoded bytes that are UTF-8 characters outside
+/// ASCII, keeping encoded ASCII such as `%20` or `%2F`, which would mean
+/// something else decoded.
+fn decode_non_ascii(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let mut run = vec![];
+        let mut end = i;
+        while let Some(byte) = encoded_byte(bytes, end).filter(|&byte| byte >= 0x80) {
+            run.push(byte);
+            end += 3;
+        }
+        match String::from_utf8(run) {
+            Ok(decoded) if end > i => {
+                out.push_str(&decoded);
+                i = end;
+            }
+            _ => {
+                let next = if end > i {
+                    end
+                } else {
+                    i + text[i..].chars().next().map_or(1, char::len_utf8)
+                };
+                out.push_str(&text[i..next]);
+                i = next;
+            }
+        }
+    }
+    out
+}
+
+/// The byte of a `%XX` escape starting at `i`, if there is one.
+fn encoded_byte(bytes: &[u8], i: usize) -> Option<u8> {
+    if bytes.get(i) != Some(&b'%') {
+        return None;
+    }
+    let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+    u8::from_str_radix(hex, 16).ok()
+}
+
+/// Leaves out the query parameters matching one of the patterns, e.g.
+/// `utm_*` or `fbclid`, and a query left empty. The other parameters stay
+/// as they were written.
+pub fn strip_params(url: &mut Url, patterns: &[String]) {
+    let Some(query) = url.query() else {
+        return;
+    };
+    let kept: Vec<&str> = query
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .filter(|param| {
+            let Some((name, _)) = form_urlencoded::parse(param.as_bytes()).next() else {
+                return true;
+            };
+            !patterns.iter().any(|pattern| matches_param(pattern, &name))
+        })
+        .collect();
+    if kept.is_empty() {
+        url.set_query(None);
+    } else {
+        let query = kept.join("&");
+        url.set_query(Some(&query));
+    }
+}
+
+/// Whether a parameter name matches a pattern, where a trailing `*`
+/// matches anything.
+fn matches_param(pattern: &str, name: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == pattern,
+    }
+}
+
+/// The target with a query string added to its own, before its fragment,
+/// e.g. for a short link opened with `?ref=mail`.
+pub fn with_query(target: &str, query: &str) -> String {
+    if query.is_empty() {
+        return target.to_string();
+    }
+    let (rest, fragment) = match target.split_once('#') {
+        Some((rest, fragment)) => (rest, Some(fragment)),
+        None => (target, None),
+    };
+    let separator = if !rest.contains('?') {
+        "?"
+    } else if rest.ends_with(['?', '&']) {
+        ""
+    } else {
+        "&"
+    };
+    let mut out = format!("{rest}{separator}{query}");
+    if let Some(fragment) = fragment {
+        out.push('#');
+        out.push_str(fragment);
+    }
+    out
+}
+
+/// Fails for the schemes of [`BLOCKED_SCHEMES`].
+pub fn check_scheme(scheme: &str) -> Result<(), String> {
+    if BLOCKED_SCHEMES
+        .iter()
+        .any(|blocked| blocked.eq_ignore_ascii_case(scheme))
+    {
+        return Err(format!("{scheme}: URLs can't be targets of links"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(cfg: &ShurlConfig, target: &str) -> String {
+        parse(cfg, target).unwrap().to_string()
+    }
+
+    #[test]
+    fn targets_are_normalized() {
+        let cfg = ShurlConfig::default();
+        assert_eq!(
+            parsed(&cfg, "HTTPS://Example.COM:443/a/./b/../c?#"),
+            "https://example.com/a/c"
+        );
+        assert_eq!(
+            parsed(&cfg, "http://example.com:8080/?q=1#top"),
+            "http://example.com:8080/?q=1#top"
+        );
+    }
+
+    #[test]
+    fn tracking_params_are_stripped() {
+        let cfg = ShurlConfig {
+            strip_params: vec!["utm_*".to_string(), "fbclid".to_string()],
+            ..ShurlConfig::default()
+        };
+        assert_eq!(
+            parsed(
+                &cfg,
+                "https://example.com/?utm_source=x&q=a+b&fbclid=1&utm%5Fmedium=y&fbclid2=z"
+            ),
+            "https://example.com/?q=a+b&fbclid2=z"
+        );
+        assert_eq!(
+            parsed(&cfg, "https://example.com/docs?utm_source=x#intro"),
+            "https://example.com/docs#intro"
+        );
+    }
+
+    #[test]
+    fn targets_are_stored_encoded_and_shown_decoded() {
+        let cfg = ShurlConfig::default();
+        let target = parsed(&cfg, "https://bücher.example/straße?q=ü%20x#ä");
+        assert_eq!(
+            target,
+            "https://xn--bcher-kva.example/stra%C3%9Fe?q=%C3%BC%20x#%C3%A4"
+        );
+        assert_eq!(display(&target), "https://bücher.example/straße?q=ü%20x#ä");
+        assert_eq!(
+            display("https://example.com/a%2Fb%FF"),
+            "https://example.com/a%2Fb%FF"
+        );
+    }
+
+    #[test]
+    fn schemes_follow_the_policy() {
+        let cfg = ShurlConfig::default();
+        assert!(parse(&cfg, "javascript:alert(1)").is_err());
+        assert!(parse(&cfg, " JavaScript:alert(1)").is_err());
+        assert!(parse(&cfg, "mailto:someone@example.com").is_err());
+
+        let cfg = ShurlConfig {
+            https_policy: HttpsPolicy::Upgrade,
+            ..ShurlConfig::default()
+        };
+        assert_eq!(parsed(&cfg, "http://example.com/"), "https://example.com/");
+        let cfg = ShurlConfig {
+            https_policy: HttpsPolicy::Require,
+            ..ShurlConfig::default()
+        };
+        assert!(parse(&cfg, "http://example.com/").is_err());
+    }
+
+    #[test]
+    fn queries_are_passed_on() {
+        assert_eq!(
+            with_query("https://example.com/docs", "x=1"),
+            "https://example.com/docs?x=1"
+        );
+        assert_eq!(
+            with_query("https://example.com/?q=a#intro", "x=1&y=2"),
+            "https://example.com/?q=a&x=1&y=2#intro"
+        );
+        assert_eq!(
+            with_query("https://example.com/", ""),
+            "https://example.com/"
+        );
+    }
+}
@@ -0,0 +1,337 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Minimal mustache-style templates.
+//!
+//! Supported tags:
+//! - `{{name}}` inserts an HTML-escaped value
+//! - `{{{name}}}` and `{{& name}}` insert a value as-is
+//! - `{{#name}}...{{/name}}` renders once for a truthy value or once per item of a list
+//! - `{{^name}}...{{/name}}` renders when the value is missing, false or empty
+//! - `{{! comment }}` is ignored
+
+use std::collections::BTreeMap;
+
+pub enum Value {
+    Text(String),
+    Bool(bool),
+    List(Vec<Context>),
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::Text(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::Text(value.to_string())
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl From<Vec<Context>> for Value {
+    fn from(value: Vec<Context>) -> Self {
+        Value::List(value)
+    }
+}
+
+#[derive(Default)]
+pub struct Context {
+    values: BTreeMap<String, Value>,
+}
+
+impl Context {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, key: &str, value: impl Into<Value>) -> &mut Self {
+        self.values.insert(key.to_string(), value.into());
+        self
+    }
+}
+
+enum Node {
+    Text(String),
+    Var {
+        name: String,
+        escape: bool,
+    },
+    Section {
+        name: String,
+        inverted: bool,
+        children: Vec<Node>,
+    },
+}
+
+pub fn render(template: &str, context: &Context) -> Result<String, String> {
+    let nodes = parse(template)?;
+    let mut out = String::with_capacity(template.len());
+    render_nodes(&nodes, &mut vec![context], &mut out);
+    Ok(out)
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// A section being parsed: its name and whether it is inverted, plus the
+/// nodes collected so far. The top level has no name.
+type OpenSection = (Option<(String, bool)>, Vec<Node>);
+
+fn parse(template: &str) -> Result<Vec<Node>, String> {
+    let mut stack: Vec<OpenSection> = vec![(None, vec![])];
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            stack
+                .last_mut()
+                .unwrap()
+                .1
+                .push(Node::Text(rest[..start].to_string()));
+        }
+        rest = &rest[start..];
+
+        let (tag, raw, len) = if rest.starts_with("{{{") {
+            let end = rest
+                .find("}}}")
+                .ok_or_else(|| "unclosed `{{{` tag in template".to_string())?;
+            (rest[3..end].trim(), true, end + 3)
+        } else {
+            let end = rest
+                .find("}}")
+                .ok_or_else(|| "unclosed `{{` tag in template".to_string())?;
+            (rest[2..end].trim(), false, end + 2)
+        };
+        rest = &rest[len..];
+
+        if raw {
+            stack.last_mut().unwrap().1.push(Node::Var {
+                name: tag.to_string(),
+                escape: false,
+            });
+            continue;
+        }
+
+        // Section and comment tags on a line of their own shouldn't leave
+        // blank lines behind in the output.
+        if matches!(tag.chars().next(), Some('#' | '^' | '/' | '!')) {
+            let nodes = &mut stack.last_mut().unwrap().1;
+            let line_start = match nodes.last() {
+                Some(Node::Text(text)) => {
+                    let start = text.rfind('\n').map_or(0, |i| i + 1);
+                    text[start..].trim().is_empty().then_some(start)
+                }
+                None if rest.len() + len == template.len() => Some(0),
+                _ => None,
+            };
+            let line_end = match rest.find('\n') {
+                Some(end) => rest[..end].trim().is_empty().then_some(end + 1),
+                None => rest.trim().is_empty().then_some(rest.len()),
+            };
+            if let (Some(start), Some(end)) = (line_start, line_end) {
+                if let Some(Node::Text(text)) = nodes.last_mut() {
+                    text.truncate(start);
+                }
+                rest = &rest[end..];
+            }
+        }
+
+        match tag.chars().next() {
+            Some('!') => {}
+            Some('&') => stack.last_mut().unwrap().1.push(Node::Var {
+                name: tag[1..].trim().to_string(),
+                escape: false,
+            }),
+            Some(c @ ('#' | '^')) => {
+                stack.push((Some((tag[1..].trim().to_string(), c == '^')), vec![]));
+            }
+            Some('/') => {
+                let name = tag[1..].trim();
+                let (Some((open, inverted)), children) = stack.pop().unwrap() else {
+                    return Err(format!("unexpected closing tag `{name}` in template"));
+                };
+                if open != name {
+                    return Err(format!(
+                        "closing tag `{name}` does not match section `{open}` in template"
+                    ));
+                }
+                stack.last_mut().unwrap().1.push(Node::Section {
+                    name: open,
+                    inverted,
+                    children,
+                });
+            }
+            _ => stack.last_mut().unwrap().1.push(Node::Var {
+                name: tag.to_string(),
+                escape: true,
+            }),
+        }
+    }
+
+    if !rest.is_empty() {
+        stack
+            .last_mut()
+            .unwrap()
+            .1
+            .push(Node::Text(rest.to_string()));
+    }
+
+    let (open, nodes) = stack.pop().unwrap();
+    if let Some((name, _)) = open {
+        return Err(format!("unclosed section `{name}` in template"));
+    }
+    Ok(nodes)
+}
+
+fn lookup<'a>(stack: &[&'a Context], name: &str) -> Option<&'a Value> {
+    stack.iter().rev().find_map(|ctx| ctx.values.get(name))
+}
+
+fn render_nodes<'a>(nodes: &'a [Node], stack: &mut Vec<&'a Context>, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Var { name, escape } => match lookup(stack, name) {
+                Some(Value::Text(text)) if *escape => out.push_str(&escape_html(text)),
+                Some(Value::Text(text)) => out.push_str(text),
+                Some(Value::Bool(value)) => out.push_str(if *value { "true" } else { "false" }),
+                Some(Value::List(_)) | None => {}
+            },
+            Node::Section {
+                name,
+                inverted,
+                children,
+            } => {
+                let value = lookup(stack, name);
+                let truthy = match value {
+                    Some(Value::Text(text)) => !text.is_empty(),
+                    Some(Value::Bool(value)) => *value,
+                    Some(Value::List(items)) => !items.is_empty(),
+                    None => false,
+                };
+
+                if *inverted {
+                    if !truthy {
+                        render_nodes(children, stack, out);
+                    }
+                } else if let Some(Value::List(items)) = value {
+                    for item in items {
+                        stack.push(item);
+                        render_nodes(children, stack, out);
+                        stack.pop();
+                    }
+                } else if truthy {
+                    render_nodes(children, stack, out);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> Context {
+        let mut context = Context::new();
+        context
+            .insert("name", "<b>Tom & \"Jerry\"</b>")
+            .insert("yes", true)
+            .insert("no", false)
+            .insert("empty", "");
+        context
+    }
+
+    #[test]
+    fn values_are_escaped_unless_raw() {
+        let context = context();
+        assert_eq!(
+            render("{{name}}", &context).unwrap(),
+            "&lt;b&gt;Tom &amp; &quot;Jerry&quot;&lt;/b&gt;"
+        );
+        assert_eq!(
+            render("{{{name}}}", &context).unwrap(),
+            "<b>Tom & \"Jerry\"</b>"
+        );
+        assert_eq!(
+            render("{{& name }}", &context).unwrap(),
+            "<b>Tom & \"Jerry\"</b>"
+        );
+        assert_eq!(
+            render("a{{missing}}b{{! note }}c", &context).unwrap(),
+            "abc"
+        );
+    }
+
+    #[test]
+    fn sections_follow_their_value() {
+        let mut context = context();
+        let items = ["x", "y"]
+            .iter()
+            .map(|item| {
+                let mut item_context = Context::new();
+                item_context.insert("item", *item);
+                item_context
+            })
+            .collect::<Vec<_>>();
+        context.insert("items", items);
+        let template = "{{#yes}}1{{/yes}}{{#no}}2{{/no}}{{^no}}3{{/no}}{{^empty}}4{{/empty}}\
+                        {{#items}}[{{item}}{{#yes}}!{{/yes}}]{{/items}}{{^items}}none{{/items}}";
+        assert_eq!(render(template, &context).unwrap(), "134[x!][y!]");
+    }
+
+    #[test]
+    fn section_lines_leave_no_blank_lines() {
+        let template = "<ul>\n  {{#yes}}\n  <li>a</li>\n  {{/yes}}\n</ul>\n";
+        assert_eq!(
+            render(template, &context()).unwrap(),
+            "<ul>\n  <li>a</li>\n</ul>\n"
+        );
+    }
+
+    #[test]
+    fn broken_templates_are_rejected() {
+        for template in [
+            "{{name",
+            "{{{name}}",
+            "{{#yes}}",
+            "{{/yes}}",
+            "{{#yes}}{{/no}}",
+        ] {
+            assert!(render(template, &context()).is_err(), "{template}");
+        }
+    }
+}
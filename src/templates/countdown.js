@@ -0,0 +1,26 @@
+(function () {
+    var root = document.documentElement;
+    var starts = Date.parse(root.getAttribute("data-shurl-starts"));
+    var target = root.getAttribute("data-shurl-target");
+    var countdown = document.getElementById("countdown");
+    document.getElementById("starts").textContent = new Date(starts).toLocaleString(root.lang);
+
+    function tick() {
+        var left = starts - Date.now();
+        if (left <= 0) {
+            window.location.replace(target);
+            return;
+        }
+        var seconds = Math.floor(left / 1000);
+        var parts = [
+            Math.floor(seconds / 86400) + "d",
+            Math.floor((seconds % 86400) / 3600) + "h",
+            Math.floor((seconds % 3600) / 60) + "m",
+            (seconds % 60) + "s"
+        ];
+        countdown.textContent =
+            ", " + countdown.getAttribute("data-in") + " " + (seconds < 86400 ? parts.slice(1) : parts).join(" ");
+        setTimeout(tick, left % 1000 || 1000);
+    }
+    tick();
+})();
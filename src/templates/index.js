@@ -0,0 +1,93 @@
+(function () {
+    var form = document.getElementById("filter");
+    var input = form.elements.query;
+    var root = form.getAttribute("data-root");
+    var listing = document.getElementById("listing");
+    var matches = document.getElementById("matches");
+    var rows = listing.querySelectorAll("table.links tbody tr");
+    // Every link, also those on other pages, once links.json is loaded.
+    // Until then, or if it can't be, only the rows of this page are filtered.
+    var links = null;
+    // Shown once it works, useless to readers running no scripts.
+    form.hidden = false;
+    form.addEventListener("submit", function (event) {
+        event.preventDefault();
+    });
+
+    function matchesTerms(text, terms) {
+        text = text.toLowerCase();
+        return terms.every(function (term) {
+            return text.indexOf(term) !== -1;
+        });
+    }
+
+    function cell(row, className) {
+        var td = row.insertCell();
+        td.className = className;
+        return td;
+    }
+
+    function anchor(parent, href, text) {
+        var a = document.createElement("a");
+        a.href = href;
+        a.textContent = text;
+        parent.appendChild(a);
+    }
+
+    function filter() {
+        var terms = input.value.toLowerCase().split(/\s+/).filter(Boolean);
+        if (links === null) {
+            rows.forEach(function (row) {
+                row.hidden = !matchesTerms(row.textContent, terms);
+            });
+            return;
+        }
+        listing.hidden = terms.length > 0;
+        matches.hidden = terms.length === 0;
+        var body = matches.tBodies[0];
+        body.textContent = "";
+        links.forEach(function (link) {
+            var tags = link.tags.map(function (tag) {
+                return "#" + tag;
+            });
+            var text = [link.slug, link.target, link.description || ""].concat(tags).join(" ");
+            if (!matchesTerms(text, terms)) {
+                return;
+            }
+            var row = body.insertRow();
+            anchor(cell(row, "slug"), root + link.href, link.slug);
+            var target = cell(row, "target");
+            if (link.description) {
+                var description = document.createElement("span");
+                description.className = "description";
+                description.textContent = link.description;
+                target.appendChild(description);
+            }
+            anchor(target, link.target, link.target);
+            tags.forEach(function (tag) {
+                var span = document.createElement("span");
+                span.className = "tag";
+                span.textContent = tag;
+                target.appendChild(document.createTextNode(" "));
+                target.appendChild(span);
+            });
+            cell(row, "date").textContent = link.created.slice(0, 10);
+        });
+    }
+
+    input.addEventListener("input", filter);
+    fetch(root + "links.json")
+        .then(function (response) {
+            return response.ok ? response.json() : Promise.reject(response.status);
+        })
+        .then(function (all) {
+            // Rows hidden by filtering the page are shown again with the
+            // listing.
+            rows.forEach(function (row) {
+                row.hidden = false;
+            });
+            links = all;
+            filter();
+        })
+        .catch(function () {});
+})();
@@ -0,0 +1,36 @@
+(function () {
+    var root = document.documentElement;
+    var targets = {};
+    var locales = JSON.parse(root.getAttribute("data-shurl-locales"));
+    Object.keys(locales).forEach(function (tag) {
+        targets[tag.toLowerCase()] = locales[tag];
+    });
+
+    // Readers of `de-AT` get `de`, readers of `pt` get `pt-BR`, unless
+    // there's a target for exactly their language.
+    function find(language) {
+        var tag = language.toLowerCase();
+        var primary = tag.split("-")[0];
+        if (targets[tag]) {
+            return targets[tag];
+        }
+        if (targets[primary]) {
+            return targets[primary];
+        }
+        var match = Object.keys(targets).filter(function (key) {
+            return key.split("-")[0] === primary;
+        })[0];
+        return match && targets[match];
+    }
+
+    var languages = navigator.languages || [navigator.language];
+    var target = root.getAttribute("data-shurl-target");
+    for (var i = 0; i < languages.length; i++) {
+        var found = languages[i] && find(languages[i]);
+        if (found) {
+            target = found;
+            break;
+        }
+    }
+    window.location.replace(target);
+})();
@@ -0,0 +1,18 @@
+(function () {
+    var form = document.getElementById("search");
+    var root = new URL(form.getAttribute("data-shurl-root"), window.location.href);
+    var input = form.elements.slug;
+    // Starts from the mistyped path, so the browser suggests the links
+    // close to it.
+    var path = decodeURIComponent(window.location.pathname);
+    if (path.indexOf(root.pathname) === 0) {
+        input.value = path.slice(root.pathname.length).replace(/(\.html|\/)$/, "");
+    }
+    form.addEventListener("submit", function (event) {
+        event.preventDefault();
+        var slug = input.value.trim();
+        if (slug) {
+            window.location.href = new URL(encodeURI(slug), root).href;
+        }
+    });
+})();
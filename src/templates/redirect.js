@@ -0,0 +1,36 @@
+(function () {
+    var root = document.documentElement;
+    var target = root.getAttribute("data-shurl-target");
+    var expires = Date.parse(root.getAttribute("data-shurl-expires-at"));
+    if (Date.now() >= expires) {
+        document.addEventListener("DOMContentLoaded", function () {
+            var date = new Date(expires).toLocaleDateString(root.lang || undefined);
+            document.getElementById("redirecting").textContent = root
+                .getAttribute("data-shurl-expired")
+                .replace("{date}", date);
+            document.getElementById("follow").textContent = "";
+        });
+        return;
+    }
+    // The reader's own query string and fragment, added to the target's.
+    if (root.hasAttribute("data-shurl-passthrough")) {
+        var url = new URL(target);
+        new URLSearchParams(window.location.search).forEach(function (value, name) {
+            url.searchParams.append(name, value);
+        });
+        if (window.location.hash) {
+            url.hash = window.location.hash;
+        }
+        target = url.href;
+        document.addEventListener("DOMContentLoaded", function () {
+            document.querySelectorAll("#redirecting a, #follow a").forEach(function (link) {
+                link.href = target;
+            });
+        });
+    }
+    // Redirects, also for browsers ignoring the meta refresh.
+    var delay = parseFloat(root.getAttribute("data-shurl-delay")) || 0;
+    setTimeout(function () {
+        window.location.replace(target);
+    }, delay * 1000);
+})();
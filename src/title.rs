@@ -0,0 +1,178 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Titles of target pages, for descriptions and names of new links.
+
+use crate::http::Request;
+use crate::verify;
+
+/// Longest title kept, in characters. Some pages stuff keywords into it.
+const MAX_LENGTH: usize = 200;
+
+/// Fetches the target and reads the `<title>` of the page.
+pub fn fetch(url: &str) -> Result<String, String> {
+    extract(&fetch_page(url)?).ok_or_else(|| format!("{url} has no title"))
+}
+
+/// Fetches the HTML of the target page.
+pub fn fetch_page(url: &str) -> Result<String, String> {
+    let response = Request::new("GET", url)
+        .header("User-Agent", "shurl")
+        .header("Accept", "text/html")
+        .timeout(verify::TIMEOUT)
+        .send()
+        .map_err(|e| format!("failed to fetch {url}: {e}"))?;
+    if response.status >= 400 {
+        return Err(format!("{url} answers {}", response.status));
+    }
+    Ok(response.body)
+}
+
+/// The text of the first `<title>` element, with entities decoded and
+/// whitespace collapsed. `None` if it's missing or empty.
+pub fn extract(html: &str) -> Option<String> {
+    // Tags are ASCII, so positions in the lowercase copy are the same.
+    let lower = html.to_ascii_lowercase();
+    let mut from = 0;
+    let start = loop {
+        let open = from + lower[from..].find("<title")?;
+        let after = lower.as_bytes().get(open + 6).copied();
+        // Not `<titles>` or some other tag starting the same.
+        if matches!(after, Some(b'>' | b' ' | b'\t' | b'\n' | b'\r')) {
+            break open + lower[open..].find('>')? + 1;
+        }
+        from = open + 6;
+    };
+    let end = start + lower[start..].find("</title")?;
+
+    clean(&html[start..end], MAX_LENGTH)
+}
+
+/// Text from HTML with entities decoded, whitespace collapsed and cut to at
+/// most `max_length` characters. `None` if nothing is left.
+pub fn clean(html: &str, max_length: usize) -> Option<String> {
+    let text = decode_entities(html);
+    let text: Vec<&str> = text.split_whitespace().collect();
+    let text = text.join(" ");
+    if text.is_empty() {
+        return None;
+    }
+    Some(match text.char_indices().nth(max_length) {
+        Some((end, _)) => format!("{}…", text[..end].trim_end()),
+        None => text,
+    })
+}
+
+/// Decodes the character references of text in HTML: the named ones common
+/// in titles and numeric ones. Anything else is left as it is.
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| Some((decode_entity(&rest[1..end + 1])?, end + 2)));
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    if let Some(number) = name.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => number.parse().ok()?,
+        };
+        return char::from_u32(code);
+    }
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{a0}',
+        "ndash" => '–',
+        "mdash" => '—',
+        "hellip" => '…',
+        "middot" => '·',
+        "laquo" => '«',
+        "raquo" => '»',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "aacute" => 'á',
+        "agrave" => 'à',
+        "acirc" => 'â',
+        "auml" => 'ä',
+        "aring" => 'å',
+        "ccedil" => 'ç',
+        "eacute" => 'é',
+        "egrave" => 'è',
+        "ecirc" => 'ê',
+        "euml" => 'ë',
+        "iacute" => 'í',
+        "icirc" => 'î',
+        "iuml" => 'ï',
+        "ntilde" => 'ñ',
+        "oacute" => 'ó',
+        "ocirc" => 'ô',
+        "ouml" => 'ö',
+        "oslash" => 'ø',
+        "uacute" => 'ú',
+        "ucirc" => 'û',
+        "uuml" => 'ü',
+        "szlig" => 'ß',
+        "Auml" => 'Ä',
+        "Eacute" => 'É',
+        "Ouml" => 'Ö',
+        "Uuml" => 'Ü',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn title_is_extracted_and_decoded() {
+        let html = "<html><head><titles>no</titles><TITLE data-x=1>\n  Rust &amp; You &#8212; Caf&eacute; &#x2F; &bogus;\n</TITLE>";
+        assert_eq!(
+            extract(html).as_deref(),
+            Some("Rust & You — Café / &bogus;")
+        );
+        assert_eq!(extract("<title>  </title>"), None);
+        assert_eq!(extract("<html><head></head></html>"), None);
+    }
+}
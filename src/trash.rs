@@ -0,0 +1,203 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Removed links are kept for a while, so a mistaken `shurl rm` in a shared
+//! repository can be undone. Their pages say the link is gone until the
+//! retention period is over.
+
+use crate::config::ReferrerPolicy;
+use crate::date;
+use crate::manifest::{Link, Manifest};
+use crate::preview::Preview;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::value::Datetime;
+
+pub const TRASH_DIR: &str = ".trash";
+const TRASH_FILE: &str = "links.toml";
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Trash {
+    #[serde(default, rename = "link")]
+    pub links: Vec<TrashedLink>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TrashedLink {
+    pub slug: String,
+    pub target: String,
+    pub created: Datetime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<Datetime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub starts: Option<Datetime>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub locales: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issue: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delay: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub noindex: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub referrer: Option<ReferrerPolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<Preview>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clicks: Option<u64>,
+    pub deleted: Datetime,
+}
+
+impl TrashedLink {
+    pub fn new(link: Link) -> Self {
+        Self {
+            slug: link.slug,
+            target: link.target,
+            created: link.created,
+            status: link.status,
+            expires: link.expires,
+            starts: link.starts,
+            locales: link.locales,
+            owner: link.owner,
+            issue: link.issue,
+            delay: link.delay,
+            noindex: link.noindex,
+            referrer: link.referrer,
+            template: link.template,
+            lang: link.lang,
+            description: link.description,
+            tags: link.tags,
+            preview: link.preview,
+            clicks: link.clicks,
+            deleted: date::now(),
+        }
+    }
+
+    pub fn restore(self) -> Link {
+        Link {
+            slug: self.slug,
+            target: self.target,
+            created: self.created,
+            status: self.status,
+            expires: self.expires,
+            starts: self.starts,
+            locales: self.locales,
+            owner: self.owner,
+            issue: self.issue,
+            delay: self.delay,
+            noindex: self.noindex,
+            referrer: self.referrer,
+            template: self.template,
+            lang: self.lang,
+            description: self.description,
+            tags: self.tags,
+            preview: self.preview,
+            clicks: self.clicks,
+        }
+    }
+
+    /// When the link is deleted for good, never if `retention_days` is 0.
+    pub fn purge_date(&self, retention_days: u32) -> Option<Datetime> {
+        (retention_days > 0).then(|| {
+            date::from_unix(date::to_unix(&self.deleted) + i64::from(retention_days) * 86400)
+        })
+    }
+}
+
+/// Where the trash of the repository is kept.
+pub fn trash_path(repo_path: &Path) -> PathBuf {
+    repo_path.join(TRASH_DIR).join(TRASH_FILE)
+}
+
+impl Trash {
+    pub fn load(repo_path: &Path) -> Result<Self, String> {
+        let path = trash_path(repo_path);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        toml::from_str(&content).map_err(|e| format!("failed to parse {}: {e}", path.display()))
+    }
+
+    /// Saves the trash, removing `.trash` altogether once it's empty.
+    pub fn save(&self, repo_path: &Path) -> Result<(), String> {
+        let path = trash_path(repo_path);
+        if self.links.is_empty() {
+            if path.exists() {
+                fs::remove_file(&path)
+                    .map_err(|e| format!("failed to remove {}: {e}", path.display()))?;
+            }
+            // Only succeeds if nothing else was put in it.
+            let _ = fs::remove_dir(repo_path.join(TRASH_DIR));
+            return Ok(());
+        }
+
+        fs::create_dir_all(repo_path.join(TRASH_DIR))
+            .map_err(|e| format!("failed to create {TRASH_DIR}: {e}"))?;
+        let content = toml::to_string_pretty(self)
+            .map_err(|e| format!("failed to serialize {}: {e}", path.display()))?;
+        fs::write(&path, content).map_err(|e| format!("failed to write {}: {e}", path.display()))
+    }
+
+    /// Adds a link, replacing an older trashed link with the same slug.
+    pub fn insert(&mut self, link: TrashedLink) {
+        self.links.retain(|existing| existing.slug != link.slug);
+        self.links.push(link);
+    }
+
+    pub fn take(&mut self, slug: &str) -> Option<TrashedLink> {
+        let index = self.links.iter().position(|link| link.slug == slug)?;
+        Some(self.links.remove(index))
+    }
+
+    /// Sorted slugs of the removed links that haven't been taken again.
+    pub fn gone_slugs<'a>(&'a self, manifest: &Manifest) -> Vec<&'a str> {
+        let mut slugs: Vec<&str> = self
+            .links
+            .iter()
+            .map(|link| link.slug.as_str())
+            .filter(|slug| manifest.get(slug).is_none())
+            .collect();
+        slugs.sort();
+        slugs
+    }
+
+    /// Takes out the links whose retention period is over.
+    pub fn take_expired(&mut self, retention_days: u32) -> Vec<TrashedLink> {
+        let now = date::to_unix(&date::now());
+        let (expired, kept) = self.links.drain(..).partition(|link| {
+            link.purge_date(retention_days)
+                .is_some_and(|purge_date| date::to_unix(&purge_date) <= now)
+        });
+        self.links = kept;
+        expired
+    }
+}
@@ -0,0 +1,659 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! `shurl tui`, a table of the links to browse, search and change them in
+//! the terminal, and the picker of commands given no slug. The terminal is
+//! put into raw mode with `stty` and drawn on with ANSI escapes, so no
+//! terminal libraries need to be linked.
+
+use crate::clipboard;
+use crate::error::ShurlError;
+use crate::git;
+use crate::manifest::{Link, Manifest};
+use crate::output;
+use crate::pages;
+use crate::repo_config::RepoConfig;
+use crate::search::Query;
+use crate::tags;
+use crate::target;
+use crate::Shurl;
+use std::io::{self, IsTerminal, Read, Write};
+use std::process::{Command, Stdio};
+
+const HELP: &str =
+    "↑↓ move  / search  o open  c copy  e edit target  r rename  t tag  d delete  p push  q quit";
+
+/// Shows the links until `q`, committing and pushing every change made to
+/// them like the commands doing the same.
+pub fn run(shurl: &Shurl) -> Result<(), ShurlError> {
+    // Git and the backends mustn't print over the table.
+    let quiet = output::is_quiet();
+    output::set_quiet(true);
+    let result = RawMode::enter()
+        .map_err(ShurlError::Other)
+        .and_then(|_raw| App::new(shurl)?.run());
+    output::set_quiet(quiet);
+    result
+}
+
+/// Lets someone pick a link by typing parts of its slug, target or
+/// description, the best matches first. Nothing if they pressed Escape.
+pub fn pick(links: &[Link]) -> Result<Option<String>, ShurlError> {
+    if !io::stdin().is_terminal() {
+        return Err(ShurlError::InvalidInput(
+            "give a slug, there's no terminal to pick one in".to_string(),
+        ));
+    }
+    let _raw = RawMode::enter().map_err(ShurlError::Other)?;
+    let mut input = io::stdin().lock();
+    let mut query = String::new();
+    let mut selected = 0;
+    loop {
+        let matches = ranked(links, &query);
+        selected = selected.min(matches.len().saturating_sub(1));
+        draw_picker(&query, &matches, selected, links.len())?;
+        match read_key(&mut input).map_err(|e| format!("failed to read key: {e}"))? {
+            Key::Enter => {
+                if let Some(link) = matches.get(selected) {
+                    return Ok(Some(link.slug.clone()));
+                }
+            }
+            Key::Escape | Key::Interrupt => return Ok(None),
+            Key::Up => selected = selected.saturating_sub(1),
+            Key::Down => selected += 1,
+            Key::Backspace => {
+                query.pop();
+                selected = 0;
+            }
+            Key::Char(c) => {
+                query.push(c);
+                selected = 0;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The links matching the query, best first, slugs counting double.
+fn ranked<'a>(links: &'a [Link], query: &str) -> Vec<&'a Link> {
+    let mut scored: Vec<(i64, &Link)> = links
+        .iter()
+        .filter_map(|link| {
+            let slug = fuzzy_score(query, &link.slug).map(|score| score * 2);
+            let rest = format!(
+                "{} {}",
+                link.target,
+                link.description.as_deref().unwrap_or_default()
+            );
+            let score = slug.max(fuzzy_score(query, &rest))?;
+            Some((score, link))
+        })
+        .collect();
+    scored.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+    scored.into_iter().map(|(_, link)| link).collect()
+}
+
+/// How well the characters of the query are found in the text in order,
+/// ignoring case, more for runs of them and ones starting words. Nothing if
+/// they aren't all there.
+fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous = None;
+    for c in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = position + text[position..].iter().position(|&t| t == c)?;
+        score += 1;
+        if found > 0 && previous == Some(found - 1) {
+            score += 5;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
+fn draw_picker(
+    query: &str,
+    matches: &[&Link],
+    selected: usize,
+    total: usize,
+) -> Result<(), ShurlError> {
+    let (rows, columns) = size();
+    let height = rows.saturating_sub(2).max(1);
+    let offset = (selected + 1).saturating_sub(height);
+    let mut screen = String::from("\x1b[H\x1b[2J");
+    screen.push_str(&format!(
+        "\x1b[1m>\x1b[0m {query}  \x1b[2m{}/{total}\x1b[0m\r\n",
+        matches.len()
+    ));
+    let slug_width = matches
+        .iter()
+        .map(|link| link.slug.chars().count())
+        .max()
+        .unwrap_or_default()
+        .min(columns / 3);
+    for (i, link) in matches.iter().enumerate().skip(offset).take(height) {
+        let about = match &link.description {
+            Some(description) => format!("{}  {description}", target::display(&link.target)),
+            None => target::display(&link.target),
+        };
+        let row = format!(
+            "{}  {}",
+            fit(&link.slug, slug_width),
+            fit(&about, columns.saturating_sub(slug_width + 2))
+        );
+        if i == selected {
+            screen.push_str(&format!("\x1b[7m{row}\x1b[0m\r\n"));
+        } else {
+            screen.push_str(&format!("{row}\r\n"));
+        }
+    }
+    // The cursor after the query.
+    screen.push_str(&format!("\x1b[1;{}H\x1b[?25h", query.chars().count() + 3));
+    let mut stdout = io::stdout().lock();
+    stdout
+        .write_all(screen.as_bytes())
+        .and_then(|()| stdout.flush())
+        .map_err(|e| ShurlError::Other(format!("failed to draw: {e}")))
+}
+
+/// Raw mode and the alternate screen of the terminal, left when dropped.
+struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    fn enter() -> Result<Self, String> {
+        let saved = stty(&["-g"])?.trim().to_string();
+        // Reads give up after a tenth of a second, telling Escape from the
+        // start of an escape sequence.
+        stty(&["raw", "-echo", "min", "0", "time", "1"])?;
+        print!("\x1b[?1049h\x1b[?25l");
+        Ok(Self { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+fn stty(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|e| format!("failed to run stty: {e}"))?;
+    if !output.status.success() {
+        return Err("failed to set up the terminal, is it one?".to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Rows and columns of the terminal, 24 by 80 if it doesn't say.
+fn size() -> (usize, usize) {
+    stty(&["size"])
+        .ok()
+        .and_then(|size| {
+            let (rows, columns) = size.trim().split_once(' ')?;
+            Some((rows.parse().ok()?, columns.parse().ok()?))
+        })
+        .unwrap_or((24, 80))
+}
+
+enum Key {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Escape,
+    Backspace,
+    Interrupt,
+    Char(char),
+}
+
+/// The next byte, if one comes within the read timeout.
+fn read_byte(input: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut byte = [0];
+    Ok((input.read(&mut byte)? == 1).then_some(byte[0]))
+}
+
+fn read_key(input: &mut impl Read) -> io::Result<Key> {
+    let first = loop {
+        if let Some(byte) = read_byte(input)? {
+            break byte;
+        }
+    };
+    Ok(match first {
+        0x1b => match (read_byte(input)?, read_byte(input)?) {
+            (Some(b'[' | b'O'), Some(b'A')) => Key::Up,
+            (Some(b'[' | b'O'), Some(b'B')) => Key::Down,
+            (Some(b'[' | b'O'), Some(b'H')) => Key::Home,
+            (Some(b'[' | b'O'), Some(b'F')) => Key::End,
+            (Some(b'['), Some(digit @ b'1'..=b'8')) => {
+                // `~` ends the sequence.
+                read_byte(input)?;
+                match digit {
+                    b'1' | b'7' => Key::Home,
+                    b'4' | b'8' => Key::End,
+                    b'5' => Key::PageUp,
+                    b'6' => Key::PageDown,
+                    _ => Key::Escape,
+                }
+            }
+            _ => Key::Escape,
+        },
+        b'\r' | b'\n' => Key::Enter,
+        0x7f | 0x08 => Key::Backspace,
+        0x03 | 0x04 => Key::Interrupt,
+        byte => {
+            // The rest of a character of several bytes in UTF-8.
+            let len = match byte {
+                0xf0.. => 4,
+                0xe0.. => 3,
+                0xc0.. => 2,
+                _ => 1,
+            };
+            let mut bytes = vec![byte];
+            for _ in 1..len {
+                bytes.extend(read_byte(input)?);
+            }
+            let text = String::from_utf8_lossy(&bytes);
+            Key::Char(text.chars().next().unwrap_or(char::REPLACEMENT_CHARACTER))
+        }
+    })
+}
+
+/// The text cut to `width` characters, ending in `…` if it was longer.
+fn fit(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return format!("{text:width$}");
+    }
+    let mut fitted: String = text.chars().take(width.saturating_sub(1)).collect();
+    if width > 0 {
+        fitted.push('…');
+    }
+    fitted
+}
+
+/// Opens the URL in the default browser.
+pub fn open(url: &str) -> Result<(), String> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    command
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(drop)
+        .map_err(|e| format!("failed to open {url}: {e}"))
+}
+
+struct App<'a> {
+    shurl: &'a Shurl,
+    /// Every link, newest first.
+    links: Vec<Link>,
+    query: String,
+    /// Position of the selected link among the ones matching the query.
+    selected: usize,
+    /// Position of the first link on the screen.
+    offset: usize,
+    message: String,
+}
+
+impl<'a> App<'a> {
+    fn new(shurl: &'a Shurl) -> Result<Self, ShurlError> {
+        Ok(Self {
+            shurl,
+            links: shurl.list_links()?,
+            query: String::new(),
+            selected: 0,
+            offset: 0,
+            message: String::new(),
+        })
+    }
+
+    fn run(&mut self) -> Result<(), ShurlError> {
+        let mut input = io::stdin().lock();
+        loop {
+            self.draw(None)?;
+            let key = read_key(&mut input).map_err(|e| format!("failed to read key: {e}"))?;
+            let count = self.matching().len();
+            let page = size().0.saturating_sub(6).max(1);
+            match key {
+                Key::Char('q') | Key::Interrupt => return Ok(()),
+                Key::Up | Key::Char('k') => self.selected = self.selected.saturating_sub(1),
+                Key::Down | Key::Char('j') => self.selected += 1,
+                Key::PageUp => self.selected = self.selected.saturating_sub(page),
+                Key::PageDown => self.selected += page,
+                Key::Home | Key::Char('g') => self.selected = 0,
+                Key::End | Key::Char('G') => self.selected = count.saturating_sub(1),
+                Key::Char('/') => self.search(&mut input)?,
+                Key::Escape => self.query.clear(),
+                Key::Char(action @ ('o' | 'c' | 'e' | 'r' | 't' | 'd')) => {
+                    if let Some(link) = self.matching().get(self.selected).copied().cloned() {
+                        self.message = match self.act(&mut input, action, &link) {
+                            Ok(message) => message,
+                            Err(e) => format!("\x1b[31mError:\x1b[0m {e}"),
+                        };
+                        self.links = self.shurl.list_links()?;
+                    }
+                }
+                Key::Char('p') => {
+                    let cfg = self.shurl.config();
+                    self.message = match git::push(self.shurl.repo_path(), &cfg.branch) {
+                        Ok(()) => format!("pushed {}", cfg.branch),
+                        Err(e) => format!("\x1b[31mError:\x1b[0m {e}"),
+                    };
+                }
+                _ => {}
+            }
+            self.selected = self.selected.min(self.matching().len().saturating_sub(1));
+        }
+    }
+
+    fn matching(&self) -> Vec<&Link> {
+        let query = Query::Text(self.query.to_lowercase());
+        self.links
+            .iter()
+            .filter(|link| query.matches(link))
+            .collect()
+    }
+
+    /// Filters the links as the query is typed, Escape clearing it again.
+    fn search(&mut self, input: &mut impl Read) -> Result<(), ShurlError> {
+        let previous = self.query.clone();
+        loop {
+            let query = self.query.clone();
+            self.draw(Some(("/", &query)))?;
+            match read_key(input).map_err(|e| format!("failed to read key: {e}"))? {
+                Key::Enter => return Ok(()),
+                Key::Escape | Key::Interrupt => {
+                    self.query = previous;
+                    return Ok(());
+                }
+                Key::Backspace => {
+                    self.query.pop();
+                }
+                Key::Char(c) => self.query.push(c),
+                _ => {}
+            }
+            self.selected = 0;
+        }
+    }
+
+    /// Reads a line on the bottom of the screen, starting with `initial`.
+    /// Escape gives nothing.
+    fn ask(
+        &mut self,
+        input: &mut impl Read,
+        label: &str,
+        initial: &str,
+    ) -> Result<Option<String>, ShurlError> {
+        let mut answer = initial.to_string();
+        loop {
+            self.draw(Some((label, &answer)))?;
+            match read_key(input).map_err(|e| format!("failed to read key: {e}"))? {
+                Key::Enter => return Ok(Some(answer.trim().to_string())),
+                Key::Escape | Key::Interrupt => return Ok(None),
+                Key::Backspace => {
+                    answer.pop();
+                }
+                Key::Char(c) => answer.push(c),
+                _ => {}
+            }
+        }
+    }
+
+    /// Does what the key stands for to the link, returning what to tell.
+    fn act(
+        &mut self,
+        input: &mut impl Read,
+        action: char,
+        link: &Link,
+    ) -> Result<String, ShurlError> {
+        let shurl = self.shurl;
+        let cfg = shurl.config();
+        let slug = &link.slug;
+        let mut manifest = Manifest::load(shurl.repo_path())?;
+        let Some(mut changed) = manifest.get(slug).cloned() else {
+            return Err(ShurlError::NotFound(format!("no link named `{slug}`")));
+        };
+        match action {
+            'o' => {
+                open(&link.target)?;
+                Ok(format!("opened {}", link.target))
+            }
+            'c' => {
+                let short_url = cfg.short_url(slug).ok_or_else(|| {
+                    ShurlError::Config("copying needs `base_url` in the config".to_string())
+                })?;
+                clipboard::write(&short_url)?;
+                Ok(format!("copied {short_url}"))
+            }
+            'e' => {
+                let Some(answer) = self.ask(input, "Target: ", &link.target)? else {
+                    return Ok(String::new());
+                };
+                let url = target::parse(cfg, &answer).map_err(ShurlError::InvalidInput)?;
+                let repo_config =
+                    RepoConfig::load(shurl.repo_path()).map_err(ShurlError::Config)?;
+                if let Some(namespace) = repo_config.namespace_for(slug) {
+                    namespace
+                        .check(slug, &url)
+                        .map_err(ShurlError::InvalidInput)?;
+                }
+                changed.target = url.to_string();
+                let message = format!("Point {slug} to {}", changed.target);
+                manifest.insert(changed);
+                shurl.publish(&manifest, &message)?;
+                Ok(message)
+            }
+            'r' => {
+                let Some(answer) = self.ask(input, "New name: ", slug)? else {
+                    return Ok(String::new());
+                };
+                if answer == *slug {
+                    return Ok(String::new());
+                }
+                let new = shurl
+                    .new_link(&manifest, &link.target, Some(answer), None)?
+                    .slug;
+                if manifest.get(&new).is_some() {
+                    return Err(ShurlError::Conflict(format!("`{new}` is taken")));
+                }
+                manifest.remove(slug);
+                changed.slug = new.clone();
+                manifest.insert(changed);
+                pages::remove_page(shurl.repo_path(), slug)?;
+                let message = format!("Rename {slug} to {new}");
+                shurl.publish(&manifest, &message)?;
+                Ok(message)
+            }
+            't' => {
+                let Some(answer) = self.ask(input, "Tags: ", &link.tags.join(", "))? else {
+                    return Ok(String::new());
+                };
+                let repo_config =
+                    RepoConfig::load(shurl.repo_path()).map_err(ShurlError::Config)?;
+                let mut tags = vec![];
+                for tag in answer.split(',').filter(|tag| !tag.trim().is_empty()) {
+                    let tag = tags::check(&repo_config, tag).map_err(ShurlError::InvalidInput)?;
+                    if !tags.contains(&tag) {
+                        tags.push(tag);
+                    }
+                }
+                if tags == link.tags {
+                    return Ok(String::new());
+                }
+                let message = if tags.is_empty() {
+                    format!("Untag {slug}")
+                } else {
+                    format!("Tag {slug} with {}", tags.join(", "))
+                };
+                changed.tags = tags;
+                manifest.insert(changed);
+                shurl.publish(&manifest, &message)?;
+                Ok(message)
+            }
+            'd' => {
+                self.draw(Some((&format!("Move {slug} to the trash? [y/N] "), "")))?;
+                let key = read_key(input).map_err(|e| format!("failed to read key: {e}"))?;
+                if !matches!(key, Key::Char('y' | 'Y')) {
+                    return Ok(String::new());
+                }
+                shurl.remove_link(slug)?;
+                Ok(format!(
+                    "moved {slug} to the trash, undo with `shurl trash restore {slug}`"
+                ))
+            }
+            _ => Ok(String::new()),
+        }
+    }
+
+    /// Draws the screen, with a line being asked for at the bottom if
+    /// `prompt` has its label and what was typed so far.
+    fn draw(&mut self, prompt: Option<(&str, &str)>) -> Result<(), ShurlError> {
+        let (rows, columns) = size();
+        let cfg = self.shurl.config();
+        let height = rows.saturating_sub(5).max(1);
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + height {
+            self.offset = self.selected + 1 - height;
+        }
+        let links = self.matching();
+
+        let mut screen = String::from("\x1b[H\x1b[2J");
+        let title = if self.query.is_empty() {
+            format!("{} links", links.len())
+        } else {
+            format!(
+                "{} of {} links matching `{}`",
+                links.len(),
+                self.links.len(),
+                self.query
+            )
+        };
+        screen.push_str(&format!("\x1b[1m{}\x1b[0m\r\n", fit(&title, columns)));
+        let slug_width = links
+            .iter()
+            .map(|link| link.slug.chars().count())
+            .max()
+            .unwrap_or(4)
+            .clamp(4, columns / 4);
+        let target_width = (columns.saturating_sub(slug_width + 4) * 2 / 3).max(6);
+        let rest_width = columns.saturating_sub(slug_width + target_width + 4);
+        screen.push_str(&format!(
+            "\x1b[4m{}  {}  {}\x1b[0m\r\n",
+            fit("Slug", slug_width),
+            fit("Target", target_width),
+            fit("Description and tags", rest_width)
+        ));
+        for (i, link) in links.iter().enumerate().skip(self.offset).take(height) {
+            let mut about = link.description.clone().unwrap_or_default();
+            for tag in &link.tags {
+                about.push_str(&format!(" #{tag}"));
+            }
+            let row = format!(
+                "{}  {}  {}",
+                fit(&link.slug, slug_width),
+                fit(&target::display(&link.target), target_width),
+                fit(about.trim(), rest_width)
+            );
+            if i == self.selected {
+                screen.push_str(&format!("\x1b[7m{row}\x1b[0m\r\n"));
+            } else {
+                screen.push_str(&format!("{row}\r\n"));
+            }
+        }
+
+        let status = match git::pending(self.shurl.repo(), &cfg.branch) {
+            Ok((0, Some(0))) => format!("Everything is pushed to origin/{}", cfg.branch),
+            Ok((changes, unpushed)) => {
+                let mut parts = vec![];
+                match changes {
+                    0 => {}
+                    1 => parts.push("1 change not committed".to_string()),
+                    changes => parts.push(format!("{changes} changes not committed")),
+                }
+                match unpushed {
+                    Some(0) => {}
+                    Some(1) => parts.push("1 commit not pushed, p to push".to_string()),
+                    Some(commits) => parts.push(format!("{commits} commits not pushed, p to push")),
+                    None => parts.push(format!("origin/{} isn't known yet", cfg.branch)),
+                }
+                parts.join(", ")
+            }
+            Err(e) => e,
+        };
+        screen.push_str(&format!("\x1b[{};1H", rows.saturating_sub(2)));
+        screen.push_str(&format!("\x1b[7m{}\x1b[0m\r\n", fit(&status, columns)));
+        match prompt {
+            Some((label, answer)) => {
+                screen.push_str(&format!("{label}{answer}\x1b[?25h\r\n"));
+            }
+            None => screen.push_str(&format!("\x1b[?25l{}\r\n", self.message)),
+        }
+        screen.push_str(&format!("\x1b[2m{}\x1b[0m", fit(HELP, columns)));
+        if prompt.is_some() {
+            // Back to the end of the answer.
+            let (label, answer) = prompt.unwrap_or_default();
+            let column = label.chars().count() + answer.chars().count() + 1;
+            screen.push_str(&format!("\x1b[{};{column}H", rows.saturating_sub(1)));
+        }
+
+        let mut stdout = io::stdout().lock();
+        stdout
+            .write_all(screen.as_bytes())
+            .and_then(|()| stdout.flush())
+            .map_err(|e| ShurlError::Other(format!("failed to draw: {e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_matches_rank_runs_and_word_starts() {
+        assert!(fuzzy_score("xyz", "docs-install").is_none());
+        assert!(fuzzy_score("dsi", "docs-install").is_some());
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+        assert!(fuzzy_score("docs", "docs-install") > fuzzy_score("docs", "d-o-c-s"));
+        assert!(fuzzy_score("in", "docs-install") > fuzzy_score("in", "login"));
+        assert_eq!(fuzzy_score("DOCS", "docs"), fuzzy_score("docs", "DOCS"));
+    }
+}
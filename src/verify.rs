@@ -0,0 +1,167 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Checking that the targets of links still answer, before a link is added
+//! and for every link in the repository.
+
+use crate::http::{self, Request};
+use std::fmt::{self, Display, Formatter};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use url::Url;
+
+/// Seconds a target gets to answer.
+pub const TIMEOUT: u64 = 10;
+
+/// Redirects followed before a target is given up on.
+const MAX_REDIRECTS: usize = 10;
+
+/// Targets checked at the same time.
+const JOBS: usize = 8;
+
+pub enum Outcome {
+    /// The target answers, maybe after temporary redirects.
+    Ok,
+    /// The target redirects permanently to `location`, which the link could
+    /// point to instead.
+    Moved {
+        status: u16,
+        location: String,
+    },
+    /// The target answers with an error status such as 404 or 410.
+    Dead(u16),
+    TimedOut,
+    /// No answer at all, e.g. because the host doesn't resolve.
+    Failed(String),
+}
+
+impl Outcome {
+    pub fn is_ok(&self) -> bool {
+        matches!(self, Outcome::Ok)
+    }
+
+    /// Whether the target is still there, if maybe somewhere else.
+    pub fn answers(&self) -> bool {
+        matches!(self, Outcome::Ok | Outcome::Moved { .. })
+    }
+
+    /// Name of the outcome in machine-readable output.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Outcome::Ok => "ok",
+            Outcome::Moved { .. } => "moved",
+            Outcome::Dead(_) => "dead",
+            Outcome::TimedOut => "timed_out",
+            Outcome::Failed(_) => "failed",
+        }
+    }
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Ok => write!(f, "answers"),
+            Outcome::Moved { status, location } => {
+                write!(f, "moved permanently ({status}) to {location}")
+            }
+            Outcome::Dead(status) => write!(f, "answers {status}"),
+            Outcome::TimedOut => write!(f, "timed out after {TIMEOUT} seconds"),
+            Outcome::Failed(e) => write!(f, "failed: {e}"),
+        }
+    }
+}
+
+/// Requests the target, following redirects. Only a `HEAD` request is sent
+/// unless the server doesn't take those.
+pub fn check(url: &str) -> Outcome {
+    let mut current = url.to_string();
+    // Where the permanent redirects from the target lead, as long as no
+    // temporary redirect came between them.
+    let mut moved: Option<(u16, String)> = None;
+    let mut permanent = true;
+    for _ in 0..=MAX_REDIRECTS {
+        let response = match request("HEAD", &current) {
+            Ok(response) if matches!(response.status, 403 | 405 | 501) => request("GET", &current),
+            result => result,
+        };
+        let response = match response {
+            Ok(response) => response,
+            Err(e) if e == http::TIMED_OUT => return Outcome::TimedOut,
+            Err(e) => return Outcome::Failed(e),
+        };
+
+        match (response.status, response.location) {
+            (300..=399, Some(location)) => {
+                let next = Url::parse(&current)
+                    .and_then(|base| base.join(&location))
+                    .map(String::from)
+                    .unwrap_or(location);
+                if permanent && matches!(response.status, 301 | 308) {
+                    let status = moved
+                        .as_ref()
+                        .map_or(response.status, |(status, _)| *status);
+                    moved = Some((status, next.clone()));
+                } else {
+                    permanent = false;
+                }
+                current = next;
+            }
+            (200..=399, _) => {
+                return match moved {
+                    Some((status, location)) => Outcome::Moved { status, location },
+                    None => Outcome::Ok,
+                }
+            }
+            (0, _) => return Outcome::Failed(format!("no status from {current}")),
+            (status, _) => return Outcome::Dead(status),
+        }
+    }
+    Outcome::Failed(format!("more than {MAX_REDIRECTS} redirects"))
+}
+
+/// Checks several targets at once, returning the outcomes in the order of
+/// the targets.
+pub fn check_all(urls: &[&str]) -> Vec<Outcome> {
+    let next = AtomicUsize::new(0);
+    let outcomes = Mutex::new((0..urls.len()).map(|_| None).collect::<Vec<_>>());
+    thread::scope(|scope| {
+        for _ in 0..JOBS.min(urls.len()) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(url) = urls.get(i) else {
+                    break;
+                };
+                let outcome = check(url);
+                outcomes.lock().unwrap()[i] = Some(outcome);
+            });
+        }
+    });
+    outcomes
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+fn request(method: &str, url: &str) -> Result<http::Response, String> {
+    Request::new(method, url)
+        .header("User-Agent", "shurl")
+        .timeout(TIMEOUT)
+        .no_redirects()
+        .send()
+}
@@ -0,0 +1,186 @@
+// Shurl - Small utility to manage short URLs in a Git repository
+// Copyright (C) 2023  MD Gaziur Rahman Noor
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Mirrors the manifest into a Cloudflare Workers KV namespace, for a Worker
+//! that answers with real redirects. The namespace belongs to shurl: keys
+//! that aren't slugs in the manifest are deleted.
+
+use crate::backend;
+use crate::config::ShurlConfig;
+use crate::date;
+use crate::http::Request;
+use crate::json::{self, Value};
+use crate::manifest::Manifest;
+use std::collections::BTreeSet;
+use std::env;
+use url::form_urlencoded;
+
+/// Most keys the bulk endpoints take per request.
+const BULK_LIMIT: usize = 10000;
+
+pub struct WorkersKv {
+    /// Base URL of the namespace's API endpoints.
+    namespace_url: String,
+    token: String,
+}
+
+/// What a sync changed.
+pub struct Summary {
+    pub written: usize,
+    pub deleted: usize,
+}
+
+impl WorkersKv {
+    /// Uses `cloudflare_account_id` and `cloudflare_kv_namespace` from the
+    /// config, and `cloudflare_api_token` or `$CLOUDFLARE_API_TOKEN`.
+    pub fn new(cfg: &ShurlConfig) -> Result<Self, String> {
+        let account = cfg
+            .cloudflare_account_id
+            .as_ref()
+            .ok_or_else(|| "set `cloudflare_account_id` in the config".to_string())?;
+        let namespace = cfg
+            .cloudflare_kv_namespace
+            .as_ref()
+            .ok_or_else(|| "set `cloudflare_kv_namespace` in the config".to_string())?;
+        let token = cfg
+            .cloudflare_api_token
+            .clone()
+            .or_else(|| env::var("CLOUDFLARE_API_TOKEN").ok())
+            .ok_or_else(|| {
+                "set `cloudflare_api_token` in the config or $CLOUDFLARE_API_TOKEN".to_string()
+            })?;
+        let api_url = env::var("CLOUDFLARE_API_URL")
+            .unwrap_or_else(|_| "https://api.cloudflare.com/client/v4".to_string());
+
+        Ok(Self {
+            namespace_url: format!(
+                "{}/accounts/{account}/storage/kv/namespaces/{namespace}",
+                api_url.trim_end_matches('/')
+            ),
+            token,
+        })
+    }
+
+    /// Writes every link that hasn't expired and isn't left to its page, and
+    /// deletes every other key.
+    /// The target is the value; the status code is in the key's metadata.
+    pub fn sync(&self, cfg: &ShurlConfig, manifest: &Manifest) -> Result<Summary, String> {
+        let now = date::to_unix(&date::now());
+        let mut entries = vec![];
+        let mut slugs = BTreeSet::new();
+        for link in &manifest.links {
+            let expiration = link.expires.as_ref().map(date::to_unix);
+            if expiration.is_some_and(|expiration| expiration <= now) || link.needs_page() {
+                continue;
+            }
+
+            let mut metadata = Value::object();
+            metadata.set("status", backend::status_of(cfg, link));
+            let mut entry = Value::object();
+            entry
+                .set("key", link.slug.as_str())
+                .set("value", link.target.as_str())
+                .set("metadata", metadata);
+            if let Some(expiration) = expiration {
+                entry.set("expiration", expiration);
+            }
+            entries.push(entry);
+            slugs.insert(link.slug.as_str());
+        }
+
+        let stale: Vec<Value> = self
+            .keys()?
+            .into_iter()
+            .filter(|key| !slugs.contains(key.as_str()))
+            .map(Value::from)
+            .collect();
+
+        for chunk in entries.chunks(BULK_LIMIT) {
+            self.request("PUT", "/bulk", Some(&Value::from(chunk.to_vec())))?;
+        }
+        for chunk in stale.chunks(BULK_LIMIT) {
+            self.request("POST", "/bulk/delete", Some(&Value::from(chunk.to_vec())))?;
+        }
+        Ok(Summary {
+            written: entries.len(),
+            deleted: stale.len(),
+        })
+    }
+
+    /// Every key in the namespace, following the list cursor.
+    fn keys(&self) -> Result<Vec<String>, String> {
+        let mut keys = vec![];
+        let mut cursor = String::new();
+        loop {
+            let mut path = "/keys?limit=1000".to_string();
+            if !cursor.is_empty() {
+                let cursor: String = form_urlencoded::byte_serialize(cursor.as_bytes()).collect();
+                path.push_str(&format!("&cursor={cursor}"));
+            }
+            let response = self.request("GET", &path, None)?;
+
+            let result = response.get("result").and_then(Value::as_array);
+            for key in result.unwrap_or_default() {
+                if let Some(name) = key.get("name").and_then(Value::as_str) {
+                    keys.push(name.to_string());
+                }
+            }
+
+            cursor = response
+                .get("result_info")
+                .and_then(|info| info.get("cursor"))
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            if cursor.is_empty() {
+                return Ok(keys);
+            }
+        }
+    }
+
+    fn request(&self, method: &str, path: &str, body: Option<&Value>) -> Result<Value, String> {
+        let url = format!("{}{path}", self.namespace_url);
+        let mut request = Request::new(method, &url)
+            .header("Authorization", &format!("Bearer {}", self.token))
+            .header("User-Agent", "shurl");
+        if let Some(body) = body {
+            request = request
+                .header("Content-Type", "application/json")
+                .body(body.to_string());
+        }
+
+        let response = request.send()?;
+        let value = if response.body.trim().is_empty() {
+            Value::Null
+        } else {
+            json::parse(&response.body)?
+        };
+        if !(200..300).contains(&response.status) {
+            let message = value
+                .get("errors")
+                .and_then(Value::as_array)
+                .and_then(|errors| errors.first())
+                .and_then(|error| error.get("message"))
+                .and_then(Value::as_str)
+                .unwrap_or("no message");
+            return Err(format!(
+                "Cloudflare API {method} {path} failed with {}: {message}",
+                response.status
+            ));
+        }
+        Ok(value)
+    }
+}